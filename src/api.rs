@@ -11,13 +11,16 @@ use axum::http::header;
 use futures_util::{SinkExt, StreamExt};
 use renderdag::{Ancestor, GraphRow, GraphRowRenderer, Renderer};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing::{info, warn};
 
-use crate::jj::Jj;
-use crate::review::{Author, Review, ReviewStore, ThreadStatus};
+use crate::vcs::{self, Vcs};
+use crate::identity::Identity;
+use crate::review::{Comment, CommentExtras, Review, ReviewStore, Severity, Side, ThreadAnchor, ThreadStatus, VerdictKind};
 use crate::session::{SessionStatus, SessionStore};
 use crate::timeline::TimelineStore;
 use crate::todo::TodoStore;
@@ -59,11 +62,37 @@ async fn static_handler(uri: axum::http::Uri) -> impl IntoResponse {
 }
 
 struct AppState {
-    jj: Jj,
+    /// The main repo
+    repo_path: PathBuf,
+    /// Backends opened so far, by repo path: the main repo and session clones
+    backends: Mutex<HashMap<PathBuf, Arc<dyn Vcs>>>,
     store: ReviewStore,
     todos: TodoStore,
     timeline: TimelineStore,
     sessions: SessionStore,
+    /// Who is reviewing from this machine
+    identity: Identity,
+}
+
+impl AppState {
+    /// The main repo, for one request
+    fn vcs(&self) -> Box<dyn Vcs> {
+        self.vcs_at(&self.repo_path)
+    }
+
+    /// The repo at `path`, for one request. Its backend stays open between
+    /// requests; the cache in front of it answers as of the first query made
+    /// through it.
+    fn vcs_at(&self, path: &std::path::Path) -> Box<dyn Vcs> {
+        let backend = self
+            .backends
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_insert_with(|| vcs::open_backend(path).into())
+            .clone();
+        Box::new(vcs::Cached::new(backend))
+    }
 }
 
 /// Resolve the port to bind to:
@@ -75,23 +104,34 @@ fn resolve_port(explicit: Option<u16>) -> u16 {
         return p;
     }
     let port_file = std::path::Path::new(".aipair/port");
-    if let Ok(contents) = std::fs::read_to_string(port_file) {
-        if let Ok(p) = contents.trim().parse::<u16>() {
-            return p;
-        }
+    if let Ok(contents) = std::fs::read_to_string(port_file)
+        && let Ok(p) = contents.trim().parse::<u16>()
+    {
+        return p;
     }
     0
 }
 
 pub async fn serve(port: Option<u16>) -> anyhow::Result<()> {
-    let jj = Jj::discover()?;
-    let store = ReviewStore::new(jj.repo_path());
-    store.init()?;
-
-    let todos = TodoStore::new(jj.repo_path());
-    let timeline = TimelineStore::new(jj.repo_path());
-    let sessions = SessionStore::new(jj.repo_path());
-    let state = Arc::new(AppState { jj, store, todos, timeline, sessions });
+    let repo_path = vcs::discover()?.repo_path().to_path_buf();
+    let storage = crate::storage::open(&repo_path);
+    info!("Using {} storage backend", storage.backend());
+
+    let store = ReviewStore::with_storage(storage.clone());
+    let todos = TodoStore::with_storage(storage.clone());
+    let timeline = TimelineStore::with_storage(storage.clone());
+    let sessions = SessionStore::with_storage(storage);
+    let identity = Identity::load()?;
+    info!("Reviewing as {}", identity.name);
+    let state = Arc::new(AppState {
+        repo_path,
+        backends: Mutex::new(HashMap::new()),
+        store,
+        todos,
+        timeline,
+        sessions,
+        identity,
+    });
 
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -102,17 +142,35 @@ pub async fn serve(port: Option<u16>) -> anyhow::Result<()> {
         .route("/api/health", get(health))
         .route("/api/changes", get(list_changes))
         .route("/api/changes/{change_id}/diff", get(get_diff))
+        .route("/api/changes/{change_id}/lines", get(get_file_lines))
+        .route("/api/changes/{change_id}/interdiff", get(get_interdiff))
+        .route("/api/changes/{change_id}/unseen", get(get_unseen))
+        .route("/api/changes/{change_id}/seen", post(mark_seen))
         .route("/api/changes/{change_id}/review", get(get_review))
         .route("/api/changes/{change_id}/review", post(create_review))
+        .route("/api/changes/{change_id}/review/submit", post(submit_review))
         .route("/api/changes/{change_id}/comments", post(add_comment))
         .route("/api/changes/{change_id}/threads/{thread_id}/reply", post(reply_to_thread))
         .route("/api/changes/{change_id}/threads/{thread_id}/resolve", post(resolve_thread))
         .route("/api/changes/{change_id}/threads/{thread_id}/reopen", post(reopen_thread))
+        .route("/api/changes/{change_id}/threads/{thread_id}/status", post(set_thread_status))
+        .route("/api/changes/{change_id}/threads/{thread_id}/fix", get(get_thread_fix))
+        .route("/api/changes/{change_id}/threads/{thread_id}/classify", post(classify_thread))
+        .route("/api/changes/{change_id}/threads/{thread_id}/apply-suggestion", post(apply_suggestion))
+        .route("/api/changes/{change_id}/threads/{thread_id}/comments/{comment_id}", patch(edit_comment))
+        .route("/api/changes/{change_id}/threads/{thread_id}/comments/{comment_id}", delete(delete_comment))
+        .route("/api/changes/{change_id}/conflicts", get(get_conflicts))
+        .route("/api/changes/{change_id}/conflicts/resolve", post(resolve_conflict))
+        .route("/api/changes/{change_id}/verdict", post(set_verdict))
         .route("/api/changes/{change_id}/merge", post(merge_change))
+        .route("/api/reviews/gc", post(gc_reviews))
+        .route("/api/operations", get(list_operations))
+        .route("/api/operations/{operation_id}/undo", post(undo_operation))
         .route("/api/todos", get(get_todos))
         .route("/api/todos", post(create_todo))
         .route("/api/todos/{id}", patch(update_todo))
         .route("/api/todos/{id}", delete(delete_todo))
+        .route("/api/identity", get(get_identity))
         .route("/api/timeline", get(get_timeline))
         .route("/api/sessions", post(create_session))
         .route("/api/sessions/{name}/merge", post(merge_session))
@@ -205,6 +263,17 @@ async fn health() -> &'static str {
     "ok"
 }
 
+/// Error response for a failed store operation: 409 when another writer
+/// modified the same document concurrently, `fallback` otherwise.
+fn store_error(e: anyhow::Error, fallback: StatusCode) -> axum::response::Response {
+    let status = if crate::storage::is_conflict(&e) {
+        StatusCode::CONFLICT
+    } else {
+        fallback
+    };
+    (status, e.to_string()).into_response()
+}
+
 /// Change with merged status and review info for API response
 #[derive(Serialize)]
 struct ChangeWithStatus {
@@ -212,12 +281,32 @@ struct ChangeWithStatus {
     change: crate::jj::Change,
     merged: bool,
     open_thread_count: usize,
+    /// Open threads that hold up merging
+    blocking_thread_count: usize,
     revision_count: usize,
     has_pending_changes: bool,
+    /// Whether the change moved on since the reviewer last reviewed it
+    has_unseen_revision: bool,
+    /// Agent replies the reviewer hasn't read
+    unread_reply_count: usize,
+    /// The verdict standing on the change's current commit
+    verdict: Option<VerdictKind>,
 
     session_name: Option<String>,
 }
 
+/// `has_unseen_revision` and `unread_reply_count` for a change. Changes
+/// without a review have nothing to read but haven't been reviewed either.
+fn unseen_status(review: Option<&Review>, commit_id: &str, reviewer: &str) -> (bool, usize) {
+    match review {
+        Some(review) => (
+            review.has_unseen_revision(reviewer, commit_id),
+            review.unread_replies(reviewer).iter().map(|(_, comments)| comments.len()).sum(),
+        ),
+        None => (true, 0),
+    }
+}
+
 /// Serializable graph row for the DAG visualization.
 /// We re-serialize from renderdag's GraphRow to control the JSON format
 /// (especially LinkLine which bitflags serializes as strings, not numbers).
@@ -284,14 +373,25 @@ struct ChangesResponse {
     sessions: Vec<SessionSummary>,
 }
 
-async fn list_changes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+#[derive(Deserialize)]
+struct ChangesQuery {
+    /// Whose unseen revisions and unread replies to report (defaults to this machine's reviewer)
+    reviewer: Option<String>,
+}
+
+async fn list_changes(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ChangesQuery>,
+) -> impl IntoResponse {
+    let reviewer = query.reviewer.as_deref().unwrap_or(&state.identity.name);
+    let vcs = state.vcs();
     // Scope to main's ancestors — sessions get their own per-session query
-    let changes = match state.jj.log_revset("ancestors(main, 100)") {
+    let changes = match vcs.log_revset("ancestors(main, 100)") {
         Ok(c) => c,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
 
-    let main_change_id = match state.jj.get_bookmark("main") {
+    let main_change_id = match vcs.get_bookmark("main") {
         Ok(id) => id,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
@@ -311,11 +411,11 @@ async fn list_changes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
             // Count open threads across this session's changes
             let open_threads: usize = s.changes.iter()
                 .filter_map(|cid| review_map.get(cid))
-                .map(|r| r.threads.iter().filter(|t| t.status == ThreadStatus::Open).count())
+                .map(|r| r.threads.iter().filter(|t| t.status.is_unresolved()).count())
                 .sum();
             // Count changes from jj (if bookmark exists)
             let revset = format!("{}..{}", s.base_bookmark, s.bookmark);
-            let current_changes = state.jj.log_revset(&revset).ok();
+            let current_changes = vcs.log_revset(&revset).ok();
             let change_count = current_changes.as_ref()
                 .map(|c| c.len())
                 .unwrap_or(s.changes.len());
@@ -326,11 +426,11 @@ async fn list_changes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
                 if last_push.changes.is_empty() {
                     false
                 } else {
-                    let clone_path = state.jj.repo_path().join(&s.clone_path);
+                    let clone_path = state.repo_path.join(&s.clone_path);
                     let clone_changes = if clone_path.exists() {
-                        let clone_jj = Jj::new(&clone_path);
+                        let clone_vcs = state.vcs_at(&clone_path);
                         let revset = format!("{}@origin..visible_heads()", s.base_bookmark);
-                        clone_jj.log_revset(&revset).ok()
+                        clone_vcs.log_revset(&revset).ok()
                     } else {
                         // No clone — fall back to main repo bookmark state
                         current_changes.clone()
@@ -417,7 +517,7 @@ async fn list_changes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
                     let open = r
                         .threads
                         .iter()
-                        .filter(|t| t.status == ThreadStatus::Open)
+                        .filter(|t| t.status.is_unresolved())
                         .count();
                     // Pending if working_commit differs from last revision's commit
                     let pending = match (r.working_commit_id.as_ref(), r.revisions.last()) {
@@ -435,12 +535,22 @@ async fn list_changes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
                     r.working_commit_id.as_ref().map(|w| w != &change.commit_id).unwrap_or(false)
                 })
                 .unwrap_or(false);
+            let (has_unseen_revision, unread_reply_count) =
+                unseen_status(review_map.get(&change.change_id), &change.commit_id, reviewer);
+            let verdict = review_map.get(&change.change_id).and_then(|r| r.verdict_on(&change.commit_id));
+            let blocking_thread_count = review_map
+                .get(&change.change_id)
+                .map_or(0, |r| r.threads.iter().filter(|t| t.blocks_merge()).count());
             ChangeWithStatus {
                 change,
                 merged,
                 open_thread_count,
+                blocking_thread_count,
                 revision_count,
                 has_pending_changes,
+                has_unseen_revision,
+                unread_reply_count,
+                verdict,
                 session_name: None,
             }
         })
@@ -455,21 +565,21 @@ async fn list_changes(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     .into_response()
 }
 
-/// Resolve which jj repo path to use. If a session name is given, return its
-/// clone's Jj (error if session not found or clone missing). Otherwise return
-/// the main repo's Jj.
-fn resolve_jj_for_session(state: &AppState, session_name: Option<&str>) -> Result<Jj, (StatusCode, String)> {
+/// Resolve which repo to use. If a session name is given, return its clone
+/// (error if session not found or clone missing). Otherwise return the main
+/// repo.
+fn resolve_vcs_for_session(state: &AppState, session_name: Option<&str>) -> Result<Box<dyn Vcs>, (StatusCode, String)> {
     if let Some(name) = session_name {
         let session = state.sessions.get(name)
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
             .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Session '{name}' not found")))?;
-        let clone_path = state.jj.repo_path().join(&session.clone_path);
+        let clone_path = state.repo_path.join(&session.clone_path);
         if !clone_path.exists() {
             return Err((StatusCode::NOT_FOUND, format!("Clone for session '{name}' not found")));
         }
-        return Ok(Jj::new(&clone_path));
+        return Ok(state.vcs_at(&clone_path));
     }
-    Ok(Jj::new(state.jj.repo_path()))
+    Ok(state.vcs())
 }
 
 /// A single chunk in a text diff
@@ -519,6 +629,8 @@ struct DiffQuery {
     base: Option<String>,
     /// Optional session name — when set, queries the session's clone
     session: Option<String>,
+    /// Lines of context around each change (defaults to 3)
+    context: Option<usize>,
 }
 
 async fn get_diff(
@@ -527,46 +639,322 @@ async fn get_diff(
     axum::extract::Query(query): axum::extract::Query<DiffQuery>,
 ) -> impl IntoResponse {
     // Resolve which jj instance to use: clone (for session) or main repo
-    let jj = match resolve_jj_for_session(&state, query.session.as_deref()) {
-        Ok(jj) => jj,
+    let vcs = match resolve_vcs_for_session(&state, query.session.as_deref()) {
+        Ok(vcs) => vcs,
         Err((status, msg)) => return (status, msg).into_response(),
     };
 
     // If a specific commit is requested, use it as the "to" revision
     let to_rev = query.commit.as_deref().unwrap_or(&change_id);
 
-    let diff = match jj.diff(to_rev, query.base.as_deref()) {
+    let context = query.context.unwrap_or(crate::diff::DEFAULT_CONTEXT);
+    let diff = match vcs.diff(to_rev, query.base.as_deref(), context) {
         Ok(diff) => diff,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
 
     // Get target message when viewing a specific revision
     let target_message = query.commit.as_ref().and_then(|commit| {
-        jj.get_change(commit).ok().map(|c| c.description)
+        vcs.get_change(commit).ok().map(|c| c.description)
     });
 
     // Compute message diff when comparing revisions
     let message_diff = match (query.base.as_ref(), query.commit.as_ref()) {
-        (Some(base), Some(_commit)) => {
-            let base_msg = jj.get_change(base).ok().map(|c| c.description).unwrap_or_default();
-            let target_msg = target_message.clone().unwrap_or_default();
-            if base_msg != target_msg {
-                Some(compute_text_diff(&base_msg, &target_msg))
-            } else {
-                None
-            }
-        }
+        (Some(base), Some(_commit)) => message_diff(vcs.as_ref(), base, target_message.as_deref().unwrap_or_default()),
         _ => None,
     };
 
     Json(DiffResponse { diff, target_message, message_diff }).into_response()
 }
 
+/// Line diff of `base`'s description against `target_message`, if they differ
+fn message_diff(vcs: &dyn Vcs, base: &str, target_message: &str) -> Option<Vec<DiffChunk>> {
+    let base_msg = vcs.get_change(base).ok().map(|c| c.description).unwrap_or_default();
+    (base_msg != target_message).then(|| compute_text_diff(&base_msg, target_message))
+}
+
+#[derive(Deserialize)]
+struct InterdiffQuery {
+    /// The older revision: a review revision number or a commit id
+    from: String,
+    /// The newer revision, in the same form (defaults to the change's current commit)
+    to: Option<String>,
+    /// Lines of context around each change (defaults to 3)
+    context: Option<usize>,
+    /// Optional session name — when set, queries the session's clone
+    session: Option<String>,
+}
+
+#[derive(Serialize)]
+struct InterdiffResponse {
+    /// The author's edits between the two revisions, shaped like a diff from
+    /// `from` to `to`
+    diff: crate::jj::Diff,
+    target_message: Option<String>,
+    message_diff: Option<Vec<DiffChunk>>,
+    /// Whether the change was rebased between the two revisions
+    parent_moved: bool,
+    /// Files where the author's edits overlapped upstream ones, so the
+    /// interdiff there also contains upstream changes
+    conflicted_files: Vec<String>,
+    /// The review's threads, mapped onto `to`
+    threads: Vec<crate::review::Thread>,
+}
+
+/// What the author changed between two revisions of a change, each compared
+/// against its own parent so a rebase in between adds no noise
+async fn get_interdiff(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Query(query): Query<InterdiffQuery>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, query.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err((status, msg)) => return (status, msg).into_response(),
+    };
+    let review = match state.store.get_by_prefix(&change_id) {
+        Ok(review) => review,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    // Revision numbers refer to the review's recorded revisions
+    let resolve = |rev: &str| match &review {
+        Some(review) => review.resolve_revision(rev),
+        None => rev.to_string(),
+    };
+    let from = resolve(&query.from);
+    let to = match &query.to {
+        Some(to) => resolve(to),
+        None => match vcs.get_change(&change_id) {
+            Ok(change) => change.commit_id,
+            Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+        },
+    };
+
+    let context = query.context.unwrap_or(crate::diff::DEFAULT_CONTEXT);
+    let interdiff = match crate::interdiff::interdiff(vcs.as_ref(), &from, &to, context) {
+        Ok(interdiff) => interdiff,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    let target_message = vcs.get_change(&to).ok().map(|c| c.description);
+    let message_diff = message_diff(vcs.as_ref(), &from, target_message.as_deref().unwrap_or_default());
+
+    let threads = match review {
+        Some(review) => {
+            let mut review = visible_review(&state, review);
+            populate_display_positions(vcs.as_ref(), &mut review, &to);
+            review.threads
+        }
+        None => Vec::new(),
+    };
+
+    Json(InterdiffResponse {
+        diff: crate::jj::Diff {
+            change_id: to,
+            base: from,
+            files: interdiff.files,
+            context,
+        },
+        target_message,
+        message_diff,
+        parent_moved: interdiff.parent_moved,
+        conflicted_files: interdiff.conflicted_files,
+        threads,
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct UnseenQuery {
+    /// Defaults to this machine's reviewer
+    reviewer: Option<String>,
+    /// Lines of context around each change (defaults to 3)
+    context: Option<usize>,
+    /// Optional session name — when set, queries the session's clone
+    session: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UnreadThread {
+    thread_id: String,
+    comments: Vec<Comment>,
+}
+
+#[derive(Serialize)]
+struct UnseenResponse {
+    reviewer: String,
+    /// The revision the reviewer last reviewed; without one, `diff` is the
+    /// whole change
+    since_revision: Option<u32>,
+    /// What the author changed since then (an interdiff, so rebases don't
+    /// show up)
+    diff: crate::jj::Diff,
+    parent_moved: bool,
+    unread: Vec<UnreadThread>,
+}
+
+/// What's new in a change since the reviewer last looked: the diff since
+/// their last reviewed revision and the agent replies they haven't read
+async fn get_unseen(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Query(query): Query<UnseenQuery>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, query.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err((status, msg)) => return (status, msg).into_response(),
+    };
+    let review = match state.store.get_by_prefix(&change_id) {
+        Ok(review) => review,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let current = match vcs.get_change(&change_id) {
+        Ok(change) => change.commit_id,
+        Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    };
+    let reviewer = query.reviewer.unwrap_or_else(|| state.identity.name.clone());
+    let context = query.context.unwrap_or(crate::diff::DEFAULT_CONTEXT);
+
+    let since = review.as_ref().and_then(|review| {
+        let number = review.seen.get(&reviewer)?.last_reviewed_revision?;
+        review.revisions.iter().find(|r| r.number == number)
+    });
+    let (diff, parent_moved) = match since {
+        Some(since) => match crate::interdiff::interdiff(vcs.as_ref(), &since.commit_id, &current, context) {
+            Ok(interdiff) => (
+                crate::jj::Diff {
+                    change_id: current,
+                    base: since.commit_id.clone(),
+                    files: interdiff.files,
+                    context,
+                },
+                interdiff.parent_moved,
+            ),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+        None => match vcs.diff(&current, None, context) {
+            Ok(diff) => (diff, false),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
+    };
+
+    let unread = review
+        .as_ref()
+        .map(|review| {
+            review
+                .unread_replies(&reviewer)
+                .into_iter()
+                .map(|(thread, comments)| UnreadThread {
+                    thread_id: thread.id.clone(),
+                    comments: comments.into_iter().cloned().collect(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Json(UnseenResponse {
+        since_revision: since.map(|r| r.number),
+        reviewer,
+        diff,
+        parent_moved,
+        unread,
+    })
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct MarkSeenRequest {
+    /// Defaults to this machine's reviewer
+    reviewer: Option<String>,
+    /// Mark only this thread's comments as read
+    thread_id: Option<String>,
+    /// The revision reviewed (defaults to the change's current commit)
+    revision: Option<u32>,
+}
+
+/// Record what a reviewer has seen: one thread's comments, or else the
+/// change up to a revision along with every comment on it
+async fn mark_seen(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Json(req): Json<MarkSeenRequest>,
+) -> impl IntoResponse {
+    let reviewer = req.reviewer.as_deref().unwrap_or(&state.identity.name);
+    if let Some(thread_id) = &req.thread_id {
+        return match state.store.mark_read(&change_id, reviewer, Some(thread_id)) {
+            Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+            Err(e) => store_error(e, StatusCode::NOT_FOUND),
+        };
+    }
+
+    let vcs = state.vcs();
+    let commit_id = match vcs.get_change(&change_id) {
+        Ok(change) => change.commit_id,
+        Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    };
+    let result = state
+        .store
+        .mark_reviewed(&change_id, reviewer, req.revision, &commit_id)
+        .and_then(|_| state.store.mark_read(&change_id, reviewer, None));
+    match result {
+        Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::BAD_REQUEST),
+    }
+}
+
+#[derive(Deserialize)]
+struct FileLinesQuery {
+    path: String,
+    /// First and last line to return (1-based, inclusive); without `end`,
+    /// everything up to the end of the file
+    start: usize,
+    end: Option<usize>,
+    /// Which side of the diff the line numbers refer to
+    #[serde(default)]
+    side: Side,
+    /// Same as for the diff: the revision shown and the one it's compared to
+    commit: Option<String>,
+    base: Option<String>,
+    session: Option<String>,
+}
+
+/// Lines of a file on one side of a diff, for expanding the context the
+/// diff left out
+async fn get_file_lines(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Query(query): Query<FileLinesQuery>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, query.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err((status, msg)) => return (status, msg).into_response(),
+    };
+
+    let to_rev = query.commit.unwrap_or(change_id);
+    let rev = match query.side {
+        Side::New => to_rev,
+        Side::Old => query.base.unwrap_or_else(|| format!("{to_rev}-")),
+    };
+
+    match vcs.show_file(&rev, &query.path) {
+        Ok(content) => Json(crate::diff::line_range(&content, query.start, query.end.unwrap_or(usize::MAX))).into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    }
+}
+
 #[derive(Serialize)]
 struct ReviewResponse {
     review: Option<Review>,
 }
 
+/// The review as this machine's reviewer may see it: other reviewers'
+/// pending comments aren't ours to see
+fn visible_review(state: &AppState, mut review: Review) -> Review {
+    review.hide_drafts(Some(&state.identity.name));
+    review
+}
+
 /// Add a virtual pending revision if the current commit differs from the last recorded revision
 fn add_pending_revision_if_needed(mut review: Review, current_commit_id: &str) -> Review {
     let has_pending = match review.revisions.last() {
@@ -589,12 +977,12 @@ fn add_pending_revision_if_needed(mut review: Review, current_commit_id: &str) -
 }
 
 /// Populate display positions on threads by mapping through diffs
-fn populate_display_positions(jj: &crate::jj::Jj, review: &mut Review, current_commit_id: &str) {
+fn populate_display_positions(vcs: &dyn Vcs, review: &mut Review, current_commit_id: &str) {
     if current_commit_id.is_empty() {
         return;
     }
 
-    let mapped = crate::line_mapper::map_all_threads(jj, &review.threads, current_commit_id);
+    let mapped = crate::line_mapper::map_all_threads(vcs, &review.threads, current_commit_id);
 
     for thread in &mut review.threads {
         if let Some(pos) = mapped.get(&thread.id) {
@@ -602,7 +990,10 @@ fn populate_display_positions(jj: &crate::jj::Jj, review: &mut Review, current_c
             thread.display_line_end = Some(pos.line_end);
             thread.is_deleted = pos.is_deleted;
             thread.is_displaced = pos.line_start != thread.line_start
-                || pos.line_end != thread.line_end;
+                || pos.line_end != thread.line_end
+                || pos.file.is_some();
+            thread.match_confidence = Some(pos.confidence);
+            thread.display_file = pos.file.clone();
         }
     }
 }
@@ -611,15 +1002,16 @@ async fn get_review(
     State(state): State<Arc<AppState>>,
     Path(change_id): Path<String>,
 ) -> impl IntoResponse {
+    let vcs = state.vcs();
     // Get current commit_id for this change
-    let current_commit_id = state.jj.get_change(&change_id)
+    let current_commit_id = vcs.get_change(&change_id)
         .map(|c| c.commit_id)
         .unwrap_or_default();
 
     match state.store.get(&change_id) {
         Ok(Some(review)) => {
-            let mut review = add_pending_revision_if_needed(review, &current_commit_id);
-            populate_display_positions(&state.jj, &mut review, &current_commit_id);
+            let mut review = visible_review(&state, add_pending_revision_if_needed(review, &current_commit_id));
+            populate_display_positions(vcs.as_ref(), &mut review, &current_commit_id);
             Json(ReviewResponse { review: Some(review) }).into_response()
         }
         Ok(None) => Json(ReviewResponse { review: None }).into_response(),
@@ -639,29 +1031,118 @@ async fn create_review(
 ) -> impl IntoResponse {
     let base = req.base.as_deref().unwrap_or("@-");
 
+    let vcs = state.vcs();
     // Get commit_id for this change
-    let current_commit_id = state.jj.get_change(&change_id)
+    let current_commit_id = vcs.get_change(&change_id)
         .map(|c| c.commit_id)
         .unwrap_or_default();
 
     match state.store.get_or_create(&change_id, base, &current_commit_id) {
         Ok(review) => {
-            let review = add_pending_revision_if_needed(review, &current_commit_id);
+            let review = visible_review(&state, add_pending_revision_if_needed(review, &current_commit_id));
             Json(ReviewResponse {
                 review: Some(review),
             })
             .into_response()
         }
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => store_error(e, StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[derive(Deserialize)]
+struct SubmitReviewRequest {
+    /// Defaults to this machine's reviewer
+    reviewer: Option<String>,
+    /// A verdict on the current commit to submit along with the comments
+    verdict: Option<VerdictKind>,
+    /// Summary to go with the verdict
+    comment: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SubmitReviewResponse {
+    review: Review,
+    /// How many draft comments were published
+    published: usize,
+}
+
+/// Publish the pending draft review in one go
+async fn submit_review(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Json(req): Json<SubmitReviewRequest>,
+) -> impl IntoResponse {
+    let vcs = state.vcs();
+    let commit_id = match vcs.get_change(&change_id) {
+        Ok(change) => change.commit_id,
+        Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    };
+    let reviewer = req.reviewer.as_deref().unwrap_or(&state.identity.name);
+    let verdict = req.verdict.map(|kind| (kind, req.comment));
+    match state.store.submit_review(&change_id, reviewer, verdict, &commit_id) {
+        Ok((review, published)) => {
+            record_published(&state, &review, &published);
+            Json(SubmitReviewResponse { review: visible_review(&state, review), published: published.len() })
+                .into_response()
+        }
+        Err(e) => store_error(e, StatusCode::BAD_REQUEST),
+    }
+}
+
+/// Put comments that just went out on the timeline: a thread's first comment
+/// as the review comment that started it, later ones as replies
+fn record_published(state: &AppState, review: &Review, published: &[(String, String)]) {
+    for (thread_id, comment_id) in published {
+        let Some(thread) = review.threads.iter().find(|t| &t.id == thread_id) else {
+            continue;
+        };
+        let Some(index) = thread.comments.iter().position(|c| &c.id == comment_id) else {
+            continue;
+        };
+        let comment = &thread.comments[index];
+        let data = if index == 0 {
+            crate::timeline::TimelineEventData::ReviewComment {
+                change_id: review.change_id.clone(),
+                thread_id: thread.id.clone(),
+                file: thread.file.clone(),
+                line_start: thread.line_start,
+                line_end: thread.line_end,
+                text: comment.text.clone(),
+            }
+        } else {
+            crate::timeline::TimelineEventData::ReviewReply {
+                change_id: review.change_id.clone(),
+                thread_id: thread.id.clone(),
+                author: comment.author.clone(),
+                text: comment.text.clone(),
+            }
+        };
+        let _ = state.timeline.append(&crate::timeline::TimelineEntry { timestamp: chrono::Utc::now(), data });
     }
 }
 
 #[derive(Deserialize)]
 struct AddCommentRequest {
+    /// What the comment is attached to (defaults to a line range)
+    #[serde(default)]
+    anchor: ThreadAnchor,
+    #[serde(default)]
     file: String,
-    line_start: usize,
-    line_end: usize,
+    /// Required for line comments
+    line_start: Option<usize>,
+    line_end: Option<usize>,
+    /// Diff side the lines refer to (defaults to new)
+    #[serde(default)]
+    side: Side,
     text: String,
+    /// Hold the comment back until the review is submitted
+    #[serde(default)]
+    draft: bool,
+    /// Replacement lines for the commented range
+    suggestion: Option<Vec<String>>,
+    /// Defaults to blocking
+    severity: Option<Severity>,
+    labels: Option<Vec<String>>,
 }
 
 #[derive(Serialize)]
@@ -670,13 +1151,35 @@ struct AddCommentResponse {
     thread_id: String,
 }
 
+/// Snapshot the commented lines so the thread can be re-anchored by content
+/// if later diffs lose track of them
+fn snapshot_thread_lines(
+    vcs: &dyn Vcs,
+    commit_id: &str,
+    file: &str,
+    side: Side,
+    line_start: usize,
+    line_end: usize,
+) -> Option<crate::review::AnchorSnapshot> {
+    if commit_id.is_empty() {
+        return None;
+    }
+    let rev = match side {
+        Side::New => commit_id.to_string(),
+        Side::Old => format!("{commit_id}-"),
+    };
+    let content = vcs.show_file(&rev, file).ok()?;
+    crate::line_mapper::snapshot_lines(&content, line_start, line_end)
+}
+
 async fn add_comment(
     State(state): State<Arc<AppState>>,
     Path(change_id): Path<String>,
     Json(req): Json<AddCommentRequest>,
 ) -> impl IntoResponse {
+    let vcs = state.vcs();
     // Get commit_id for this change
-    let commit_id = match state.jj.log(100) {
+    let commit_id = match vcs.log(100) {
         Ok(changes) => changes
             .iter()
             .find(|c| c.change_id == change_id)
@@ -685,36 +1188,79 @@ async fn add_comment(
         Err(_) => String::new(),
     };
 
-    match state.store.add_comment(
-        &change_id,
-        &req.file,
-        req.line_start,
-        req.line_end,
-        Author::User,
-        &req.text,
-        &commit_id,
-    ) {
+    if req.suggestion.is_some() && (req.anchor != ThreadAnchor::Line || req.side != Side::New) {
+        return (StatusCode::BAD_REQUEST, "Suggestions can only be made on lines of the change's new side")
+            .into_response();
+    }
+
+    let extras = CommentExtras {
+        draft: req.draft,
+        suggestion: req.suggestion.clone(),
+        severity: req.severity,
+        labels: req.labels.clone(),
+    };
+    let result = match (req.anchor, req.line_start, req.line_end) {
+        (ThreadAnchor::Line, Some(line_start), Some(line_end)) if line_start == 0 || line_start > line_end => {
+            return (StatusCode::BAD_REQUEST, "Lines are numbered from 1 and line_start can't be past line_end")
+                .into_response();
+        }
+        (ThreadAnchor::Line, Some(line_start), Some(line_end)) => state.store.add_comment(
+            &change_id,
+            &req.file,
+            line_start,
+            line_end,
+            req.side,
+            state.identity.human(),
+            &req.text,
+            &commit_id,
+            snapshot_thread_lines(vcs.as_ref(), &commit_id, &req.file, req.side, line_start, line_end),
+            extras,
+        ),
+        (ThreadAnchor::Line, _, _) => {
+            return (StatusCode::BAD_REQUEST, "line_start and line_end are required for line comments")
+                .into_response();
+        }
+        (anchor, _, _) => state.store.start_thread(
+            &change_id,
+            anchor,
+            Some(req.file.as_str()),
+            state.identity.human(),
+            &req.text,
+            &commit_id,
+            extras,
+        ),
+    };
+
+    match result {
         Ok((review, thread_id)) => {
-            let _ = state.timeline.append(&crate::timeline::TimelineEntry {
-                timestamp: chrono::Utc::now(),
-                data: crate::timeline::TimelineEventData::ReviewComment {
-                    change_id: change_id.clone(),
-                    thread_id: thread_id.clone(),
-                    file: req.file.clone(),
-                    line_start: req.line_start,
-                    line_end: req.line_end,
-                    text: req.text.clone(),
-                },
-            });
-            Json(AddCommentResponse { review, thread_id }).into_response()
+            // Drafts go on the timeline when the review is submitted
+            if !req.draft {
+                let _ = state.timeline.append(&crate::timeline::TimelineEntry {
+                    timestamp: chrono::Utc::now(),
+                    data: crate::timeline::TimelineEventData::ReviewComment {
+                        change_id: change_id.clone(),
+                        thread_id: thread_id.clone(),
+                        file: req.file.clone(),
+                        line_start: req.line_start.unwrap_or(0),
+                        line_end: req.line_end.unwrap_or(0),
+                        text: req.text.clone(),
+                    },
+                });
+            }
+            Json(AddCommentResponse { review: visible_review(&state, review), thread_id }).into_response()
         }
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => store_error(e, StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
 #[derive(Deserialize)]
 struct ReplyRequest {
     text: String,
+    /// Hold the reply back until the review is submitted
+    #[serde(default)]
+    draft: bool,
+    /// Replacement lines for the thread's range
+    suggestion: Option<Vec<String>>,
 }
 
 async fn reply_to_thread(
@@ -722,20 +1268,39 @@ async fn reply_to_thread(
     Path((change_id, thread_id)): Path<(String, String)>,
     Json(req): Json<ReplyRequest>,
 ) -> impl IntoResponse {
-    match state.store.reply_to_thread(&change_id, &thread_id, Author::User, &req.text) {
+    if req.suggestion.is_some() {
+        let thread = state.store.get_by_prefix(&change_id).and_then(|review| {
+            let review = review.ok_or_else(|| anyhow::anyhow!("Review not found"))?;
+            Ok(review.find_thread(&thread_id)?.clone())
+        });
+        match thread {
+            Ok(thread) if thread.anchor == ThreadAnchor::Line && thread.side == Side::New => {}
+            Ok(_) => {
+                return (StatusCode::BAD_REQUEST, "Suggestions can only be made on lines of the change's new side")
+                    .into_response();
+            }
+            Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+        }
+    }
+
+    let extras = CommentExtras { draft: req.draft, suggestion: req.suggestion.clone(), ..CommentExtras::default() };
+    let result = state.store.reply_to_thread(&change_id, &thread_id, state.identity.human(), &req.text, extras);
+    match result {
         Ok(review) => {
-            let _ = state.timeline.append(&crate::timeline::TimelineEntry {
-                timestamp: chrono::Utc::now(),
-                data: crate::timeline::TimelineEventData::ReviewReply {
-                    change_id: change_id.clone(),
-                    thread_id: thread_id.clone(),
-                    author: "user".to_string(),
-                    text: req.text.clone(),
-                },
-            });
-            Json(ReviewResponse { review: Some(review) }).into_response()
+            if !req.draft {
+                let _ = state.timeline.append(&crate::timeline::TimelineEntry {
+                    timestamp: chrono::Utc::now(),
+                    data: crate::timeline::TimelineEventData::ReviewReply {
+                        change_id: change_id.clone(),
+                        thread_id: thread_id.clone(),
+                        author: state.identity.human(),
+                        text: req.text.clone(),
+                    },
+                });
+            }
+            Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response()
         }
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => store_error(e, StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
@@ -743,9 +1308,9 @@ async fn resolve_thread(
     State(state): State<Arc<AppState>>,
     Path((change_id, thread_id)): Path<(String, String)>,
 ) -> impl IntoResponse {
-    match state.store.resolve_thread(&change_id, &thread_id) {
-        Ok(review) => Json(ReviewResponse { review: Some(review) }).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    match state.store.resolve_thread(&change_id, &thread_id, state.identity.human()) {
+        Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
@@ -753,12 +1318,344 @@ async fn reopen_thread(
     State(state): State<Arc<AppState>>,
     Path((change_id, thread_id)): Path<(String, String)>,
 ) -> impl IntoResponse {
-    match state.store.reopen_thread(&change_id, &thread_id) {
-        Ok(review) => Json(ReviewResponse { review: Some(review) }).into_response(),
+    match state.store.reopen_thread(&change_id, &thread_id, state.identity.human()) {
+        Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[derive(Deserialize)]
+struct SetStatusRequest {
+    status: ThreadStatus,
+    /// With status "addressed": the review revision number or commit id
+    /// that addresses the thread
+    addressed_in: Option<String>,
+}
+
+/// Move a thread to any status, recorded in its history as the reviewer
+async fn set_thread_status(
+    State(state): State<Arc<AppState>>,
+    Path((change_id, thread_id)): Path<(String, String)>,
+    Json(req): Json<SetStatusRequest>,
+) -> impl IntoResponse {
+    let author = state.identity.human();
+    let result = match (req.status, req.addressed_in.as_deref()) {
+        (ThreadStatus::Addressed, addressed_in) => {
+            state.store.mark_addressed(&change_id, &thread_id, author, addressed_in)
+        }
+        (_, Some(_)) => {
+            return (StatusCode::BAD_REQUEST, "addressed_in only goes with status \"addressed\"").into_response();
+        }
+        (status, None) => state.store.set_thread_status(&change_id, &thread_id, status, author),
+    };
+    match result {
+        Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::NOT_FOUND),
+    }
+}
+
+#[derive(Deserialize)]
+struct ThreadFixQuery {
+    /// Lines of context around each change (defaults to 3)
+    context: Option<usize>,
+    /// Optional session name — when set, queries the session's clone
+    session: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ThreadFixResponse {
+    /// The commit the thread was made on
+    from: String,
+    /// The commit that addresses it
+    to: String,
+    #[serde(flatten)]
+    interdiff: crate::interdiff::Interdiff,
+}
+
+/// What changed in the thread's region between the commit it was made on and
+/// the commit said to address it
+async fn get_thread_fix(
+    State(state): State<Arc<AppState>>,
+    Path((change_id, thread_id)): Path<(String, String)>,
+    Query(query): Query<ThreadFixQuery>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, query.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err((status, msg)) => return (status, msg).into_response(),
+    };
+    let review = match state.store.get_by_prefix(&change_id) {
+        Ok(Some(review)) => review,
+        Ok(None) => return (StatusCode::NOT_FOUND, format!("No review for change {change_id}")).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let thread = match review.find_thread(&thread_id) {
+        Ok(thread) => thread,
+        Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    };
+    let (Some(from), Some(to)) = (thread.created_at_commit.clone(), thread.addressed_in.clone()) else {
+        return (StatusCode::BAD_REQUEST, "The thread has no addressing commit to compare with").into_response();
+    };
+
+    let context = query.context.unwrap_or(crate::diff::DEFAULT_CONTEXT);
+    match crate::interdiff::thread_fix(vcs.as_ref(), thread, &to, context) {
+        Ok(interdiff) => Json(ThreadFixResponse { from, to, interdiff }).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
 
+#[derive(Deserialize)]
+struct EditCommentRequest {
+    text: String,
+}
+
+/// Change the text of one of the reviewer's comments
+async fn edit_comment(
+    State(state): State<Arc<AppState>>,
+    Path((change_id, thread_id, comment_id)): Path<(String, String, String)>,
+    Json(req): Json<EditCommentRequest>,
+) -> impl IntoResponse {
+    match state.store.edit_comment(&change_id, &thread_id, &comment_id, &state.identity.human(), &req.text) {
+        Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::BAD_REQUEST),
+    }
+}
+
+/// Delete one of the reviewer's comments
+async fn delete_comment(
+    State(state): State<Arc<AppState>>,
+    Path((change_id, thread_id, comment_id)): Path<(String, String, String)>,
+) -> impl IntoResponse {
+    match state.store.delete_comment(&change_id, &thread_id, &comment_id, &state.identity.human()) {
+        Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::BAD_REQUEST),
+    }
+}
+
+#[derive(Deserialize)]
+struct GcRequest {
+    /// Report without archiving
+    #[serde(default)]
+    dry_run: bool,
+    /// Archive reviews of merged changes too
+    #[serde(default)]
+    include_merged: bool,
+}
+
+/// Archive reviews of changes that are gone; see `aipair review gc`
+async fn gc_reviews(State(state): State<Arc<AppState>>, Json(req): Json<GcRequest>) -> impl IntoResponse {
+    let options = crate::gc::GcOptions { dry_run: req.dry_run, include_merged: req.include_merged };
+    let vcs = state.vcs();
+    match crate::gc::gc(vcs.as_ref(), &state.store, &state.sessions, options) {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => store_error(e, StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[derive(Deserialize)]
+struct OperationsQuery {
+    /// How many recent operations to look through
+    limit: Option<usize>,
+    /// Look in this session's clone rather than the main repo
+    session: Option<String>,
+}
+
+#[derive(Serialize)]
+struct OperationsResponse {
+    operations: Vec<crate::jj::Operation>,
+}
+
+/// The jj operations aipair ran, newest first
+async fn list_operations(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<OperationsQuery>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, query.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err(e) => return e.into_response(),
+    };
+    let jj = match vcs::require_jj(vcs.as_ref(), "Listing operations") {
+        Ok(jj) => jj,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match crate::undo::operations(jj, query.limit.unwrap_or(50)) {
+        Ok(operations) => Json(OperationsResponse { operations }).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct UndoRequest {
+    /// Also roll back operations aipair didn't make
+    #[serde(default)]
+    force: bool,
+    /// Undo in this session's clone rather than the main repo
+    session: Option<String>,
+}
+
+/// Restore the repo to just before one of aipair's operations; see
+/// `aipair undo`
+async fn undo_operation(
+    State(state): State<Arc<AppState>>,
+    Path(operation_id): Path<String>,
+    Json(req): Json<UndoRequest>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, req.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err(e) => return e.into_response(),
+    };
+    let jj = match vcs::require_jj(vcs.as_ref(), "Undo") {
+        Ok(jj) => jj,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match crate::undo::undo(jj, &operation_id, req.force) {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct SessionQuery {
+    /// Look in this session's clone rather than the main repo
+    session: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ConflictsResponse {
+    files: Vec<crate::conflict::ConflictedFile>,
+}
+
+/// The conflicted files of a change, with each conflict's sides and bases
+async fn get_conflicts(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Query(query): Query<SessionQuery>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, query.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err(e) => return e.into_response(),
+    };
+    let jj = match vcs::require_jj(vcs.as_ref(), "Conflicts") {
+        Ok(jj) => jj,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match crate::conflict::conflicted_files(jj, &change_id) {
+        Ok(files) => Json(ConflictsResponse { files }).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct ResolveConflictRequest {
+    path: String,
+    #[serde(flatten)]
+    resolution: crate::conflict::Resolution,
+    /// Add the resolution as a change on top instead of resolving the change
+    #[serde(default)]
+    separate: bool,
+    /// Resolve in this session's clone rather than the main repo
+    session: Option<String>,
+}
+
+/// Resolve one conflicted file by picking sides or supplying its text
+async fn resolve_conflict(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Json(req): Json<ResolveConflictRequest>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, req.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err(e) => return e.into_response(),
+    };
+    let jj = match vcs::require_jj(vcs.as_ref(), "Resolving conflicts") {
+        Ok(jj) => jj,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match crate::conflict::resolve(jj, &change_id, &req.path, &req.resolution, req.separate) {
+        Ok(resolved) => Json(resolved).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct ClassifyRequest {
+    severity: Option<Severity>,
+    /// Replaces the thread's labels
+    labels: Option<Vec<String>>,
+}
+
+/// Set a thread's severity and labels
+async fn classify_thread(
+    State(state): State<Arc<AppState>>,
+    Path((change_id, thread_id)): Path<(String, String)>,
+    Json(req): Json<ClassifyRequest>,
+) -> impl IntoResponse {
+    match state.store.classify_thread(&change_id, &thread_id, req.severity, req.labels) {
+        Ok(review) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::NOT_FOUND),
+    }
+}
+
+#[derive(Deserialize)]
+struct ApplySuggestionRequest {
+    /// Squash the edit into the change instead of adding a change on top
+    #[serde(default)]
+    squash: bool,
+    /// Apply in this session's clone rather than the main repo
+    session: Option<String>,
+}
+
+/// Apply a thread's suggested edit to the change
+async fn apply_suggestion(
+    State(state): State<Arc<AppState>>,
+    Path((change_id, thread_id)): Path<(String, String)>,
+    Json(req): Json<ApplySuggestionRequest>,
+) -> impl IntoResponse {
+    let vcs = match resolve_vcs_for_session(&state, req.session.as_deref()) {
+        Ok(vcs) => vcs,
+        Err(e) => return e.into_response(),
+    };
+    let review = match state.store.get_by_prefix(&change_id) {
+        Ok(Some(review)) => review,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Review not found").into_response(),
+        Err(e) => return store_error(e, StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    let jj = match vcs::require_jj(vcs.as_ref(), "Applying suggestions") {
+        Ok(jj) => jj,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match crate::suggestion::apply_suggestion(jj, &review, &thread_id, req.squash) {
+        Ok(applied) => Json(applied).into_response(),
+        Err(e) if crate::suggestion::is_conflict(&e) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct VerdictRequest {
+    /// Defaults to this machine's reviewer
+    reviewer: Option<String>,
+    verdict: VerdictKind,
+    comment: Option<String>,
+}
+
+/// Approve the change's current commit or request changes on it
+async fn set_verdict(
+    State(state): State<Arc<AppState>>,
+    Path(change_id): Path<String>,
+    Json(req): Json<VerdictRequest>,
+) -> impl IntoResponse {
+    let vcs = state.vcs();
+    let commit_id = match vcs.get_change(&change_id) {
+        Ok(change) => change.commit_id,
+        Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    };
+    let reviewer = req.reviewer.as_deref().unwrap_or(&state.identity.name);
+    match state.store.set_verdict(&change_id, reviewer, req.verdict, req.comment, &commit_id) {
+        Ok((review, _)) => Json(ReviewResponse { review: Some(visible_review(&state, review)) }).into_response(),
+        Err(e) => store_error(e, StatusCode::NOT_FOUND),
+    }
+}
+
 #[derive(Deserialize)]
 struct MergeRequest {
     #[serde(default)]
@@ -776,8 +1673,9 @@ async fn merge_change(
     Path(change_id): Path<String>,
     Json(req): Json<MergeRequest>,
 ) -> impl IntoResponse {
+    let vcs = state.vcs();
     // Check if already merged
-    let main_change_id = match state.jj.get_bookmark("main") {
+    let main_change_id = match vcs.get_bookmark("main") {
         Ok(id) => id,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
@@ -791,7 +1689,7 @@ async fn merge_change(
     }
 
     // Get current change info
-    let change = match state.jj.get_change(&change_id) {
+    let change = match vcs.get_change(&change_id) {
         Ok(change) => change,
         Err(e) => {
             return (
@@ -842,30 +1740,43 @@ async fn merge_change(
         }
 
         if let Some(review) = review {
-            let open_threads: Vec<_> = review
-                .threads
-                .iter()
-                .filter(|t| t.status == ThreadStatus::Open)
-                .collect();
+            // Suggestions, questions and nits don't hold up a merge
+            let blocking = review.threads.iter().filter(|t| t.blocks_merge()).count();
 
-            if !open_threads.is_empty() {
+            if blocking > 0 {
                 return (
                     StatusCode::BAD_REQUEST,
                     Json(MergeResponse {
                         success: false,
                         message: format!(
-                            "Cannot merge: {} open thread(s). Use force=true to override.",
-                            open_threads.len()
+                            "Cannot merge: {} open blocking thread(s). Use force=true to override.",
+                            blocking
                         ),
                     }),
                 )
                     .into_response();
             }
+
+            let blocker = match review.verdict_on(&current_commit_id) {
+                Some(VerdictKind::Approved) => None,
+                Some(VerdictKind::ChangesRequested) => Some("changes were requested"),
+                None => Some("the current commit has not been approved"),
+            };
+            if let Some(blocker) = blocker {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(MergeResponse {
+                        success: false,
+                        message: format!("Cannot merge: {blocker}. Use force=true to override."),
+                    }),
+                )
+                    .into_response();
+            }
         }
     }
 
     // Move the bookmark
-    match state.jj.move_bookmark("main", &change_id) {
+    match vcs.move_bookmark("main", &change_id) {
         Ok(()) => Json(MergeResponse {
             success: true,
             message: format!("Merged: main now at {}", &change_id[..8.min(change_id.len())]),
@@ -909,7 +1820,7 @@ async fn create_todo(
         req.after_id.as_deref(),
     ) {
         Ok(_id) => Json(tree).into_response(),
-        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        Err(e) => store_error(e, StatusCode::BAD_REQUEST),
     }
 }
 
@@ -932,17 +1843,17 @@ async fn update_todo(
     };
 
     // Update text/checked if provided
-    if req.text.is_some() || req.checked.is_some() {
-        if let Err(e) = state.todos.update_item(&mut tree, &id, req.text, req.checked) {
-            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
-        }
+    if (req.text.is_some() || req.checked.is_some())
+        && let Err(e) = state.todos.update_item(&mut tree, &id, req.text, req.checked)
+    {
+        return store_error(e, StatusCode::BAD_REQUEST);
     }
 
     // Move if parent_id is provided (including explicit null for "move to root")
     if req.parent_id.is_some() {
         let parent = req.parent_id.as_deref().filter(|s| !s.is_empty());
         if let Err(e) = state.todos.move_item(&mut tree, &id, parent, req.after_id.as_deref()) {
-            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+            return store_error(e, StatusCode::BAD_REQUEST);
         }
     }
 
@@ -959,12 +1870,17 @@ async fn delete_todo(
     };
 
     if let Err(e) = state.todos.delete_item(&mut tree, &id) {
-        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        return store_error(e, StatusCode::BAD_REQUEST);
     }
 
     Json(tree).into_response()
 }
 
+/// Who is reviewing from this machine
+async fn get_identity(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.identity.clone())
+}
+
 // --- Timeline endpoint ---
 
 #[derive(Deserialize)]
@@ -981,8 +1897,8 @@ async fn get_timeline(
     axum::extract::Query(query): axum::extract::Query<TimelineQuery>,
 ) -> impl IntoResponse {
     // Auto-import new Claude Code session content
-    let repo_path = state.jj.repo_path().to_path_buf();
-    if let Err(e) = state.timeline.import_claude_sessions(&repo_path) {
+    let repo_path = state.repo_path.clone();
+    if let Err(e) = state.timeline.import_claude_sessions(&repo_path, &state.identity.human()) {
         tracing::warn!("Failed to import Claude sessions: {}", e);
     }
 
@@ -1021,6 +1937,8 @@ struct SessionChangesQuery {
     /// "live", "latest", or a push index (0 = oldest)
     #[serde(default = "default_version")]
     version: String,
+    /// Whose unseen revisions and unread replies to report (defaults to this machine's reviewer)
+    reviewer: Option<String>,
 }
 
 fn default_version() -> String {
@@ -1055,8 +1973,12 @@ fn changes_to_status(changes: Vec<crate::jj::Change>, session_name: &str) -> Vec
         change,
         merged: false,
         open_thread_count: 0,
+        blocking_thread_count: 0,
         revision_count: 0,
         has_pending_changes: false,
+        has_unseen_revision: false,
+        unread_reply_count: 0,
+        verdict: None,
         session_name: Some(session_name.to_string()),
     }).collect()
 }
@@ -1072,28 +1994,30 @@ async fn get_session_changes(
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
 
+    let vcs = state.vcs();
     // What the base bookmark currently resolves to in the main repo
-    let base_current_commit_id = state.jj.get_change(&session.base_bookmark)
+    let base_current_commit_id = vcs.get_change(&session.base_bookmark)
         .ok().map(|c| c.commit_id);
 
+    let reviewer = query.reviewer.as_deref().unwrap_or(&state.identity.name);
     let (changes_with_status, base_commit_id) = if query.version == "live" {
         // Query the clone directory
-        let clone_path = state.jj.repo_path().join(&session.clone_path);
+        let clone_path = state.repo_path.join(&session.clone_path);
         if !clone_path.exists() {
             // No clone — fall back to latest pushed state
-            return get_session_changes_latest(&state, &session, &name).into_response();
+            return get_session_changes_latest(&state, vcs.as_ref(), &session, &name, reviewer).into_response();
         }
-        let clone_jj = Jj::new(&clone_path);
+        let clone_vcs = state.vcs_at(&clone_path);
         let revset = format!("{}@origin..visible_heads()", session.base_bookmark);
         // Base in the clone: what base_bookmark@origin resolves to
-        let base = clone_jj.get_change(&format!("{}@origin", session.base_bookmark))
+        let base = clone_vcs.get_change(&format!("{}@origin", session.base_bookmark))
             .ok().map(|c| c.commit_id);
-        match clone_jj.log_revset(&revset) {
+        match clone_vcs.log_revset(&revset) {
             Ok(changes) => (changes_to_status(changes, &name), base),
             Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
         }
     } else if query.version == "latest" {
-        return get_session_changes_latest(&state, &session, &name).into_response();
+        return get_session_changes_latest(&state, vcs.as_ref(), &session, &name, reviewer).into_response();
     } else if let Ok(push_idx) = query.version.parse::<usize>() {
         // Historical push — reconstruct from stored commit_ids
         if push_idx >= session.pushes.len() {
@@ -1102,18 +2026,18 @@ async fn get_session_changes(
         let push = &session.pushes[push_idx];
         if push.changes.is_empty() {
             // Old push without snapshot data — fall back to latest
-            return get_session_changes_latest(&state, &session, &name).into_response();
+            return get_session_changes_latest(&state, vcs.as_ref(), &session, &name, reviewer).into_response();
         }
         // Query the clone (not main repo) — push snapshot commit IDs are clone-local
-        let clone_path = state.jj.repo_path().join(&session.clone_path);
-        let jj = if clone_path.exists() {
-            Jj::new(&clone_path)
+        let clone_path = state.repo_path.join(&session.clone_path);
+        let vcs = if clone_path.exists() {
+            state.vcs_at(&clone_path)
         } else {
-            Jj::new(state.jj.repo_path())
+            state.vcs()
         };
         let commit_ids: Vec<&str> = push.changes.iter().map(|c| c.commit_id.as_str()).collect();
         let revset = commit_ids.join(" | ");
-        match jj.log_revset(&revset) {
+        match vcs.log_revset(&revset) {
             Ok(changes) => (changes_to_status(changes, &name), None),
             Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
         }
@@ -1133,14 +2057,16 @@ async fn get_session_changes(
 /// Helper: get changes from the current pushed state (main repo bookmark).
 fn get_session_changes_latest(
     state: &AppState,
+    vcs: &dyn Vcs,
     session: &crate::session::Session,
     name: &str,
+    reviewer: &str,
 ) -> Json<SessionChangesResponse> {
     let revset = format!("{}..{}", session.base_bookmark, session.bookmark);
-    let changes = state.jj.log_revset(&revset).unwrap_or_default();
+    let changes = vcs.log_revset(&revset).unwrap_or_default();
 
     // For "latest", base is the current base bookmark in the main repo
-    let base_commit_id = state.jj.get_change(&session.base_bookmark)
+    let base_commit_id = vcs.get_change(&session.base_bookmark)
         .ok().map(|c| c.commit_id);
 
     // Load reviews for thread/revision info
@@ -1150,7 +2076,7 @@ fn get_session_changes_latest(
         .map(|r| (r.change_id.clone(), r))
         .collect();
 
-    let main_change_id = state.jj.get_bookmark("main").ok().flatten();
+    let main_change_id = vcs.get_bookmark("main").ok().flatten();
 
     let changes_with_status: Vec<ChangeWithStatus> = changes
         .into_iter()
@@ -1159,7 +2085,7 @@ fn get_session_changes_latest(
             let (open_thread_count, revision_count, has_pending_changes) = review_map
                 .get(&change.change_id)
                 .map(|r| {
-                    let open = r.threads.iter().filter(|t| t.status == ThreadStatus::Open).count();
+                    let open = r.threads.iter().filter(|t| t.status.is_unresolved()).count();
                     let pending = match (r.working_commit_id.as_ref(), r.revisions.last()) {
                         (Some(working), Some(last_rev)) => working != &last_rev.commit_id,
                         (Some(_), None) => true,
@@ -1172,12 +2098,22 @@ fn get_session_changes_latest(
                 .get(&change.change_id)
                 .map(|r| r.working_commit_id.as_ref().map(|w| w != &change.commit_id).unwrap_or(false))
                 .unwrap_or(false);
+            let (has_unseen_revision, unread_reply_count) =
+                unseen_status(review_map.get(&change.change_id), &change.commit_id, reviewer);
+            let verdict = review_map.get(&change.change_id).and_then(|r| r.verdict_on(&change.commit_id));
+            let blocking_thread_count = review_map
+                .get(&change.change_id)
+                .map_or(0, |r| r.threads.iter().filter(|t| t.blocks_merge()).count());
             ChangeWithStatus {
                 change,
                 merged,
                 open_thread_count,
+                blocking_thread_count,
                 revision_count,
                 has_pending_changes,
+                has_unseen_revision,
+                unread_reply_count,
+                verdict,
                 session_name: Some(name.to_string()),
             }
         })
@@ -1207,7 +2143,8 @@ async fn create_session(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreateSessionRequest>,
 ) -> impl IntoResponse {
-    match crate::session::session_new_inner(&state.jj, &state.sessions, &req.name, &req.base) {
+    let vcs = state.vcs();
+    match crate::session::session_new_inner(vcs.as_ref(), &state.sessions, &req.name, &req.base) {
         Ok(_session) => Json(MergeResponse {
             success: true,
             message: format!("Session '{}' created", req.name),
@@ -1227,6 +2164,7 @@ async fn create_session(
 async fn merge_session(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
+    Json(req): Json<MergeRequest>,
 ) -> impl IntoResponse {
     let mut session = match state.sessions.get(&name) {
         Ok(Some(s)) => s,
@@ -1248,12 +2186,13 @@ async fn merge_session(
         .into_response();
     }
 
+    let vcs = state.vcs();
     // Fetch to get latest from clone's pushes
-    let _ = state.jj.git_fetch();
+    let _ = vcs.git_fetch();
 
     // Find session bookmark tip
     let bookmark = &session.bookmark;
-    let session_tip = match state.jj.get_bookmark(bookmark) {
+    let session_tip = match vcs.get_bookmark(bookmark) {
         Ok(Some(id)) => id,
         Ok(None) => {
             return Json(MergeResponse {
@@ -1265,18 +2204,39 @@ async fn merge_session(
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
 
+    // Every change being merged needs an approval on its current commit
+    if !req.force {
+        let blocked = vcs
+            .log_revset(&format!("{}..{}", session.base_bookmark, bookmark))
+            .and_then(|changes| state.store.unapproved(&changes));
+        match blocked {
+            Ok(blocked) if blocked.is_empty() => {}
+            Ok(blocked) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(MergeResponse {
+                        success: false,
+                        message: format!("Cannot merge: {}. Use force=true to override.", blocked.join("; ")),
+                    }),
+                )
+                    .into_response();
+            }
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        }
+    }
+
     // Move main bookmark to session tip
-    if let Err(e) = state.jj.move_bookmark("main", &session_tip) {
+    if let Err(e) = vcs.move_bookmark("main", &session_tip) {
         return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
     }
 
     // Delete session bookmark
-    let _ = state.jj.bookmark_delete(bookmark);
+    let _ = vcs.bookmark_delete(bookmark);
 
     // Update status
     session.status = SessionStatus::Merged;
-    if let Err(e) = state.sessions.save(&session) {
-        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    if let Err(e) = state.sessions.save(&mut session) {
+        return store_error(e, StatusCode::INTERNAL_SERVER_ERROR);
     }
 
     Json(MergeResponse {
@@ -1315,7 +2275,7 @@ async fn terminal_ws(
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     };
 
-    let clone_path = state.jj.repo_path().join(&session.clone_path);
+    let clone_path = state.repo_path.join(&session.clone_path);
     if !clone_path.exists() {
         return (StatusCode::NOT_FOUND, format!("Clone for session '{name}' not found")).into_response();
     }
@@ -1371,23 +2331,23 @@ async fn handle_terminal(socket: WebSocket, name: String, working_dir: std::path
         while let Some(Ok(msg)) = ws_receiver.next().await {
             match msg {
                 Message::Binary(data) => {
-                    if writer.write_all(&data).is_err() {
+                    let Ok(()) = writer.write_all(&data) else {
                         break;
-                    }
+                    };
                 }
                 Message::Text(text) => {
                     // JSON control messages (resize)
-                    if let Ok(ctrl) = serde_json::from_str::<serde_json::Value>(&text) {
-                        if ctrl.get("type").and_then(|v| v.as_str()) == Some("resize") {
-                            let new_cols = ctrl.get("cols").and_then(|v| v.as_u64()).unwrap_or(80) as u16;
-                            let new_rows = ctrl.get("rows").and_then(|v| v.as_u64()).unwrap_or(24) as u16;
-                            let _ = master.resize(portable_pty::PtySize {
-                                rows: new_rows,
-                                cols: new_cols,
-                                pixel_width: 0,
-                                pixel_height: 0,
-                            });
-                        }
+                    if let Ok(ctrl) = serde_json::from_str::<serde_json::Value>(&text)
+                        && ctrl.get("type").and_then(|v| v.as_str()) == Some("resize")
+                    {
+                        let new_cols = ctrl.get("cols").and_then(|v| v.as_u64()).unwrap_or(80) as u16;
+                        let new_rows = ctrl.get("rows").and_then(|v| v.as_u64()).unwrap_or(24) as u16;
+                        let _ = master.resize(portable_pty::PtySize {
+                            rows: new_rows,
+                            cols: new_cols,
+                            pixel_width: 0,
+                            pixel_height: 0,
+                        });
                     }
                 }
                 Message::Close(_) => break,
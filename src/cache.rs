@@ -0,0 +1,119 @@
+//! In-process caches for results that are expensive to recompute.
+//!
+//! Anything computed from commits alone (diffs, file contents, where a thread
+//! lands in a later commit) never goes stale, since commits are immutable.
+//! Anything that resolves names (revsets, bookmarks, change ids) holds only
+//! for the operation it was resolved at; `vcs::Cached` keys those by it. A cache
+//! that fills up is emptied rather than evicting one entry at a time.
+//!
+//! Every lookup is traced at `trace` level, and each cache reports its hits
+//! and misses at `debug` level every `REPORT_EVERY` lookups.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, trace};
+
+/// How many lookups pass between reports of a cache's hit rate
+const REPORT_EVERY: u64 = 100;
+
+pub struct Cache<K, V> {
+    name: &'static str,
+    capacity: usize,
+    entries: Mutex<HashMap<K, V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Eq + Hash, V: Clone> Cache<K, V> {
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let value = self.entries.lock().unwrap().get(key).cloned();
+        self.record(value.is_some());
+        value
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            debug!(cache = self.name, entries = entries.len(), "Cache full, emptying it");
+            entries.clear();
+        }
+        entries.insert(key, value);
+    }
+
+    /// The cached value for `key`, or the one `compute` returns, which is
+    /// cached unless it's an error. `compute` runs without holding the lock.
+    pub fn get_or_try_insert(&self, key: K, compute: impl FnOnce() -> Result<V>) -> Result<V> {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = compute()?;
+        self.insert(key, value.clone());
+        Ok(value)
+    }
+
+    fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        trace!(cache = self.name, hit, "Cache lookup");
+
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        if (hits + misses).is_multiple_of(REPORT_EVERY) {
+            debug!(
+                cache = self.name,
+                hits,
+                misses,
+                hit_rate = format!("{:.0}%", 100.0 * hits as f64 / (hits + misses) as f64),
+                "Cache metrics"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caches_values_but_not_errors() {
+        let cache: Cache<&str, usize> = Cache::new("test", 10);
+        let mut calls = 0;
+        let mut compute = |value: Result<usize>| {
+            calls += 1;
+            value
+        };
+
+        assert!(cache.get_or_try_insert("a", || compute(Err(anyhow::anyhow!("failed")))).is_err());
+        assert_eq!(cache.get_or_try_insert("a", || compute(Ok(1))).unwrap(), 1);
+        assert_eq!(cache.get_or_try_insert("a", || compute(Ok(2))).unwrap(), 1);
+        assert_eq!(calls, 2);
+        assert_eq!(cache.hits.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_empties_when_full() {
+        let cache = Cache::new("test", 2);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        cache.insert(2, "still two");
+        assert_eq!(cache.get(&1), Some("one"));
+
+        cache.insert(3, "three");
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&3), Some("three"));
+    }
+}
@@ -0,0 +1,439 @@
+//! Conflicted files of a change, and resolving them from the review UI.
+//!
+//! jj keeps conflicts in the commit and only writes markers when a file is
+//! materialized. We ask for snapshot-style markers, where every side and
+//! base is written out in full:
+//!
+//! ```text
+//! <<<<<<< Conflict 1 of 1
+//! +++++++ Contents of side #1
+//! ...
+//! ------- Contents of base
+//! ...
+//! +++++++ Contents of side #2
+//! ...
+//! >>>>>>> Conflict 1 of 1 ends
+//! ```
+//!
+//! Diff-style `%%%%%%%` sections are understood too. A side that deletes
+//! the file is written out as empty, so which sides are absent comes from
+//! `jj resolve --list` instead. A resolution picks a side per conflict or
+//! replaces the file outright, and is written back the way suggestions are:
+//! through a scratch workspace, into the conflicted change or as a change on
+//! top of it.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::jj::Jj;
+use crate::vcs::Vcs;
+
+/// Length of the shortest conflict marker; jj uses longer ones when the
+/// file itself contains lines that look like markers
+const MIN_MARKER_LEN: usize = 7;
+
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct ConflictedFile {
+    pub path: String,
+    pub regions: Vec<ConflictRegion>,
+    /// Whether the last line ends in a newline
+    pub final_newline: bool,
+}
+
+impl ConflictedFile {
+    /// Parse materialized content, with `deletions` of the sides deleting
+    /// the file. Such a conflict spans the whole file, since an absent side
+    /// shares no lines with the others, and its empty sides are the absent
+    /// ones.
+    pub fn parse(path: String, content: &str, deletions: usize) -> Result<Self> {
+        let mut regions = parse(content)?;
+        if deletions > 0
+            && let [ConflictRegion::Conflict { sides, .. }] = regions.as_mut_slice()
+        {
+            for side in sides.iter_mut().filter(|side| side.as_ref().is_some_and(Vec::is_empty)).take(deletions) {
+                *side = None;
+            }
+        }
+        // A conflict's last line is always followed by its end marker
+        let final_newline = content.ends_with('\n') || !matches!(regions.last(), Some(ConflictRegion::Resolved { .. }));
+        Ok(Self { path, regions, final_newline })
+    }
+
+    pub fn conflict_count(&self) -> usize {
+        self.regions.iter().filter(|r| matches!(r, ConflictRegion::Conflict { .. })).count()
+    }
+
+    /// The file with each conflict replaced by the chosen side, or None if
+    /// a chosen side deletes it
+    pub fn render(&self, sides: &[usize]) -> Result<Option<String>> {
+        let mut chosen = sides.iter();
+        let mut lines: Vec<&str> = Vec::new();
+        let mut deleted = false;
+        for region in &self.regions {
+            match region {
+                ConflictRegion::Resolved { lines: resolved } => lines.extend(resolved.iter().map(String::as_str)),
+                ConflictRegion::Conflict { sides, .. } => {
+                    let side = *chosen.next().context("Not every conflict has a side chosen")?;
+                    match sides.get(side).with_context(|| format!("There is no side #{}", side + 1))? {
+                        Some(side) => lines.extend(side.iter().map(String::as_str)),
+                        None => deleted = true,
+                    }
+                }
+            }
+        }
+        if chosen.next().is_some() {
+            anyhow::bail!("More sides chosen than there are conflicts");
+        }
+        if deleted {
+            return Ok(None);
+        }
+        let mut out = lines.join("\n");
+        if self.final_newline && !lines.is_empty() {
+            out.push('\n');
+        }
+        Ok(Some(out))
+    }
+}
+
+/// A stretch of a conflicted file: either text all sides agree on, or a
+/// conflict between the sides
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConflictRegion {
+    Resolved {
+        lines: Vec<String>,
+    },
+    Conflict {
+        /// What each side has here, in jj's side order; None for a side
+        /// that deletes the file
+        sides: Vec<Option<Vec<String>>>,
+        /// The common ancestors the sides diverged from, one fewer than sides
+        bases: Vec<Vec<String>>,
+    },
+}
+
+/// How to resolve one file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// The side to take for each conflict, by index into its `sides`
+    Sides(Vec<usize>),
+    /// The file's full resolved content
+    Text(String),
+}
+
+/// What resolving produced
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedConflict {
+    /// The conflicted change, or the new change on top of it
+    pub change_id: String,
+    pub path: String,
+    /// Conflicts left in the change after resolving
+    pub remaining: Vec<String>,
+}
+
+/// The conflicted files of `revision`, with their conflicts parsed
+pub fn conflicted_files(jj: &Jj, revision: &str) -> Result<Vec<ConflictedFile>> {
+    jj.conflicted_files(revision)?
+        .into_iter()
+        .map(|(path, deletions)| {
+            let content = jj.show_file_with_conflicts(revision, &path)?;
+            ConflictedFile::parse(path.clone(), &content, deletions)
+                .with_context(|| format!("Failed to parse conflicts in {path}"))
+        })
+        .collect()
+}
+
+/// Resolve one conflicted file of `change_id`, in the change itself or, with
+/// `separate`, as a new change on top of it. The file is written in a scratch
+/// workspace, so the working copy is left alone.
+pub fn resolve(jj: &Jj, change_id: &str, path: &str, resolution: &Resolution, separate: bool) -> Result<ResolvedConflict> {
+    let file = conflicted_files(jj, change_id)?
+        .into_iter()
+        .find(|f| f.path == path)
+        .with_context(|| format!("{path} has no conflicts in {change_id}"))?;
+    let content = match resolution {
+        Resolution::Sides(sides) => file.render(sides)?,
+        Resolution::Text(text) => Some(text.clone()),
+    };
+
+    let change_id = jj.in_scratch_workspace(change_id, |scratch| {
+        let file = scratch.repo_path().join(path);
+        match &content {
+            Some(content) => std::fs::write(&file, content).with_context(|| format!("Failed to write {path}"))?,
+            None => std::fs::remove_file(&file).with_context(|| format!("Failed to delete {path}"))?,
+        }
+        if separate {
+            scratch.describe(&format!("Resolve conflicts in {path}"))?;
+            scratch.working_copy_change_id()
+        } else {
+            scratch.squash_into("@", change_id)?;
+            Ok(change_id.to_string())
+        }
+    })?;
+    let remaining = jj.conflicted_files(&change_id)?.into_iter().map(|(path, _)| path).collect();
+    Ok(ResolvedConflict { change_id, path: path.to_string(), remaining })
+}
+
+/// A marker line: `ch` repeated at least `MIN_MARKER_LEN` times (exactly
+/// `len` times if given), then a space or the end of the line. Returns the
+/// marker's length.
+fn marker(line: &str, ch: char, len: Option<usize>) -> Option<usize> {
+    let count = line.chars().take_while(|&c| c == ch).count();
+    let rest = &line[count..];
+    let ok = match len {
+        Some(len) => count == len,
+        None => count >= MIN_MARKER_LEN,
+    };
+    (ok && (rest.is_empty() || rest.starts_with(' '))).then_some(count)
+}
+
+/// Split materialized file content into resolved text and conflicts
+pub fn parse(content: &str) -> Result<Vec<ConflictRegion>> {
+    enum Section {
+        Side,
+        Base,
+        Diff,
+    }
+
+    let mut regions = Vec::new();
+    let mut resolved: Vec<String> = Vec::new();
+    let mut lines = content.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let Some(len) = marker(line, '<', None) else {
+            resolved.push(line.to_string());
+            continue;
+        };
+        if !resolved.is_empty() {
+            regions.push(ConflictRegion::Resolved { lines: std::mem::take(&mut resolved) });
+        }
+
+        let (mut sides, mut bases): (Vec<Vec<String>>, Vec<Vec<String>>) = (Vec::new(), Vec::new());
+        let mut section = None;
+        loop {
+            let (_, line) = lines
+                .next()
+                .with_context(|| format!("Conflict starting on line {} never ends", number + 1))?;
+            if marker(line, '>', Some(len)).is_some() {
+                break;
+            } else if marker(line, '\\', Some(len)).is_some() {
+                // The second header line of a diff section
+                continue;
+            } else if marker(line, '+', Some(len)).is_some() {
+                sides.push(Vec::new());
+                section = Some(Section::Side);
+            } else if marker(line, '-', Some(len)).is_some() {
+                bases.push(Vec::new());
+                section = Some(Section::Base);
+            } else if marker(line, '%', Some(len)).is_some() {
+                sides.push(Vec::new());
+                bases.push(Vec::new());
+                section = Some(Section::Diff);
+            } else {
+                let (side, base) = (sides.last_mut(), bases.last_mut());
+                match (&section, side, base) {
+                    (Some(Section::Side), Some(side), _) => side.push(line.to_string()),
+                    (Some(Section::Base), _, Some(base)) => base.push(line.to_string()),
+                    (Some(Section::Diff), Some(side), Some(base)) => match line.split_at_checked(1) {
+                        Some(("+", text)) => side.push(text.to_string()),
+                        Some(("-", text)) => base.push(text.to_string()),
+                        Some((" ", text)) => {
+                            side.push(text.to_string());
+                            base.push(text.to_string());
+                        }
+                        _ if line.is_empty() => {
+                            side.push(String::new());
+                            base.push(String::new());
+                        }
+                        _ => anyhow::bail!("Unexpected line {} in a diff section: {line}", number + 1),
+                    },
+                    _ => anyhow::bail!("Text before the first section of the conflict on line {}", number + 1),
+                }
+            }
+        }
+        regions.push(ConflictRegion::Conflict { sides: sides.into_iter().map(Some).collect(), bases });
+    }
+    if !resolved.is_empty() {
+        regions.push(ConflictRegion::Resolved { lines: resolved });
+    }
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn jj_cmd(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("jj").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("jj {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn log(dir: &std::path::Path, rev: &str, template: &str) -> String {
+        jj_cmd(dir, &["log", "--no-graph", "-r", rev, "-T", template]).trim().to_string()
+    }
+
+    const SNAPSHOT: &str = "\
+fn main() {
+<<<<<<< Conflict 1 of 1
++++++++ Contents of side #1
+    println!(\"one\");
+------- Contents of base
+    println!(\"zero\");
++++++++ Contents of side #2
+    println!(\"two\");
+    println!(\"more\");
+>>>>>>> Conflict 1 of 1 ends
+}
+";
+
+    #[test]
+    fn test_parse_snapshot_markers() {
+        let regions = parse(SNAPSHOT).unwrap();
+        assert_eq!(
+            regions,
+            vec![
+                ConflictRegion::Resolved { lines: vec!["fn main() {".into()] },
+                ConflictRegion::Conflict {
+                    sides: vec![
+                        Some(vec!["    println!(\"one\");".into()]),
+                        Some(vec!["    println!(\"two\");".into(), "    println!(\"more\");".into()]),
+                    ],
+                    bases: vec![vec!["    println!(\"zero\");".into()]],
+                },
+                ConflictRegion::Resolved { lines: vec!["}".into()] },
+            ]
+        );
+
+        let file = ConflictedFile::parse("main.rs".into(), SNAPSHOT, 0).unwrap();
+        assert_eq!(
+            file.render(&[1]).unwrap().unwrap(),
+            "fn main() {\n    println!(\"two\");\n    println!(\"more\");\n}\n"
+        );
+        assert!(file.render(&[2]).is_err());
+        assert!(file.render(&[]).is_err());
+        assert!(file.render(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_parse_diff_markers() {
+        let content = "\
+<<<<<<< Conflict 1 of 1
+%%%%%%% diff from: base
+\\\\\\\\\\\\\\        to: side #1
+ keep
+-old
++new
++++++++ Contents of side #2
+other
+>>>>>>> Conflict 1 of 1 ends
+";
+        let regions = parse(content).unwrap();
+        assert_eq!(
+            regions,
+            vec![ConflictRegion::Conflict {
+                sides: vec![Some(vec!["keep".into(), "new".into()]), Some(vec!["other".into()])],
+                bases: vec![vec!["keep".into(), "old".into()]],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_longer_markers_leave_marker_like_text_alone() {
+        let content = "\
+<<<<<<<<<<< Conflict 1 of 1
++++++++++++ Contents of side #1
+<<<<<<< not a marker
+----------- Contents of base
++++++++++++ Contents of side #2
+>>>>>>>>>>> Conflict 1 of 1 ends
+";
+        let regions = parse(content).unwrap();
+        assert_eq!(
+            regions,
+            vec![ConflictRegion::Conflict {
+                sides: vec![Some(vec!["<<<<<<< not a marker".into()]), Some(vec![])],
+                bases: vec![vec![]],
+            }]
+        );
+        assert!(parse("<<<<<<< Conflict 1 of 1\n+++++++ side\nx\n").is_err());
+    }
+
+    #[test]
+    fn test_render_keeps_a_missing_final_newline() {
+        let content = SNAPSHOT.strip_suffix('\n').unwrap();
+        let file = ConflictedFile::parse("main.rs".into(), content, 0).unwrap();
+        assert!(!file.final_newline);
+        assert_eq!(file.render(&[0]).unwrap().unwrap(), "fn main() {\n    println!(\"one\");\n}");
+    }
+
+    #[test]
+    fn test_absent_side_deletes_the_file() {
+        let content = "\
+<<<<<<< Conflict 1 of 1
++++++++ Contents of side #1
+one
+TWO
+------- Contents of base
+one
+two
++++++++ Contents of side #2
+>>>>>>> Conflict 1 of 1 ends
+";
+        let file = ConflictedFile::parse("a.txt".into(), content, 1).unwrap();
+        let ConflictRegion::Conflict { sides, .. } = &file.regions[0] else {
+            panic!("expected a conflict: {:?}", file.regions);
+        };
+        assert_eq!(sides, &vec![Some(vec!["one".to_string(), "TWO".to_string()]), None]);
+        assert_eq!(file.render(&[0]).unwrap().as_deref(), Some("one\nTWO\n"));
+        assert_eq!(file.render(&[1]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_rebase_conflict() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path();
+        jj_cmd(path, &["git", "init"]);
+        std::fs::write(path.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        jj_cmd(path, &["new", "-m", "ours"]);
+        std::fs::write(path.join("a.txt"), "one\nTWO\nthree\n").unwrap();
+        let ours = log(path, "@", "change_id");
+        jj_cmd(path, &["new", "-m", "theirs", "@-"]);
+        std::fs::write(path.join("a.txt"), "one\n2\nthree\n").unwrap();
+        let theirs = log(path, "@", "change_id");
+        jj_cmd(path, &["rebase", "-r", &theirs, "-d", &ours]);
+        jj_cmd(path, &["new", "root()"]);
+        let jj = Jj::new(path);
+
+        let files = conflicted_files(&jj, &theirs).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "a.txt");
+        assert_eq!(files[0].conflict_count(), 1);
+        let ConflictRegion::Conflict { sides, bases } = &files[0].regions[1] else {
+            panic!("expected a conflict: {:?}", files[0].regions);
+        };
+        assert_eq!(bases, &vec![vec!["two".to_string()]]);
+        let two = Some(vec!["2".to_string()]);
+        assert!(sides.contains(&two));
+        let pick = sides.iter().position(|s| s == &two).unwrap();
+
+        // Uncommitted edits in the working copy must stay there
+        std::fs::write(path.join("notes.txt"), "wip\n").unwrap();
+        let original = log(path, "@", "change_id");
+        let resolved = resolve(&jj, &theirs, "a.txt", &Resolution::Sides(vec![pick]), false).unwrap();
+        assert_eq!(resolved.change_id, theirs);
+        assert!(resolved.remaining.is_empty());
+        assert_eq!(jj.show_file(&theirs, "a.txt").unwrap(), "one\n2\nthree\n");
+        assert_eq!(log(path, "@", "change_id"), original, "working copy should be left alone");
+        assert_eq!(jj.show_file("@", "notes.txt").unwrap(), "wip\n");
+        assert!(jj.show_file(&theirs, "notes.txt").is_err());
+        assert_eq!(std::fs::read_to_string(path.join("a.txt")).ok(), None);
+    }
+}
@@ -0,0 +1,379 @@
+//! Structured unified diffs.
+//!
+//! `jj diff --git` output is parsed once, on the server, into per-file hunks.
+//! The UI renders them directly (asking for more lines through the `/lines`
+//! endpoint when the user expands context) and the line mapper walks the
+//! same hunks to follow threads from one commit to the next.
+
+use serde::Serialize;
+use ts_rs::TS;
+
+/// Lines of context around each change when the caller doesn't ask for more
+pub const DEFAULT_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    /// Text after the closing `@@`, usually the enclosing function
+    pub section: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub content: String,
+    /// Line number in the old file (None for added lines)
+    pub old_line: Option<usize>,
+    /// Line number in the new file (None for deleted lines)
+    pub new_line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Context,
+    Add,
+    Delete,
+}
+
+/// One file's section of a git-format diff
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilePatch {
+    /// Path on the new side (the old path for deleted files)
+    pub path: String,
+    pub hunks: Vec<Hunk>,
+    pub binary: bool,
+}
+
+/// A slice of a file at some revision, for expanding context in the UI
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct FileLines {
+    /// Line number of the first entry in `lines`
+    pub start: usize,
+    pub lines: Vec<String>,
+    pub total_lines: usize,
+}
+
+/// Parse the output of `jj diff --git` (any number of files).
+pub fn parse(diff_text: &str) -> Vec<FilePatch> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    // Next line numbers on each side while inside a hunk
+    let mut pos: Option<(usize, usize)> = None;
+
+    for line in diff_text.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git a/") {
+            // "a/<path> b/<path>": both halves are equal unless the file was
+            // renamed or copied, in which case "rename to" names it below
+            let path = paths.get(..paths.len().saturating_sub(3) / 2).unwrap_or(paths);
+            files.push(FilePatch { path: path.to_string(), ..Default::default() });
+            pos = None;
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        if line.starts_with("@@") {
+            pos = parse_hunk_header(line).map(|hunk| {
+                let start = (hunk.old_start, hunk.new_start);
+                file.hunks.push(hunk);
+                start
+            });
+        } else if let Some((old, new)) = pos.as_mut()
+            && let Some(hunk) = file.hunks.last_mut()
+        {
+            let (kind, content) = match line.split_at_checked(1) {
+                Some(("+", rest)) => (LineKind::Add, rest),
+                Some(("-", rest)) => (LineKind::Delete, rest),
+                Some((" ", rest)) => (LineKind::Context, rest),
+                None => (LineKind::Context, ""),
+                // "\ No newline at end of file" and the like
+                _ => continue,
+            };
+            let old_line = (kind != LineKind::Add).then(|| post_inc(old));
+            let new_line = (kind != LineKind::Delete).then(|| post_inc(new));
+            hunk.lines.push(DiffLine { kind, content: content.to_string(), old_line, new_line });
+        } else if let Some(to) = line.strip_prefix("rename to ").or_else(|| line.strip_prefix("copy to ")) {
+            file.path = to.to_string();
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        }
+    }
+
+    files
+}
+
+/// Hunks for a single file of a git-format diff; empty if the file isn't in it.
+pub fn file_hunks(diff_text: &str, path: &str) -> Vec<Hunk> {
+    parse(diff_text)
+        .into_iter()
+        .find(|f| f.path == path)
+        .map(|f| f.hunks)
+        .unwrap_or_default()
+}
+
+fn post_inc(n: &mut usize) -> usize {
+    *n += 1;
+    *n - 1
+}
+
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    // @@ -old_start,old_count +new_start,new_count @@ section
+    // or @@ -old_start +new_start,new_count @@ (count defaults to 1)
+    let rest = line.strip_prefix("@@ ")?;
+    let (ranges, section) = rest.split_once(" @@").unwrap_or((rest, ""));
+    let (old_part, new_part) = ranges.split_once(' ')?;
+
+    let (old_start, old_count) = parse_range(old_part.trim_start_matches('-'))?;
+    let (new_start, new_count) = parse_range(new_part.trim_start_matches('+'))?;
+
+    Some(Hunk {
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+        section: section.trim().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    if let Some((start, count)) = s.split_once(',') {
+        Some((start.parse().ok()?, count.parse().ok()?))
+    } else {
+        Some((s.parse().ok()?, 1))
+    }
+}
+
+/// Hunks turning `old` into `new`, for content that didn't come out of
+/// `jj diff` (such as an in-memory rebase).
+pub fn diff_texts(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    use similar::{ChangeTag, TextDiff};
+
+    let diff = TextDiff::from_lines(old, new);
+    diff.grouped_ops(context)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            // Like git, an empty side names the line before it
+            let start = |range: &std::ops::Range<usize>| range.start + usize::from(!range.is_empty());
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| DiffLine {
+                    kind: match change.tag() {
+                        ChangeTag::Equal => LineKind::Context,
+                        ChangeTag::Insert => LineKind::Add,
+                        ChangeTag::Delete => LineKind::Delete,
+                    },
+                    content: change.value().trim_end_matches('\n').to_string(),
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                })
+                .collect();
+            Some(Hunk {
+                old_start: start(&old_range),
+                old_count: old_range.len(),
+                new_start: start(&new_range),
+                new_count: new_range.len(),
+                section: String::new(),
+                lines,
+            })
+        })
+        .collect()
+}
+
+/// Lines `start..=end` (1-based, clamped to the file) of `content`.
+pub fn line_range(content: &str, start: usize, end: usize) -> FileLines {
+    let all: Vec<&str> = content.lines().collect();
+    let start = start.max(1);
+    let end = end.min(all.len());
+    let lines = if start <= end {
+        all[start - 1..end].iter().map(|l| l.to_string()).collect()
+    } else {
+        Vec::new()
+    };
+    FileLines { start, lines, total_lines: all.len() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hunk_header() {
+        let hunk = parse_hunk_header("@@ -10,5 +12,7 @@ some context").unwrap();
+        assert_eq!(hunk.old_start, 10);
+        assert_eq!(hunk.old_count, 5);
+        assert_eq!(hunk.new_start, 12);
+        assert_eq!(hunk.new_count, 7);
+    }
+
+    #[test]
+    fn test_parse_hunk_header_no_count() {
+        let hunk = parse_hunk_header("@@ -1 +1,3 @@").unwrap();
+        assert_eq!(hunk.old_start, 1);
+        assert_eq!(hunk.old_count, 1);
+        assert_eq!(hunk.new_start, 1);
+        assert_eq!(hunk.new_count, 3);
+    }
+
+    #[test]
+    fn test_file_hunks_single_file() {
+        let diff = "\
+diff --git a/src/main.rs b/src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -5,3 +5,5 @@ fn main() {
+     let x = 1;
++    let y = 2;
++    let z = 3;
+     let a = 4;
+     let b = 5;
+";
+        let hunks = file_hunks(diff, "src/main.rs");
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].old_start, 5);
+        assert_eq!(hunks[0].old_count, 3);
+        assert_eq!(hunks[0].new_start, 5);
+        assert_eq!(hunks[0].new_count, 5);
+    }
+
+    #[test]
+    fn test_file_hunks_multi_file_diff() {
+        // Verify we only get hunks for the target file
+        let diff = "\
+diff --git a/foo.rs b/foo.rs
+--- a/foo.rs
++++ b/foo.rs
+@@ -1,3 +1,4 @@
+ line1
++inserted
+ line2
+ line3
+diff --git a/bar.rs b/bar.rs
+--- a/bar.rs
++++ b/bar.rs
+@@ -1,2 +1,3 @@
+ a
++b
+ c
+";
+        let foo_hunks = file_hunks(diff, "foo.rs");
+        assert_eq!(foo_hunks.len(), 1);
+        assert_eq!(foo_hunks[0].old_count, 3);
+        assert_eq!(foo_hunks[0].new_count, 4);
+
+        let bar_hunks = file_hunks(diff, "bar.rs");
+        assert_eq!(bar_hunks.len(), 1);
+        assert_eq!(bar_hunks[0].old_count, 2);
+        assert_eq!(bar_hunks[0].new_count, 3);
+
+        let missing = file_hunks(diff, "nope.rs");
+        assert!(missing.is_empty());
+    }
+
+
+    #[test]
+    fn test_parse_numbers_lines_on_both_sides() {
+        let diff = "\
+diff --git a/src/main.rs b/src/main.rs
+index 1111111..2222222 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -5,3 +5,3 @@ fn main() {
+     let x = 1;
+-    let y = 2;
++    let y = 3;
+     let z = 4;
+\\ No newline at end of file
+";
+        let files = parse(diff);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "src/main.rs");
+
+        let hunk = &files[0].hunks[0];
+        assert_eq!(hunk.section, "fn main() {");
+        let numbered: Vec<_> = hunk.lines.iter().map(|l| (l.kind, l.old_line, l.new_line)).collect();
+        assert_eq!(
+            numbered,
+            vec![
+                (LineKind::Context, Some(5), Some(5)),
+                (LineKind::Delete, Some(6), None),
+                (LineKind::Add, None, Some(6)),
+                (LineKind::Context, Some(7), Some(7)),
+            ]
+        );
+        assert_eq!(hunk.lines[2].content, "    let y = 3;");
+    }
+
+    #[test]
+    fn test_parse_renames_deletions_and_binaries() {
+        let diff = "\
+diff --git a/old name.rs b/new name.rs
+rename from old name.rs
+rename to new name.rs
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+diff --git a/logo.png b/logo.png
+Binary files a/logo.png and b/logo.png differ
+";
+        let files = parse(diff);
+        let paths: Vec<_> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["new name.rs", "gone.rs", "logo.png"]);
+        assert!(files[0].hunks.is_empty());
+        assert_eq!(files[1].hunks[0].lines[0].old_line, Some(1));
+        assert!(files[2].binary);
+        assert!(file_hunks(diff, "missing.rs").is_empty());
+    }
+
+    #[test]
+    fn test_diff_texts_matches_git_numbering() {
+        let old: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+        let new = old.replace("line 5\n", "line five\n");
+        let hunks = diff_texts(&old, &new, 2);
+        assert_eq!(hunks.len(), 1);
+        let hunk = &hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count), (3, 5, 3, 5));
+        let changed: Vec<_> = hunk
+            .lines
+            .iter()
+            .filter(|l| l.kind != LineKind::Context)
+            .map(|l| (l.kind, l.content.as_str(), l.old_line, l.new_line))
+            .collect();
+        assert_eq!(
+            changed,
+            vec![(LineKind::Delete, "line 5", Some(5), None), (LineKind::Add, "line five", None, Some(5))]
+        );
+
+        // A new file has an empty old side
+        let added = diff_texts("", "a\nb\n", 3);
+        assert_eq!((added[0].old_start, added[0].old_count, added[0].new_start), (0, 0, 1));
+        assert!(diff_texts(&old, &old, 3).is_empty());
+    }
+
+    #[test]
+    fn test_line_range_clamps() {
+        let content = "a\nb\nc\n";
+        assert_eq!(
+            line_range(content, 2, 10),
+            FileLines { start: 2, lines: vec!["b".into(), "c".into()], total_lines: 3 }
+        );
+        assert!(line_range(content, 5, 8).lines.is_empty());
+        assert_eq!(line_range(content, 0, 1).lines, vec!["a".to_string()]);
+    }
+}
@@ -0,0 +1,296 @@
+//! Garbage collection for reviews of changes that are gone.
+//!
+//! Reviews are keyed by change id and nothing removes them when jj stops
+//! showing their change, so their threads would be counted forever. `gc`
+//! classifies each review against the repo: live, merged into main,
+//! abandoned (no visible commit, e.g. abandoned or squashed away) or
+//! divergent (more than one visible commit). Abandoned reviews, and merged
+//! ones if asked, are moved to the archive, where `aipair review restore`
+//! can bring them back. Divergent reviews are only reported, since which
+//! commit they belong to is for the user to decide. Changes an agent hasn't
+//! pushed yet only exist in its session's clone, so the clones of active
+//! sessions count as visible too.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::vcs::Vcs;
+use crate::review::{Review, ReviewStore};
+use crate::session::{Session, SessionStatus, SessionStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewState {
+    /// The change is visible, here or in an active session's clone, and not
+    /// yet in main
+    Live,
+    /// The change is an ancestor of main
+    Merged,
+    /// No visible commit has the change id
+    Abandoned,
+    /// Several visible commits have the change id
+    Divergent,
+}
+
+impl std::fmt::Display for ReviewState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ReviewState::Live => "live",
+            ReviewState::Merged => "merged",
+            ReviewState::Abandoned => "abandoned",
+            ReviewState::Divergent => "divergent",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcOptions {
+    /// Report what would happen without archiving anything
+    pub dry_run: bool,
+    /// Archive reviews of merged changes too
+    pub include_merged: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GcEntry {
+    pub change_id: String,
+    pub state: ReviewState,
+    pub unresolved_threads: usize,
+    /// The visible commits of a divergent change
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub commit_ids: Vec<String>,
+    /// Archived, or (in a dry run) would be
+    pub archived: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub entries: Vec<GcEntry>,
+}
+
+/// Where each change id stands in the repo
+struct RepoState {
+    /// Visible commits per change id
+    visible: HashMap<String, Vec<String>>,
+    merged: HashSet<String>,
+    /// Changes visible in the clone of an active session, or pushed from one
+    in_sessions: HashSet<String>,
+}
+
+impl RepoState {
+    fn load(vcs: &dyn Vcs, sessions: &[Session]) -> Result<Self> {
+        let mut visible: HashMap<String, Vec<String>> = HashMap::new();
+        for (change_id, commit_id) in vcs.query_commit_ids("all()")? {
+            visible.entry(change_id).or_default().push(commit_id);
+        }
+        let merged = match vcs.get_bookmark("main")? {
+            Some(_) => vcs.query_change_ids("::main")?.into_iter().collect(),
+            None => HashSet::new(),
+        };
+
+        let mut in_sessions = HashSet::new();
+        for session in sessions.iter().filter(|s| s.status == SessionStatus::Active) {
+            in_sessions.extend(session.changes.iter().cloned());
+            let clone_path = vcs.repo_path().join(&session.clone_path);
+            if !clone_path.exists() {
+                tracing::warn!("The clone of session {} is missing: {}", session.name, clone_path.display());
+                continue;
+            }
+            in_sessions.extend(crate::vcs::open(&clone_path).query_change_ids("all()")?);
+        }
+        Ok(Self { visible, merged, in_sessions })
+    }
+
+    fn classify(&self, change_id: &str) -> (ReviewState, &[String]) {
+        match self.visible.get(change_id).map(Vec::as_slice) {
+            None | Some([]) if self.in_sessions.contains(change_id) => (ReviewState::Live, &[]),
+            None | Some([]) => (ReviewState::Abandoned, &[]),
+            Some(commits @ [_, _, ..]) => (ReviewState::Divergent, commits),
+            Some(_) if self.merged.contains(change_id) => (ReviewState::Merged, &[]),
+            Some(_) => (ReviewState::Live, &[]),
+        }
+    }
+}
+
+fn plan(repo: &RepoState, reviews: &[Review], options: GcOptions) -> Vec<GcEntry> {
+    let mut entries: Vec<GcEntry> = reviews
+        .iter()
+        .map(|review| {
+            let (state, commits) = repo.classify(&review.change_id);
+            let archived = match state {
+                ReviewState::Abandoned => true,
+                ReviewState::Merged => options.include_merged,
+                ReviewState::Live | ReviewState::Divergent => false,
+            };
+            GcEntry {
+                change_id: review.change_id.clone(),
+                state,
+                unresolved_threads: review.threads.iter().filter(|t| t.status.is_unresolved()).count(),
+                commit_ids: commits.to_vec(),
+                archived,
+            }
+        })
+        .collect();
+    entries.sort_by_key(|e| (!e.archived, e.change_id.clone()));
+    entries
+}
+
+/// Classify every review and archive the ones whose changes are gone
+pub fn gc(vcs: &dyn Vcs, store: &ReviewStore, sessions: &SessionStore, options: GcOptions) -> Result<GcReport> {
+    let repo = RepoState::load(vcs, &sessions.list()?)?;
+    let entries = plan(&repo, &store.list()?, options);
+    if !options.dry_run {
+        for entry in entries.iter().filter(|e| e.archived) {
+            store.archive(&entry.change_id, &entry.state.to_string())?;
+        }
+    }
+    Ok(GcReport { dry_run: options.dry_run, entries })
+}
+
+/// Describe a gc run for the terminal
+pub fn format_report(report: &GcReport) -> String {
+    let mut output = String::new();
+    for entry in &report.entries {
+        let short_id = &entry.change_id[..8.min(entry.change_id.len())];
+        let action = match (entry.archived, report.dry_run) {
+            (true, true) => "would archive",
+            (true, false) => "archived",
+            (false, _) => "kept",
+        };
+        output.push_str(&format!("{short_id}  {:<9}  {action}", entry.state.to_string()));
+        if entry.unresolved_threads > 0 {
+            output.push_str(&format!(" ({} unresolved thread(s))", entry.unresolved_threads));
+        }
+        if entry.state == ReviewState::Divergent {
+            let commits: Vec<_> = entry.commit_ids.iter().map(|c| &c[..12.min(c.len())]).collect();
+            output.push_str(&format!(
+                "\n          commits {}; abandon all but one with `jj abandon`",
+                commits.join(", ")
+            ));
+        }
+        output.push('\n');
+    }
+    let count = report.entries.iter().filter(|e| e.archived).count();
+    output.push_str(&match (count, report.dry_run) {
+        (0, _) => "Nothing to archive.\n".to_string(),
+        (n, true) => format!("{n} review(s) would be archived; run without --dry-run to archive them.\n"),
+        (n, false) => format!("Archived {n} review(s); `aipair review restore <change-id>` brings one back.\n"),
+    });
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review::{Author, CommentExtras};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_classifies_reviews() {
+        let dir = TempDir::new().unwrap();
+        let store = ReviewStore::new(dir.path());
+        for change_id in ["live", "merged", "gone", "split"] {
+            store.get_or_create(change_id, "@-", "c1").unwrap();
+        }
+        store
+            .start_thread("gone", crate::review::ThreadAnchor::Change, None, Author::human("user"), "Hm", "c1", CommentExtras::default())
+            .unwrap();
+
+        let repo = RepoState {
+            visible: HashMap::from([
+                ("live".to_string(), vec!["c1".to_string()]),
+                ("merged".to_string(), vec!["c2".to_string()]),
+                ("split".to_string(), vec!["c3".to_string(), "c4".to_string()]),
+            ]),
+            merged: HashSet::from(["merged".to_string(), "split".to_string()]),
+            in_sessions: HashSet::new(),
+        };
+        let reviews = store.list().unwrap();
+
+        let entries = plan(&repo, &reviews, GcOptions::default());
+        let states: Vec<_> = entries.iter().map(|e| (e.change_id.as_str(), e.state, e.archived)).collect();
+        assert_eq!(
+            states,
+            vec![
+                ("gone", ReviewState::Abandoned, true),
+                ("live", ReviewState::Live, false),
+                ("merged", ReviewState::Merged, false),
+                ("split", ReviewState::Divergent, false),
+            ]
+        );
+        assert_eq!(entries[0].unresolved_threads, 1);
+        assert_eq!(entries[3].commit_ids, vec!["c3", "c4"]);
+
+        let entries = plan(&repo, &reviews, GcOptions { include_merged: true, ..Default::default() });
+        let archived: Vec<_> = entries.iter().filter(|e| e.archived).map(|e| e.change_id.as_str()).collect();
+        assert_eq!(archived, vec!["gone", "merged"]);
+    }
+
+    fn git_cmd(dir: &std::path::Path, args: &[&str]) {
+        let output = std::process::Command::new("git").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+    }
+
+    fn commit(dir: &std::path::Path, file: &str) {
+        std::fs::write(dir.join(file), "content\n").unwrap();
+        git_cmd(dir, &["add", "-A"]);
+        git_cmd(dir, &["-c", "user.name=Test", "-c", "user.email=test@example.com", "commit", "-q", "-m", file]);
+    }
+
+    #[test]
+    fn test_keeps_changes_only_in_a_session_clone() {
+        let dir = TempDir::new().unwrap();
+        let main = dir.path().join("main");
+        std::fs::create_dir(&main).unwrap();
+        git_cmd(&main, &["init", "-q", "-b", "main"]);
+        commit(&main, "a.txt");
+        git_cmd(dir.path(), &["clone", "-q", "main", "clone"]);
+        commit(&dir.path().join("clone"), "b.txt");
+
+        let vcs = crate::vcs::open(&main);
+        let unpushed = crate::vcs::open(dir.path().join("clone")).get_change("@").unwrap().change_id;
+        let store = ReviewStore::new(&main);
+        for change_id in [unpushed.as_str(), "gone"] {
+            store.get_or_create(change_id, "@-", "c1").unwrap();
+        }
+        let sessions = SessionStore::new(&main);
+        sessions
+            .save(&mut Session {
+                name: "agent".to_string(),
+                clone_path: "../clone".to_string(),
+                bookmark: "agent".to_string(),
+                base_change_id: String::new(),
+                base_bookmark: "main".to_string(),
+                status: SessionStatus::Active,
+                created_at: chrono::Utc::now(),
+                pushes: Vec::new(),
+                changes: Vec::new(),
+                version: 0,
+            })
+            .unwrap();
+
+        let report = gc(vcs.as_ref(), &store, &sessions, GcOptions { dry_run: true, ..Default::default() }).unwrap();
+        let states: Vec<_> = report.entries.iter().map(|e| (e.change_id.as_str(), e.state)).collect();
+        assert_eq!(states, vec![("gone", ReviewState::Abandoned), (unpushed.as_str(), ReviewState::Live)]);
+    }
+
+    #[test]
+    fn test_archive_and_restore() {
+        let dir = TempDir::new().unwrap();
+        let store = ReviewStore::new(dir.path());
+        store.get_or_create("abc123", "@-", "c1").unwrap();
+
+        store.archive("abc123", "abandoned").unwrap();
+        assert!(store.get("abc123").unwrap().is_none());
+        assert!(store.list().unwrap().is_empty());
+        assert!(dir.path().join(".aipair/archived-reviews/abc123.json").exists());
+
+        let review = store.restore("abc").unwrap();
+        assert_eq!(review.change_id, "abc123");
+        assert!(store.restore("abc").is_err());
+    }
+}
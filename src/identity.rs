@@ -0,0 +1,201 @@
+//! Who said what: authors of comments and chat messages, and the identity
+//! configured for this machine.
+//!
+//! The machine's identity lives in `~/.config/aipair/identity.json` (set it
+//! with `aipair identity`). `AIPAIR_NAME` and `AIPAIR_AGENT` override it, so
+//! an agent process can run under its own name.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use ts_rs::TS;
+
+/// The name reviews used for the human before authors had names
+pub const DEFAULT_HUMAN: &str = "user";
+/// The name reviews used for the agent before authors had names
+pub const DEFAULT_AGENT: &str = "claude";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorKind {
+    Human,
+    Agent,
+    /// Automated tooling, e.g. CI
+    Bot,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct Author {
+    pub kind: AuthorKind,
+    pub name: String,
+    /// The aipair session the author was working in, for agents
+    pub session: Option<String>,
+}
+
+impl Author {
+    pub fn human(name: &str) -> Self {
+        Self { kind: AuthorKind::Human, name: name.to_string(), session: None }
+    }
+
+    pub fn agent(name: &str, session: Option<&str>) -> Self {
+        Self { kind: AuthorKind::Agent, name: name.to_string(), session: session.map(str::to_string) }
+    }
+
+    pub fn is_human(&self) -> bool {
+        self.kind == AuthorKind::Human
+    }
+}
+
+impl std::fmt::Display for Author {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.session {
+            Some(session) => write!(f, "{} ({session})", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// Authors as stored: the current struct, or a bare "user"/"claude" from
+/// before authors had names
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AuthorRepr {
+    Legacy(LegacyAuthor),
+    Full {
+        kind: AuthorKind,
+        name: String,
+        #[serde(default)]
+        session: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LegacyAuthor {
+    User,
+    Claude,
+}
+
+impl<'de> Deserialize<'de> for Author {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match AuthorRepr::deserialize(deserializer)? {
+            AuthorRepr::Legacy(LegacyAuthor::User) => Author::human(DEFAULT_HUMAN),
+            AuthorRepr::Legacy(LegacyAuthor::Claude) => Author::agent(DEFAULT_AGENT, None),
+            AuthorRepr::Full { kind, name, session } => Author { kind, name, session },
+        })
+    }
+}
+
+/// Who is at this machine: the reviewer's name, and the name agents run
+/// from here go by
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Identity {
+    #[serde(default = "default_human")]
+    pub name: String,
+    #[serde(default = "default_agent")]
+    pub agent: String,
+}
+
+fn default_human() -> String {
+    DEFAULT_HUMAN.to_string()
+}
+
+fn default_agent() -> String {
+    DEFAULT_AGENT.to_string()
+}
+
+impl Default for Identity {
+    fn default() -> Self {
+        Self { name: default_human(), agent: default_agent() }
+    }
+}
+
+impl Identity {
+    /// Where the machine's identity is configured
+    pub fn path() -> Option<PathBuf> {
+        let config = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config.join("aipair").join("identity.json"))
+    }
+
+    /// The identity configured in the file, without environment overrides
+    pub fn load_file() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                serde_json::from_str(&content).with_context(|| format!("Invalid identity in {}", path.display()))
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// The configured identity, with environment overrides applied. Without
+    /// any configuration this is the "user" and "claude" reviews always had.
+    pub fn load() -> Result<Self> {
+        let mut identity = Self::load_file()?;
+        if let Some(name) = std::env::var("AIPAIR_NAME").ok().filter(|n| !n.is_empty()) {
+            identity.name = name;
+        }
+        if let Some(agent) = std::env::var("AIPAIR_AGENT").ok().filter(|n| !n.is_empty()) {
+            identity.agent = agent;
+        }
+        Ok(identity)
+    }
+
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = Self::path().context("HOME not set")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(path)
+    }
+
+    pub fn human(&self) -> Author {
+        Author::human(&self.name)
+    }
+
+    /// The agent working in `session`, if it is working in one
+    pub fn agent(&self, session: Option<&str>) -> Author {
+        Author::agent(&self.agent, session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_authors_deserialize() {
+        let user: Author = serde_json::from_str(r#""user""#).unwrap();
+        assert_eq!(user, Author::human("user"));
+        let claude: Author = serde_json::from_str(r#""claude""#).unwrap();
+        assert_eq!(claude, Author::agent("claude", None));
+    }
+
+    #[test]
+    fn test_author_roundtrip() {
+        let author = Author::agent("codex", Some("feature-x"));
+        let json = serde_json::to_string(&author).unwrap();
+        assert_eq!(json, r#"{"kind":"agent","name":"codex","session":"feature-x"}"#);
+        assert_eq!(serde_json::from_str::<Author>(&json).unwrap(), author);
+        assert_eq!(author.to_string(), "codex (feature-x)");
+
+        // Session is optional on disk
+        let bot: Author = serde_json::from_str(r#"{"kind":"bot","name":"ci"}"#).unwrap();
+        assert_eq!(bot.kind, AuthorKind::Bot);
+        assert_eq!(bot.session, None);
+    }
+
+    #[test]
+    fn test_identity_defaults() {
+        let identity: Identity = serde_json::from_str(r#"{"name":"jelle"}"#).unwrap();
+        assert_eq!(identity.human(), Author::human("jelle"));
+        assert_eq!(identity.agent(None), Author::agent("claude", None));
+    }
+}
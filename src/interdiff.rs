@@ -0,0 +1,423 @@
+//! Interdiffs: what the author changed between two revisions of a change,
+//! without the noise of the change's parent having moved in between.
+//!
+//! The older revision is rebased in memory onto the newer revision's parent
+//! (a three-way merge per file, the same thing `jj rebase` would do) and the
+//! result is diffed against the newer revision. Where the author and upstream
+//! changed the same lines the merge keeps the older revision's lines, so
+//! those regions still show upstream's edits; such files are reported.
+//! Binary files aren't merged: the older revision's version is kept when
+//! both sides changed one, and it's reported without hunks.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use crate::diff::{self, Hunk};
+use crate::jj::{FileDiff, FileStatus};
+use crate::vcs::Vcs;
+use crate::review::{Side, Thread, ThreadAnchor};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Interdiff {
+    pub files: Vec<FileDiff>,
+    /// Whether the two revisions have different parents
+    pub parent_moved: bool,
+    /// Files where the author's and upstream's edits overlapped
+    pub conflicted_files: Vec<String>,
+}
+
+/// Diff revision `from` of a change against revision `to`, each taken
+/// relative to its own parent.
+pub fn interdiff(vcs: &dyn Vcs, from: &str, to: &str, context: usize) -> Result<Interdiff> {
+    let from_parent = format!("{from}-");
+    let to_parent = format!("{to}-");
+    let parent_moved = vcs.get_change(&from_parent)?.commit_id != vcs.get_change(&to_parent)?.commit_id;
+
+    // Only files one of the two patches touches can differ
+    let mut paths = BTreeSet::new();
+    for (parent, rev) in [(&from_parent, from), (&to_parent, to)] {
+        for file in vcs.changed_files_between(parent, rev)? {
+            paths.extend(file.source().map(str::to_string));
+            paths.insert(file.path);
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut conflicted_files = Vec::new();
+    for path in paths {
+        let old_base = content(vcs, &from_parent, &path)?;
+        let old = content(vcs, from, &path)?;
+        let new_base = content(vcs, &to_parent, &path)?;
+        let new = content(vcs, to, &path)?;
+        let binary = [&old_base, &old, &new_base, &new].into_iter().any(|c| matches!(c, Some(Content::Binary(_))));
+
+        // `from` rebased onto `to`'s parent
+        let rebased = if old == old_base {
+            new_base.clone()
+        } else if binary {
+            if new_base != old_base {
+                conflicted_files.push(path.clone());
+            }
+            old.clone()
+        } else {
+            let merged = merge3(text(&old_base), text(&old), text(&new_base));
+            if merged.conflicts > 0 {
+                conflicted_files.push(path.clone());
+            }
+            // The file exists if the change created it, or if it wasn't
+            // deleted and exists upstream
+            let exists = if old.is_some() != old_base.is_some() { old.is_some() } else { new_base.is_some() };
+            exists.then_some(Content::Text(merged.text))
+        };
+
+        if rebased == new {
+            continue;
+        }
+        let status = match (&rebased, &new) {
+            (None, _) => FileStatus::Added,
+            (_, None) => FileStatus::Deleted,
+            _ => FileStatus::Modified,
+        };
+        let binary = [&rebased, &new].into_iter().any(|c| matches!(c, Some(Content::Binary(_))));
+        let hunks = if binary { Vec::new() } else { diff::diff_texts(text(&rebased), text(&new), context) };
+        files.push(FileDiff { path, status, hunks, binary });
+    }
+
+    Ok(Interdiff { files, parent_moved, conflicted_files })
+}
+
+/// A file's content at one revision
+#[derive(Clone, PartialEq)]
+enum Content {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// `path` at `rev`, or None if there's no such file there. Content that
+/// isn't UTF-8 or has NUL bytes is binary, as git decides.
+fn content(vcs: &dyn Vcs, rev: &str, path: &str) -> Result<Option<Content>> {
+    match vcs.show_file(rev, path) {
+        Ok(text) if text.contains('\0') => Ok(Some(Content::Binary(text.into_bytes()))),
+        Ok(text) => Ok(Some(Content::Text(text))),
+        Err(err) if crate::vcs::is_no_such_path(&err) => Ok(None),
+        Err(err) => match err.downcast::<std::string::FromUtf8Error>() {
+            Ok(not_utf8) => Ok(Some(Content::Binary(not_utf8.into_bytes()))),
+            Err(err) => Err(err.context(format!("Failed to read {path} at {rev}"))),
+        },
+    }
+}
+
+/// The text of a file that isn't binary, empty if it's absent
+fn text(content: &Option<Content>) -> &str {
+    match content {
+        Some(Content::Text(text)) => text,
+        _ => "",
+    }
+}
+
+/// The interdiff between the commit a thread was made on and `fix`, cut
+/// down to what concerns the thread: for line threads the hunks touching the
+/// commented lines, for file threads the file, otherwise everything.
+pub fn thread_fix(vcs: &dyn Vcs, thread: &Thread, fix: &str, context: usize) -> Result<Interdiff> {
+    let from = thread
+        .created_at_commit
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Thread {} doesn't know which commit it was made on", thread.id))?;
+    let mut interdiff = interdiff(vcs, from, fix, context)?;
+    if matches!(thread.anchor, ThreadAnchor::Description | ThreadAnchor::Change) {
+        return Ok(interdiff);
+    }
+
+    // Where the commented lines are at the fix, possibly in another file
+    let mapped = crate::line_mapper::map_all_threads(vcs, std::slice::from_ref(thread), fix).remove(&thread.id);
+    let file = mapped.as_ref().and_then(|m| m.file.clone()).unwrap_or_else(|| thread.file.clone());
+    interdiff.files.retain(|f| f.path == file || f.path == thread.file);
+    interdiff.conflicted_files.retain(|f| *f == file || *f == thread.file);
+
+    // Old-side threads point into the parent, which the interdiff doesn't
+    // number; keep their whole file
+    if thread.anchor == ThreadAnchor::Line && thread.side == Side::New {
+        let old = thread.line_start..=thread.line_end;
+        let new = mapped.filter(|m| !m.is_deleted).map(|m| m.line_start..=m.line_end);
+        for file in &mut interdiff.files {
+            file.hunks = hunks_touching(std::mem::take(&mut file.hunks), &old, new.as_ref());
+        }
+        interdiff.files.retain(|f| !f.hunks.is_empty());
+    }
+    Ok(interdiff)
+}
+
+/// The hunks that overlap `old` on their old side or `new` on their new side
+fn hunks_touching(
+    hunks: Vec<Hunk>,
+    old: &std::ops::RangeInclusive<usize>,
+    new: Option<&std::ops::RangeInclusive<usize>>,
+) -> Vec<Hunk> {
+    let overlaps = |start: usize, count: usize, range: &std::ops::RangeInclusive<usize>| {
+        // An empty side still sits between two lines; count it as touching
+        // the line it follows
+        let end = start + count.max(1) - 1;
+        start <= *range.end() && end >= *range.start()
+    };
+    hunks
+        .into_iter()
+        .filter(|h| {
+            overlaps(h.old_start, h.old_count, old) || new.is_some_and(|range| overlaps(h.new_start, h.new_count, range))
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct Merged {
+    text: String,
+    /// Regions both sides changed differently; `ours` wins in `text`
+    conflicts: usize,
+}
+
+/// One side's replacement of `base[range]` with `lines`
+struct Edit<'a> {
+    range: Range<usize>,
+    lines: &'a [&'a str],
+}
+
+fn edits<'a>(base: &[&str], side: &'a [&'a str]) -> Vec<Edit<'a>> {
+    similar::capture_diff_slices(similar::Algorithm::Myers, base, side)
+        .iter()
+        .filter(|op| op.tag() != similar::DiffTag::Equal)
+        .map(|op| Edit { range: op.old_range(), lines: &side[op.new_range()] })
+        .collect()
+}
+
+/// `base[range]` with `edits` (all inside the range) applied.
+fn apply<'a>(base: &[&'a str], range: Range<usize>, edits: &[&Edit<'a>]) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut pos = range.start;
+    for edit in edits {
+        out.extend_from_slice(&base[pos..edit.range.start]);
+        out.extend_from_slice(edit.lines);
+        pos = edit.range.end;
+    }
+    out.extend_from_slice(&base[pos..range.end]);
+    out
+}
+
+/// Line-based three-way merge of `ours` and `theirs` against `base`.
+fn merge3(base: &str, ours: &str, theirs: &str) -> Merged {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let ours_edits = edits(&base, &ours);
+    let theirs_edits = edits(&base, &theirs);
+
+    let mut out: Vec<&str> = Vec::new();
+    let mut conflicts = 0;
+    let mut pos = 0;
+    let (mut i, mut j) = (0, 0);
+
+    while i < ours_edits.len() || j < theirs_edits.len() {
+        let start = match (ours_edits.get(i), theirs_edits.get(j)) {
+            (Some(a), Some(b)) => a.range.start.min(b.range.start),
+            (Some(a), None) => a.range.start,
+            (None, Some(b)) => b.range.start,
+            (None, None) => unreachable!(),
+        };
+        out.extend_from_slice(&base[pos..start]);
+
+        // Grow the region while edits from either side overlap it; two
+        // insertions at the same spot overlap too
+        let mut end = start;
+        let (mut ours_group, mut theirs_group) = (Vec::new(), Vec::new());
+        loop {
+            let overlaps = |e: &Edit| e.range.start < end || e.range.start == start;
+            if let Some(e) = ours_edits.get(i).filter(|e| overlaps(e)) {
+                end = end.max(e.range.end);
+                ours_group.push(e);
+                i += 1;
+            } else if let Some(e) = theirs_edits.get(j).filter(|e| overlaps(e)) {
+                end = end.max(e.range.end);
+                theirs_group.push(e);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        let merged = match (ours_group.is_empty(), theirs_group.is_empty()) {
+            (false, true) => apply(&base, start..end, &ours_group),
+            (true, false) => apply(&base, start..end, &theirs_group),
+            _ => {
+                let ours_region = apply(&base, start..end, &ours_group);
+                if ours_region != apply(&base, start..end, &theirs_group) {
+                    conflicts += 1;
+                }
+                ours_region
+            }
+        };
+        out.extend(merged);
+        pos = end;
+    }
+    out.extend_from_slice(&base[pos..]);
+
+    Merged { text: out.concat(), conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jj::Jj;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn lines(n: usize) -> String {
+        (1..=n).map(|i| format!("line {i}\n")).collect()
+    }
+
+    #[test]
+    fn test_merge3_combines_separate_edits() {
+        let base = lines(10);
+        let ours = base.replace("line 2\n", "line two\n");
+        let theirs = base.replace("line 9\n", "line nine\n").replace("line 5\n", "");
+        let merged = merge3(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.text, ours.replace("line 9\n", "line nine\n").replace("line 5\n", ""));
+    }
+
+    #[test]
+    fn test_hunks_touching() {
+        let old = lines(30);
+        let new = old.replace("line 3\n", "line three\n").replace("line 20\n", "line twenty\n");
+        let hunks = diff::diff_texts(&old, &new, 1);
+        assert_eq!(hunks.len(), 2);
+
+        // A thread on lines 19-21 sees the second edit only
+        let touching = hunks_touching(hunks.clone(), &(19..=21), None);
+        assert_eq!(touching.len(), 1);
+        assert_eq!(touching[0].old_start, 19);
+
+        // After the fix the lines moved; the new side still finds them
+        let touching = hunks_touching(hunks.clone(), &(25..=26), Some(&(20..=20)));
+        assert_eq!(touching.len(), 1);
+        assert!(hunks_touching(hunks, &(10..=12), Some(&(10..=12))).is_empty());
+    }
+
+    #[test]
+    fn test_merge3_same_edit_on_both_sides_is_not_a_conflict() {
+        let base = lines(5);
+        let both = base.replace("line 3\n", "line three\n");
+        assert_eq!(merge3(&base, &both, &both), Merged { text: both.clone(), conflicts: 0 });
+    }
+
+    #[test]
+    fn test_merge3_conflict_keeps_ours() {
+        let base = lines(5);
+        let ours = base.replace("line 3\n", "ours\n");
+        let theirs = base.replace("line 3\n", "theirs\n").replace("line 1\n", "first\n");
+        let merged = merge3(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(merged.text, ours.replace("line 1\n", "first\n"));
+    }
+
+    #[test]
+    fn test_merge3_insertions_and_missing_files() {
+        // Both sides appending at the end of the file
+        let merged = merge3("a\n", "a\nb\n", "a\nc\n");
+        assert_eq!(merged, Merged { text: "a\nb\n".into(), conflicts: 1 });
+        // A file the change created
+        assert_eq!(merge3("", "new\n", ""), Merged { text: "new\n".into(), conflicts: 0 });
+    }
+
+    fn git_cmd(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_interdiff_reports_binary_files_without_hunks() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path();
+        git_cmd(path, &["init", "-q", "-b", "main"]);
+        git_cmd(path, &["config", "user.email", "test@example.com"]);
+        git_cmd(path, &["config", "user.name", "Test"]);
+        std::fs::write(path.join("a.txt"), "one\n").unwrap();
+        git_cmd(path, &["add", "-A"]);
+        git_cmd(path, &["commit", "-q", "-m", "Base"]);
+
+        std::fs::write(path.join("image.bin"), [0x89, b'P', b'N', b'G', 0, 0xff]).unwrap();
+        std::fs::write(path.join("a.txt"), "one\ntwo\n").unwrap();
+        git_cmd(path, &["add", "-A"]);
+        git_cmd(path, &["commit", "-q", "-m", "Change"]);
+        let rev1 = git_cmd(path, &["rev-parse", "HEAD"]).trim().to_string();
+        std::fs::write(path.join("image.bin"), [0x89, b'P', b'N', b'G', 0, 0xfe]).unwrap();
+        git_cmd(path, &["commit", "-q", "-a", "--amend", "--no-edit"]);
+        let rev2 = git_cmd(path, &["rev-parse", "HEAD"]).trim().to_string();
+
+        let git = crate::vcs::Git::new(path);
+        let result = interdiff(&git, &rev1, &rev2, 3).unwrap();
+        assert!(!result.parent_moved);
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].path, "image.bin");
+        assert_eq!(result.files[0].status, FileStatus::Modified);
+        assert!(result.files[0].binary);
+        assert!(result.files[0].hunks.is_empty());
+    }
+
+    fn jj_cmd(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("jj").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("jj {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn commit_id(dir: &std::path::Path) -> String {
+        jj_cmd(dir, &["log", "--no-graph", "-r", "@", "-T", "commit_id"]).trim().to_string()
+    }
+
+    #[test]
+    fn test_interdiff_ignores_upstream_changes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path();
+        jj_cmd(path, &["git", "init"]);
+        let jj = Jj::new(path);
+
+        let base = lines(20);
+        std::fs::write(path.join("f.txt"), &base).unwrap();
+        jj_cmd(path, &["describe", "-m", "base"]);
+
+        // Revision 1 of the change edits line 2
+        jj_cmd(path, &["new", "-m", "change"]);
+        let change_id = jj_cmd(path, &["log", "--no-graph", "-r", "@", "-T", "change_id"]).trim().to_string();
+        std::fs::write(path.join("f.txt"), base.replace("line 2\n", "line two\n")).unwrap();
+        let rev1 = commit_id(path);
+
+        // Upstream edits line 18, which rebases the change
+        jj_cmd(path, &["edit", "@-"]);
+        std::fs::write(path.join("f.txt"), base.replace("line 18\n", "line eighteen\n")).unwrap();
+        jj_cmd(path, &["edit", &change_id]);
+
+        // Revision 2 also edits line 10
+        let current = std::fs::read_to_string(path.join("f.txt")).unwrap();
+        std::fs::write(path.join("f.txt"), current.replace("line 10\n", "line ten\n")).unwrap();
+        let rev2 = commit_id(path);
+
+        let result = interdiff(&jj, &rev1, &rev2, 1).unwrap();
+        assert!(result.parent_moved);
+        assert!(result.conflicted_files.is_empty());
+        assert_eq!(result.files.len(), 1);
+        let changed: Vec<_> = result.files[0].hunks[0]
+            .lines
+            .iter()
+            .filter(|l| l.kind != diff::LineKind::Context)
+            .map(|l| l.content.as_str())
+            .collect();
+        assert_eq!(changed, vec!["line 10", "line ten"]);
+
+        // Nothing changed between a revision and itself
+        assert!(interdiff(&jj, &rev2, &rev2, 3).unwrap().files.is_empty());
+    }
+}
@@ -2,8 +2,12 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use ts_rs::TS;
 
+use crate::diff::Hunk;
+use crate::vcs::{NoSuchPath, Vcs};
+
 #[derive(Debug, Clone, Serialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
 pub struct Change {
@@ -18,6 +22,31 @@ pub struct Change {
     pub parent_change_ids: Vec<String>,
 }
 
+/// One commit in a change's evolution log
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvologEntry {
+    pub change_id: String,
+    pub commit_id: String,
+    /// Description of the operation that created the commit, when jj
+    /// still has it
+    pub operation: Option<String>,
+}
+
+/// Config key aipair sets on every jj command that changes the repo. jj
+/// records each command's arguments on its operation, so the key marks the
+/// operation as aipair's and names what it was doing.
+const OPERATION_TAG: &str = "aipair.operation";
+
+/// One entry in the operation log
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Operation {
+    pub id: String,
+    pub time: String,
+    pub description: String,
+    /// The `Jj` method that ran it, for operations aipair made
+    pub aipair: Option<String>,
+}
+
 /// Internal struct for deserializing jj's JSON output
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
@@ -42,23 +71,45 @@ pub struct Diff {
     pub change_id: String,
     pub base: String,
     pub files: Vec<FileDiff>,
-    pub raw: String,
+    /// Lines of context the hunks were produced with
+    pub context: usize,
 }
 
-#[derive(Debug, Clone, Serialize, TS)]
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
 pub struct FileDiff {
     pub path: String,
     pub status: FileStatus,
+    /// Empty for summaries, and for renames and copies without edits
+    pub hunks: Vec<Hunk>,
+    pub binary: bool,
 }
 
-#[derive(Debug, Clone, Serialize, TS)]
+impl FileDiff {
+    pub(crate) fn new(path: String, status: FileStatus) -> Self {
+        Self { path, status, hunks: Vec::new(), binary: false }
+    }
+
+    /// Where a renamed or copied file came from
+    pub fn source(&self) -> Option<&str> {
+        match &self.status {
+            FileStatus::Renamed { from } | FileStatus::Copied { from } => Some(from),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
 #[serde(rename_all = "lowercase")]
 pub enum FileStatus {
     Added,
     Modified,
     Deleted,
+    /// Moved from `from`, possibly with edits
+    Renamed { from: String },
+    /// Copied from `from`, which still exists
+    Copied { from: String },
 }
 
 pub struct Jj {
@@ -89,300 +140,208 @@ impl Jj {
         Ok(Self::new(root))
     }
 
-    pub fn repo_path(&self) -> &Path {
-        &self.repo_path
+    /// A jj command that changes the repo, tagged so its operation can be
+    /// told apart from the user's
+    fn tagged(&self, operation: &str) -> Command {
+        let mut command = Command::new("jj");
+        command
+            .current_dir(&self.repo_path)
+            .arg(format!("--config={OPERATION_TAG}={operation}"));
+        command
     }
 
-    /// List recent changes across all heads
-    pub fn log(&self, limit: usize) -> Result<Vec<Change>> {
-        self.log_revset(&format!("ancestors(visible_heads(), {limit})"))
-    }
+    /// Clone a repo, fetching only specific branches. Returns Jj for the new clone.
+    pub fn git_clone_branches(source: &Path, dest: &Path, branches: &[&str]) -> Result<Self> {
+        let mut args = vec![
+            "git".to_string(),
+            "clone".to_string(),
+        ];
+        for b in branches {
+            args.push("-b".to_string());
+            args.push(b.to_string());
+        }
+        args.push(source.to_string_lossy().to_string());
+        args.push(dest.to_string_lossy().to_string());
 
-    /// List changes matching an arbitrary revset
-    pub fn log_revset(&self, revset: &str) -> Result<Vec<Change>> {
         let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args([
-                "log",
-                "--no-graph",
-                "-r",
-                revset,
-                "-T",
-                r#"json(self) ++ "\t" ++ empty ++ "\t" ++ conflict ++ "\t" ++ self.current_working_copy() ++ "\t" ++ parents.map(|c| c.change_id()).join(",") ++ "\n""#,
-            ])
+            .args(&args)
             .output()
-            .context("Failed to run jj log")?;
+            .context("Failed to run jj git clone")?;
 
         if !output.status.success() {
-            anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
-        }
-
-        let stdout = String::from_utf8(output.stdout)?;
-        let mut changes = Vec::new();
-
-        for line in stdout.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-
-            // Parse "json\tempty\tconflict\tis_wc\tparents" format
-            let parts: Vec<&str> = line.rsplitn(5, '\t').collect();
-            if parts.len() < 5 {
-                continue;
-            }
-            let parents_str = parts[0];
-            let is_wc_str = parts[1];
-            let conflict_str = parts[2];
-            let empty_str = parts[3];
-            let json_str = parts[4];
-
-            let jj_change: JjChange = serde_json::from_str(json_str)
-                .with_context(|| format!("Failed to parse jj log output: {json_str}"))?;
-
-            // Skip the root commit (all z's) - it has no parent and can't be diffed
-            if jj_change.change_id.chars().all(|c| c == 'z') {
-                continue;
-            }
-
-            let parent_change_ids: Vec<String> = if parents_str.is_empty() {
-                Vec::new()
-            } else {
-                parents_str.split(',').map(|s| s.to_string()).collect()
-            };
-
-            changes.push(Change {
-                change_id: jj_change.change_id,
-                commit_id: jj_change.commit_id,
-                description: jj_change.description.trim_end().to_string(),
-                author: jj_change.author.email,
-                timestamp: jj_change.committer.timestamp,
-                empty: empty_str == "true",
-                conflict: conflict_str == "true",
-                is_working_copy: is_wc_str == "true",
-                parent_change_ids,
-            });
+            anyhow::bail!(
+                "jj git clone failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
         }
 
-        Ok(changes)
-    }
-
-    /// Get diff for a change (compared to its parent by default)
-    pub fn diff(&self, change_id: &str, base: Option<&str>) -> Result<Diff> {
-        let default_base = format!("{change_id}-");
-        let base = base.unwrap_or(&default_base);
-        let raw = self.diff_raw(change_id, base)?;
-        let files = self.diff_stat(change_id, base)?;
-
-        Ok(Diff {
-            change_id: change_id.to_string(),
-            base: base.to_string(),
-            files,
-            raw,
-        })
+        Ok(Self::new(dest))
     }
 
-    fn diff_raw(&self, change_id: &str, base: &str) -> Result<String> {
-        // TODO: --context=10000 is a hack to get full file context for the UI's
-        // collapsible sections. jj doesn't have a --context=all option. Consider
-        // fetching full files separately and reconstructing the diff in the UI.
+    /// Set a config value in the repo-level config.
+    pub fn set_repo_config(&self, key: &str, value: &str) -> Result<()> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args([
-                "diff",
-                "--from",
-                base,
-                "--to",
-                change_id,
-                "--git",
-                "--context=10000",
-            ])
+            .args(["config", "set", "--repo", key, value])
             .output()
-            .context("Failed to run jj diff")?;
+            .context("Failed to run jj config set")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj diff failed: {}",
+                "jj config set failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(String::from_utf8(output.stdout)?)
+        Ok(())
     }
 
-    fn diff_stat(&self, change_id: &str, base: &str) -> Result<Vec<FileDiff>> {
-        let output = Command::new("jj")
+    /// Add a value to a multi-valued git config key (e.g. remote.origin.fetch).
+    pub fn git_config_add(&self, key: &str, value: &str) -> Result<()> {
+        let output = Command::new("git")
             .current_dir(&self.repo_path)
-            .args(["diff", "--from", base, "--to", change_id, "--summary"])
+            .args(["config", "--add", key, value])
             .output()
-            .context("Failed to run jj diff --summary")?;
+            .context("Failed to run git config --add")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj diff --summary failed: {}",
+                "git config --add failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        let stdout = String::from_utf8(output.stdout)?;
-        let mut files = Vec::new();
+        Ok(())
+    }
 
-        for line in stdout.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let status = match parts[0] {
-                    "A" => FileStatus::Added,
-                    "M" => FileStatus::Modified,
-                    "D" => FileStatus::Deleted,
-                    _ => continue,
-                };
-                files.push(FileDiff {
-                    path: parts[1].to_string(),
-                    status,
-                });
-            }
-        }
+    /// Run `f` in a temporary workspace of this repo whose working copy is a
+    /// new change on top of `revision`. Edits that have to go through a
+    /// working copy are made there, so the user's working copy (or a session
+    /// agent's) is never moved or snapshotted into the wrong change. The
+    /// workspace is forgotten and deleted afterwards, even if `f` fails.
+    pub fn in_scratch_workspace<T>(&self, revision: &str, f: impl FnOnce(&Jj) -> Result<T>) -> Result<T> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("aipair_scratch_{}_{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join(&name);
+        // Left behind by a process that died half way
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let output = self.tagged("in_scratch_workspace")
+            .args(["workspace", "add", "--name", &name, "-r", revision])
+            .arg(&dir)
+            .output()
+            .context("Failed to run jj workspace add")?;
 
-        Ok(files)
-    }
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj workspace add failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
 
-    /// Get raw git diff between two commits for a specific file
-    pub fn diff_raw_between(&self, from: &str, to: &str, file: &str) -> Result<String> {
-        self.diff_raw_between_ctx(from, to, file, None)
+        let result = f(&Jj::new(&dir));
+        let cleanup = self.forget_scratch_workspace(&name);
+        let _ = std::fs::remove_dir_all(&dir);
+        let value = result?;
+        cleanup?;
+        Ok(value)
     }
 
-    /// Get raw git diff between two commits for a specific file, with configurable context lines.
-    pub fn diff_raw_between_ctx(&self, from: &str, to: &str, file: &str, context: Option<usize>) -> Result<String> {
-        let ctx_flag;
-        let mut args = vec!["diff", "--from", from, "--to", to, "--git"];
-        if let Some(ctx) = context {
-            ctx_flag = format!("--context={}", ctx);
-            args.push(&ctx_flag);
-        }
-        args.push("--");
-        args.push(file);
+    fn forget_scratch_workspace(&self, name: &str) -> Result<()> {
+        // Squashing out of the workspace leaves an empty change behind
+        let leftover = self.query_change_ids(&format!(r#"{name}@ & empty() & description(exact:"")"#))?;
 
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(&args)
+        let output = self.tagged("forget_scratch_workspace")
+            .args(["workspace", "forget", name])
             .output()
-            .context("Failed to run jj diff")?;
+            .context("Failed to run jj workspace forget")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj diff failed: {}",
+                "jj workspace forget failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(String::from_utf8(output.stdout)?)
-    }
+        if !leftover.is_empty() {
+            let revset: Vec<String> = leftover.iter().map(|id| format!("present({id})")).collect();
+            self.abandon(&revset.join(" | "))?;
+        }
 
-    /// Show file content at a specific revision
-    pub fn show_file(&self, change_id: &str, path: &str) -> Result<String> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["file", "show", "-r", change_id, path])
+        // Rewriting an ancestor of this workspace's working copy from the
+        // scratch one leaves this one stale until it's updated
+        let output = self.tagged("update_stale")
+            .args(["workspace", "update-stale"])
             .output()
-            .context("Failed to run jj file show")?;
+            .context("Failed to run jj workspace update-stale")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj file show failed: {}",
+                "jj workspace update-stale failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(String::from_utf8(output.stdout)?)
+        Ok(())
     }
 
-    /// Get the change_id that a bookmark points to, if it exists
-    pub fn get_bookmark(&self, name: &str) -> Result<Option<String>> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["log", "--no-graph", "-r", name, "-T", "change_id"])
+    pub fn new_change_on(&self, revision: &str) -> Result<()> {
+        let output = self.tagged("new_change_on")
+            .args(["new", revision])
             .output()
-            .context("Failed to run jj log for bookmark")?;
+            .context("Failed to run jj new")?;
 
         if !output.status.success() {
-            // Bookmark doesn't exist
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if stderr.contains("doesn't exist") {
-                return Ok(None);
-            }
-            anyhow::bail!("jj log failed: {}", stderr);
+            anyhow::bail!(
+                "jj new failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
         }
 
-        let change_id = String::from_utf8(output.stdout)?.trim().to_string();
-        Ok(Some(change_id))
+        Ok(())
     }
 
-    /// Get info about a specific change
-    pub fn get_change(&self, change_id: &str) -> Result<Change> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args([
-                "log",
-                "--no-graph",
-                "-r",
-                change_id,
-                "-T",
-                r#"json(self) ++ "\t" ++ empty ++ "\t" ++ conflict ++ "\t" ++ self.current_working_copy() ++ "\t" ++ parents.map(|c| c.change_id()).join(",") ++ "\n""#,
-            ])
+    pub fn describe(&self, message: &str) -> Result<()> {
+        let output = self.tagged("describe")
+            .args(["describe", "-m", message])
             .output()
-            .context("Failed to run jj log")?;
+            .context("Failed to run jj describe")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj log failed: {}",
+                "jj describe failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        let stdout = String::from_utf8(output.stdout)?;
-        let line = stdout.lines().next().context("No output from jj log")?;
-        let parts: Vec<&str> = line.rsplitn(5, '\t').collect();
-        if parts.len() < 5 {
-            anyhow::bail!("Invalid jj log output format");
-        }
-        let parents_str = parts[0];
-        let is_wc_str = parts[1];
-        let conflict_str = parts[2];
-        let empty_str = parts[3];
-        let json_str = parts[4];
+        Ok(())
+    }
 
-        let jj_change: JjChange = serde_json::from_str(json_str)
-            .with_context(|| format!("Failed to parse jj log output: {json_str}"))?;
+    pub fn squash_into(&self, from: &str, into: &str) -> Result<()> {
+        let output = self.tagged("squash_into")
+            .args(["squash", "--from", from, "--into", into])
+            .output()
+            .context("Failed to run jj squash")?;
 
-        let parent_change_ids: Vec<String> = if parents_str.is_empty() {
-            Vec::new()
-        } else {
-            parents_str.split(',').map(|s| s.to_string()).collect()
-        };
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj squash failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
 
-        Ok(Change {
-            change_id: jj_change.change_id,
-            commit_id: jj_change.commit_id,
-            description: jj_change.description.trim_end().to_string(),
-            author: jj_change.author.email,
-            timestamp: jj_change.committer.timestamp,
-            empty: empty_str == "true",
-            conflict: conflict_str == "true",
-            is_working_copy: is_wc_str == "true",
-            parent_change_ids,
-        })
+        Ok(())
     }
 
-    /// Move a bookmark to point to a specific change
-    pub fn move_bookmark(&self, name: &str, change_id: &str) -> Result<()> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["bookmark", "set", name, "-r", change_id, "--allow-backwards"])
+    pub fn abandon(&self, revision: &str) -> Result<()> {
+        let output = self.tagged("abandon")
+            .args(["abandon", revision])
             .output()
-            .context("Failed to run jj bookmark set")?;
+            .context("Failed to run jj abandon")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj bookmark set failed: {}",
+                "jj abandon failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
@@ -390,67 +349,82 @@ impl Jj {
         Ok(())
     }
 
-    /// Clone a repo via jj git clone. Returns Jj for the new clone.
-    pub fn git_clone(source: &Path, dest: &Path) -> Result<Self> {
+    /// The commits `revision` evolved from, newest first. Besides the
+    /// change's own earlier commits this includes the commits of changes
+    /// squashed or absorbed into it, and of the change it was split from.
+    pub fn evolog(&self, revision: &str) -> Result<Vec<EvologEntry>> {
         let output = Command::new("jj")
+            .current_dir(&self.repo_path)
             .args([
-                "git",
-                "clone",
-                &source.to_string_lossy(),
-                &dest.to_string_lossy(),
+                "evolog",
+                "--no-graph",
+                "-r",
+                revision,
+                "-T",
+                r#"commit.change_id() ++ "\t" ++ commit.commit_id() ++ "\t" ++ if(operation, operation.description().first_line()) ++ "\n""#,
             ])
             .output()
-            .context("Failed to run jj git clone")?;
+            .context("Failed to run jj evolog")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj git clone failed: {}",
+                "jj evolog failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(Self::new(dest))
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, '\t');
+                let change_id = parts.next()?.to_string();
+                let commit_id = parts.next()?.to_string();
+                let operation = parts.next().filter(|o| !o.is_empty()).map(str::to_string);
+                Some(EvologEntry { change_id, commit_id, operation })
+            })
+            .collect())
     }
 
-    /// Clone a repo, fetching only specific branches. Returns Jj for the new clone.
-    pub fn git_clone_branches(source: &Path, dest: &Path, branches: &[&str]) -> Result<Self> {
-        let mut args = vec![
-            "git".to_string(),
-            "clone".to_string(),
-        ];
-        for b in branches {
-            args.push("-b".to_string());
-            args.push(b.to_string());
-        }
-        args.push(source.to_string_lossy().to_string());
-        args.push(dest.to_string_lossy().to_string());
-
+    /// The last `limit` operations, newest first
+    pub fn op_log(&self, limit: usize) -> Result<Vec<Operation>> {
+        let limit = limit.to_string();
         let output = Command::new("jj")
-            .args(&args)
+            .current_dir(&self.repo_path)
+            .args([
+                "op",
+                "log",
+                "--no-graph",
+                "--limit",
+                &limit,
+                "-T",
+                r#"id ++ "\t" ++ time.start().format("%Y-%m-%dT%H:%M:%S%:z") ++ "\t" ++ description.first_line() ++ "\t" ++ tags.lines().join(" ") ++ "\n""#,
+            ])
             .output()
-            .context("Failed to run jj git clone")?;
+            .context("Failed to run jj op log")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj git clone failed: {}",
+                "jj op log failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(Self::new(dest))
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout.lines().filter_map(parse_operation_line).collect())
     }
 
-    /// Set a config value in the repo-level config.
-    pub fn set_repo_config(&self, key: &str, value: &str) -> Result<()> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["config", "set", "--repo", key, value])
+    /// Restore the repo to how it was at `operation`
+    pub fn op_restore(&self, operation: &str) -> Result<()> {
+        let output = self
+            .tagged("op_restore")
+            .args(["op", "restore", operation])
             .output()
-            .context("Failed to run jj config set")?;
+            .context("Failed to run jj op restore")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj config set failed: {}",
+                "jj op restore failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
@@ -458,39 +432,48 @@ impl Jj {
         Ok(())
     }
 
-    /// Add a value to a multi-valued git config key (e.g. remote.origin.fetch).
-    pub fn git_config_add(&self, key: &str, value: &str) -> Result<()> {
-        let output = Command::new("git")
+    /// Paths with unresolved conflicts at `revision`, each with how many of
+    /// the conflict's sides delete the file
+    pub fn conflicted_files(&self, revision: &str) -> Result<Vec<(String, usize)>> {
+        let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["config", "--add", key, value])
+            .args(["resolve", "--list", "-r", revision])
             .output()
-            .context("Failed to run git config --add")?;
+            .context("Failed to run jj resolve")?;
 
         if !output.status.success() {
-            anyhow::bail!(
-                "git config --add failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // jj treats "nothing to resolve" as an error
+            if stderr.contains("No conflicts") {
+                return Ok(Vec::new());
+            }
+            anyhow::bail!("jj resolve --list failed: {}", stderr);
         }
 
-        Ok(())
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout.lines().filter_map(parse_conflict_line).collect())
     }
 
-    pub fn git_push_bookmark(&self, bookmark: &str, allow_new: bool) -> Result<String> {
-        let mut args = vec!["git", "push", "--bookmark", bookmark];
-        if allow_new {
-            args.push("--allow-new");
-        }
-
+    /// File content at `revision` with any conflicts materialized as
+    /// snapshot-style markers (every side and base written out in full)
+    pub fn show_file_with_conflicts(&self, revision: &str, path: &str) -> Result<String> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(&args)
+            .args([
+                "file",
+                "show",
+                "--config",
+                "ui.conflict-marker-style=snapshot",
+                "-r",
+                revision,
+                path,
+            ])
             .output()
-            .context("Failed to run jj git push")?;
+            .context("Failed to run jj file show")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj git push failed: {}",
+                "jj file show failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
@@ -498,137 +481,321 @@ impl Jj {
         Ok(String::from_utf8(output.stdout)?)
     }
 
-    pub fn git_fetch(&self) -> Result<String> {
+    /// Get the working copy change ID
+    pub fn working_copy_change_id(&self) -> Result<String> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["git", "fetch"])
+            .args(["log", "--no-graph", "-r", "@", "-T", "change_id"])
             .output()
-            .context("Failed to run jj git fetch")?;
+            .context("Failed to run jj log")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj git fetch failed: {}",
+                "jj log failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(String::from_utf8(output.stdout)?)
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
     }
+}
 
-    /// Fetch only specific branches from origin.
-    pub fn git_fetch_branches(&self, branches: &[&str]) -> Result<String> {
-        let mut args = vec!["git", "fetch"];
-        for b in branches {
-            args.push("-b");
-            args.push(b);
+impl Vcs for Jj {
+    fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    /// `jj op log` snapshots the working copy first, so edits show up as a
+    /// new operation
+    fn operation_id(&self) -> Result<Option<String>> {
+        Ok(self.op_log(1)?.into_iter().next().map(|op| op.id))
+    }
+
+    /// List changes matching an arbitrary revset
+    fn log_revset(&self, revset: &str) -> Result<Vec<Change>> {
+        let output = Command::new("jj")
+            .current_dir(&self.repo_path)
+            .args([
+                "log",
+                "--no-graph",
+                "-r",
+                revset,
+                "-T",
+                r#"json(self) ++ "\t" ++ empty ++ "\t" ++ conflict ++ "\t" ++ self.current_working_copy() ++ "\t" ++ parents.map(|c| c.change_id()).join(",") ++ "\n""#,
+            ])
+            .output()
+            .context("Failed to run jj log")?;
+
+        if !output.status.success() {
+            anyhow::bail!("jj log failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        let stdout = String::from_utf8(output.stdout)?;
+        let mut changes = Vec::new();
+
+        for line in stdout.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Parse "json\tempty\tconflict\tis_wc\tparents" format
+            let parts: Vec<&str> = line.rsplitn(5, '\t').collect();
+            if parts.len() < 5 {
+                continue;
+            }
+            let parents_str = parts[0];
+            let is_wc_str = parts[1];
+            let conflict_str = parts[2];
+            let empty_str = parts[3];
+            let json_str = parts[4];
+
+            let jj_change: JjChange = serde_json::from_str(json_str)
+                .with_context(|| format!("Failed to parse jj log output: {json_str}"))?;
+
+            // Skip the root commit (all z's) - it has no parent and can't be diffed
+            if jj_change.change_id.chars().all(|c| c == 'z') {
+                continue;
+            }
+
+            let parent_change_ids: Vec<String> = if parents_str.is_empty() {
+                Vec::new()
+            } else {
+                parents_str.split(',').map(|s| s.to_string()).collect()
+            };
+
+            changes.push(Change {
+                change_id: jj_change.change_id,
+                commit_id: jj_change.commit_id,
+                description: jj_change.description.trim_end().to_string(),
+                author: jj_change.author.email,
+                timestamp: jj_change.committer.timestamp,
+                empty: empty_str == "true",
+                conflict: conflict_str == "true",
+                is_working_copy: is_wc_str == "true",
+                parent_change_ids,
+            });
         }
+
+        Ok(changes)
+    }
+
+    /// Get info about a specific change
+    fn get_change(&self, change_id: &str) -> Result<Change> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(&args)
+            .args([
+                "log",
+                "--no-graph",
+                "-r",
+                change_id,
+                "-T",
+                r#"json(self) ++ "\t" ++ empty ++ "\t" ++ conflict ++ "\t" ++ self.current_working_copy() ++ "\t" ++ parents.map(|c| c.change_id()).join(",") ++ "\n""#,
+            ])
             .output()
-            .context("Failed to run jj git fetch")?;
+            .context("Failed to run jj log")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj git fetch failed: {}",
+                "jj log failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(String::from_utf8(output.stdout)?)
+        let stdout = String::from_utf8(output.stdout)?;
+        let line = stdout.lines().next().context("No output from jj log")?;
+        let parts: Vec<&str> = line.rsplitn(5, '\t').collect();
+        if parts.len() < 5 {
+            anyhow::bail!("Invalid jj log output format");
+        }
+        let parents_str = parts[0];
+        let is_wc_str = parts[1];
+        let conflict_str = parts[2];
+        let empty_str = parts[3];
+        let json_str = parts[4];
+
+        let jj_change: JjChange = serde_json::from_str(json_str)
+            .with_context(|| format!("Failed to parse jj log output: {json_str}"))?;
+
+        let parent_change_ids: Vec<String> = if parents_str.is_empty() {
+            Vec::new()
+        } else {
+            parents_str.split(',').map(|s| s.to_string()).collect()
+        };
+
+        Ok(Change {
+            change_id: jj_change.change_id,
+            commit_id: jj_change.commit_id,
+            description: jj_change.description.trim_end().to_string(),
+            author: jj_change.author.email,
+            timestamp: jj_change.committer.timestamp,
+            empty: empty_str == "true",
+            conflict: conflict_str == "true",
+            is_working_copy: is_wc_str == "true",
+            parent_change_ids,
+        })
     }
 
-    pub fn bookmark_create(&self, name: &str, revision: &str) -> Result<()> {
+    /// Run a revset and return the matching change_ids.
+    fn query_change_ids(&self, revset: &str) -> Result<Vec<String>> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["bookmark", "create", name, "-r", revision])
+            .args([
+                "log",
+                "--no-graph",
+                "-r",
+                revset,
+                "-T",
+                r#"change_id ++ "\n""#,
+            ])
             .output()
-            .context("Failed to run jj bookmark create")?;
+            .context("Failed to run jj log")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj bookmark create failed: {}",
+                "jj log failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(())
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect())
     }
 
-    /// Track a remote bookmark. Silently succeeds if already tracked or doesn't exist.
-    pub fn bookmark_track(&self, bookmark_remote: &str) -> Result<()> {
+    /// Run a revset and return (change_id, commit_id) for every matching
+    /// commit. A divergent change shows up once per visible commit.
+    fn query_commit_ids(&self, revset: &str) -> Result<Vec<(String, String)>> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["bookmark", "track", bookmark_remote])
+            .args([
+                "log",
+                "--no-graph",
+                "-r",
+                revset,
+                "-T",
+                r#"change_id ++ "\t" ++ commit_id ++ "\n""#,
+            ])
             .output()
-            .context("Failed to run jj bookmark track")?;
+            .context("Failed to run jj log")?;
 
-        // Ignore failures (e.g. bookmark doesn't exist on remote yet)
-        let _ = output;
-        Ok(())
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj log failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout
+            .lines()
+            .filter_map(|l| l.split_once('\t'))
+            .map(|(change, commit)| (change.to_string(), commit.to_string()))
+            .collect())
     }
 
-    pub fn bookmark_delete(&self, name: &str) -> Result<()> {
+    /// Files added, modified, deleted, renamed or copied between two commits
+    fn changed_files_between(&self, from: &str, to: &str) -> Result<Vec<FileDiff>> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["bookmark", "delete", name])
+            .args(["diff", "--from", from, "--to", to, "--summary"])
             .output()
-            .context("Failed to run jj bookmark delete")?;
+            .context("Failed to run jj diff --summary")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj bookmark delete failed: {}",
+                "jj diff --summary failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(())
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter_map(parse_summary_line)
+            .collect())
     }
 
-    pub fn new_change(&self) -> Result<()> {
+    /// Get raw git diff between two commits for several paths. Passing both
+    /// sides of a rename lets jj report it as one renamed file.
+    fn diff_raw_between_paths(&self, from: &str, to: &str, paths: &[&str], context: Option<usize>) -> Result<String> {
+        let ctx_flag;
+        let mut args = vec!["diff", "--from", from, "--to", to, "--git"];
+        if let Some(ctx) = context {
+            ctx_flag = format!("--context={}", ctx);
+            args.push(&ctx_flag);
+        }
+        args.push("--");
+        args.extend_from_slice(paths);
+
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["new"])
+            .args(&args)
             .output()
-            .context("Failed to run jj new")?;
+            .context("Failed to run jj diff")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj new failed: {}",
+                "jj diff failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(())
+        Ok(String::from_utf8(output.stdout)?)
     }
 
-    pub fn new_change_on(&self, revision: &str) -> Result<()> {
+    /// Show file content at a specific revision
+    fn show_file(&self, change_id: &str, path: &str) -> Result<String> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["new", revision])
+            .args(["file", "show", "-r", change_id, path])
             .output()
-            .context("Failed to run jj new")?;
+            .context("Failed to run jj file show")?;
 
         if !output.status.success() {
+            if String::from_utf8_lossy(&output.stderr).contains("No such path") {
+                return Err(NoSuchPath { path: path.to_string() }.into());
+            }
             anyhow::bail!(
-                "jj new failed: {}",
+                "jj file show failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(())
+        Ok(String::from_utf8(output.stdout)?)
     }
 
-    pub fn describe(&self, message: &str) -> Result<()> {
+    /// Get the change_id that a bookmark points to, if it exists
+    fn get_bookmark(&self, name: &str) -> Result<Option<String>> {
         let output = Command::new("jj")
             .current_dir(&self.repo_path)
-            .args(["describe", "-m", message])
+            .args(["log", "--no-graph", "-r", name, "-T", "change_id"])
             .output()
-            .context("Failed to run jj describe")?;
+            .context("Failed to run jj log for bookmark")?;
+
+        if !output.status.success() {
+            // Bookmark doesn't exist
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("doesn't exist") {
+                return Ok(None);
+            }
+            anyhow::bail!("jj log failed: {}", stderr);
+        }
+
+        let change_id = String::from_utf8(output.stdout)?.trim().to_string();
+        Ok(Some(change_id))
+    }
+
+    fn bookmark_create(&self, name: &str, revision: &str) -> Result<()> {
+        let output = self.tagged("bookmark_create")
+            .args(["bookmark", "create", name, "-r", revision])
+            .output()
+            .context("Failed to run jj bookmark create")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj describe failed: {}",
+                "jj bookmark create failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
@@ -636,33 +803,32 @@ impl Jj {
         Ok(())
     }
 
-    pub fn rebase(&self, revision: &str, destination: &str) -> Result<String> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["rebase", "-r", revision, "-d", destination])
+    /// Move a bookmark to point to a specific change
+    fn move_bookmark(&self, name: &str, change_id: &str) -> Result<()> {
+        let output = self.tagged("move_bookmark")
+            .args(["bookmark", "set", name, "-r", change_id, "--allow-backwards"])
             .output()
-            .context("Failed to run jj rebase")?;
+            .context("Failed to run jj bookmark set")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj rebase failed: {}",
+                "jj bookmark set failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(String::from_utf8(output.stdout)?)
+        Ok(())
     }
 
-    pub fn squash_into(&self, from: &str, into: &str) -> Result<()> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["squash", "--from", from, "--into", into])
+    fn bookmark_delete(&self, name: &str) -> Result<()> {
+        let output = self.tagged("bookmark_delete")
+            .args(["bookmark", "delete", name])
             .output()
-            .context("Failed to run jj squash")?;
+            .context("Failed to run jj bookmark delete")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj squash failed: {}",
+                "jj bookmark delete failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
@@ -670,76 +836,241 @@ impl Jj {
         Ok(())
     }
 
-    pub fn abandon(&self, revision: &str) -> Result<()> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["abandon", revision])
+    fn git_push_bookmark(&self, bookmark: &str, allow_new: bool) -> Result<String> {
+        let mut args = vec!["git", "push", "--bookmark", bookmark];
+        if allow_new {
+            args.push("--allow-new");
+        }
+
+        let output = self.tagged("git_push_bookmark")
+            .args(&args)
             .output()
-            .context("Failed to run jj abandon")?;
+            .context("Failed to run jj git push")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj abandon failed: {}",
+                "jj git push failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(())
+        Ok(String::from_utf8(output.stdout)?)
     }
 
-    /// Run a revset and return the matching change_ids.
-    pub fn query_change_ids(&self, revset: &str) -> Result<Vec<String>> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args([
-                "log",
-                "--no-graph",
-                "-r",
-                revset,
-                "-T",
-                r#"change_id ++ "\n""#,
-            ])
+    fn git_fetch(&self) -> Result<String> {
+        let output = self.tagged("git_fetch")
+            .args(["git", "fetch"])
             .output()
-            .context("Failed to run jj log")?;
+            .context("Failed to run jj git fetch")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj log failed: {}",
+                "jj git fetch failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        let stdout = String::from_utf8(output.stdout)?;
-        Ok(stdout
-            .lines()
-            .filter(|l| !l.is_empty())
-            .map(|l| l.to_string())
-            .collect())
+        Ok(String::from_utf8(output.stdout)?)
     }
 
-    /// Get the working copy change ID
-    pub fn working_copy_change_id(&self) -> Result<String> {
-        let output = Command::new("jj")
-            .current_dir(&self.repo_path)
-            .args(["log", "--no-graph", "-r", "@", "-T", "change_id"])
+    /// Fetch only specific branches from origin.
+    fn git_fetch_branches(&self, branches: &[&str]) -> Result<String> {
+        let mut args = vec!["git", "fetch"];
+        for b in branches {
+            args.push("-b");
+            args.push(b);
+        }
+        let output = self.tagged("git_fetch_branches")
+            .args(&args)
             .output()
-            .context("Failed to run jj log")?;
+            .context("Failed to run jj git fetch")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "jj log failed: {}",
+                "jj git fetch failed: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn rebase(&self, revision: &str, destination: &str) -> Result<String> {
+        let output = self.tagged("rebase")
+            .args(["rebase", "-r", revision, "-d", destination])
+            .output()
+            .context("Failed to run jj rebase")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj rebase failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn clone_for_session(&self, dest: &Path, base: &str, bookmark: &str) -> Result<Box<dyn Vcs>> {
+        let clone = Jj::git_clone_branches(&self.repo_path, dest, &[base])?;
+
+        // The clone was created with `-b <base>` so git only fetches that branch.
+        // Add a refspec for the session bookmark so jj's auto-import doesn't
+        // discard it after push (jj removes bookmarks not in the fetch refspec).
+        clone.git_config_add(
+            "remote.origin.fetch",
+            &format!("+refs/heads/{bookmark}:refs/remotes/origin/{bookmark}"),
+        )?;
+
+        // Override immutable_heads to only include trunk. jj's default includes
+        // untracked_remote_bookmarks(), which makes other sessions' commits immutable.
+        clone.set_repo_config(
+            "revset-aliases.\"immutable_heads()\"",
+            "present(trunk()) | tags()",
+        )?;
+
+        // The clone's WC lands on root, not main. Create a new change on top of base@origin.
+        clone.new_change_on(&format!("{base}@origin"))?;
+
+        // Create bookmark in clone
+        clone.bookmark_create(bookmark, "@")?;
+
+        Ok(Box::new(clone))
     }
+    fn as_jj(&self) -> Option<&Jj> {
+        Some(self)
+    }
+}
+
+/// Parse a `jj resolve --list` line such as `src/lib.rs    2-sided conflict`
+/// or `a.txt  2-sided conflict including 1 deletion` into its path and
+/// number of deletions.
+fn parse_conflict_line(line: &str) -> Option<(String, usize)> {
+    let sided = line.find("-sided conflict")?;
+    let number_start = line[..sided].trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let path = line[..number_start].trim_end();
+    let deletions = line[sided..]
+        .split_once(" including ")
+        .and_then(|(_, rest)| rest.split_once(' '))
+        .and_then(|(count, _)| count.parse().ok())
+        .unwrap_or(0);
+    (!path.is_empty()).then(|| (path.to_string(), deletions))
+}
+
+/// Parse an op log line: id, time, description and tags, tab-separated.
+/// The tags include the command's arguments, where `tagged` leaves the
+/// operation name.
+fn parse_operation_line(line: &str) -> Option<Operation> {
+    let mut parts = line.splitn(4, '\t');
+    let id = parts.next()?.to_string();
+    let time = parts.next()?.to_string();
+    let description = parts.next()?.to_string();
+    let tags = parts.next().unwrap_or_default();
+    let marker = format!("{OPERATION_TAG}=");
+    let aipair = tags.find(&marker).map(|start| {
+        tags[start + marker.len()..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect()
+    });
+    Some(Operation { id, time, description, aipair })
+}
+
+/// Parse a `jj diff --summary` line such as `M src/lib.rs` or
+/// `R src/{old.rs => new.rs}`.
+fn parse_summary_line(line: &str) -> Option<FileDiff> {
+    let (status, rest) = line.split_once(' ')?;
+    let status = match status {
+        "A" => FileStatus::Added,
+        "M" => FileStatus::Modified,
+        "D" => FileStatus::Deleted,
+        "R" | "C" => {
+            // Renames and copies print the differing middle part in braces
+            let (prefix, rest) = rest.split_once('{')?;
+            let (middle, suffix) = rest.split_once('}')?;
+            let (from, to) = middle.split_once(" => ")?;
+            let join = |part: &str| format!("{prefix}{part}{suffix}").replace("//", "/");
+            let from = join(from);
+            let path = join(to);
+            let status = if status == "R" {
+                FileStatus::Renamed { from }
+            } else {
+                FileStatus::Copied { from }
+            };
+            return Some(FileDiff::new(path, status));
+        }
+        _ => return None,
+    };
+    Some(FileDiff::new(rest.to_string(), status))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_conflict_line() {
+        assert_eq!(parse_conflict_line("src/main.rs    2-sided conflict"), Some(("src/main.rs".into(), 0)));
+        assert_eq!(
+            parse_conflict_line("my file.txt  3-sided conflict including 1 deletion"),
+            Some(("my file.txt".into(), 1))
+        );
+        assert_eq!(parse_conflict_line("Error: nothing here"), None);
+    }
+
+    #[test]
+    fn test_parse_operation_line() {
+        let line = "abc123\t2026-01-01T10:00:00+01:00\trebase commit 1234\targs: jj --config=aipair.operation=rebase rebase -r x -d main";
+        assert_eq!(
+            parse_operation_line(line),
+            Some(Operation {
+                id: "abc123".into(),
+                time: "2026-01-01T10:00:00+01:00".into(),
+                description: "rebase commit 1234".into(),
+                aipair: Some("rebase".into()),
+            })
+        );
+        // Quoted by jj when it records the arguments
+        let line = "def456\tt\tbookmark set\targs: jj '--config=aipair.operation=move_bookmark' bookmark set main";
+        assert_eq!(parse_operation_line(line).unwrap().aipair.as_deref(), Some("move_bookmark"));
+        let line = "789abc\tt\tsnapshot working copy\targs: jj log";
+        assert_eq!(parse_operation_line(line).unwrap().aipair, None);
+    }
+
+    #[test]
+    fn test_parse_summary_line() {
+        assert_eq!(
+            parse_summary_line("M src/main.rs"),
+            Some(FileDiff::new("src/main.rs".into(), FileStatus::Modified))
+        );
+        assert_eq!(
+            parse_summary_line("R src/{old.rs => new.rs}"),
+            Some(FileDiff::new("src/new.rs".into(), FileStatus::Renamed { from: "src/old.rs".into() }))
+        );
+        assert_eq!(
+            parse_summary_line("R {a.rs => sub/b.rs}"),
+            Some(FileDiff::new("sub/b.rs".into(), FileStatus::Renamed { from: "a.rs".into() }))
+        );
+        // Moving out of a directory leaves an empty side in the braces
+        assert_eq!(
+            parse_summary_line("C src/{ => nested}/lib.rs"),
+            Some(FileDiff::new("src/nested/lib.rs".into(), FileStatus::Copied { from: "src/lib.rs".into() }))
+        );
+        assert_eq!(parse_summary_line("? weird"), None);
+    }
+
+    #[test]
+    fn test_file_status_serialization() {
+        let renamed = FileDiff::new("b.rs".into(), FileStatus::Renamed { from: "a.rs".into() });
+        assert_eq!(
+            serde_json::to_value(&renamed).unwrap()["status"],
+            serde_json::json!({ "renamed": { "from": "a.rs" } })
+        );
+        // Plain statuses stay plain strings
+        let added = FileDiff::new("c.rs".into(), FileStatus::Added);
+        assert_eq!(serde_json::to_value(&added).unwrap()["status"], "added");
+    }
+
     #[test]
     fn test_jj_discover() {
         // This test only works if run from within a jj repo
@@ -1,24 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
 use tracing::warn;
 
-use crate::jj::Jj;
-use crate::review::Thread;
-
-#[derive(Debug, Clone)]
-pub struct Hunk {
-    pub old_start: usize,
-    pub old_count: usize,
-    pub new_start: usize,
-    pub new_count: usize,
-    pub lines: Vec<HunkLine>,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum HunkLine {
-    Context,
-    Add,
-    Delete,
-}
+use crate::cache::Cache;
+use crate::diff::{Hunk, LineKind, file_hunks};
+use crate::jj::{FileDiff, FileStatus};
+use crate::vcs::{Vcs, is_commit_id};
+use crate::review::{AnchorSnapshot, Side, Thread, ThreadAnchor};
 
 /// Result of mapping a thread's position through a diff
 #[derive(Debug, Clone)]
@@ -26,90 +14,32 @@ pub struct MappedPosition {
     pub line_start: usize,
     pub line_end: usize,
     pub is_deleted: bool,
+    /// 1.0 when the position followed the diff exactly, the similarity score
+    /// for a content match, 0.0 when the lines were lost
+    pub confidence: f32,
+    /// The file the lines live in now, when it isn't the thread's own
+    /// (the file was renamed, or the code moved to another file)
+    pub file: Option<String>,
 }
 
-/// Parse hunks for a single file from a git-format unified diff.
-/// The diff_text should be the raw output of `jj diff --git` (may contain multiple files).
-/// Returns hunks only for the specified file.
-pub fn parse_file_hunks(diff_text: &str, target_file: &str) -> Vec<Hunk> {
-    let mut hunks = Vec::new();
-    let mut in_target_file = false;
-    let mut current_hunk: Option<Hunk> = None;
-
-    for line in diff_text.lines() {
-        if line.starts_with("diff --git") {
-            // Flush any current hunk
-            if let Some(hunk) = current_hunk.take() {
-                if in_target_file {
-                    hunks.push(hunk);
-                }
-            }
-            // Check if this diff section is for our target file
-            // Format: "diff --git a/path b/path"
-            in_target_file = line.ends_with(&format!(" b/{}", target_file));
-        } else if line.starts_with("@@") && in_target_file {
-            // Flush previous hunk
-            if let Some(hunk) = current_hunk.take() {
-                hunks.push(hunk);
-            }
-            // Parse hunk header: @@ -old_start,old_count +new_start,new_count @@
-            if let Some(hunk) = parse_hunk_header(line) {
-                current_hunk = Some(hunk);
-            }
-        } else if let Some(ref mut hunk) = current_hunk {
-            if in_target_file {
-                if line.starts_with('+') {
-                    hunk.lines.push(HunkLine::Add);
-                } else if line.starts_with('-') {
-                    hunk.lines.push(HunkLine::Delete);
-                } else if line.starts_with(' ') || line.is_empty() {
-                    hunk.lines.push(HunkLine::Context);
-                }
-                // Skip other lines (e.g., "\ No newline at end of file")
-            }
-        }
+impl MappedPosition {
+    fn exact(line_start: usize, line_end: usize) -> Self {
+        Self { line_start, line_end, is_deleted: false, confidence: 1.0, file: None }
     }
 
-    // Flush final hunk
-    if let Some(hunk) = current_hunk {
-        if in_target_file {
-            hunks.push(hunk);
-        }
+    fn deleted(line_start: usize, line_end: usize) -> Self {
+        Self { line_start, line_end, is_deleted: true, confidence: 0.0, file: None }
     }
-
-    hunks
 }
 
-fn parse_hunk_header(line: &str) -> Option<Hunk> {
-    // @@ -old_start,old_count +new_start,new_count @@
-    // or @@ -old_start +new_start,new_count @@ (count defaults to 1)
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() < 4 {
-        return None;
-    }
-
-    let old_part = parts[1].trim_start_matches('-');
-    let new_part = parts[2].trim_start_matches('+');
+/// Lines of context kept on either side of a thread's snapshot
+const SNAPSHOT_CONTEXT: usize = 3;
 
-    let (old_start, old_count) = parse_range(old_part)?;
-    let (new_start, new_count) = parse_range(new_part)?;
-
-    Some(Hunk {
-        old_start,
-        old_count,
-        new_start,
-        new_count,
-        lines: Vec::new(),
-    })
-}
+/// Content matches scoring below this are not trusted
+const MIN_MATCH_CONFIDENCE: f32 = 0.7;
 
-fn parse_range(s: &str) -> Option<(usize, usize)> {
-    if let Some((start, count)) = s.split_once(',') {
-        Some((start.parse().ok()?, count.parse().ok()?))
-    } else {
-        Some((s.parse().ok()?, 1))
-    }
-}
+/// How much the surrounding context counts towards a match's score
+const CONTEXT_WEIGHT: f32 = 0.2;
 
 /// Result of mapping a single line through hunks.
 #[derive(Debug, Clone, PartialEq)]
@@ -142,8 +72,8 @@ pub fn map_line(old_line: usize, hunks: &[Hunk]) -> LineMapping {
             let mut last_surviving = None;
 
             for hunk_line in &hunk.lines {
-                match hunk_line {
-                    HunkLine::Context => {
+                match hunk_line.kind {
+                    LineKind::Context => {
                         if old_pos == old_line {
                             return LineMapping { new_line: new_pos, was_deleted: false };
                         }
@@ -151,7 +81,7 @@ pub fn map_line(old_line: usize, hunks: &[Hunk]) -> LineMapping {
                         old_pos += 1;
                         new_pos += 1;
                     }
-                    HunkLine::Delete => {
+                    LineKind::Delete => {
                         if old_pos == old_line {
                             let anchor = find_nearest_surviving(hunk, old_line);
                             return LineMapping {
@@ -161,7 +91,7 @@ pub fn map_line(old_line: usize, hunks: &[Hunk]) -> LineMapping {
                         }
                         old_pos += 1;
                     }
-                    HunkLine::Add => {
+                    LineKind::Add => {
                         new_pos += 1;
                     }
                 }
@@ -197,8 +127,8 @@ fn find_nearest_surviving(hunk: &Hunk, deleted_old_line: usize) -> usize {
     let mut reached_target = false;
 
     for hunk_line in &hunk.lines {
-        match hunk_line {
-            HunkLine::Context => {
+        match hunk_line.kind {
+            LineKind::Context => {
                 if reached_target {
                     return new_pos; // First surviving line after deletion
                 }
@@ -206,13 +136,13 @@ fn find_nearest_surviving(hunk: &Hunk, deleted_old_line: usize) -> usize {
                 old_pos += 1;
                 new_pos += 1;
             }
-            HunkLine::Delete => {
+            LineKind::Delete => {
                 if old_pos == deleted_old_line {
                     reached_target = true;
                 }
                 old_pos += 1;
             }
-            HunkLine::Add => {
+            LineKind::Add => {
                 if reached_target {
                     // An added line right after the deletion — anchor here
                     return new_pos;
@@ -226,157 +156,445 @@ fn find_nearest_surviving(hunk: &Hunk, deleted_old_line: usize) -> usize {
     last_before.unwrap_or(hunk.new_start.max(1))
 }
 
+/// Capture lines `line_start..=line_end` (1-based) of `content` plus some
+/// context, or `None` if the range is out of bounds.
+pub fn snapshot_lines(content: &str, line_start: usize, line_end: usize) -> Option<AnchorSnapshot> {
+    let lines: Vec<&str> = content.lines().collect();
+    if line_start == 0 || line_start > line_end || line_end > lines.len() {
+        return None;
+    }
+    let (start, end) = (line_start - 1, line_end);
+    let to_owned = |s: &[&str]| s.iter().map(|l| l.to_string()).collect();
+    Some(AnchorSnapshot {
+        lines: to_owned(&lines[start..end]),
+        before: to_owned(&lines[start.saturating_sub(SNAPSHOT_CONTEXT)..start]),
+        after: to_owned(&lines[end..(end + SNAPSHOT_CONTEXT).min(lines.len())]),
+    })
+}
+
+/// Find where a snapshot's lines most likely ended up in `content`.
+///
+/// Every window of the snapshot's length is scored by per-line similarity,
+/// with the surrounding context counting for `CONTEXT_WEIGHT`. Ties go to the
+/// window closest to `near` (the diff-mapped guess). Returns `None` when no
+/// window reaches `MIN_MATCH_CONFIDENCE`.
+///
+/// If `added` is given (1-based line numbers the diff added), only windows
+/// made up mostly of added lines are considered: lines the diff carried over
+/// unchanged can't be where the lost ones went.
+pub fn find_best_match(
+    snapshot: &AnchorSnapshot,
+    content: &str,
+    near: usize,
+    added: Option<&HashSet<usize>>,
+) -> Option<MappedPosition> {
+    let target: Vec<&str> = content.lines().collect();
+    let n = snapshot.lines.len();
+    if n == 0 || n > target.len() {
+        return None;
+    }
+
+    let mut best: Option<(f32, usize)> = None;
+    for start in 0..=target.len() - n {
+        if let Some(added) = added {
+            let added_in_window = (start + 1..=start + n).filter(|l| added.contains(l)).count();
+            if added_in_window * 2 <= n {
+                continue;
+            }
+        }
+
+        let core = (0..n)
+            .map(|i| line_similarity(&snapshot.lines[i], target[start + i]))
+            .sum::<f32>()
+            / n as f32;
+        // Can't win even with perfect context
+        if core * (1.0 - CONTEXT_WEIGHT) + CONTEXT_WEIGHT < best.map_or(MIN_MATCH_CONFIDENCE, |b| b.0) {
+            continue;
+        }
+
+        let context_len = snapshot.before.len() + snapshot.after.len();
+        let score = if context_len == 0 {
+            core
+        } else {
+            let before = snapshot.before.iter().rev().enumerate().map(|(i, line)| {
+                start.checked_sub(i + 1).map_or(0.0, |t| line_similarity(line, target[t]))
+            });
+            let after = snapshot.after.iter().enumerate().map(|(i, line)| {
+                target.get(start + n + i).map_or(0.0, |t| line_similarity(line, t))
+            });
+            let context = before.chain(after).sum::<f32>() / context_len as f32;
+            core * (1.0 - CONTEXT_WEIGHT) + context * CONTEXT_WEIGHT
+        };
+
+        let better = match best {
+            None => true,
+            Some((best_score, best_start)) => {
+                score > best_score
+                    || (score == best_score && (start + 1).abs_diff(near) < (best_start + 1).abs_diff(near))
+            }
+        };
+        if better {
+            best = Some((score, start));
+        }
+    }
+
+    let (score, start) = best.filter(|(score, _)| *score >= MIN_MATCH_CONFIDENCE)?;
+    Some(MappedPosition {
+        line_start: start + 1,
+        line_end: start + n,
+        is_deleted: false,
+        confidence: score,
+        file: None,
+    })
+}
+
+/// Similarity of two lines in [0, 1], ignoring surrounding whitespace.
+fn line_similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (a.trim(), b.trim());
+    if a == b {
+        return 1.0;
+    }
+    similar::TextDiff::from_chars(a, b).ratio()
+}
+
 /// Map all threads to their positions at the target commit.
 /// Groups by file to avoid redundant diffs.
+///
+/// New-side threads are mapped through the diff between the commit they were
+/// created on and `target_commit`. Old-side threads point into the parent, so
+/// they are mapped through the diff between the two parents instead; that diff
+/// is empty unless the change was rebased.
+///
+/// Renamed files are followed using jj's rename detection. Threads whose lines
+/// the diff loses are looked up by content using their snapshot, in their own
+/// file first and then in every other file the diff touched, so a block moved
+/// into another module takes its threads along.
+///
+/// Positions are cached by the commits involved, so threads already mapped
+/// to the same target aren't mapped again.
 pub fn map_all_threads(
-    jj: &Jj,
+    vcs: &dyn Vcs,
     threads: &[Thread],
     target_commit: &str,
 ) -> HashMap<String, MappedPosition> {
+    let target = if is_commit_id(target_commit) {
+        Some(target_commit.to_string())
+    } else {
+        match vcs.query_commit_ids(target_commit).ok().as_deref() {
+            Some([(_, commit_id)]) => Some(commit_id.clone()),
+            _ => None,
+        }
+    };
+
     let mut results = HashMap::new();
+    let mut keys = HashMap::new();
+    let mut uncached = Vec::new();
+    for thread in threads {
+        let key = target.as_deref().and_then(|target| mapping_key(thread, target));
+        match key {
+            Some(key) => match MAPPINGS.get(&key) {
+                Some(pos) => {
+                    results.insert(thread.id.clone(), pos);
+                }
+                None => {
+                    keys.insert(thread.id.as_str(), key);
+                    uncached.push(thread);
+                }
+            },
+            None => uncached.push(thread),
+        }
+    }
 
-    // Group threads by (file, created_at_commit)
-    let mut groups: HashMap<(String, String), Vec<&Thread>> = HashMap::new();
+    for (id, pos) in map_threads(vcs, &uncached, target_commit) {
+        if let Some(key) = keys.remove(id.as_str()) {
+            MAPPINGS.insert(key, pos.clone());
+        }
+        results.insert(id, pos);
+    }
+    results
+}
+
+/// Where a thread lands depends only on the commits it's mapped between and
+/// where it was placed
+type MappingKey = (String, String, String, String, Side, usize, usize);
+
+/// Mapped positions of threads
+static MAPPINGS: LazyLock<Cache<MappingKey, MappedPosition>> = LazyLock::new(|| Cache::new("mappings", 4096));
+
+/// The cache key for mapping `thread` to `target`, if its commit is known
+fn mapping_key(thread: &Thread, target: &str) -> Option<MappingKey> {
+    let created = thread.created_at_commit.as_ref().filter(|c| is_commit_id(c))?;
+    Some((
+        target.to_string(),
+        thread.id.clone(),
+        created.clone(),
+        thread.file.clone(),
+        thread.side,
+        thread.line_start,
+        thread.line_end,
+    ))
+}
+
+fn map_threads(
+    vcs: &dyn Vcs,
+    threads: &[&Thread],
+    target_commit: &str,
+) -> HashMap<String, MappedPosition> {
+    let mut results = HashMap::new();
+
+    // Group threads by (file, created_at_commit, side)
+    let mut groups: HashMap<(String, String, Side), Vec<&Thread>> = HashMap::new();
+    // Changed files per (from, to) diff, shared between groups
+    let mut changed_files: HashMap<(String, String), Vec<FileDiff>> = HashMap::new();
+
+    for &thread in threads {
+        // Description and change threads have nothing to follow
+        if !matches!(thread.anchor, ThreadAnchor::Line | ThreadAnchor::File) {
+            continue;
+        }
 
-    for thread in threads {
         let commit = match &thread.created_at_commit {
             Some(c) if c != target_commit => c.clone(),
             _ => {
                 // No mapping needed — use stored positions
                 results.insert(
                     thread.id.clone(),
-                    MappedPosition {
-                        line_start: thread.line_start,
-                        line_end: thread.line_end,
-                        is_deleted: false,
-                    },
+                    MappedPosition::exact(thread.line_start, thread.line_end),
                 );
                 continue;
             }
         };
 
+        // File threads only need to know where their file went
+        if thread.anchor == ThreadAnchor::File {
+            let changed = changed_between(vcs, &mut changed_files, &commit, target_commit);
+            let mut pos = MappedPosition::exact(0, 0);
+            match follow_rename(changed, &thread.file) {
+                Some(renamed) => pos.file = Some(renamed.to_string()),
+                None => {
+                    if changed.iter().any(|c| c.status == FileStatus::Deleted && c.path == thread.file) {
+                        pos = MappedPosition::deleted(0, 0);
+                    }
+                }
+            }
+            results.insert(thread.id.clone(), pos);
+            continue;
+        }
+
         groups
-            .entry((thread.file.clone(), commit))
+            .entry((thread.file.clone(), commit, thread.side))
             .or_default()
             .push(thread);
     }
 
-    // For each unique (file, commit) pair, run one diff and map all threads
-    for ((file, from_commit), group_threads) in &groups {
-        let diff_text = match jj.diff_raw_between(from_commit, target_commit, &file) {
-            Ok(text) => text,
-            Err(e) => {
-                warn!("Failed to get diff for {} from {} to {}: {}", file, from_commit, target_commit, e);
-                // If diff fails (e.g., file deleted), mark all threads as deleted
-                for thread in group_threads {
-                    results.insert(
-                        thread.id.clone(),
-                        MappedPosition {
-                            line_start: thread.line_start,
-                            line_end: thread.line_end,
-                            is_deleted: true,
-                        },
-                    );
-                }
-                continue;
-            }
+    // For each unique (file, commit, side), run one diff and map all threads
+    for ((file, commit, side), group_threads) in &groups {
+        let (from_commit, to_commit) = match side {
+            Side::New => (commit.clone(), target_commit.to_string()),
+            Side::Old => (format!("{commit}-"), format!("{target_commit}-")),
+        };
+        let changed = changed_between(vcs, &mut changed_files, &from_commit, &to_commit);
+        let target_file = follow_rename(changed, file).unwrap_or(file);
+
+        let (mapped, added) = map_group(vcs, file, target_file, &from_commit, &to_commit, group_threads);
+
+        // Candidate files for a content search: the thread's own file, then
+        // every other file with new content (except aipair's own data, which
+        // quotes the snapshots)
+        let mut candidates = vec![(target_file, (target_file != file.as_str()).then_some(file.as_str()))];
+        candidates.extend(
+            changed
+                .iter()
+                .filter(|c| c.status != FileStatus::Deleted && c.path != target_file && !c.path.starts_with(".aipair/"))
+                .map(|c| (c.path.as_str(), c.source())),
+        );
+        let mut targets = TargetFiles {
+            vcs,
+            from: &from_commit,
+            to: &to_commit,
+            files: HashMap::new(),
         };
+        let mut own_added = Some(added);
 
-        // Check if the diff is empty (no changes to this file)
-        if diff_text.trim().is_empty() {
-            for thread in group_threads {
-                results.insert(
-                    thread.id.clone(),
-                    MappedPosition {
-                        line_start: thread.line_start,
-                        line_end: thread.line_end,
-                        is_deleted: false,
-                    },
-                );
+        for (thread, mut pos) in group_threads.iter().zip(mapped) {
+            if target_file != file {
+                pos.file = Some(target_file.to_string());
             }
-            continue;
+
+            // Fall back to a content search for threads the diff lost
+            if pos.is_deleted
+                && let Some(snapshot) = &thread.snapshot
+            {
+                let mut best: Option<MappedPosition> = None;
+                for &(path, source) in &candidates {
+                    let target = if path == target_file {
+                        targets.get_with_added(path, &mut own_added)
+                    } else {
+                        targets.get(path, source)
+                    };
+                    let Some((content, added)) = target else { continue };
+                    let near = if path == target_file { pos.line_start } else { 0 };
+                    if let Some(mut found) = find_best_match(snapshot, content, near, Some(added))
+                        && best.as_ref().is_none_or(|b| found.confidence > b.confidence)
+                    {
+                        found.file = (path != file).then(|| path.to_string());
+                        best = Some(found);
+                    }
+                }
+                if let Some(found) = best {
+                    pos = found;
+                }
+            }
+            results.insert(thread.id.clone(), pos);
         }
+    }
 
-        let hunks = parse_file_hunks(&diff_text, &file);
+    results
+}
 
-        // If no hunks found but diff text wasn't empty, it might be a file deletion
-        if hunks.is_empty() && diff_text.contains("deleted file") {
-            for thread in group_threads {
-                results.insert(
-                    thread.id.clone(),
-                    MappedPosition {
-                        line_start: thread.line_start,
-                        line_end: thread.line_end,
-                        is_deleted: true,
-                    },
-                );
-            }
-            continue;
+/// Files changed between two commits, fetched once per pair.
+fn changed_between<'a>(
+    vcs: &dyn Vcs,
+    cache: &'a mut HashMap<(String, String), Vec<FileDiff>>,
+    from: &str,
+    to: &str,
+) -> &'a [FileDiff] {
+    cache
+        .entry((from.to_string(), to.to_string()))
+        .or_insert_with(|| {
+            vcs.changed_files_between(from, to).unwrap_or_else(|e| {
+                warn!("Failed to list changed files from {} to {}: {}", from, to, e);
+                Vec::new()
+            })
+        })
+}
+
+/// Where `file` was renamed to, if it was.
+fn follow_rename<'a>(changed: &'a [FileDiff], file: &str) -> Option<&'a str> {
+    changed
+        .iter()
+        .find(|c| matches!(&c.status, FileStatus::Renamed { from } if from == file))
+        .map(|c| c.path.as_str())
+}
+
+/// Target-side content and added lines per file, loaded on first use.
+struct TargetFiles<'a> {
+    vcs: &'a dyn Vcs,
+    from: &'a str,
+    to: &'a str,
+    files: HashMap<String, Option<(String, HashSet<usize>)>>,
+}
+
+impl TargetFiles<'_> {
+    /// Content of `path` at `to` and the lines the diff added to it. `source`
+    /// is where the file came from if it was renamed or copied.
+    fn get(&mut self, path: &str, source: Option<&str>) -> Option<(&str, &HashSet<usize>)> {
+        let (vcs, from, to) = (self.vcs, self.from, self.to);
+        let entry = self.files.entry(path.to_string()).or_insert_with(|| {
+            let mut paths = vec![path];
+            paths.extend(source);
+            let diff = vcs.diff_raw_between_paths(from, to, &paths, None).ok()?;
+            let content = vcs.show_file(to, path).ok()?;
+            Some((content, added_lines(&file_hunks(&diff, path))))
+        });
+        entry.as_ref().map(|(content, added)| (content.as_str(), added))
+    }
+
+    /// Like `get`, reusing added lines already computed while mapping.
+    fn get_with_added(&mut self, path: &str, added: &mut Option<HashSet<usize>>) -> Option<(&str, &HashSet<usize>)> {
+        if !self.files.contains_key(path)
+            && let Some(added) = added.take()
+        {
+            let content = self.vcs.show_file(self.to, path).ok();
+            self.files.insert(path.to_string(), content.map(|c| (c, added)));
+        }
+        self.get(path, None)
+    }
+}
+
+/// Map one group of threads on `file` through the diff `from..to`, returning
+/// positions in the same order as `threads`, plus the new-side lines the
+/// diff added. `target_file` is where `file` lives at `to` (differs if it
+/// was renamed).
+fn map_group(
+    vcs: &dyn Vcs,
+    file: &str,
+    target_file: &str,
+    from: &str,
+    to: &str,
+    threads: &[&Thread],
+) -> (Vec<MappedPosition>, HashSet<usize>) {
+    let mut paths = vec![file];
+    if target_file != file {
+        paths.push(target_file);
+    }
+    let diff_text = match vcs.diff_raw_between_paths(from, to, &paths, None) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to get diff for {} from {} to {}: {}", file, from, to, e);
+            // If diff fails (e.g., file deleted), mark all threads as deleted
+            let mapped = threads
+                .iter()
+                .map(|t| MappedPosition::deleted(t.line_start, t.line_end))
+                .collect();
+            return (mapped, HashSet::new());
         }
+    };
+
+    // Check if the diff is empty (no changes to this file)
+    if diff_text.trim().is_empty() {
+        let mapped = threads
+            .iter()
+            .map(|t| MappedPosition::exact(t.line_start, t.line_end))
+            .collect();
+        return (mapped, HashSet::new());
+    }
+
+    let hunks = file_hunks(&diff_text, target_file);
+
+    // If no hunks found but diff text wasn't empty, it might be a file deletion
+    if hunks.is_empty() && diff_text.contains("deleted file") {
+        let mapped = threads
+            .iter()
+            .map(|t| MappedPosition::deleted(t.line_start, t.line_end))
+            .collect();
+        return (mapped, HashSet::new());
+    }
 
-        for thread in group_threads {
+    let mapped = threads
+        .iter()
+        .map(|thread| {
             let mapped_start = map_line(thread.line_start, &hunks);
             let mapped_end = map_line(thread.line_end, &hunks);
 
             let is_deleted = mapped_start.was_deleted || mapped_end.was_deleted;
 
-            results.insert(
-                thread.id.clone(),
-                MappedPosition {
-                    line_start: mapped_start.new_line,
-                    line_end: mapped_end.new_line,
-                    is_deleted,
-                },
-            );
-        }
-    }
+            MappedPosition {
+                line_start: mapped_start.new_line,
+                line_end: mapped_end.new_line,
+                is_deleted,
+                confidence: if is_deleted { 0.0 } else { 1.0 },
+                file: None,
+            }
+        })
+        .collect();
+    (mapped, added_lines(&hunks))
+}
 
-    results
+/// New-side line numbers of all lines the hunks add.
+fn added_lines(hunks: &[Hunk]) -> HashSet<usize> {
+    hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter(|line| line.kind == LineKind::Add)
+        .filter_map(|line| line.new_line)
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_parse_hunk_header() {
-        let hunk = parse_hunk_header("@@ -10,5 +12,7 @@ some context").unwrap();
-        assert_eq!(hunk.old_start, 10);
-        assert_eq!(hunk.old_count, 5);
-        assert_eq!(hunk.new_start, 12);
-        assert_eq!(hunk.new_count, 7);
-    }
-
-    #[test]
-    fn test_parse_hunk_header_no_count() {
-        let hunk = parse_hunk_header("@@ -1 +1,3 @@").unwrap();
-        assert_eq!(hunk.old_start, 1);
-        assert_eq!(hunk.old_count, 1);
-        assert_eq!(hunk.new_start, 1);
-        assert_eq!(hunk.new_count, 3);
-    }
-
-    #[test]
-    fn test_parse_file_hunks() {
-        let diff = "\
-diff --git a/src/main.rs b/src/main.rs
---- a/src/main.rs
-+++ b/src/main.rs
-@@ -5,3 +5,5 @@ fn main() {
-     let x = 1;
-+    let y = 2;
-+    let z = 3;
-     let a = 4;
-     let b = 5;
-";
-        let hunks = parse_file_hunks(diff, "src/main.rs");
-        assert_eq!(hunks.len(), 1);
-        assert_eq!(hunks[0].old_start, 5);
-        assert_eq!(hunks[0].old_count, 3);
-        assert_eq!(hunks[0].new_start, 5);
-        assert_eq!(hunks[0].new_count, 5);
-    }
-
     fn at(line: usize) -> LineMapping {
         LineMapping { new_line: line, was_deleted: false }
     }
@@ -386,7 +604,7 @@ diff --git a/src/main.rs b/src/main.rs
     }
 
     fn hunks(diff: &str) -> Vec<Hunk> {
-        parse_file_hunks(diff, "f.rs")
+        file_hunks(diff, "f.rs")
     }
 
     #[test]
@@ -540,43 +758,72 @@ diff --git a/f.rs b/f.rs
     }
 
     #[test]
-    fn test_parse_file_hunks_multi_file_diff() {
-        // Verify we only get hunks for the target file
-        let diff = "\
-diff --git a/foo.rs b/foo.rs
---- a/foo.rs
-+++ b/foo.rs
-@@ -1,3 +1,4 @@
- line1
-+inserted
- line2
- line3
-diff --git a/bar.rs b/bar.rs
---- a/bar.rs
-+++ b/bar.rs
-@@ -1,2 +1,3 @@
- a
-+b
- c
-";
-        let foo_hunks = parse_file_hunks(diff, "foo.rs");
-        assert_eq!(foo_hunks.len(), 1);
-        assert_eq!(foo_hunks[0].old_count, 3);
-        assert_eq!(foo_hunks[0].new_count, 4);
-
-        let bar_hunks = parse_file_hunks(diff, "bar.rs");
-        assert_eq!(bar_hunks.len(), 1);
-        assert_eq!(bar_hunks[0].old_count, 2);
-        assert_eq!(bar_hunks[0].new_count, 3);
-
-        let missing = parse_file_hunks(diff, "nope.rs");
-        assert!(missing.is_empty());
+    fn test_snapshot_lines_captures_context() {
+        let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        let snap = snapshot_lines(&content, 2, 3).unwrap();
+        assert_eq!(snap.lines, vec!["line 2", "line 3"]);
+        assert_eq!(snap.before, vec!["line 1"]);
+        assert_eq!(snap.after, vec!["line 4", "line 5", "line 6"]);
+
+        assert!(snapshot_lines(&content, 0, 1).is_none());
+        assert!(snapshot_lines(&content, 9, 11).is_none());
+    }
+
+    #[test]
+    fn test_find_best_match_moved_block() {
+        let old = "a\nb\nfn helper() {\n    compute(1, 2)\n}\nc\nd\n";
+        let snap = snapshot_lines(old, 3, 5).unwrap();
+
+        let new = "a\nb\nc\nd\nfn helper() {\n    compute(1, 2)\n}\n";
+        let pos = find_best_match(&snap, new, 3, None).unwrap();
+        assert_eq!((pos.line_start, pos.line_end), (5, 7));
+        assert!(!pos.is_deleted);
+        // Exact lines, but the surrounding context changed
+        assert!(pos.confidence >= 0.8 && pos.confidence < 1.0, "confidence {}", pos.confidence);
+    }
+
+    #[test]
+    fn test_find_best_match_edited_block() {
+        let old = "x\nlet total = price * quantity;\nreturn total;\ny\n";
+        let snap = snapshot_lines(old, 2, 3).unwrap();
+
+        let new = "x\nunrelated\nlet total = price * qty;\nreturn total;\ny\n";
+        let pos = find_best_match(&snap, new, 2, None).unwrap();
+        assert_eq!((pos.line_start, pos.line_end), (3, 4));
+        assert!(pos.confidence >= MIN_MATCH_CONFIDENCE && pos.confidence < 1.0);
+    }
+
+    #[test]
+    fn test_find_best_match_rejects_unrelated_content() {
+        let snap = snapshot_lines("fn parse_config(path: &Path) -> Config {\n", 1, 1).unwrap();
+        assert!(find_best_match(&snap, "struct Widget;\nimpl Drop for Widget {}\n", 1, None).is_none());
+    }
+
+    #[test]
+    fn test_find_best_match_only_in_added_lines() {
+        // "line 5" was deleted; the similar-looking neighbours were there all along
+        let snap = snapshot_lines("line 4\nline 5\nline 6\n", 2, 2).unwrap();
+        let content = "line 4\nline 6\n";
+        assert!(find_best_match(&snap, content, 2, None).is_some());
+        assert!(find_best_match(&snap, content, 2, Some(&HashSet::new())).is_none());
+        assert!(find_best_match(&snap, content, 2, Some(&HashSet::from([2]))).is_some());
+    }
+
+    #[test]
+    fn test_find_best_match_prefers_nearest_duplicate() {
+        let snap = AnchorSnapshot {
+            lines: vec!["}".to_string()],
+            ..Default::default()
+        };
+        let pos = find_best_match(&snap, "{\n}\n{\n}\n{\n}\n", 6, None).unwrap();
+        assert_eq!(pos.line_start, 6);
     }
 }
 
 #[cfg(test)]
 mod integration_tests {
     use super::*;
+    use crate::jj::Jj;
     use crate::review::{Thread, ThreadStatus, Comment, Author};
     use std::process::Command;
     use tempfile::TempDir;
@@ -621,21 +868,27 @@ mod integration_tests {
     fn make_thread(id: &str, file: &str, start: usize, end: usize, commit: &str) -> Thread {
         Thread {
             id: id.to_string(),
+            anchor: ThreadAnchor::Line,
             file: file.to_string(),
             line_start: start,
             line_end: end,
+            side: Side::New,
             status: ThreadStatus::Open,
-            comments: vec![Comment {
-                author: Author::User,
-                text: "test".to_string(),
-                timestamp: chrono::Utc::now(),
-            }],
+            history: Vec::new(),
+            addressed_in: None,
+            moved_from: None,
+            severity: Default::default(),
+            labels: Vec::new(),
+            comments: vec![Comment::new(Author::human("user"), "test", false)],
             created_at_commit: Some(commit.to_string()),
             created_at_revision: Some(1),
             display_line_start: None,
             display_line_end: None,
+            snapshot: None,
             is_displaced: false,
             is_deleted: false,
+            match_confidence: None,
+            display_file: None,
         }
     }
 
@@ -731,6 +984,137 @@ mod integration_tests {
         assert_eq!(pos.line_start, 5);
     }
 
+    #[test]
+    fn test_moved_block_found_by_content() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+
+        let content = "fn main() {\n    run();\n}\n\nfn helper() {\n    compute(1, 2)\n}\n\nfn other() {}\n";
+        std::fs::write(path.join("test.rs"), content).unwrap();
+        jj_cmd(path, &["describe", "-m", "initial"]);
+        let commit1 = get_commit_id(path);
+
+        // New change: move helper() below other(), touching one line
+        jj_cmd(path, &["new", "-m", "move helper"]);
+        let moved = "fn main() {\n    run();\n}\n\nfn other() {}\n\nfn helper() {\n    compute(1, 3)\n}\n";
+        std::fs::write(path.join("test.rs"), moved).unwrap();
+        let commit2 = get_commit_id(path);
+
+        let mut thread = make_thread("t1", "test.rs", 5, 7, &commit1);
+        thread.snapshot = snapshot_lines(content, 5, 7);
+        let without_snapshot = make_thread("t2", "test.rs", 5, 7, &commit1);
+
+        let mapped = map_all_threads(&jj, &[thread, without_snapshot], &commit2);
+
+        let pos = &mapped["t1"];
+        assert!(!pos.is_deleted);
+        assert_eq!((pos.line_start, pos.line_end), (7, 9));
+        assert!(pos.confidence > MIN_MATCH_CONFIDENCE && pos.confidence < 1.0);
+
+        // Without a snapshot the thread is still lost
+        assert!(mapped["t2"].is_deleted);
+        assert_eq!(mapped["t2"].confidence, 0.0);
+    }
+
+    #[test]
+    fn test_thread_follows_renamed_file() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+
+        let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(path.join("old.rs"), &content).unwrap();
+        jj_cmd(path, &["describe", "-m", "initial"]);
+        let commit1 = get_commit_id(path);
+
+        // Rename and insert a line at the top
+        jj_cmd(path, &["new", "-m", "rename"]);
+        std::fs::remove_file(path.join("old.rs")).unwrap();
+        std::fs::write(path.join("new.rs"), format!("header\n{content}")).unwrap();
+        let commit2 = get_commit_id(path);
+
+        let mut file_thread = make_thread("t2", "old.rs", 0, 0, &commit1);
+        file_thread.anchor = ThreadAnchor::File;
+        let threads = vec![make_thread("t1", "old.rs", 5, 6, &commit1), file_thread];
+        let mapped = map_all_threads(&jj, &threads, &commit2);
+
+        let pos = &mapped["t1"];
+        assert!(!pos.is_deleted);
+        assert_eq!((pos.line_start, pos.line_end), (6, 7));
+        assert_eq!(pos.confidence, 1.0);
+        assert_eq!(pos.file.as_deref(), Some("new.rs"));
+
+        assert!(!mapped["t2"].is_deleted);
+        assert_eq!(mapped["t2"].file.as_deref(), Some("new.rs"));
+    }
+
+    #[test]
+    fn test_block_moved_to_another_file() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+
+        let content = "fn main() {\n    run();\n}\n\nfn helper() {\n    compute(1, 2)\n}\n\nfn other() {}\n";
+        std::fs::write(path.join("main.rs"), content).unwrap();
+        jj_cmd(path, &["describe", "-m", "initial"]);
+        let commit1 = get_commit_id(path);
+
+        // Move helper() into its own module
+        jj_cmd(path, &["new", "-m", "extract helper"]);
+        std::fs::write(path.join("main.rs"), "mod util;\n\nfn main() {\n    run();\n}\n\nfn other() {}\n").unwrap();
+        std::fs::write(path.join("util.rs"), "use crate::x;\n\npub fn helper() {\n    compute(1, 2)\n}\n").unwrap();
+        let commit2 = get_commit_id(path);
+
+        let mut thread = make_thread("t1", "main.rs", 5, 7, &commit1);
+        thread.snapshot = snapshot_lines(content, 5, 7);
+        let mapped = map_all_threads(&jj, &[thread], &commit2);
+
+        let pos = &mapped["t1"];
+        assert!(!pos.is_deleted);
+        assert_eq!(pos.file.as_deref(), Some("util.rs"));
+        assert_eq!((pos.line_start, pos.line_end), (3, 5));
+        assert!(pos.confidence >= MIN_MATCH_CONFIDENCE && pos.confidence < 1.0);
+    }
+
+    #[test]
+    fn test_old_side_thread_follows_parent() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+
+        let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(path.join("test.rs"), &content).unwrap();
+        jj_cmd(path, &["describe", "-m", "initial"]);
+
+        // Change deletes line 5; comment on the deleted line (old side)
+        jj_cmd(path, &["new", "-m", "delete line 5"]);
+        let change_id = jj_cmd(path, &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+            .trim()
+            .to_string();
+        let deleted: String = (1..=10)
+            .filter(|i| *i != 5)
+            .map(|i| format!("line {}\n", i))
+            .collect();
+        std::fs::write(path.join("test.rs"), &deleted).unwrap();
+        let commit1 = get_commit_id(path);
+
+        let mut thread = make_thread("t1", "test.rs", 5, 5, &commit1);
+        thread.side = Side::Old;
+
+        // Amending the change itself leaves old-side lines alone
+        std::fs::write(path.join("other.rs"), "x\n").unwrap();
+        let commit2 = get_commit_id(path);
+        let mapped = map_all_threads(&jj, std::slice::from_ref(&thread), &commit2);
+        assert_eq!(mapped["t1"].line_start, 5);
+        assert!(!mapped["t1"].is_deleted);
+
+        // Inserting two lines at the top of the parent shifts them down
+        jj_cmd(path, &["edit", "@-"]);
+        std::fs::write(path.join("test.rs"), format!("a\nb\n{content}")).unwrap();
+        jj_cmd(path, &["edit", &change_id]);
+        let commit3 = get_commit_id(path);
+        let mapped = map_all_threads(&jj, &[thread], &commit3);
+        assert_eq!(mapped["t1"].line_start, 7);
+        assert!(!mapped["t1"].is_deleted);
+    }
+
     #[test]
     fn test_no_change_same_commit() {
         let (dir, jj) = make_jj_repo();
@@ -832,17 +1216,27 @@ mod integration_tests {
         // Thread without created_at_commit (old threads)
         let threads = vec![Thread {
             id: "t1".to_string(),
+            anchor: ThreadAnchor::Line,
             file: "test.rs".to_string(),
             line_start: 1,
             line_end: 1,
+            side: Side::New,
             status: ThreadStatus::Open,
+            history: Vec::new(),
+            addressed_in: None,
+            moved_from: None,
+            severity: Default::default(),
+            labels: Vec::new(),
             comments: vec![],
             created_at_commit: None,
             created_at_revision: None,
             display_line_start: None,
             display_line_end: None,
+            snapshot: None,
             is_displaced: false,
             is_deleted: false,
+            match_confidence: None,
+            display_file: None,
         }];
 
         let mapped = map_all_threads(&jj, &threads, &commit1);
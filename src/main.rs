@@ -1,11 +1,22 @@
 mod api;
+mod cache;
+mod conflict;
+mod diff;
+mod gc;
+mod identity;
+mod interdiff;
 mod jj;
 mod line_mapper;
 mod review;
+mod rewrites;
 mod session;
+mod storage;
+mod suggestion;
 mod terminal;
 mod timeline;
 mod todo;
+mod undo;
+mod vcs;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -34,6 +45,11 @@ enum Commands {
         #[command(subcommand)]
         command: SessionCommands,
     },
+    /// Manage stored reviews
+    Review {
+        #[command(subcommand)]
+        command: ReviewCommands,
+    },
     /// Push changes to main repo (from session clone)
     Push {
         #[arg(short, long)]
@@ -56,9 +72,75 @@ enum Commands {
         thread_id: String,
         /// Your response message
         message: String,
-        /// Resolve the thread after responding
-        #[arg(long)]
+        /// Mark the thread addressed, for the reviewer to verify
+        #[arg(long, conflicts_with = "status")]
         resolve: bool,
+        /// Move the thread to this status (the reviewer resolves it)
+        #[arg(long, value_enum)]
+        status: Option<review::ThreadStatus>,
+        /// The revision that addresses the thread (a jj revision in this
+        /// clone, pushed, or a review revision number); implies --resolve
+        #[arg(long)]
+        addressed_in: Option<String>,
+    },
+    /// Apply a review thread's suggested edit as a new change on top of the
+    /// reviewed change
+    ApplySuggestion {
+        /// Change ID (prefix ok) containing the thread
+        change_id: String,
+        /// Thread ID (prefix ok) with the suggestion
+        thread_id: String,
+        /// Squash the edit into the change instead
+        #[arg(long)]
+        squash: bool,
+    },
+    /// Change the text of one of your review comments
+    EditComment {
+        /// Change ID (prefix ok) containing the thread
+        change_id: String,
+        /// Thread ID (prefix ok) containing the comment
+        thread_id: String,
+        /// Comment ID (prefix ok) to edit
+        comment_id: String,
+        /// The new text
+        text: String,
+    },
+    /// Delete one of your review comments
+    DeleteComment {
+        /// Change ID (prefix ok) containing the thread
+        change_id: String,
+        /// Thread ID (prefix ok) containing the comment
+        thread_id: String,
+        /// Comment ID (prefix ok) to delete
+        comment_id: String,
+    },
+    /// List what aipair did to this repo, or restore it to before one of
+    /// those operations
+    Undo {
+        /// Operation ID (prefix ok) to undo; lists aipair's operations if
+        /// left out
+        operation: Option<String>,
+        /// Also roll back operations aipair didn't make since then
+        #[arg(long)]
+        force: bool,
+        /// How many recent operations to look through when listing
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// Show or set who you are on this machine
+    Identity {
+        /// Your name as a reviewer
+        #[arg(long)]
+        name: Option<String>,
+        /// The name agents started from this machine go by
+        #[arg(long)]
+        agent: Option<String>,
+    },
+    /// Move all .aipair data to another storage backend
+    MigrateStore {
+        /// Backend to migrate to
+        #[arg(long, value_enum)]
+        to: storage::Backend,
     },
 }
 
@@ -74,7 +156,31 @@ enum SessionCommands {
     /// List all sessions
     List,
     /// Merge a session into main
-    Merge { name: String },
+    Merge {
+        name: String,
+        /// Merge even if some changes aren't approved at their current commit
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReviewCommands {
+    /// Archive reviews of changes that were abandoned or squashed away, and
+    /// report divergent ones
+    Gc {
+        /// Only report what would be archived
+        #[arg(long)]
+        dry_run: bool,
+        /// Also archive reviews of changes already merged into main
+        #[arg(long)]
+        merged: bool,
+    },
+    /// Bring an archived review back
+    Restore {
+        /// Change ID (prefix ok) of the archived review
+        change_id: String,
+    },
 }
 
 #[tokio::main]
@@ -102,8 +208,22 @@ async fn main() -> Result<()> {
             SessionCommands::List => {
                 session::session_list()?;
             }
-            SessionCommands::Merge { name } => {
-                session::session_merge(&name)?;
+            SessionCommands::Merge { name, force } => {
+                session::session_merge(&name, force)?;
+            }
+        },
+        Commands::Review { command } => match command {
+            ReviewCommands::Gc { dry_run, merged } => {
+                let (vcs, store) = main_repo()?;
+                let sessions = session::SessionStore::new(vcs.repo_path());
+                let options = gc::GcOptions { dry_run, include_merged: merged };
+                let report = gc::gc(vcs.as_ref(), &store, &sessions, options)?;
+                print!("{}", gc::format_report(&report));
+            }
+            ReviewCommands::Restore { change_id } => {
+                let (_, store) = main_repo()?;
+                let review = store.restore(&change_id)?;
+                println!("Restored the review of {}.", review.change_id);
             }
         },
         Commands::Push { message, rev } => {
@@ -123,8 +243,50 @@ async fn main() -> Result<()> {
             thread_id,
             message,
             resolve,
+            status,
+            addressed_in,
+        } => {
+            let status = if resolve { Some(review::ThreadStatus::Addressed) } else { status };
+            session::respond(&change_id, &thread_id, &message, status, addressed_in.as_deref())?;
+        }
+        Commands::ApplySuggestion {
+            change_id,
+            thread_id,
+            squash,
+        } => {
+            apply_suggestion(&change_id, &thread_id, squash)?;
+        }
+        Commands::EditComment {
+            change_id,
+            thread_id,
+            comment_id,
+            text,
+        } => {
+            let (store, author) = comment_context()?;
+            store.edit_comment(&change_id, &thread_id, &comment_id, &author, &text)?;
+            println!("Edited comment {}.", &comment_id[..8.min(comment_id.len())]);
+        }
+        Commands::DeleteComment {
+            change_id,
+            thread_id,
+            comment_id,
         } => {
-            session::respond(&change_id, &thread_id, &message, resolve)?;
+            let (store, author) = comment_context()?;
+            store.delete_comment(&change_id, &thread_id, &comment_id, &author)?;
+            println!("Deleted comment {}.", &comment_id[..8.min(comment_id.len())]);
+        }
+        Commands::Undo { operation, force, limit } => {
+            let jj = jj::Jj::discover()?;
+            match operation {
+                Some(operation) => print!("{}", undo::format_report(&undo::undo(&jj, &operation, force)?)),
+                None => print!("{}", undo::format_operations(&undo::operations(&jj, limit)?)),
+            }
+        }
+        Commands::Identity { name, agent } => {
+            identity(name, agent)?;
+        }
+        Commands::MigrateStore { to } => {
+            migrate_store(to)?;
         }
     }
 
@@ -132,7 +294,7 @@ async fn main() -> Result<()> {
 }
 
 fn init() -> Result<()> {
-    let jj = jj::Jj::discover()?;
+    let vcs = vcs::discover()?;
 
     // Create .aipair directory
     let aipair_dir = Path::new(".aipair");
@@ -142,11 +304,21 @@ fn init() -> Result<()> {
     }
 
     // Create 'main' bookmark if it doesn't exist
-    if jj.get_bookmark("main")?.is_none() {
-        jj.bookmark_create("main", "@")?;
+    if vcs.get_bookmark("main")?.is_none() {
+        vcs.bookmark_create("main", "@")?;
         println!("Created 'main' bookmark at current change");
     }
 
+    // Plain git has no change ids; a commit-msg hook adds them
+    if vcs.as_jj().is_none() {
+        if vcs::Git::new(vcs.repo_path()).install_change_id_hook()? {
+            println!("Installed a commit-msg hook that gives commits a Change-Id trailer");
+        } else {
+            eprintln!("Warning: .git/hooks/commit-msg exists, so commits won't get change ids;");
+            eprintln!("  reviews will then be keyed by commit and restart when a commit is amended.");
+        }
+    }
+
     // Add .aipair to .gitignore
     setup_gitignore()?;
 
@@ -163,6 +335,89 @@ fn init() -> Result<()> {
     Ok(())
 }
 
+fn apply_suggestion(change_id: &str, thread_id: &str, squash: bool) -> Result<()> {
+    // Reviews live in the main repo; the change is applied where we are
+    let (vcs, store) = match session::detect_context()? {
+        session::SessionContext::SessionClone { vcs, marker } => (vcs, review::ReviewStore::new(&marker.main_repo)),
+        session::SessionContext::MainRepo { vcs, repo_path } => (vcs, review::ReviewStore::new(&repo_path)),
+    };
+    let jj = vcs::require_jj(vcs.as_ref(), "Applying suggestions")?;
+    let review = store
+        .get_by_prefix(change_id)?
+        .ok_or_else(|| anyhow::anyhow!("No review found for change {change_id}"))?;
+    let applied = suggestion::apply_suggestion(jj, &review, thread_id, squash)?;
+    if applied.squashed {
+        println!(
+            "Squashed the suggestion for {}:{}-{} into {}.",
+            applied.file, applied.line_start, applied.line_end, applied.change_id
+        );
+    } else {
+        println!(
+            "Applied the suggestion for {}:{}-{} as change {}.",
+            applied.file, applied.line_start, applied.line_end, applied.change_id
+        );
+    }
+    Ok(())
+}
+
+/// The main repo and its review store, from the main repo or a session clone
+fn main_repo() -> Result<(Box<dyn vcs::Vcs>, review::ReviewStore)> {
+    let repo_path = match session::detect_context()? {
+        session::SessionContext::SessionClone { marker, .. } => std::path::PathBuf::from(marker.main_repo),
+        session::SessionContext::MainRepo { repo_path, .. } => repo_path,
+    };
+    Ok((vcs::open(&repo_path), review::ReviewStore::new(&repo_path)))
+}
+
+/// The review store and who is commenting: the agent in a session clone,
+/// the reviewer in the main repo
+fn comment_context() -> Result<(review::ReviewStore, review::Author)> {
+    let identity = identity::Identity::load()?;
+    Ok(match session::detect_context()? {
+        session::SessionContext::SessionClone { marker, .. } => {
+            (review::ReviewStore::new(&marker.main_repo), identity.agent(Some(&marker.session_name)))
+        }
+        session::SessionContext::MainRepo { repo_path, .. } => (review::ReviewStore::new(&repo_path), identity.human()),
+    })
+}
+
+fn identity(name: Option<String>, agent: Option<String>) -> Result<()> {
+    if name.is_some() || agent.is_some() {
+        let mut identity = identity::Identity::load_file()?;
+        if let Some(name) = name {
+            identity.name = name;
+        }
+        if let Some(agent) = agent {
+            identity.agent = agent;
+        }
+        let path = identity.save()?;
+        println!("Saved to {}", path.display());
+    }
+    let identity = identity::Identity::load()?;
+    println!("Reviewer: {}", identity.name);
+    println!("Agent:    {}", identity.agent);
+    Ok(())
+}
+
+fn migrate_store(to: storage::Backend) -> Result<()> {
+    let vcs = vcs::discover()?;
+    let stats = storage::migrate(vcs.repo_path(), to)?;
+    println!(
+        "Migrated {} documents and {} timeline entries to the {} backend",
+        stats.documents, stats.log_entries, to
+    );
+    match to {
+        storage::Backend::Sqlite => {
+            println!("The old JSON files were left in .aipair/ as a backup; delete them once you're satisfied.");
+        }
+        storage::Backend::Json => {
+            println!("The database was kept as .aipair/aipair.db.bak.");
+        }
+    }
+    println!("Restart any running `aipair serve` to pick up the new backend.");
+    Ok(())
+}
+
 fn setup_gitignore() -> Result<()> {
     let gitignore = Path::new(".gitignore");
     if gitignore.exists() {
@@ -198,18 +453,21 @@ fn setup_claude_md() -> Result<()> {
 ## Session Workflow (aipair)
 
 ### Commands (run from session clone directory)
-- `aipair push -m "summary"` — push changes for review
+- `aipair push -m "summary"` — push changes for review; threads on changes you squashed, split or absorbed move to the change that now holds their lines
 - `aipair pull` — pull latest main and rebase
-- `aipair feedback` — show pending review comments
-- `aipair respond <change-id> <thread-id> "message" [--resolve]` — reply to a review thread
+- `aipair feedback` — show pending review comments and whether changes are approved
+- `aipair respond <change-id> <thread-id> "message" [--resolve]` — reply to a review thread; `--resolve` marks it addressed for the reviewer to verify, `--addressed-in <rev>` also names the pushed revision with the fix (or pass `--status acknowledged|in-progress`)
+- `aipair edit-comment <change-id> <thread-id> <comment-id> "text"` / `aipair delete-comment <change-id> <thread-id> <comment-id>` — change or remove one of your own comments; feedback marks edited comments and shows what they said before
+- `aipair apply-suggestion <change-id> <thread-id> [--squash]` — apply a reviewer's suggested edit
 - `aipair status` — show session info
+- `aipair undo [<operation-id>]` — list the jj operations aipair ran, or restore the repo to just before one of them (refuses to roll back your own operations unless `--force`)
 
 ### Workflow
 1. Make changes, then push: `aipair push -m "description"`
 2. Check for feedback: `aipair feedback`
-3. Address comments, respond: `aipair respond <change-id> <thread-id> "Fixed" --resolve`
-4. Push again: `aipair push -m "Address feedback"`
-5. Repeat until all threads resolved
+3. Address comments and push again: `aipair push -m "Address feedback"`
+4. Respond, naming the fix: `aipair respond <change-id> <thread-id> "Fixed" --addressed-in <rev>`
+5. Repeat until the reviewer has verified and resolved every thread and approves
 "#;
 
     if claude_md.exists() {
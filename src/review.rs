@@ -1,12 +1,14 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
 use ts_rs::TS;
 use uuid::Uuid;
 
-const REVIEWS_DIR: &str = ".aipair/reviews";
+pub use crate::identity::Author;
+use crate::storage::{self, Collection, Storage, Versioned};
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
@@ -31,21 +33,261 @@ pub struct Review {
     pub revisions: Vec<Revision>,
     #[serde(default)]
     pub working_commit_id: Option<String>,
+    /// What each reviewer has already looked at, keyed by reviewer name
+    #[serde(default)]
+    pub seen: BTreeMap<String, ReviewerProgress>,
+    /// Approvals and change requests, oldest first
+    #[serde(default)]
+    pub verdicts: Vec<Verdict>,
+    /// Bumped on every save; used to detect concurrent modifications.
+    #[serde(default)]
+    pub version: u32,
+}
+
+/// A review moved out of the way because its change is gone, kept so it can
+/// be restored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedReview {
+    #[serde(flatten)]
+    pub review: Review,
+    pub archived_at: DateTime<Utc>,
+    /// Why it was archived, e.g. "abandoned"
+    pub reason: String,
+}
+
+/// How far one reviewer has got through a change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct ReviewerProgress {
+    /// The last revision they looked at the diff of
+    #[serde(default)]
+    pub last_reviewed_revision: Option<u32>,
+    /// Timestamp of the last comment they read, across all threads
+    #[serde(default)]
+    pub last_read_comment: Option<DateTime<Utc>>,
+    /// The same per thread, for threads read on their own since
+    #[serde(default)]
+    pub threads: BTreeMap<String, DateTime<Utc>>,
+}
+
+/// A reviewer's overall call on one revision of a change.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct Verdict {
+    pub reviewer: String,
+    pub kind: VerdictKind,
+    /// The revision it was given on; a later revision supersedes it
+    pub revision: u32,
+    #[serde(default)]
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+#[serde(rename_all = "snake_case")]
+pub enum VerdictKind {
+    Approved,
+    ChangesRequested,
+}
+
+fn thread_index(threads: &[Thread], thread_id_prefix: &str) -> Result<usize> {
+    // Try exact match first
+    if let Some(idx) = threads.iter().position(|t| t.id == thread_id_prefix) {
+        return Ok(idx);
+    }
+
+    // Try prefix match
+    let matches: Vec<_> = threads.iter().enumerate()
+        .filter(|(_, t)| t.id.starts_with(thread_id_prefix))
+        .map(|(i, _)| i)
+        .collect();
+
+    match matches.len() {
+        0 => anyhow::bail!("Thread not found: {}", thread_id_prefix),
+        1 => Ok(matches[0]),
+        _ => anyhow::bail!("Ambiguous thread_id prefix '{}': matches {} threads", thread_id_prefix, matches.len()),
+    }
+}
+
+fn comment_index(comments: &[Comment], comment_id_prefix: &str) -> Result<usize> {
+    if let Some(idx) = comments.iter().position(|c| c.id == comment_id_prefix) {
+        return Ok(idx);
+    }
+
+    let matches: Vec<_> = comments.iter().enumerate()
+        .filter(|(_, c)| c.id.starts_with(comment_id_prefix))
+        .map(|(i, _)| i)
+        .collect();
+
+    match matches.len() {
+        0 => anyhow::bail!("Comment not found: {}", comment_id_prefix),
+        1 => Ok(matches[0]),
+        _ => anyhow::bail!("Ambiguous comment_id prefix '{}': matches {} comments", comment_id_prefix, matches.len()),
+    }
+}
+
+impl Review {
+    /// Give comments stored before comments had ids one
+    fn fill_comment_ids(&mut self) {
+        for comment in self.threads.iter_mut().flat_map(|t| t.comments.iter_mut()) {
+            if comment.id.is_empty() {
+                comment.id = comment.legacy_id();
+            }
+        }
+    }
+
+    /// Leave out what `viewer` can't see yet: other reviewers' drafts, or
+    /// every draft when the viewer is the agent (None)
+    pub fn hide_drafts(&mut self, viewer: Option<&str>) {
+        self.threads.retain(|t| !t.is_draft(viewer));
+        for thread in &mut self.threads {
+            thread.comments.retain(|c| c.visible_to(viewer));
+        }
+    }
+
+    /// Look a thread up by id or unique id prefix
+    pub fn find_thread(&self, thread_id_prefix: &str) -> Result<&Thread> {
+        Ok(&self.threads[thread_index(&self.threads, thread_id_prefix)?])
+    }
+
+    /// Each reviewer's latest verdict on the latest revision. Verdicts on
+    /// earlier revisions no longer count.
+    pub fn current_verdicts(&self) -> Vec<&Verdict> {
+        let Some(latest) = self.revisions.last() else {
+            return Vec::new();
+        };
+        let mut current: Vec<&Verdict> = Vec::new();
+        for verdict in self.verdicts.iter().filter(|v| v.revision == latest.number) {
+            current.retain(|v| v.reviewer != verdict.reviewer);
+            current.push(verdict);
+        }
+        current
+    }
+
+    /// The verdict that stands for `commit_id`: changes requested if any
+    /// reviewer asked for them, approved if someone approved, and None when
+    /// nobody has weighed in on this commit.
+    pub fn verdict_on(&self, commit_id: &str) -> Option<VerdictKind> {
+        if self.revisions.last()?.commit_id != commit_id {
+            return None;
+        }
+        let kinds: Vec<_> = self.current_verdicts().iter().map(|v| v.kind).collect();
+        if kinds.contains(&VerdictKind::ChangesRequested) {
+            Some(VerdictKind::ChangesRequested)
+        } else {
+            kinds.first().copied()
+        }
+    }
+
+    /// The commit for `rev`: a review revision number, or else a commit id
+    /// taken as is.
+    pub fn resolve_revision(&self, rev: &str) -> String {
+        rev.parse::<u32>()
+            .ok()
+            .and_then(|n| self.revisions.iter().find(|r| r.number == n))
+            .map(|r| r.commit_id.clone())
+            .unwrap_or_else(|| rev.to_string())
+    }
+
+    /// Whether `commit_id` is newer than the last revision `reviewer` reviewed.
+    pub fn has_unseen_revision(&self, reviewer: &str, commit_id: &str) -> bool {
+        let reviewed = self
+            .seen
+            .get(reviewer)
+            .and_then(|p| p.last_reviewed_revision)
+            .and_then(|n| self.revisions.iter().find(|r| r.number == n));
+        reviewed.is_none_or(|r| r.commit_id != commit_id)
+    }
+
+    /// Comments by anyone but `reviewer` that they haven't read, per thread
+    /// (threads with none are left out). Other reviewers' drafts don't count.
+    pub fn unread_replies(&self, reviewer: &str) -> Vec<(&Thread, Vec<&Comment>)> {
+        let progress = self.seen.get(reviewer);
+        self.threads
+            .iter()
+            .filter_map(|thread| {
+                let read_until = progress.and_then(|p| p.last_read_comment.max(p.threads.get(&thread.id).copied()));
+                let unread: Vec<_> = thread
+                    .comments
+                    .iter()
+                    .filter(|c| !(c.author.is_human() && c.author.name == reviewer) && c.visible_to(Some(reviewer)))
+                    .filter(|c| read_until.is_none_or(|t| c.updated_at() > t))
+                    .collect();
+                (!unread.is_empty()).then_some((thread, unread))
+            })
+            .collect()
+    }
+}
+
+impl Thread {
+    /// Whether the thread is still only drafts `viewer` can't see: nothing
+    /// on it is submitted or theirs. None stands for the agent, who sees no
+    /// drafts at all.
+    pub fn is_draft(&self, viewer: Option<&str>) -> bool {
+        !self.comments.iter().any(|c| c.visible_to(viewer))
+    }
+
+    /// Whether the thread holds up merging: unresolved, blocking, and submitted
+    pub fn blocks_merge(&self) -> bool {
+        self.status.is_unresolved() && self.severity == Severity::Blocking && !self.is_draft(None)
+    }
+
+    /// The most recent submitted suggested edit on the thread
+    pub fn suggestion(&self) -> Option<&[String]> {
+        self.comments.iter().rev().filter(|c| !c.draft).find_map(|c| c.suggestion.as_deref())
+    }
+}
+
+impl Versioned for Review {
+    fn version(&self) -> u32 {
+        self.version
+    }
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
 pub struct Thread {
     pub id: String,
+    /// What the thread is attached to; `file` and the line range only
+    /// apply to the anchors that use them
+    #[serde(default)]
+    pub anchor: ThreadAnchor,
     pub file: String,
     pub line_start: usize,
     pub line_end: usize,
+    /// Which side of the diff the lines refer to
+    #[serde(default)]
+    pub side: Side,
     pub status: ThreadStatus,
+    /// Every status change, oldest first
+    #[serde(default)]
+    pub history: Vec<StatusChange>,
+    /// The commit the agent says addresses the thread
+    #[serde(default)]
+    pub addressed_in: Option<String>,
+    /// Set when the thread moved here from a change that was squashed,
+    /// split or absorbed
+    #[serde(default)]
+    pub moved_from: Option<ThreadMove>,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Free-form categories such as "perf" or "style"
+    #[serde(default)]
+    pub labels: Vec<String>,
     pub comments: Vec<Comment>,
     #[serde(default)]
     pub created_at_commit: Option<String>,
     #[serde(default)]
     pub created_at_revision: Option<u32>,
+    /// The anchored lines as they were when the thread was created, used to
+    /// find them again when the diff loses track of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(skip)]
+    pub snapshot: Option<AnchorSnapshot>,
     /// Display position after mapping through diffs (not persisted)
     #[serde(default)]
     pub display_line_start: Option<usize>,
@@ -55,62 +297,236 @@ pub struct Thread {
     pub is_displaced: bool,
     #[serde(default)]
     pub is_deleted: bool,
+    /// How sure the display position is: 1.0 when the diff was followed
+    /// exactly, lower for a content match (not persisted)
+    #[serde(default)]
+    pub match_confidence: Option<f32>,
+    /// The file the thread's code lives in now, when it was renamed or the
+    /// code moved to another file (not persisted)
+    #[serde(default)]
+    pub display_file: Option<String>,
+}
+
+/// Text of a line thread's lines plus a few lines of context either side.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AnchorSnapshot {
+    pub lines: Vec<String>,
+    #[serde(default)]
+    pub before: Vec<String>,
+    #[serde(default)]
+    pub after: Vec<String>,
+}
+
+/// What a thread is attached to. Line threads carry a file and line range;
+/// file threads only a file; description and change threads neither.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadAnchor {
+    #[default]
+    Line,
+    File,
+    Description,
+    Change,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+/// Side of a diff. `Old` lines are numbered in the change's parent, which
+/// lets a thread point at lines the change deleted.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash, TS)]
 #[ts(export, export_to = "../web/src/types/")]
 #[serde(rename_all = "lowercase")]
+pub enum Side {
+    Old,
+    #[default]
+    New,
+}
+
+/// Where a thread is in its life. The agent moves threads up to
+/// `Addressed`; only the reviewer verifies them and resolves them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, TS, clap::ValueEnum)]
+#[ts(export, export_to = "../web/src/types/")]
+#[serde(rename_all = "snake_case")]
 pub enum ThreadStatus {
     Open,
+    /// The agent has seen it and will get to it
+    Acknowledged,
+    InProgress,
+    /// The agent says it's done; the reviewer still has to verify
+    Addressed,
     Resolved,
 }
 
+impl ThreadStatus {
+    /// Anything short of resolved still counts against the change
+    pub fn is_unresolved(self) -> bool {
+        self != ThreadStatus::Resolved
+    }
+
+    /// Whether the agent still has work to do on the thread
+    pub fn awaits_agent(self) -> bool {
+        matches!(self, ThreadStatus::Open | ThreadStatus::Acknowledged | ThreadStatus::InProgress)
+    }
+}
+
+impl std::fmt::Display for ThreadStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ThreadStatus::Open => "open",
+            ThreadStatus::Acknowledged => "acknowledged",
+            ThreadStatus::InProgress => "in progress",
+            ThreadStatus::Addressed => "addressed",
+            ThreadStatus::Resolved => "resolved",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One entry in a thread's status history.
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
-pub struct Comment {
+pub struct StatusChange {
+    pub from: ThreadStatus,
+    pub to: ThreadStatus,
     pub author: Author,
-    pub text: String,
     pub timestamp: DateTime<Utc>,
+    /// The change's latest revision at the time
+    pub revision: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+/// Where a thread came from when it followed its lines to another change
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct ThreadMove {
+    pub from_change_id: String,
+    /// The jj operation that rewrote the changes, e.g. "squash commits into ..."
+    pub operation: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// How much a thread matters. Only blocking threads hold up a merge; the
+/// variants are ordered most important first.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, TS)]
 #[ts(export, export_to = "../web/src/types/")]
 #[serde(rename_all = "lowercase")]
-pub enum Author {
-    User,
-    Claude,
+pub enum Severity {
+    /// Must be addressed before merging (what every thread used to be)
+    #[default]
+    Blocking,
+    Suggestion,
+    Question,
+    Nit,
 }
 
-pub struct ReviewStore {
-    base_path: PathBuf,
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Blocking => "blocking",
+            Severity::Suggestion => "suggestion",
+            Severity::Question => "question",
+            Severity::Nit => "nit",
+        };
+        f.write_str(name)
+    }
 }
 
-impl ReviewStore {
-    pub fn new(repo_path: impl AsRef<Path>) -> Self {
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct Comment {
+    /// Unique within the thread. Comments from before comments had ids get
+    /// one derived from their timestamp when loaded.
+    #[serde(default)]
+    pub id: String,
+    pub author: Author,
+    pub text: String,
+    pub timestamp: DateTime<Utc>,
+    /// Part of a review that hasn't been submitted yet; the agent doesn't
+    /// see it until it is
+    #[serde(default)]
+    pub draft: bool,
+    /// Replacement text for the thread's anchored lines, one entry per line
+    #[serde(default)]
+    pub suggestion: Option<Vec<String>>,
+    /// Earlier versions of the text, oldest first
+    #[serde(default)]
+    pub edits: Vec<CommentEdit>,
+}
+
+impl Comment {
+    pub fn new(author: Author, text: &str, draft: bool) -> Self {
         Self {
-            base_path: repo_path.as_ref().join(REVIEWS_DIR),
+            id: Uuid::new_v4().to_string(),
+            author,
+            text: text.to_string(),
+            timestamp: Utc::now(),
+            draft,
+            suggestion: None,
+            edits: Vec::new(),
         }
     }
 
-    pub fn init(&self) -> Result<()> {
-        std::fs::create_dir_all(&self.base_path)?;
-        Ok(())
+    /// Whether `viewer` can see the comment: a draft only shows to the
+    /// reviewer writing it. None stands for the agent.
+    pub fn visible_to(&self, viewer: Option<&str>) -> bool {
+        !self.draft || viewer.is_some_and(|name| self.author.is_human() && self.author.name == name)
     }
 
-    fn review_path(&self, change_id: &str) -> PathBuf {
-        self.base_path.join(format!("{change_id}.json"))
+    /// When the comment last changed: posted, or edited since
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.edits.last().map_or(self.timestamp, |e| e.edited_at)
+    }
+
+    /// A stable id for a comment stored without one
+    fn legacy_id(&self) -> String {
+        let nanos = self.timestamp.timestamp_nanos_opt().unwrap_or_default() as u64;
+        // Spread the bits so nearby timestamps don't share a short prefix
+        format!("{:016x}", nanos.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+}
+
+/// A comment's text before an edit
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../web/src/types/")]
+pub struct CommentEdit {
+    pub previous_text: String,
+    pub edited_at: DateTime<Utc>,
+}
+
+/// What a new comment can carry besides its author and text. It's all
+/// written together with the comment, so nobody sees it half made.
+#[derive(Debug, Clone, Default)]
+pub struct CommentExtras {
+    /// Hold the comment back until the review is submitted
+    pub draft: bool,
+    /// Replacement lines for the thread's range; only for lines on the new
+    /// side of the diff
+    pub suggestion: Option<Vec<String>>,
+    /// Give the thread this severity
+    pub severity: Option<Severity>,
+    /// Replace the thread's labels with these
+    pub labels: Option<Vec<String>>,
+}
+
+const SUGGESTION_ANCHOR: &str = "Suggestions can only be made on lines of the change's new side";
+
+pub struct ReviewStore {
+    storage: Arc<dyn Storage>,
+}
+
+impl ReviewStore {
+    pub fn new(repo_path: impl AsRef<Path>) -> Self {
+        Self::with_storage(storage::open(repo_path.as_ref()))
+    }
+
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
     }
 
     pub fn get(&self, change_id: &str) -> Result<Option<Review>> {
-        let path = self.review_path(change_id);
-        if !path.exists() {
-            return Ok(None);
+        let mut review: Option<Review> = storage::get_doc(self.storage.as_ref(), Collection::Reviews, change_id)?;
+        if let Some(review) = &mut review {
+            review.fill_comment_ids();
         }
-
-        let content = std::fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read review file: {}", path.display()))?;
-        let review: Review = serde_json::from_str(&content)?;
-        Ok(Some(review))
+        Ok(review)
     }
 
     /// Get a review by change_id prefix (supports short IDs like "zwlsqumm")
@@ -120,138 +536,300 @@ impl ReviewStore {
             return Ok(Some(review));
         }
 
-        // Search for files matching the prefix
-        let entries = std::fs::read_dir(&self.base_path)?;
-        let mut matches = Vec::new();
-
-        for entry in entries.flatten() {
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-            if name_str.starts_with(prefix) && name_str.ends_with(".json") {
-                matches.push(entry.path());
-            }
-        }
+        let matches: Vec<String> = self
+            .storage
+            .keys(Collection::Reviews)?
+            .into_iter()
+            .filter(|key| key.starts_with(prefix))
+            .collect();
 
         match matches.len() {
             0 => Ok(None),
-            1 => {
-                let content = std::fs::read_to_string(&matches[0])?;
-                let review: Review = serde_json::from_str(&content)?;
-                Ok(Some(review))
-            }
+            1 => self.get(&matches[0]),
             _ => anyhow::bail!("Ambiguous change_id prefix '{}': matches {} reviews", prefix, matches.len()),
         }
     }
 
-    pub fn save(&self, review: &Review) -> Result<()> {
-        self.init()?;
-        let path = self.review_path(&review.change_id);
-        let content = serde_json::to_string_pretty(review)?;
-        std::fs::write(&path, content)?;
-        Ok(())
-    }
-
-    pub fn get_or_create(&self, change_id: &str, base: &str, commit_id: &str) -> Result<Review> {
-        if let Some(mut review) = self.get(change_id)? {
-            // Update working_commit_id if not set (migration for old reviews)
-            if review.working_commit_id.is_none() {
-                review.working_commit_id = Some(commit_id.to_string());
-                self.save(&review)?;
-            }
-            return Ok(review);
-        }
-
-        let review = Review {
-            change_id: change_id.to_string(),
-            base: base.to_string(),
-            created_at: Utc::now(),
-            threads: Vec::new(),
-            revisions: Vec::new(),
-            working_commit_id: Some(commit_id.to_string()),
-        };
-
-        self.save(&review)?;
-        Ok(review)
+    /// Atomically read-modify-write a review, so concurrent writers (the
+    /// server and `aipair respond`) can't lose each other's updates.
+    fn update<T>(
+        &self,
+        change_id: &str,
+        f: impl FnOnce(&mut Review) -> Result<T>,
+    ) -> Result<(Review, T)> {
+        let (review, out) = storage::update_doc(
+            self.storage.as_ref(),
+            Collection::Reviews,
+            change_id,
+            |slot: &mut Option<Review>| {
+                let review = slot
+                    .as_mut()
+                    .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", change_id))?;
+                review.fill_comment_ids();
+                f(review)
+            },
+        )?;
+        Ok((review.expect("review exists after successful update"), out))
     }
 
-    pub fn record_revision(
+    /// Like `update`, but resolves a change_id prefix first.
+    fn update_by_prefix<T>(
         &self,
         change_id: &str,
-        commit_id: &str,
-        description: Option<String>,
-    ) -> Result<(Review, u32)> {
-        let mut review = self
-            .get(change_id)?
+        f: impl FnOnce(&mut Review) -> Result<T>,
+    ) -> Result<(Review, T)> {
+        let review = self
+            .get_by_prefix(change_id)?
             .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", change_id))?;
+        self.update(&review.change_id, f)
+    }
 
-        let number = review.revisions.len() as u32 + 1;
-        review.revisions.push(Revision {
-            number,
-            commit_id: commit_id.to_string(),
-            created_at: Utc::now(),
-            description,
-            is_pending: false,
-        });
-        review.working_commit_id = Some(commit_id.to_string());
+    pub fn get_or_create(&self, change_id: &str, base: &str, commit_id: &str) -> Result<Review> {
+        if let Some(review) = self.get(change_id)?
+            && review.working_commit_id.is_some()
+        {
+            return Ok(review);
+        }
 
-        self.save(&review)?;
-        Ok((review, number))
+        let (review, ()) = storage::update_doc(
+            self.storage.as_ref(),
+            Collection::Reviews,
+            change_id,
+            |slot: &mut Option<Review>| {
+                match slot {
+                    // Update working_commit_id if not set (migration for old reviews)
+                    Some(review) => {
+                        review.fill_comment_ids();
+                        review.working_commit_id.get_or_insert_with(|| commit_id.to_string());
+                    }
+                    None => {
+                        *slot = Some(Review {
+                            change_id: change_id.to_string(),
+                            base: base.to_string(),
+                            created_at: Utc::now(),
+                            threads: Vec::new(),
+                            revisions: Vec::new(),
+                            working_commit_id: Some(commit_id.to_string()),
+                            seen: BTreeMap::new(),
+                            verdicts: Vec::new(),
+                            version: 0,
+                        });
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        Ok(review.expect("review exists after successful update"))
     }
 
     pub fn list(&self) -> Result<Vec<Review>> {
-        if !self.base_path.exists() {
-            return Ok(Vec::new());
+        let mut reviews: Vec<Review> = storage::list_docs(self.storage.as_ref(), Collection::Reviews)?;
+        for review in &mut reviews {
+            review.fill_comment_ids();
         }
 
-        let mut reviews = Vec::new();
-        for entry in std::fs::read_dir(&self.base_path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.extension().is_some_and(|ext| ext == "json") {
-                let content = std::fs::read_to_string(&path)?;
-                if let Ok(review) = serde_json::from_str::<Review>(&content) {
-                    reviews.push(review);
-                }
+        // Sort by created_at descending
+        reviews.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+        Ok(reviews)
+    }
+
+    /// Move a review to the archive, out of listings and counts. A review
+    /// that changes while being archived is archived again as it is now.
+    pub fn archive(&self, change_id: &str, reason: &str) -> Result<()> {
+        loop {
+            let review = self
+                .get(change_id)?
+                .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", change_id))?;
+            let version = review.version;
+            let archived = ArchivedReview { review, archived_at: Utc::now(), reason: reason.to_string() };
+            let body = serde_json::to_string_pretty(&archived)?;
+            self.storage.update(Collection::ArchivedReviews, change_id, &mut |_| Ok(Some(body.clone())))?;
+            if storage::delete_doc(self.storage.as_ref(), Collection::Reviews, change_id, version)? {
+                return Ok(());
             }
         }
+    }
 
-        // Sort by created_at descending
-        reviews.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-        Ok(reviews)
+    /// Bring an archived review back, by change_id prefix
+    pub fn restore(&self, change_id: &str) -> Result<Review> {
+        let matches: Vec<String> = self
+            .storage
+            .keys(Collection::ArchivedReviews)?
+            .into_iter()
+            .filter(|key| key.starts_with(change_id))
+            .collect();
+        let key = match matches.as_slice() {
+            [key] => key,
+            [] => anyhow::bail!("No archived review for change: {}", change_id),
+            _ => anyhow::bail!("Ambiguous change_id prefix '{}': matches {} archived reviews", change_id, matches.len()),
+        };
+        let archived: ArchivedReview = storage::get_doc(self.storage.as_ref(), Collection::ArchivedReviews, key)?
+            .ok_or_else(|| anyhow::anyhow!("No archived review for change: {}", change_id))?;
+        let body = serde_json::to_string_pretty(&archived.review)?;
+        self.storage.update(Collection::Reviews, key, &mut |current| {
+            if current.is_some() {
+                anyhow::bail!("Change {} already has a review", key);
+            }
+            Ok(Some(body.clone()))
+        })?;
+        self.storage.delete(Collection::ArchivedReviews, key)?;
+        Ok(self.get(key)?.expect("review exists after restore"))
+    }
+
+    /// Move a thread to the review of another change, creating that review
+    /// if needed. The reviewer's read marks go along with it.
+    pub fn move_thread(
+        &self,
+        from_change: &str,
+        thread_id: &str,
+        to_change: &str,
+        to_commit: &str,
+        moved: ThreadMove,
+    ) -> Result<Review> {
+        let from = self
+            .get(from_change)?
+            .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", from_change))?;
+        let mut thread = from.find_thread(thread_id)?.clone();
+        let thread_id = thread.id.clone();
+        // Revision numbers belong to the old change's review
+        thread.created_at_revision = None;
+        thread.moved_from = Some(moved);
+        let read: Vec<(String, DateTime<Utc>)> = from
+            .seen
+            .iter()
+            .filter_map(|(reviewer, p)| Some((reviewer.clone(), p.last_read_comment.max(p.threads.get(&thread_id).copied())?)))
+            .collect();
+
+        // Add before removing, so a failure can't lose the thread
+        self.get_or_create(to_change, &format!("{to_change}-"), to_commit)?;
+        let (review, ()) = self.update(to_change, |review| {
+            if review.threads.iter().all(|t| t.id != thread_id) {
+                review.threads.push(thread);
+            }
+            for (reviewer, at) in read {
+                review.seen.entry(reviewer).or_default().threads.insert(thread_id.clone(), at);
+            }
+            Ok(())
+        })?;
+        self.update(from_change, |review| {
+            review.threads.retain(|t| t.id != thread_id);
+            for progress in review.seen.values_mut() {
+                progress.threads.remove(&thread_id);
+            }
+            Ok(())
+        })?;
+        Ok(review)
     }
 
-    /// List reviews that have open threads.
+    /// List reviews that have threads awaiting the agent.
     /// If `change_ids` is Some, only include reviews for those changes.
     pub fn list_with_open_threads(&self, change_ids: Option<&HashSet<String>>) -> Result<Vec<Review>> {
         let reviews = self.list()?;
         Ok(reviews
             .into_iter()
             .filter(|r| {
-                if let Some(ids) = change_ids {
-                    if !ids.contains(&r.change_id) {
-                        return false;
-                    }
+                if let Some(ids) = change_ids
+                    && !ids.contains(&r.change_id)
+                {
+                    return false;
                 }
-                r.threads.iter().any(|t| t.status == ThreadStatus::Open)
+                r.threads.iter().any(|t| t.status.awaits_agent() && !t.is_draft(None))
             })
             .collect())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_comment(
         &self,
         change_id: &str,
         file: &str,
         line_start: usize,
         line_end: usize,
+        side: Side,
         author: Author,
         text: &str,
         commit_id: &str,
+        snapshot: Option<AnchorSnapshot>,
+        extras: CommentExtras,
     ) -> Result<(Review, String)> {
-        let mut review = self
-            .get(change_id)?
-            .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", change_id))?;
+        if extras.suggestion.is_some() && side != Side::New {
+            anyhow::bail!(SUGGESTION_ANCHOR);
+        }
+        let CommentExtras { draft, suggestion, severity, labels } = extras;
+        self.update(change_id, |review| {
+            let current_revision = Self::touch_revision(review, commit_id);
+            let comment = Comment { suggestion, ..Comment::new(author, text, draft) };
+
+            // Find existing thread or create new one
+            let existing = review.threads.iter_mut().find(|t| {
+                t.anchor == ThreadAnchor::Line
+                    && t.file == file
+                    && t.line_start == line_start
+                    && t.line_end == line_end
+                    && t.side == side
+            });
 
-        // Auto-create revision if commit differs from last revision (or no revisions yet)
+            let thread_id = match existing {
+                Some(thread) => {
+                    thread.comments.push(comment);
+                    thread.id.clone()
+                }
+                None => Self::push_thread(
+                    review,
+                    ThreadAnchor::Line,
+                    file,
+                    (line_start, line_end),
+                    side,
+                    comment,
+                    commit_id,
+                    current_revision,
+                    snapshot,
+                ),
+            };
+            Self::classify(Self::find_thread_mut(&mut review.threads, &thread_id)?, severity, labels);
+
+            Ok(thread_id)
+        })
+    }
+
+    /// Start a thread that isn't tied to a line range: on a whole file, the
+    /// commit description, or the change as a whole. `file` is only used for
+    /// `ThreadAnchor::File`. Unlike line comments, every call opens a new
+    /// thread, since there is no location to tell two remarks apart.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_thread(
+        &self,
+        change_id: &str,
+        anchor: ThreadAnchor,
+        file: Option<&str>,
+        author: Author,
+        text: &str,
+        commit_id: &str,
+        extras: CommentExtras,
+    ) -> Result<(Review, String)> {
+        if extras.suggestion.is_some() {
+            anyhow::bail!(SUGGESTION_ANCHOR);
+        }
+        let file = match (anchor, file) {
+            (ThreadAnchor::Line, _) => anyhow::bail!("Line threads need a line range"),
+            (ThreadAnchor::File, Some(file)) if !file.is_empty() => file,
+            (ThreadAnchor::File, _) => anyhow::bail!("File threads need a file"),
+            (ThreadAnchor::Description | ThreadAnchor::Change, _) => "",
+        };
+
+        self.update(change_id, |review| {
+            let current_revision = Self::touch_revision(review, commit_id);
+            let comment = Comment::new(author, text, extras.draft);
+            let thread_id =
+                Self::push_thread(review, anchor, file, (0, 0), Side::New, comment, commit_id, current_revision, None);
+            Self::classify(Self::find_thread_mut(&mut review.threads, &thread_id)?, extras.severity, extras.labels);
+            Ok(thread_id)
+        })
+    }
+
+    /// Auto-create a revision if the commit differs from the last revision (or
+    /// there are no revisions yet). Returns the current revision number.
+    fn touch_revision(review: &mut Review, commit_id: &str) -> Option<u32> {
         let last_revision_commit = review.revisions.last().map(|r| r.commit_id.as_str());
         if last_revision_commit != Some(commit_id) {
             let number = review.revisions.len() as u32 + 1;
@@ -265,132 +843,427 @@ impl ReviewStore {
         }
         review.working_commit_id = Some(commit_id.to_string());
 
-        // Get the current revision number for tagging the thread
-        let current_revision = review.revisions.last().map(|r| r.number);
+        review.revisions.last().map(|r| r.number)
+    }
 
-        // Find existing thread or create new one
-        let thread_id = review
-            .threads
-            .iter()
-            .find(|t| t.file == file && t.line_start == line_start && t.line_end == line_end)
-            .map(|t| t.id.clone());
-
-        let thread_id = match thread_id {
-            Some(id) => {
-                let thread = review.threads.iter_mut().find(|t| t.id == id).unwrap();
-                thread.comments.push(Comment {
-                    author,
-                    text: text.to_string(),
-                    timestamp: Utc::now(),
-                });
-                id
+    #[allow(clippy::too_many_arguments)]
+    fn push_thread(
+        review: &mut Review,
+        anchor: ThreadAnchor,
+        file: &str,
+        (line_start, line_end): (usize, usize),
+        side: Side,
+        comment: Comment,
+        commit_id: &str,
+        revision: Option<u32>,
+        snapshot: Option<AnchorSnapshot>,
+    ) -> String {
+        let id = Uuid::new_v4().to_string()[..8].to_string();
+        review.threads.push(Thread {
+            id: id.clone(),
+            anchor,
+            file: file.to_string(),
+            line_start,
+            line_end,
+            side,
+            status: ThreadStatus::Open,
+            history: Vec::new(),
+            addressed_in: None,
+            moved_from: None,
+            severity: Severity::default(),
+            labels: Vec::new(),
+            comments: vec![comment],
+            created_at_commit: Some(commit_id.to_string()),
+            created_at_revision: revision,
+            snapshot,
+            display_line_start: None,
+            display_line_end: None,
+            is_displaced: false,
+            is_deleted: false,
+            match_confidence: None,
+            display_file: None,
+        });
+        id
+    }
+
+    /// Find a thread by ID or prefix in a review
+    fn find_thread_mut<'a>(threads: &'a mut [Thread], thread_id_prefix: &str) -> Result<&'a mut Thread> {
+        let idx = thread_index(threads, thread_id_prefix)?;
+        Ok(&mut threads[idx])
+    }
+
+    pub fn reply_to_thread(
+        &self,
+        change_id: &str,
+        thread_id: &str,
+        author: Author,
+        text: &str,
+        extras: CommentExtras,
+    ) -> Result<Review> {
+        let CommentExtras { draft, suggestion, severity, labels } = extras;
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+            if suggestion.is_some() && (thread.anchor != ThreadAnchor::Line || thread.side != Side::New) {
+                anyhow::bail!(SUGGESTION_ANCHOR);
             }
-            None => {
-                let id = Uuid::new_v4().to_string()[..8].to_string();
-                review.threads.push(Thread {
-                    id: id.clone(),
-                    file: file.to_string(),
-                    line_start,
-                    line_end,
-                    status: ThreadStatus::Open,
-                    comments: vec![Comment {
-                        author,
-                        text: text.to_string(),
-                        timestamp: Utc::now(),
-                    }],
-                    created_at_commit: Some(commit_id.to_string()),
-                    created_at_revision: current_revision,
-                    display_line_start: None,
-                    display_line_end: None,
-                    is_displaced: false,
-                    is_deleted: false,
-                });
-                id
+            thread.comments.push(Comment { suggestion, ..Comment::new(author, text, draft) });
+            Self::classify(thread, severity, labels);
+            Ok(())
+        })?;
+        Ok(review)
+    }
+
+    /// Change the text of a comment, keeping the old text in its edit
+    /// history. Only the comment's author can edit it.
+    pub fn edit_comment(
+        &self,
+        change_id: &str,
+        thread_id: &str,
+        comment_id: &str,
+        editor: &Author,
+        text: &str,
+    ) -> Result<Review> {
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+            let idx = comment_index(&thread.comments, comment_id)?;
+            let comment = &mut thread.comments[idx];
+            Self::check_comment_author(comment, editor, "edit")?;
+            if comment.text == text {
+                return Ok(());
             }
-        };
+            let previous_text = std::mem::replace(&mut comment.text, text.to_string());
+            comment.edits.push(CommentEdit { previous_text, edited_at: Utc::now() });
+            Ok(())
+        })?;
+        Ok(review)
+    }
 
-        self.save(&review)?;
-        Ok((review, thread_id))
+    /// Delete a comment. Only the comment's author can delete it; deleting
+    /// the last comment on a thread deletes the thread.
+    pub fn delete_comment(&self, change_id: &str, thread_id: &str, comment_id: &str, editor: &Author) -> Result<Review> {
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            let thread_idx = thread_index(&review.threads, thread_id)?;
+            let thread = &mut review.threads[thread_idx];
+            let idx = comment_index(&thread.comments, comment_id)?;
+            Self::check_comment_author(&thread.comments[idx], editor, "delete")?;
+            thread.comments.remove(idx);
+            if thread.comments.is_empty() {
+                review.threads.remove(thread_idx);
+            }
+            Ok(())
+        })?;
+        Ok(review)
     }
 
-    /// Find a thread by ID or prefix in a review
-    fn find_thread_mut<'a>(threads: &'a mut [Thread], thread_id_prefix: &str) -> Result<&'a mut Thread> {
-        // Try exact match first
-        if let Some(idx) = threads.iter().position(|t| t.id == thread_id_prefix) {
-            return Ok(&mut threads[idx]);
+    fn check_comment_author(comment: &Comment, editor: &Author, action: &str) -> Result<()> {
+        if comment.author.kind != editor.kind || comment.author.name != editor.name {
+            anyhow::bail!("Only {} can {} this comment", comment.author.name, action);
         }
+        Ok(())
+    }
 
-        // Try prefix match
-        let matches: Vec<_> = threads.iter().enumerate()
-            .filter(|(_, t)| t.id.starts_with(thread_id_prefix))
-            .map(|(i, _)| i)
-            .collect();
+    /// Change a thread's severity and/or replace its labels
+    pub fn classify_thread(
+        &self,
+        change_id: &str,
+        thread_id: &str,
+        severity: Option<Severity>,
+        labels: Option<Vec<String>>,
+    ) -> Result<Review> {
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            Self::classify(Self::find_thread_mut(&mut review.threads, thread_id)?, severity, labels);
+            Ok(())
+        })?;
+        Ok(review)
+    }
 
-        match matches.len() {
-            0 => anyhow::bail!("Thread not found: {}", thread_id_prefix),
-            1 => Ok(&mut threads[matches[0]]),
-            _ => anyhow::bail!("Ambiguous thread_id prefix '{}': matches {} threads", thread_id_prefix, matches.len()),
+    fn classify(thread: &mut Thread, severity: Option<Severity>, labels: Option<Vec<String>>) {
+        if let Some(severity) = severity {
+            thread.severity = severity;
+        }
+        if let Some(labels) = labels {
+            let mut labels: Vec<String> =
+                labels.iter().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+            labels.sort();
+            labels.dedup();
+            thread.labels = labels;
         }
     }
 
-    pub fn reply_to_thread(
+    /// Move a thread to `status`, recording who did it in its history.
+    /// Setting the status it already has changes nothing.
+    pub fn set_thread_status(
         &self,
         change_id: &str,
         thread_id: &str,
+        status: ThreadStatus,
         author: Author,
-        text: &str,
     ) -> Result<Review> {
-        let mut review = self
-            .get_by_prefix(change_id)?
-            .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", change_id))?;
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            let revision = review.revisions.last().map(|r| r.number);
+            let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+            Self::transition(thread, status, author, revision);
+            Ok(())
+        })?;
+        Ok(review)
+    }
 
-        let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+    /// Mark a thread addressed, optionally by a specific commit of the change
+    /// so the reviewer can check just the fix.
+    pub fn mark_addressed(
+        &self,
+        change_id: &str,
+        thread_id: &str,
+        author: Author,
+        addressed_in: Option<&str>,
+    ) -> Result<Review> {
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            let revision = review.revisions.last().map(|r| r.number);
+            let addressed_in = addressed_in.map(|rev| review.resolve_revision(rev));
+            let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+            Self::transition(thread, ThreadStatus::Addressed, author, revision);
+            if addressed_in.is_some() {
+                thread.addressed_in = addressed_in;
+            }
+            Ok(())
+        })?;
+        Ok(review)
+    }
+
+    /// Reply to a thread and move it to `status` in the same update, so the
+    /// reply never lands without its status change. `addressed_in` is only
+    /// used with [`ThreadStatus::Addressed`], as by [`Self::mark_addressed`].
+    pub fn respond(
+        &self,
+        change_id: &str,
+        thread_id: &str,
+        author: Author,
+        text: &str,
+        status: Option<ThreadStatus>,
+        addressed_in: Option<&str>,
+    ) -> Result<Review> {
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            let revision = review.revisions.last().map(|r| r.number);
+            let addressed_in = addressed_in.map(|rev| review.resolve_revision(rev));
+            let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+            thread.comments.push(Comment::new(author.clone(), text, false));
+            if let Some(status) = status {
+                Self::transition(thread, status, author, revision);
+            }
+            if status == Some(ThreadStatus::Addressed) && addressed_in.is_some() {
+                thread.addressed_in = addressed_in;
+            }
+            Ok(())
+        })?;
+        Ok(review)
+    }
 
-        thread.comments.push(Comment {
+    fn transition(thread: &mut Thread, status: ThreadStatus, author: Author, revision: Option<u32>) {
+        if thread.status == status {
+            return;
+        }
+        thread.history.push(StatusChange {
+            from: thread.status,
+            to: status,
             author,
-            text: text.to_string(),
             timestamp: Utc::now(),
+            revision,
         });
+        thread.status = status;
+    }
 
-        self.save(&review)?;
-        Ok(review)
+    pub fn resolve_thread(&self, change_id: &str, thread_id: &str, author: Author) -> Result<Review> {
+        self.set_thread_status(change_id, thread_id, ThreadStatus::Resolved, author)
     }
 
-    pub fn resolve_thread(&self, change_id: &str, thread_id: &str) -> Result<Review> {
-        let mut review = self
-            .get_by_prefix(change_id)?
-            .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", change_id))?;
+    /// Record that `reviewer` has reviewed the change up to `revision`, or
+    /// up to `commit_id` (recorded as a new revision if needed).
+    pub fn mark_reviewed(
+        &self,
+        change_id: &str,
+        reviewer: &str,
+        revision: Option<u32>,
+        commit_id: &str,
+    ) -> Result<(Review, Option<u32>)> {
+        self.update_by_prefix(change_id, |review| {
+            let number = match revision {
+                Some(n) if review.revisions.iter().any(|r| r.number == n) => Some(n),
+                Some(n) => anyhow::bail!("Revision {} not found", n),
+                None => Self::touch_revision(review, commit_id),
+            };
+            review.seen.entry(reviewer.to_string()).or_default().last_reviewed_revision = number;
+            Ok(number)
+        })
+    }
+
+    /// Give `reviewer`'s verdict on the change as of `commit_id`, recording
+    /// it as a new revision if needed. Returns the revision number.
+    pub fn set_verdict(
+        &self,
+        change_id: &str,
+        reviewer: &str,
+        kind: VerdictKind,
+        comment: Option<String>,
+        commit_id: &str,
+    ) -> Result<(Review, u32)> {
+        self.update_by_prefix(change_id, |review| Ok(Self::push_verdict(review, reviewer, kind, comment, commit_id)))
+    }
+
+    fn push_verdict(
+        review: &mut Review,
+        reviewer: &str,
+        kind: VerdictKind,
+        comment: Option<String>,
+        commit_id: &str,
+    ) -> u32 {
+        let revision = Self::touch_revision(review, commit_id).expect("touch_revision records a revision");
+        review.verdicts.push(Verdict {
+            reviewer: reviewer.to_string(),
+            kind,
+            revision,
+            comment,
+            created_at: Utc::now(),
+        });
+        revision
+    }
+
+    /// Publish all of `reviewer`'s draft comments at once, optionally with a
+    /// verdict on `commit_id`, so the agent sees the whole review together.
+    /// Other reviewers' drafts stay drafts. Returns the published comments
+    /// as (thread id, comment id) pairs.
+    pub fn submit_review(
+        &self,
+        change_id: &str,
+        reviewer: &str,
+        verdict: Option<(VerdictKind, Option<String>)>,
+        commit_id: &str,
+    ) -> Result<(Review, Vec<(String, String)>)> {
+        self.update_by_prefix(change_id, |review| {
+            let mut published = Vec::new();
+            for thread in &mut review.threads {
+                for comment in &mut thread.comments {
+                    if comment.draft && comment.visible_to(Some(reviewer)) {
+                        comment.draft = false;
+                        published.push((thread.id.clone(), comment.id.clone()));
+                    }
+                }
+            }
+            if published.is_empty() && verdict.is_none() {
+                anyhow::bail!("Nothing to submit: no draft comments and no verdict");
+            }
+            if let Some((kind, comment)) = verdict {
+                Self::push_verdict(review, reviewer, kind, comment, commit_id);
+            }
+            Ok(published)
+        })
+    }
 
-        let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+    /// Changes among `changes` that can't merge for lack of an approval on
+    /// their current commit, one "<change id>: <reason>" line each.
+    pub fn unapproved(&self, changes: &[crate::jj::Change]) -> Result<Vec<String>> {
+        let mut blocked = Vec::new();
+        for change in changes {
+            let review = self.get(&change.change_id)?;
+            let reason = match review.as_ref().and_then(|r| r.verdict_on(&change.commit_id)) {
+                Some(VerdictKind::Approved) => continue,
+                Some(VerdictKind::ChangesRequested) => "changes requested",
+                None => "not approved at its current commit",
+            };
+            blocked.push(format!("{}: {}", &change.change_id[..8.min(change.change_id.len())], reason));
+        }
+        Ok(blocked)
+    }
 
-        thread.status = ThreadStatus::Resolved;
-        self.save(&review)?;
+    /// Record that `reviewer` has read every comment so far, on one thread
+    /// or (without `thread_id`) on all of them.
+    pub fn mark_read(&self, change_id: &str, reviewer: &str, thread_id: Option<&str>) -> Result<Review> {
+        let (review, ()) = self.update_by_prefix(change_id, |review| {
+            let latest = |thread: &Thread| thread.comments.iter().map(Comment::updated_at).max();
+            match thread_id {
+                Some(thread_id) => {
+                    let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+                    let (id, latest) = (thread.id.clone(), latest(thread));
+                    if let Some(latest) = latest {
+                        review.seen.entry(reviewer.to_string()).or_default().threads.insert(id, latest);
+                    }
+                }
+                None => {
+                    let latest = review.threads.iter().filter_map(latest).max();
+                    let progress = review.seen.entry(reviewer.to_string()).or_default();
+                    progress.last_read_comment = progress.last_read_comment.max(latest);
+                    // Per-thread marks at or before this one say nothing more
+                    let until = progress.last_read_comment;
+                    progress.threads.retain(|_, t| Some(*t) > until);
+                }
+            }
+            Ok(())
+        })?;
         Ok(review)
     }
 
-    pub fn reopen_thread(&self, change_id: &str, thread_id: &str) -> Result<Review> {
-        let mut review = self
-            .get_by_prefix(change_id)?
-            .ok_or_else(|| anyhow::anyhow!("Review not found for change: {}", change_id))?;
-
-        let thread = Self::find_thread_mut(&mut review.threads, thread_id)?;
+    pub fn reopen_thread(&self, change_id: &str, thread_id: &str, author: Author) -> Result<Review> {
+        self.set_thread_status(change_id, thread_id, ThreadStatus::Open, author)
+    }
+}
 
-        thread.status = ThreadStatus::Open;
-        self.save(&review)?;
-        Ok(review)
+/// Summarize where each review's verdicts stand, for the agent. Reviews
+/// nobody has given a verdict on are left out.
+pub(crate) fn format_verdicts(vcs: &dyn crate::vcs::Vcs, reviews: &[Review]) -> String {
+    let mut output = String::new();
+    for review in reviews {
+        let Some(last) = review.verdicts.last() else {
+            continue;
+        };
+        let short_id = &review.change_id[..8.min(review.change_id.len())];
+        let commit_id = vcs.get_change(&review.change_id).map(|c| c.commit_id).unwrap_or_default();
+        let by = |kind: VerdictKind| {
+            let verdicts: Vec<_> = review.current_verdicts().into_iter().filter(|v| v.kind == kind).collect();
+            let names: Vec<_> = verdicts.iter().map(|v| v.reviewer.as_str()).collect();
+            let comments: String = verdicts
+                .iter()
+                .filter_map(|v| Some(format!("\n  - **{}**: {}", v.reviewer, v.comment.as_deref()?)))
+                .collect();
+            format!("{}{}", names.join(", "), comments)
+        };
+        let line = match review.verdict_on(&commit_id) {
+            Some(VerdictKind::ChangesRequested) => {
+                format!("**changes requested** by {}", by(VerdictKind::ChangesRequested))
+            }
+            Some(VerdictKind::Approved) => format!("**approved** by {}", by(VerdictKind::Approved)),
+            None => {
+                let kind = match last.kind {
+                    VerdictKind::Approved => "approved",
+                    VerdictKind::ChangesRequested => "changes requested",
+                };
+                format!("{} on revision {}; newer commits await review", kind, last.revision)
+            }
+        };
+        output.push_str(&format!("- Change {}: {}\n", short_id, line));
+    }
+    if output.is_empty() {
+        return output;
     }
+    format!("## Verdicts\n\n{}\n", output)
 }
 
 /// Format pending feedback for a list of reviews with open threads.
 /// This is the core logic used by the `feedback` CLI command.
-pub(crate) fn format_pending_feedback(jj: &crate::jj::Jj, reviews: Vec<Review>) -> String {
+pub(crate) fn format_pending_feedback(vcs: &dyn crate::vcs::Vcs, mut reviews: Vec<Review>) -> String {
     let mut output = String::new();
 
+    // Changes with the most important feedback first, and within a change
+    // the most important threads first
+    reviews.sort_by_key(|r| {
+        r.threads.iter().filter(|t| t.status.awaits_agent() && !t.is_draft(None)).map(|t| t.severity).min()
+    });
+
     for mut review in reviews {
+        // Drafts stay hidden from the agent until the review is submitted
+        review.hide_drafts(None);
         let open_threads: Vec<_> = review
             .threads
             .iter()
-            .filter(|t| t.status == ThreadStatus::Open)
+            .filter(|t| t.status.awaits_agent())
             .collect();
 
         if open_threads.is_empty() {
@@ -398,27 +1271,32 @@ pub(crate) fn format_pending_feedback(jj: &crate::jj::Jj, reviews: Vec<Review>)
         }
 
         // Map thread positions to current commit
-        let target_commit = jj.get_change(&review.change_id).ok().map(|c| c.commit_id);
+        let change = vcs.get_change(&review.change_id).ok();
+        let target_commit = change.as_ref().map(|c| c.commit_id.clone());
 
         if let Some(ref target) = target_commit {
-            let mapped = crate::line_mapper::map_all_threads(jj, &review.threads, target);
+            let mapped = crate::line_mapper::map_all_threads(vcs, &review.threads, target);
             for thread in &mut review.threads {
                 if let Some(pos) = mapped.get(&thread.id) {
                     thread.display_line_start = Some(pos.line_start);
                     thread.display_line_end = Some(pos.line_end);
                     thread.is_deleted = pos.is_deleted;
                     thread.is_displaced = pos.line_start != thread.line_start
-                        || pos.line_end != thread.line_end;
+                        || pos.line_end != thread.line_end
+                        || pos.file.is_some();
+                    thread.match_confidence = Some(pos.confidence);
+                    thread.display_file = pos.file.clone();
                 }
             }
         }
 
         // Re-filter after mutation
-        let open_threads: Vec<_> = review
+        let mut open_threads: Vec<_> = review
             .threads
             .iter()
-            .filter(|t| t.status == ThreadStatus::Open)
+            .filter(|t| t.status.awaits_agent())
             .collect();
+        open_threads.sort_by_key(|t| t.severity);
 
         let current_commit = target_commit.as_deref().unwrap_or("unknown");
         output.push_str(&format!(
@@ -432,82 +1310,147 @@ pub(crate) fn format_pending_feedback(jj: &crate::jj::Jj, reviews: Vec<Review>)
         let base_rev = format!("{}-", review.change_id);
 
         for thread in open_threads {
-            // Use mapped positions for display
-            let display_start = thread.display_line_start.unwrap_or(thread.line_start);
-            let display_end = thread.display_line_end.unwrap_or(thread.line_end);
-
             // Thread header
+            let file = match &thread.display_file {
+                Some(moved) => format!("{} → {}", thread.file, moved),
+                None => thread.file.clone(),
+            };
+            let location = match thread.anchor {
+                ThreadAnchor::Line => file,
+                ThreadAnchor::File => format!("{} (whole file)", file),
+                ThreadAnchor::Description => "commit description".to_string(),
+                ThreadAnchor::Change => "whole change".to_string(),
+            };
+            let labels = if thread.labels.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", thread.labels.join(", "))
+            };
+            let status = match thread.status {
+                ThreadStatus::Open => String::new(),
+                status => format!(", {status}"),
+            };
             output.push_str(&format!(
-                "### Thread {} — {}\n",
+                "### Thread {} — {} [{}{}]{}\n",
                 &thread.id[..8.min(thread.id.len())],
-                thread.file,
-            ));
-
-            // Original position (where the comment was created)
-            let orig_commit = thread.created_at_commit.as_deref().unwrap_or("unknown");
-            output.push_str(&format!(
-                "**Originally:** lines {}-{} in {}\n",
-                thread.line_start,
-                thread.line_end,
-                &orig_commit[..12.min(orig_commit.len())],
+                location,
+                thread.severity,
+                status,
+                labels,
             ));
-
-            // Current position (where the comment maps to now)
-            if thread.is_deleted {
+            if let Some(moved) = &thread.moved_from {
+                let operation = moved.operation.as_deref().map(|o| format!(" by `{o}`")).unwrap_or_default();
                 output.push_str(&format!(
-                    "**Now:** lines deleted (nearest: {}-{} in {})\n\n",
-                    display_start,
-                    display_end,
-                    &current_commit[..12.min(current_commit.len())],
+                    "Moved here from change {}{operation}.\n",
+                    &moved.from_change_id[..8.min(moved.from_change_id.len())]
                 ));
-            } else {
+            }
+
+            if matches!(thread.anchor, ThreadAnchor::File | ThreadAnchor::Change) {
+                output.push('\n');
+            }
+
+            if thread.anchor == ThreadAnchor::Description {
+                let description = change.as_ref().map(|c| c.description.trim()).unwrap_or("");
+                output.push_str("```\n");
+                output.push_str(if description.is_empty() { "(no description set)" } else { description });
+                output.push_str("\n```\n\n");
+            }
+
+            if thread.anchor == ThreadAnchor::Line {
+                // Use mapped positions for display
+                let display_file = thread.display_file.as_ref().unwrap_or(&thread.file);
+                let display_start = thread.display_line_start.unwrap_or(thread.line_start);
+                let display_end = thread.display_line_end.unwrap_or(thread.line_end);
+
+                // Old-side threads point at lines in the parent (usually ones this change deleted)
+                let (lines_label, commit_suffix) = match thread.side {
+                    Side::New => ("lines", ""),
+                    Side::Old => ("old lines", " (parent)"),
+                };
+
+                // Original position (where the comment was created)
+                let orig_commit = thread.created_at_commit.as_deref().unwrap_or("unknown");
                 output.push_str(&format!(
-                    "**Now:** lines {}-{} in {}\n\n",
-                    display_start,
-                    display_end,
-                    &current_commit[..12.min(current_commit.len())],
+                    "**Originally:** {} {}-{} in {}{}\n",
+                    lines_label,
+                    thread.line_start,
+                    thread.line_end,
+                    &orig_commit[..12.min(orig_commit.len())],
+                    commit_suffix,
                 ));
-            }
 
-            // Show a brief diff around the comment position
-            if !thread.is_deleted {
-                let diff_text = file_diffs
-                    .entry(thread.file.clone())
-                    .or_insert_with(|| {
-                        jj.diff_raw_between_ctx(&base_rev, &review.change_id, &thread.file, Some(10))
-                            .unwrap_or_default()
-                    });
-
-                let nearby = extract_nearby_hunks(diff_text, display_start, display_end, 5);
-                if !nearby.is_empty() {
-                    output.push_str("```diff\n");
-                    output.push_str(&nearby);
-                    output.push_str("```\n\n");
+                // Current position (where the comment maps to now)
+                if thread.is_deleted {
+                    output.push_str(&format!(
+                        "**Now:** {} deleted (nearest: {}-{} in {}{})\n\n",
+                        lines_label,
+                        display_start,
+                        display_end,
+                        &current_commit[..12.min(current_commit.len())],
+                        commit_suffix,
+                    ));
                 } else {
-                    // Thread on unchanged code — show raw content as fallback
-                    if let Ok(file_content) = jj.show_file(&review.change_id, &thread.file) {
-                        let lines: Vec<&str> = file_content.lines().collect();
-                        let start = display_start.saturating_sub(3).max(1);
-                        let end = (display_end + 3).min(lines.len());
-
-                        output.push_str("```\n");
-                        for (i, line) in lines.iter().enumerate() {
-                            let line_num = i + 1;
-                            if line_num >= start && line_num <= end {
-                                let marker = if line_num >= display_start
-                                    && line_num <= display_end
-                                {
-                                    ">"
-                                } else {
-                                    " "
-                                };
-                                output.push_str(&format!(
-                                    "{} {:4} | {}\n",
-                                    marker, line_num, line
-                                ));
+                    // A content match rather than a position the diff led to
+                    let fuzzy = match thread.match_confidence {
+                        Some(c) if c < 1.0 => format!(" (matched by content, {:.0}% confidence)", c * 100.0),
+                        _ => String::new(),
+                    };
+                    output.push_str(&format!(
+                        "**Now:** {} {}-{} in {}{}{}\n\n",
+                        lines_label,
+                        display_start,
+                        display_end,
+                        &current_commit[..12.min(current_commit.len())],
+                        commit_suffix,
+                        fuzzy,
+                    ));
+                }
+
+                // Show a brief diff around the comment position
+                if !thread.is_deleted {
+                    let diff_text = file_diffs
+                        .entry(display_file.clone())
+                        .or_insert_with(|| {
+                            vcs.diff_raw_between_ctx(&base_rev, &review.change_id, display_file, Some(10))
+                                .unwrap_or_default()
+                        });
+
+                    let nearby = extract_nearby_hunks(diff_text, display_start, display_end, 5, thread.side);
+                    if !nearby.is_empty() {
+                        output.push_str("```diff\n");
+                        output.push_str(&nearby);
+                        output.push_str("```\n\n");
+                    } else {
+                        // Thread on unchanged code — show raw content as fallback
+                        let rev = match thread.side {
+                            Side::New => review.change_id.clone(),
+                            Side::Old => base_rev.clone(),
+                        };
+                        if let Ok(file_content) = vcs.show_file(&rev, display_file) {
+                            let lines: Vec<&str> = file_content.lines().collect();
+                            let start = display_start.saturating_sub(3).max(1);
+                            let end = (display_end + 3).min(lines.len());
+
+                            output.push_str("```\n");
+                            for (i, line) in lines.iter().enumerate() {
+                                let line_num = i + 1;
+                                if line_num >= start && line_num <= end {
+                                    let marker = if line_num >= display_start
+                                        && line_num <= display_end
+                                    {
+                                        ">"
+                                    } else {
+                                        " "
+                                    };
+                                    output.push_str(&format!(
+                                        "{} {:4} | {}\n",
+                                        marker, line_num, line
+                                    ));
+                                }
                             }
+                            output.push_str("```\n\n");
                         }
-                        output.push_str("```\n\n");
                     }
                 }
             }
@@ -515,34 +1458,57 @@ pub(crate) fn format_pending_feedback(jj: &crate::jj::Jj, reviews: Vec<Review>)
             // Show comments
             output.push_str("**Comments:**\n");
             for comment in &thread.comments {
-                let author = match comment.author {
-                    Author::User => "User",
-                    Author::Claude => "Claude",
-                };
-                output.push_str(&format!("- **{}**: {}\n", author, comment.text));
+                output.push_str(&format!("- **{}** `{}`: {}\n", comment.author, &comment.id[..8.min(comment.id.len())], comment.text));
+                if let Some(edit) = comment.edits.last() {
+                    output.push_str(&format!(
+                        "  (edited {}; previously: {})\n",
+                        edit.edited_at.format("%Y-%m-%d %H:%M UTC"),
+                        edit.previous_text
+                    ));
+                }
+                if let Some(lines) = &comment.suggestion {
+                    output.push_str("  Suggested replacement:\n  ```\n");
+                    for line in lines {
+                        output.push_str(&format!("  {line}\n"));
+                    }
+                    output.push_str("  ```\n");
+                }
+            }
+            if thread.suggestion().is_some() {
+                output.push_str(&format!(
+                    "Apply with `aipair apply-suggestion {} {}`.\n",
+                    &review.change_id[..8.min(review.change_id.len())],
+                    &thread.id
+                ));
             }
-            output.push_str("\n");
+            output.push('\n');
         }
     }
 
     output
 }
 
-/// Extract diff lines near a line range in the new file.
-/// Tracks new-file line numbers through the diff and only emits lines
-/// whose new-file position falls within [line_start - padding, line_end + padding].
-fn extract_nearby_hunks(diff_text: &str, line_start: usize, line_end: usize, padding: usize) -> String {
+/// Extract diff lines near a line range on one side of the diff.
+/// Tracks that side's line numbers through the diff and only emits lines
+/// whose position falls within [line_start - padding, line_end + padding].
+fn extract_nearby_hunks(diff_text: &str, line_start: usize, line_end: usize, padding: usize, side: Side) -> String {
     let target_start = line_start.saturating_sub(padding);
     let target_end = line_end + padding;
 
-    // First pass: collect all diff lines with their new-file line numbers
+    // Lines that exist only on the other side ('+' for old, '-' for new)
+    let other_side_marker = match side {
+        Side::Old => '+',
+        Side::New => '-',
+    };
+
+    // First pass: collect all diff lines with their line numbers on `side`
     struct DiffLine {
         text: String,
-        new_line: Option<usize>, // None for deleted lines
+        line: Option<usize>, // None for lines only on the other side
     }
 
     let mut diff_lines: Vec<DiffLine> = Vec::new();
-    let mut new_pos: usize = 0;
+    let mut pos: usize = 0;
 
     for line in diff_text.lines() {
         // Skip file header lines
@@ -555,45 +1521,44 @@ fn extract_nearby_hunks(diff_text: &str, line_start: usize, line_end: usize, pad
         }
 
         if line.starts_with("@@") {
-            if let Some((start, _)) = parse_new_file_range(line) {
-                new_pos = start;
+            if let Some((start, _)) = parse_file_range(line, side) {
+                pos = start;
             }
             diff_lines.push(DiffLine {
                 text: line.to_string(),
-                new_line: Some(new_pos),
+                line: Some(pos),
             });
-        } else if line.starts_with('-') {
-            // Deleted lines don't have a new-file position
+        } else if line.starts_with(other_side_marker) {
             diff_lines.push(DiffLine {
                 text: line.to_string(),
-                new_line: None,
+                line: None,
             });
         } else {
-            // Context lines and added lines occupy a new-file line
+            // Context lines and lines on our side occupy a line number
             diff_lines.push(DiffLine {
                 text: line.to_string(),
-                new_line: Some(new_pos),
+                line: Some(pos),
             });
-            new_pos += 1;
+            pos += 1;
         }
     }
 
     // Second pass: emit lines in the target window.
-    // Include deleted lines if they're adjacent to included context/add lines.
+    // Include other-side lines if they're adjacent to included lines.
     let mut result = String::new();
     let mut last_was_included = false;
 
     for dl in &diff_lines {
-        let include = match dl.new_line {
+        let include = match dl.line {
             Some(n) => n >= target_start && n <= target_end,
-            // Include deleted lines that are adjacent to included lines
+            // Include other-side lines that are adjacent to included lines
             None => last_was_included,
         };
 
         // Always include @@ headers for included regions
         if dl.text.starts_with("@@") {
             // Check if any line in this hunk falls in our window
-            if let Some((start, count)) = parse_new_file_range(&dl.text) {
+            if let Some((start, count)) = parse_file_range(&dl.text, side) {
                 let hunk_end = start + count;
                 if start <= target_end && hunk_end >= target_start {
                     result.push_str(&dl.text);
@@ -616,25 +1581,33 @@ fn extract_nearby_hunks(diff_text: &str, line_start: usize, line_end: usize, pad
     result
 }
 
-/// Parse the new-file range from a @@ hunk header: @@ -old,count +new,count @@
-fn parse_new_file_range(header: &str) -> Option<(usize, usize)> {
+/// Parse one side's range from a @@ hunk header: @@ -old,count +new,count @@
+fn parse_file_range(header: &str, side: Side) -> Option<(usize, usize)> {
     let parts: Vec<&str> = header.split_whitespace().collect();
     if parts.len() < 4 {
         return None;
     }
-    let new_part = parts[2].trim_start_matches('+');
-    if let Some((start, count)) = new_part.split_once(',') {
+    let part = match side {
+        Side::Old => parts[1].trim_start_matches('-'),
+        Side::New => parts[2].trim_start_matches('+'),
+    };
+    if let Some((start, count)) = part.split_once(',') {
         Some((start.parse().ok()?, count.parse().ok()?))
     } else {
-        Some((new_part.parse().ok()?, 1))
+        Some((part.parse().ok()?, 1))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vcs::Vcs;
     use tempfile::TempDir;
 
+    fn draft() -> CommentExtras {
+        CommentExtras { draft: true, ..CommentExtras::default() }
+    }
+
     fn setup() -> (TempDir, ReviewStore) {
         let dir = TempDir::new().unwrap();
         let store = ReviewStore::new(dir.path());
@@ -655,6 +1628,16 @@ mod tests {
         assert_eq!(fetched.change_id, "abc123");
     }
 
+    #[test]
+    fn test_list_reports_unreadable_reviews() {
+        let (dir, store) = setup();
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        std::fs::write(dir.path().join(".aipair/reviews/broken.json"), "{").unwrap();
+
+        let err = store.list().unwrap_err();
+        assert!(format!("{err:#}").contains("reviews/broken"), "{err:#}");
+    }
+
     #[test]
     fn test_add_comment_creates_thread_and_revision() {
         let (_dir, store) = setup();
@@ -666,9 +1649,12 @@ mod tests {
                 "src/main.rs",
                 10,
                 15,
-                Author::User,
+                Side::New,
+                Author::human("user"),
                 "This looks wrong",
                 "commit1",
+                None,
+                CommentExtras::default(),
             )
             .unwrap();
 
@@ -690,10 +1676,10 @@ mod tests {
 
         store.get_or_create("abc123", "@-", "commit1").unwrap();
         store
-            .add_comment("abc123", "src/main.rs", 10, 15, Author::User, "First comment", "commit1")
+            .add_comment("abc123", "src/main.rs", 10, 15, Side::New, Author::human("user"), "First comment", "commit1", None, CommentExtras::default())
             .unwrap();
         let (review, _) = store
-            .add_comment("abc123", "src/other.rs", 5, 5, Author::User, "Second comment", "commit2")
+            .add_comment("abc123", "src/other.rs", 5, 5, Side::New, Author::human("user"), "Second comment", "commit2", None, CommentExtras::default())
             .unwrap();
 
         // Should have two revisions now
@@ -712,18 +1698,41 @@ mod tests {
                 "src/main.rs",
                 10,
                 15,
-                Author::User,
+                Side::New,
+                Author::human("user"),
                 "This looks wrong",
                 "commit1",
+                None,
+                CommentExtras::default(),
             )
             .unwrap();
 
         let review = store
-            .reply_to_thread("abc123", &thread_id, Author::Claude, "Fixed it!")
+            .reply_to_thread("abc123", &thread_id, Author::agent("claude", None), "Fixed it!", CommentExtras::default())
             .unwrap();
 
         assert_eq!(review.threads[0].comments.len(), 2);
-        assert_eq!(review.threads[0].comments[1].author, Author::Claude);
+        assert_eq!(review.threads[0].comments[1].author, Author::agent("claude", None));
+    }
+
+    #[test]
+    fn test_respond_replies_and_changes_status_in_one_update() {
+        let (_dir, store) = setup();
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        let (before, thread_id) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "Add tests", "commit1", CommentExtras::default())
+            .unwrap();
+
+        let agent = Author::agent("claude", None);
+        let review = store
+            .respond("abc123", &thread_id, agent.clone(), "Added", Some(ThreadStatus::Addressed), Some("1"))
+            .unwrap();
+        assert_eq!(review.version, before.version + 1);
+        let thread = &review.threads[0];
+        assert_eq!(thread.comments[1].text, "Added");
+        assert_eq!(thread.status, ThreadStatus::Addressed);
+        assert_eq!(thread.history.last().unwrap().author, agent);
+        assert!(thread.addressed_in.is_some());
     }
 
     #[test]
@@ -737,30 +1746,334 @@ mod tests {
                 "src/main.rs",
                 10,
                 15,
-                Author::User,
+                Side::New,
+                Author::human("user"),
                 "This looks wrong",
                 "commit1",
+                None,
+                CommentExtras::default(),
             )
             .unwrap();
 
-        let review = store.resolve_thread("abc123", &thread_id).unwrap();
+        let review = store.resolve_thread("abc123", &thread_id, Author::human("user")).unwrap();
         assert_eq!(review.threads[0].status, ThreadStatus::Resolved);
     }
 
     #[test]
-    fn test_record_revision() {
+    fn test_thread_status_history() {
         let (_dir, store) = setup();
 
         store.get_or_create("abc123", "@-", "commit1").unwrap();
-        let (review, number) = store
-            .record_revision("abc123", "commit2", Some("Addressed feedback".to_string()))
+        let (_, thread_id) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "Add tests", "commit1", CommentExtras::default())
             .unwrap();
 
-        assert_eq!(number, 1);
-        assert_eq!(review.revisions.len(), 1);
-        assert_eq!(review.revisions[0].commit_id, "commit2");
-        assert_eq!(review.revisions[0].description, Some("Addressed feedback".to_string()));
-        assert_eq!(review.working_commit_id, Some("commit2".to_string()));
+        let agent = Author::agent("claude", Some("s1"));
+        store.set_thread_status("abc123", &thread_id, ThreadStatus::InProgress, agent.clone()).unwrap();
+        store.mark_reviewed("abc123", "user", None, "commit2").unwrap();
+        // Revision numbers resolve to the revision's commit
+        let review = store.mark_addressed("abc123", &thread_id, agent.clone(), Some("2")).unwrap();
+        assert_eq!(review.threads[0].addressed_in.as_deref(), Some("commit2"));
+        // Still counts until the reviewer verifies it, but the agent is done
+        assert!(review.threads[0].blocks_merge());
+        assert!(!review.threads[0].status.awaits_agent());
+        assert!(store.list_with_open_threads(None).unwrap().is_empty());
+
+        // Setting the same status again isn't a transition
+        store.set_thread_status("abc123", &thread_id, ThreadStatus::Addressed, agent.clone()).unwrap();
+        let review = store.resolve_thread("abc123", &thread_id, Author::human("user")).unwrap();
+
+        let history = &review.threads[0].history;
+        let steps: Vec<_> = history.iter().map(|h| (h.from, h.to)).collect();
+        assert_eq!(
+            steps,
+            vec![
+                (ThreadStatus::Open, ThreadStatus::InProgress),
+                (ThreadStatus::InProgress, ThreadStatus::Addressed),
+                (ThreadStatus::Addressed, ThreadStatus::Resolved),
+            ]
+        );
+        assert_eq!(history[0].author, agent);
+        assert_eq!(history[0].revision, Some(1));
+        assert_eq!(history[1].revision, Some(2));
+        assert_eq!(history[2].author, Author::human("user"));
+    }
+
+    #[test]
+    fn test_edit_and_delete_comment() {
+        let (_dir, store) = setup();
+
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        let user = Author::human("user");
+        let agent = Author::agent("claude", Some("s1"));
+        let (_, thread_id) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, user.clone(), "Add tests", "commit1", CommentExtras::default())
+            .unwrap();
+        let review = store.reply_to_thread("abc123", &thread_id, agent.clone(), "Will do", CommentExtras::default()).unwrap();
+        let question = review.threads[0].comments[0].id.clone();
+        let answer = review.threads[0].comments[1].id.clone();
+        store.mark_read("abc123", "user", None).unwrap();
+
+        // Only the author can change a comment
+        assert!(store.edit_comment("abc123", &thread_id, &question, &agent, "Nope").is_err());
+        assert!(store.delete_comment("abc123", &thread_id, &question, &agent).is_err());
+
+        // Edited after it was read, so it's unread again
+        let review = store.edit_comment("abc123", &thread_id, &answer[..8], &agent, "Done in 2").unwrap();
+        let comment = &review.threads[0].comments[1];
+        assert_eq!(comment.text, "Done in 2");
+        assert_eq!(comment.edits.len(), 1);
+        assert_eq!(comment.edits[0].previous_text, "Will do");
+        assert_eq!(review.unread_replies("user").len(), 1);
+
+        // An unchanged edit isn't recorded
+        let review = store.edit_comment("abc123", &thread_id, &answer, &agent, "Done in 2").unwrap();
+        assert_eq!(review.threads[0].comments[1].edits.len(), 1);
+
+        let review = store.delete_comment("abc123", &thread_id, &answer, &agent).unwrap();
+        assert_eq!(review.threads[0].comments.len(), 1);
+        // Deleting the last comment deletes the thread
+        let review = store.delete_comment("abc123", &thread_id, &question, &user).unwrap();
+        assert!(review.threads.is_empty());
+    }
+
+    #[test]
+    fn test_legacy_comments_get_stable_ids() {
+        let json = r#"{"author":"user","text":"hi","timestamp":"2024-01-01T00:00:00Z","draft":false}"#;
+        let comment: Comment = serde_json::from_str(json).unwrap();
+        assert!(comment.id.is_empty());
+        let again: Comment = serde_json::from_str(json).unwrap();
+        assert_eq!(comment.legacy_id(), again.legacy_id());
+
+        let later: Comment = serde_json::from_str(&json.replace("00:00:00Z", "00:00:01Z")).unwrap();
+        assert_ne!(comment.legacy_id()[..8], later.legacy_id()[..8]);
+    }
+
+    #[test]
+    fn test_move_thread() {
+        let (_dir, store) = setup();
+
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        let (_, thread_id) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "Why?", "commit1", CommentExtras::default())
+            .unwrap();
+        store.mark_read("abc123", "user", None).unwrap();
+
+        let moved = ThreadMove { from_change_id: "abc123".into(), operation: Some("squash".into()), timestamp: Utc::now() };
+        let review = store.move_thread("abc123", &thread_id, "def456", "commit2", moved).unwrap();
+        assert_eq!(review.change_id, "def456");
+        let thread = review.find_thread(&thread_id).unwrap();
+        assert_eq!(thread.moved_from.as_ref().unwrap().from_change_id, "abc123");
+        assert_eq!(thread.created_at_revision, None);
+        // Already read before the move
+        assert!(review.seen["user"].threads.contains_key(&thread_id));
+        assert!(review.unread_replies("user").is_empty());
+
+        assert!(store.get("abc123").unwrap().unwrap().threads.is_empty());
+    }
+
+    #[test]
+    fn test_reviewer_progress() {
+        let (_dir, store) = setup();
+
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        let (_, first) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "Why?", "commit1", CommentExtras::default())
+            .unwrap();
+        let (_, second) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "And this?", "commit1", CommentExtras::default())
+            .unwrap();
+        store.reply_to_thread("abc123", &first, Author::agent("claude", None), "Because", CommentExtras::default()).unwrap();
+        let review = store.reply_to_thread("abc123", &second, Author::agent("claude", None), "Fixed", CommentExtras::default()).unwrap();
+
+        // Nothing seen yet; the user's own comments never count as unread
+        assert!(review.has_unseen_revision("user", "commit1"));
+        let unread = review.unread_replies("user");
+        assert_eq!(unread.len(), 2);
+        assert_eq!(unread[0].1.len(), 1);
+
+        // Reading one thread leaves the other unread, for this reviewer only
+        let review = store.mark_read("abc123", "user", Some(&first)).unwrap();
+        let unread = review.unread_replies("user");
+        assert_eq!(unread.len(), 1);
+        assert_eq!(unread[0].0.id, second);
+        assert_eq!(review.unread_replies("alice").len(), 2);
+
+        // Reviewing records the current commit; a new commit is unseen again
+        let (review, revision) = store.mark_reviewed("abc123", "user", None, "commit2").unwrap();
+        assert_eq!(revision, Some(2));
+        assert!(!review.has_unseen_revision("user", "commit2"));
+        assert!(review.has_unseen_revision("user", "commit3"));
+        assert!(store.mark_reviewed("abc123", "user", Some(7), "commit2").is_err());
+
+        // Reading everything, then a new reply
+        let review = store.mark_read("abc123", "user", None).unwrap();
+        assert!(review.unread_replies("user").is_empty());
+        assert!(review.seen["user"].threads.is_empty());
+        let review = store.reply_to_thread("abc123", &first, Author::agent("claude", None), "Also", CommentExtras::default()).unwrap();
+        assert_eq!(review.unread_replies("user")[0].1[0].text, "Also");
+
+        // Another reviewer's draft isn't there to be read
+        let draft = CommentExtras { draft: true, ..CommentExtras::default() };
+        let review = store.reply_to_thread("abc123", &second, Author::human("alice"), "Hmm", draft).unwrap();
+        assert_eq!(review.unread_replies("user").len(), 1);
+    }
+
+    #[test]
+    fn test_verdicts_lapse_with_new_revisions() {
+        let (_dir, store) = setup();
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+
+        let (review, revision) = store
+            .set_verdict("abc123", "user", VerdictKind::ChangesRequested, Some("Needs tests".into()), "commit1")
+            .unwrap();
+        assert_eq!(revision, 1);
+        assert_eq!(review.verdict_on("commit1"), Some(VerdictKind::ChangesRequested));
+
+        // A new revision supersedes it
+        let (review, revision) = store.set_verdict("abc123", "alice", VerdictKind::Approved, None, "commit2").unwrap();
+        assert_eq!(revision, 2);
+        assert_eq!(review.verdict_on("commit2"), Some(VerdictKind::Approved));
+        assert_eq!(review.verdict_on("commit1"), None);
+        assert_eq!(review.verdicts.len(), 2);
+
+        // Any reviewer's change request outweighs an approval; the latest
+        // verdict per reviewer counts
+        let (review, _) = store.set_verdict("abc123", "user", VerdictKind::ChangesRequested, None, "commit2").unwrap();
+        assert_eq!(review.verdict_on("commit2"), Some(VerdictKind::ChangesRequested));
+        let (review, _) = store.set_verdict("abc123", "user", VerdictKind::Approved, None, "commit2").unwrap();
+        assert_eq!(review.current_verdicts().len(), 2);
+        assert_eq!(review.verdict_on("commit2"), Some(VerdictKind::Approved));
+
+        // A comment on a newer commit records a revision nobody approved
+        store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "One more thing", "commit3", CommentExtras::default())
+            .unwrap();
+        let review = store.get("abc123").unwrap().unwrap();
+        assert_eq!(review.verdict_on("commit3"), None);
+        assert!(review.current_verdicts().is_empty());
+    }
+
+    #[test]
+    fn test_drafts_hidden_until_submitted() {
+        let (_dir, store) = setup();
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+
+        let (_, thread_id) = store
+            .add_comment("abc123", "src/main.rs", 1, 1, Side::New, Author::human("user"), "Rename", "commit1", None, draft())
+            .unwrap();
+        store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "Split", "commit1", draft())
+            .unwrap();
+        assert!(store.list_with_open_threads(None).unwrap().is_empty());
+
+        let (review, published) = store
+            .submit_review("abc123", "user", Some((VerdictKind::ChangesRequested, None)), "commit1")
+            .unwrap();
+        assert_eq!(published.len(), 2);
+        assert!(review.threads.iter().all(|t| !t.is_draft(None)));
+        assert_eq!(review.verdict_on("commit1"), Some(VerdictKind::ChangesRequested));
+        assert_eq!(store.list_with_open_threads(None).unwrap().len(), 1);
+
+        // A draft reply on a published thread doesn't hide the thread
+        let review = store.reply_to_thread("abc123", &thread_id, Author::human("user"), "Also this", draft()).unwrap();
+        assert!(!review.threads[0].is_draft(None));
+        assert!(review.threads[0].comments[1].draft);
+
+        let (_, published) = store.submit_review("abc123", "user", None, "commit1").unwrap();
+        assert_eq!(published.len(), 1);
+        assert!(store.submit_review("abc123", "user", None, "commit1").is_err());
+    }
+
+    #[test]
+    fn test_extras_are_written_with_the_comment() {
+        let (_dir, store) = setup();
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+
+        let extras = CommentExtras {
+            suggestion: Some(vec!["let x = 2;".into()]),
+            severity: Some(Severity::Nit),
+            labels: Some(vec!["style".into()]),
+            ..CommentExtras::default()
+        };
+        let (review, thread_id) = store
+            .add_comment("abc123", "src/main.rs", 1, 1, Side::New, Author::human("user"), "Two", "commit1", None, extras)
+            .unwrap();
+        let thread = review.find_thread(&thread_id).unwrap();
+        assert_eq!(thread.severity, Severity::Nit);
+        assert_eq!(thread.labels, vec!["style"]);
+        assert_eq!(thread.suggestion(), Some(&["let x = 2;".to_string()][..]));
+
+        // A reply's suggestion goes on the reply, not whatever comment is last
+        let extras = CommentExtras { suggestion: Some(vec!["let x = 3;".into()]), ..CommentExtras::default() };
+        let review = store.reply_to_thread("abc123", &thread_id, Author::human("user"), "Three", extras).unwrap();
+        let thread = review.find_thread(&thread_id).unwrap();
+        assert_eq!(thread.comments[0].suggestion.as_deref(), Some(&["let x = 2;".to_string()][..]));
+        assert_eq!(thread.suggestion(), Some(&["let x = 3;".to_string()][..]));
+
+        // Suggestions off the new side's lines are refused before anything is saved
+        let extras = CommentExtras { suggestion: Some(vec![]), ..CommentExtras::default() };
+        assert!(store
+            .add_comment("abc123", "src/main.rs", 1, 1, Side::Old, Author::human("user"), "Old", "commit1", None, extras)
+            .is_err());
+        assert_eq!(store.get("abc123").unwrap().unwrap().threads.len(), 1);
+    }
+
+    #[test]
+    fn test_submit_publishes_only_the_reviewers_drafts() {
+        let (_dir, store) = setup();
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+
+        let (_, alice_thread) = store
+            .add_comment("abc123", "src/main.rs", 1, 1, Side::New, Author::human("alice"), "Rename", "commit1", None, draft())
+            .unwrap();
+        store.reply_to_thread("abc123", &alice_thread, Author::human("bob"), "Agreed", draft()).unwrap();
+        let (review, bob_thread) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("bob"), "Split", "commit1", draft())
+            .unwrap();
+        let bobs = review.find_thread(&bob_thread).unwrap();
+        assert!(!bobs.is_draft(Some("bob")));
+        assert!(bobs.is_draft(Some("alice")));
+        assert!(bobs.is_draft(None));
+
+        let (mut review, published) = store.submit_review("abc123", "alice", None, "commit1").unwrap();
+        assert_eq!(published.len(), 1);
+        assert!(!review.find_thread(&alice_thread).unwrap().is_draft(None));
+        assert!(review.find_thread(&bob_thread).unwrap().is_draft(None));
+
+        // The agent sees Alice's thread without Bob's pending reply
+        review.hide_drafts(None);
+        assert_eq!(review.threads.len(), 1);
+        assert_eq!(review.threads[0].comments.len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_replies_are_not_lost() {
+        let (dir, store) = setup();
+
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        let (_, thread_id) = store
+            .add_comment("abc123", "src/main.rs", 1, 1, Side::New, Author::human("user"), "Hmm", "commit1", None, CommentExtras::default())
+            .unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = dir.path().to_path_buf();
+                let thread_id = thread_id.clone();
+                std::thread::spawn(move || {
+                    let author = if i % 2 == 0 { Author::human("user") } else { Author::agent("claude", None) };
+                    ReviewStore::new(&path)
+                        .reply_to_thread("abc123", &thread_id, author, &format!("reply {i}"), CommentExtras::default())
+                        .unwrap();
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let review = store.get("abc123").unwrap().unwrap();
+        assert_eq!(review.threads[0].comments.len(), 9);
     }
 
     #[test]
@@ -782,7 +2095,7 @@ diff --git a/src/foo.rs b/src/foo.rs
  line 52
 ";
         // Thread at line 53 (near second hunk) — should only get second hunk
-        let result = super::extract_nearby_hunks(diff, 53, 53, 5);
+        let result = super::extract_nearby_hunks(diff, 53, 53, 5, Side::New);
         assert!(result.contains("fn late()"), "should contain second hunk header");
         assert!(result.contains("+inserted"), "should contain second hunk content");
         assert!(!result.contains("fn early()"), "should NOT contain first hunk");
@@ -803,7 +2116,7 @@ diff --git a/src/foo.rs b/src/foo.rs
  line 7
 ";
         // Thread at line 100 — far from hunk at 5-10
-        let result = super::extract_nearby_hunks(diff, 100, 100, 5);
+        let result = super::extract_nearby_hunks(diff, 100, 100, 5, Side::New);
         assert!(result.is_empty());
     }
 
@@ -821,10 +2134,100 @@ diff --git a/f.rs b/f.rs
  more
 ";
         // Thread right at line 11 (inside the hunk 10-15)
-        let result = super::extract_nearby_hunks(diff, 11, 11, 2);
+        let result = super::extract_nearby_hunks(diff, 11, 11, 2, Side::New);
         assert!(result.contains("+added line"));
     }
 
+    #[test]
+    fn test_extract_nearby_hunks_old_side_uses_old_line_numbers() {
+        let diff = "\
+diff --git a/f.rs b/f.rs
+--- a/f.rs
++++ b/f.rs
+@@ -5,4 +5,2 @@ fn early() {
+ line 5
+-removed 6
+-removed 7
+ line 8
+@@ -40,3 +38,3 @@ fn late() {
+ line 40
+-old 41
++new 41
+ line 42
+";
+        // Old line 6 was deleted — it has no new-file position, but the old side finds it
+        let result = super::extract_nearby_hunks(diff, 6, 6, 1, Side::Old);
+        assert!(result.contains("-removed 6"));
+        assert!(result.contains("fn early()"));
+        assert!(!result.contains("fn late()"));
+
+        // Old line 41 is in the second hunk, even though new numbering is offset by 2
+        let result = super::extract_nearby_hunks(diff, 41, 41, 0, Side::Old);
+        assert!(result.contains("-old 41"));
+        assert!(result.contains("+new 41"));
+        assert!(!result.contains("removed"));
+    }
+
+    #[test]
+    fn test_add_comment_same_lines_different_side_are_separate_threads() {
+        let (_dir, store) = setup();
+
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        let (_, new_id) = store
+            .add_comment("abc123", "f.rs", 6, 6, Side::New, Author::human("user"), "New line", "commit1", None, CommentExtras::default())
+            .unwrap();
+        let (review, old_id) = store
+            .add_comment("abc123", "f.rs", 6, 6, Side::Old, Author::human("user"), "Why delete?", "commit1", None, CommentExtras::default())
+            .unwrap();
+
+        assert_ne!(new_id, old_id);
+        assert_eq!(review.threads.len(), 2);
+        assert_eq!(review.threads[1].side, Side::Old);
+    }
+
+    #[test]
+    fn test_start_thread_on_file_description_and_change() {
+        let (_dir, store) = setup();
+
+        store.get_or_create("abc123", "@-", "commit1").unwrap();
+        store
+            .start_thread("abc123", ThreadAnchor::File, Some("src/lib.rs"), Author::human("user"), "Too big", "commit1", CommentExtras::default())
+            .unwrap();
+        store
+            .start_thread("abc123", ThreadAnchor::Description, None, Author::human("user"), "Typo in subject", "commit1", CommentExtras::default())
+            .unwrap();
+        store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "Split this", "commit1", CommentExtras::default())
+            .unwrap();
+        let (review, _) = store
+            .start_thread("abc123", ThreadAnchor::Change, None, Author::human("user"), "Needs tests", "commit1", CommentExtras::default())
+            .unwrap();
+
+        // Each general remark gets its own thread
+        assert_eq!(review.threads.len(), 4);
+        assert_eq!(review.threads[0].anchor, ThreadAnchor::File);
+        assert_eq!(review.threads[0].file, "src/lib.rs");
+        assert_eq!(review.threads[1].anchor, ThreadAnchor::Description);
+        assert_eq!(review.threads[1].file, "");
+        assert_eq!(review.threads[3].created_at_revision, Some(1));
+        assert_eq!(review.revisions.len(), 1);
+
+        assert!(store
+            .start_thread("abc123", ThreadAnchor::File, None, Author::human("user"), "Which file?", "commit1", CommentExtras::default())
+            .is_err());
+        assert!(store
+            .start_thread("abc123", ThreadAnchor::Line, Some("f.rs"), Author::human("user"), "Where?", "commit1", CommentExtras::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_thread_without_anchor_deserializes_as_line() {
+        let json = r#"{"id":"t1","file":"f.rs","line_start":1,"line_end":2,"status":"open","comments":[]}"#;
+        let thread: Thread = serde_json::from_str(json).unwrap();
+        assert_eq!(thread.anchor, ThreadAnchor::Line);
+        assert_eq!(thread.side, Side::New);
+    }
+
     #[test]
     fn test_extract_nearby_hunks_trims_large_hunks() {
         // Simulate a large hunk (new file with 50 lines)
@@ -836,7 +2239,7 @@ diff --git a/f.rs b/f.rs
         }
 
         // Thread at lines 25-26 with padding 3 → should show lines 22-29
-        let result = super::extract_nearby_hunks(&diff, 25, 26, 3);
+        let result = super::extract_nearby_hunks(&diff, 25, 26, 3, Side::New);
         assert!(
             result.contains("+line 25"),
             "should contain the commented line. Result:\n{result}"
@@ -939,7 +2342,6 @@ diff --git a/f.rs b/f.rs
 
         // Create a review and comment on the modified lines
         let store = ReviewStore::new(jj.repo_path());
-        store.init().unwrap();
         store
             .get_or_create(&change_id, &format!("{}-", change_id), &commit1)
             .unwrap();
@@ -949,9 +2351,12 @@ diff --git a/f.rs b/f.rs
                 "test.rs",
                 10,
                 11,
-                Author::User,
+                Side::New,
+                Author::human("user"),
                 "Fix this logic",
                 &commit1,
+                None,
+                CommentExtras::default(),
             )
             .unwrap();
 
@@ -998,7 +2403,128 @@ diff --git a/f.rs b/f.rs
 
         // Check comment
         assert!(output.contains("Fix this logic"), "should show comment text");
-        assert!(output.contains("**User**"), "should show author");
+        assert!(output.contains("**user**"), "should show author");
+    }
+
+    #[test]
+    fn test_format_feedback_general_threads() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+
+        std::fs::write(path.join("test.rs"), "line 1\n").unwrap();
+        jj_cmd(path, &["describe", "-m", "Add tset file"]);
+
+        let change_id = get_change_id(path);
+        let commit1 = get_commit_id(path);
+
+        let store = ReviewStore::new(jj.repo_path());
+        store.get_or_create(&change_id, &format!("{}-", change_id), &commit1).unwrap();
+        store
+            .start_thread(&change_id, ThreadAnchor::Description, None, Author::human("user"), "Typo", &commit1, CommentExtras::default())
+            .unwrap();
+        store
+            .start_thread(&change_id, ThreadAnchor::File, Some("test.rs"), Author::human("user"), "Rename file", &commit1, CommentExtras::default())
+            .unwrap();
+        store
+            .start_thread(&change_id, ThreadAnchor::Change, None, Author::human("user"), "Split this", &commit1, CommentExtras::default())
+            .unwrap();
+
+        let reviews = store.list_with_open_threads(None).unwrap();
+        let output = super::format_pending_feedback(&jj, reviews);
+
+        assert!(output.contains("— commit description"), "Output:\n{output}");
+        assert!(output.contains("Add tset file"), "should quote the description. Output:\n{output}");
+        assert!(output.contains("— test.rs (whole file)"), "Output:\n{output}");
+        assert!(output.contains("— whole change"), "Output:\n{output}");
+        assert!(!output.contains("**Originally:**"), "no line positions. Output:\n{output}");
+        for text in ["Typo", "Rename file", "Split this"] {
+            assert!(output.contains(text), "should show {text:?}. Output:\n{output}");
+        }
+    }
+
+    #[test]
+    fn test_format_verdicts() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+        // Keep review writes out of the commit
+        std::fs::write(path.join(".gitignore"), ".aipair/\n").unwrap();
+        std::fs::write(path.join("test.rs"), "line 1\n").unwrap();
+        let change_id = get_change_id(path);
+        let commit1 = get_commit_id(path);
+
+        let store = ReviewStore::new(jj.repo_path());
+        store.get_or_create(&change_id, &format!("{}-", change_id), &commit1).unwrap();
+        assert_eq!(super::format_verdicts(&jj, &store.list().unwrap()), "");
+
+        store
+            .set_verdict(&change_id, "user", VerdictKind::ChangesRequested, Some("Add tests".into()), &commit1)
+            .unwrap();
+        let output = super::format_verdicts(&jj, &store.list().unwrap());
+        assert!(output.contains("**changes requested** by user"), "Output:\n{output}");
+        assert!(output.contains("Add tests"), "Output:\n{output}");
+
+        store.set_verdict(&change_id, "user", VerdictKind::Approved, None, &commit1).unwrap();
+        let output = super::format_verdicts(&jj, &store.list().unwrap());
+        assert!(output.contains("**approved** by user"), "Output:\n{output}");
+
+        // The agent keeps working: the approval no longer covers the change
+        std::fs::write(path.join("test.rs"), "line 1\nline 2\n").unwrap();
+        let output = super::format_verdicts(&jj, &store.list().unwrap());
+        assert!(output.contains("approved on revision 1; newer commits await review"), "Output:\n{output}");
+    }
+
+    #[test]
+    fn test_format_feedback_leaves_out_drafts() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+        std::fs::write(path.join(".gitignore"), ".aipair/\n").unwrap();
+        std::fs::write(path.join("test.rs"), "line 1\n").unwrap();
+        let change_id = get_change_id(path);
+        let commit1 = get_commit_id(path);
+
+        let store = ReviewStore::new(jj.repo_path());
+        store.get_or_create(&change_id, &format!("{}-", change_id), &commit1).unwrap();
+        let (_, thread_id) = store
+            .start_thread(&change_id, ThreadAnchor::Change, None, Author::human("user"), "Published", &commit1, CommentExtras::default())
+            .unwrap();
+        store.reply_to_thread(&change_id, &thread_id, Author::human("user"), "Still drafting", draft()).unwrap();
+        store
+            .start_thread(&change_id, ThreadAnchor::Change, None, Author::human("user"), "Draft thread", &commit1, draft())
+            .unwrap();
+
+        let output = super::format_pending_feedback(&jj, store.list().unwrap());
+        assert!(output.contains("Published"), "Output:\n{output}");
+        assert!(!output.contains("Still drafting"), "Output:\n{output}");
+        assert!(!output.contains("Draft thread"), "Output:\n{output}");
+        assert_eq!(output.matches("### Thread").count(), 1, "Output:\n{output}");
+    }
+
+    #[test]
+    fn test_format_feedback_sorts_by_severity() {
+        let (dir, jj) = make_jj_repo();
+        let path = dir.path();
+        std::fs::write(path.join(".gitignore"), ".aipair/\n").unwrap();
+        std::fs::write(path.join("test.rs"), "line 1\n").unwrap();
+        let change_id = get_change_id(path);
+        let commit1 = get_commit_id(path);
+
+        let store = ReviewStore::new(jj.repo_path());
+        store.get_or_create(&change_id, &format!("{}-", change_id), &commit1).unwrap();
+        for (text, severity) in [("Nitpick", Severity::Nit), ("Must fix", Severity::Blocking), ("Maybe", Severity::Suggestion)] {
+            let (_, thread_id) = store
+                .start_thread(&change_id, ThreadAnchor::Change, None, Author::human("user"), text, &commit1, CommentExtras::default())
+                .unwrap();
+            let labels = (severity == Severity::Nit).then(|| vec!["style".to_string()]);
+            store.classify_thread(&change_id, &thread_id, Some(severity), labels).unwrap();
+        }
+
+        let review = store.get(&change_id).unwrap().unwrap();
+        assert_eq!(review.threads.iter().filter(|t| t.blocks_merge()).count(), 1);
+
+        let output = super::format_pending_feedback(&jj, store.list().unwrap());
+        let order: Vec<_> = ["Must fix", "Maybe", "Nitpick"].iter().map(|t| output.find(t).unwrap()).collect();
+        assert!(order.is_sorted(), "Output:\n{output}");
+        assert!(output.contains("whole change [nit] (style)"), "Output:\n{output}");
     }
 
     #[test]
@@ -1014,10 +2540,9 @@ diff --git a/f.rs b/f.rs
         let commit1 = get_commit_id(path);
 
         let store = ReviewStore::new(jj.repo_path());
-        store.init().unwrap();
         store.get_or_create(&change_id, &format!("{}-", change_id), &commit1).unwrap();
         store
-            .add_comment(&change_id, "test.rs", 5, 5, Author::User, "Remove this", &commit1)
+            .add_comment(&change_id, "test.rs", 5, 5, Side::New, Author::human("user"), "Remove this", &commit1, None, CommentExtras::default())
             .unwrap();
 
         // Delete line 5
@@ -1054,12 +2579,11 @@ diff --git a/f.rs b/f.rs
         let commit1 = get_commit_id(path);
 
         let store = ReviewStore::new(jj.repo_path());
-        store.init().unwrap();
         store.get_or_create(&change_id, &format!("{}-", change_id), &commit1).unwrap();
 
         // Comment on the file that won't change in this commit
         store
-            .add_comment(&change_id, "stable.rs", 2, 2, Author::User, "Rename this", &commit1)
+            .add_comment(&change_id, "stable.rs", 2, 2, Side::New, Author::human("user"), "Rename this", &commit1, None, CommentExtras::default())
             .unwrap();
 
         // Only modify changed.rs (stable.rs stays the same)
@@ -0,0 +1,334 @@
+//! Following threads through `jj squash`, `jj split` and `jj absorb`.
+//!
+//! Threads belong to a change id, but those rewrites move code between
+//! changes: a squashed change disappears into its destination, a split
+//! leaves part of the code in a new change, and absorb spreads hunks over
+//! the changes that last touched them. Each pushed change's evolution log
+//! names the changes it took code from; a thread on one of those moves to
+//! whichever of them now adds its anchored lines. Threads on a change that
+//! is gone move to its only successor even if their lines can't be found,
+//! so they aren't stranded on a change nobody can see; with several
+//! successors to choose from they stay put and are reported as ambiguous.
+
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use tracing::warn;
+
+use crate::diff::LineKind;
+use crate::jj::{Change, Jj};
+use crate::line_mapper::find_best_match;
+use crate::review::{ReviewStore, Side, Thread, ThreadAnchor, ThreadMove};
+use crate::vcs::Vcs;
+
+/// A change that took code from an earlier change
+#[derive(Debug, Clone, PartialEq)]
+pub struct Successor {
+    pub change_id: String,
+    pub commit_id: String,
+    /// The operation that folded the code in
+    pub operation: Option<String>,
+}
+
+/// A thread that followed its lines to another change
+#[derive(Debug, Clone)]
+pub struct MovedThread {
+    pub thread_id: String,
+    pub from_change_id: String,
+    pub to_change_id: String,
+    pub operation: Option<String>,
+}
+
+/// A thread on a gone change whose lines none of its successors add
+#[derive(Debug, Clone)]
+pub struct AmbiguousThread {
+    pub thread_id: String,
+    pub change_id: String,
+    /// The changes that took code from `change_id`
+    pub candidates: Vec<String>,
+}
+
+/// What following rewrites did
+#[derive(Debug, Clone, Default)]
+pub struct RewriteReport {
+    pub moved: Vec<MovedThread>,
+    pub ambiguous: Vec<AmbiguousThread>,
+}
+
+/// The changes each of `changes` took code from, mapped to the changes that
+/// took it, in `changes` order.
+pub fn successors(jj: &Jj, changes: &[Change]) -> Result<HashMap<String, Vec<Successor>>> {
+    let mut successors: HashMap<String, Vec<Successor>> = HashMap::new();
+    for change in changes {
+        let entries = jj.evolog(&change.commit_id)?;
+        // Entries are newest first; the operation that brought a
+        // predecessor in is the one on the change's commit just before it
+        let mut operation = None;
+        let mut seen = HashSet::new();
+        for entry in entries {
+            if entry.change_id == change.change_id {
+                operation = entry.operation;
+            } else if seen.insert(entry.change_id.clone()) {
+                successors.entry(entry.change_id).or_default().push(Successor {
+                    change_id: change.change_id.clone(),
+                    commit_id: change.commit_id.clone(),
+                    operation: operation.clone(),
+                });
+            }
+        }
+    }
+    Ok(successors)
+}
+
+/// Move threads on rewritten changes to the changes that now hold their
+/// lines. `changes` are the visible changes to consider, e.g. everything a
+/// session just pushed.
+pub fn follow_rewrites(jj: &Jj, store: &ReviewStore, changes: &[Change]) -> Result<RewriteReport> {
+    let successors = successors(jj, changes)?;
+    let commits: HashMap<&str, &str> = changes.iter().map(|c| (c.change_id.as_str(), c.commit_id.as_str())).collect();
+    let mut anchors = AnchorIndex { jj, diffs: HashMap::new() };
+    let mut report = RewriteReport::default();
+
+    for (predecessor, successors) in &successors {
+        let Some(review) = store.get(predecessor)? else {
+            continue;
+        };
+        let own_commit = commits.get(predecessor.as_str()).copied();
+        for thread in &review.threads {
+            if own_commit.is_some_and(|commit| anchors.contains(thread, commit)) {
+                continue;
+            }
+            let target = match successors.iter().find(|s| anchors.contains(thread, &s.commit_id)) {
+                Some(target) => target,
+                None if own_commit.is_some() => continue,
+                None => match successors.as_slice() {
+                    [only] => only,
+                    _ => {
+                        report.ambiguous.push(AmbiguousThread {
+                            thread_id: thread.id.clone(),
+                            change_id: predecessor.clone(),
+                            candidates: successors.iter().map(|s| s.change_id.clone()).collect(),
+                        });
+                        continue;
+                    }
+                },
+            };
+
+            store.move_thread(
+                predecessor,
+                &thread.id,
+                &target.change_id,
+                &target.commit_id,
+                ThreadMove {
+                    from_change_id: predecessor.clone(),
+                    operation: target.operation.clone(),
+                    timestamp: Utc::now(),
+                },
+            )?;
+            report.moved.push(MovedThread {
+                thread_id: thread.id.clone(),
+                from_change_id: predecessor.clone(),
+                to_change_id: target.change_id.clone(),
+                operation: target.operation.clone(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// What each commit adds over its parent, loaded on first use
+struct AnchorIndex<'a> {
+    jj: &'a Jj,
+    /// Per commit: file -> lines the commit added (1-based, new side)
+    diffs: HashMap<String, HashMap<String, HashSet<usize>>>,
+}
+
+impl AnchorIndex<'_> {
+    /// Whether `commit` adds the thread's anchored lines (or, for threads
+    /// without a snapshot to look for, touches their file)
+    fn contains(&mut self, thread: &Thread, commit: &str) -> bool {
+        if !matches!(thread.anchor, ThreadAnchor::Line | ThreadAnchor::File) {
+            return false;
+        }
+        let jj = self.jj;
+        let files = self.diffs.entry(commit.to_string()).or_insert_with(|| match jj.diff(commit, None, 0) {
+            Ok(diff) => diff
+                .files
+                .into_iter()
+                .map(|f| {
+                    let added = f
+                        .hunks
+                        .iter()
+                        .flat_map(|h| &h.lines)
+                        .filter(|l| l.kind == LineKind::Add)
+                        .filter_map(|l| l.new_line)
+                        .collect();
+                    (f.path, added)
+                })
+                .collect(),
+            Err(e) => {
+                warn!("Failed to diff {}: {}", commit, e);
+                HashMap::new()
+            }
+        });
+        let Some(added) = files.get(&thread.file) else {
+            return false;
+        };
+        match &thread.snapshot {
+            Some(snapshot) if thread.anchor == ThreadAnchor::Line && thread.side == Side::New => jj
+                .show_file(commit, &thread.file)
+                .is_ok_and(|content| find_best_match(snapshot, &content, thread.line_start, Some(added)).is_some()),
+            _ => true,
+        }
+    }
+}
+
+/// Describe moved and ambiguous threads for the terminal
+pub fn format_report(report: &RewriteReport) -> String {
+    let short = |id: &str| id[..8.min(id.len())].to_string();
+    let moved = report.moved.iter().map(|m| {
+        let operation = m.operation.as_deref().map(|o| format!(" ({o})")).unwrap_or_default();
+        format!(
+            "Moved thread {} from {} to {}{operation}\n",
+            short(&m.thread_id),
+            short(&m.from_change_id),
+            short(&m.to_change_id)
+        )
+    });
+    let ambiguous = report.ambiguous.iter().map(|a| {
+        let candidates: Vec<_> = a.candidates.iter().map(|c| short(c)).collect();
+        format!(
+            "Left thread {} on {}: it could belong to any of {}\n",
+            short(&a.thread_id),
+            short(&a.change_id),
+            candidates.join(", ")
+        )
+    });
+    moved.chain(ambiguous).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_mapper::snapshot_lines;
+    use crate::review::{Author, CommentExtras};
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn jj_cmd(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("jj").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("jj {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn log(dir: &std::path::Path, rev: &str, template: &str) -> String {
+        jj_cmd(dir, &["log", "--no-graph", "-r", rev, "-T", template]).trim().to_string()
+    }
+
+    /// A change adding `files`, with a thread on line 2 of `thread_file`
+    fn setup(files: &[(&str, &str)], thread_file: &str) -> (TempDir, Jj, ReviewStore, String, String) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path();
+        jj_cmd(path, &["git", "init"]);
+        std::fs::write(path.join(".gitignore"), ".aipair/\n").unwrap();
+        jj_cmd(path, &["new"]);
+        for (file, content) in files {
+            std::fs::write(path.join(file), content).unwrap();
+        }
+        let jj = Jj::new(path);
+        let change_id = log(path, "@", "change_id");
+        let commit = log(path, "@", "commit_id");
+
+        let store = ReviewStore::new(path);
+        store.get_or_create(&change_id, "@-", &commit).unwrap();
+        let content = files.iter().find(|(f, _)| *f == thread_file).unwrap().1;
+        let (_, thread_id) = store
+            .add_comment(&change_id, thread_file, 2, 2, Side::New, Author::human("user"), "Why?", &commit, snapshot_lines(content, 2, 2), CommentExtras::default())
+            .unwrap();
+        (dir, jj, store, change_id, thread_id)
+    }
+
+    #[test]
+    fn test_squash_moves_threads_into_destination() {
+        let (dir, jj, store, change_id, thread_id) = setup(&[("a.rs", "fn a() {\n    one();\n}\n")], "a.rs");
+        let path = dir.path();
+        jj_cmd(path, &["new", "-m", "destination"]);
+        std::fs::write(path.join("b.rs"), "fn b() {}\n").unwrap();
+        let destination = log(path, "@", "change_id");
+        jj_cmd(path, &["squash", "--from", &change_id, "--into", "@"]);
+
+        let changes = jj.log_revset("mutable()").unwrap();
+        let moved = follow_rewrites(&jj, &store, &changes).unwrap().moved;
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].to_change_id, destination);
+
+        assert!(store.get(&change_id).unwrap().unwrap().threads.is_empty());
+        let review = store.get(&destination).unwrap().unwrap();
+        let thread = review.find_thread(&thread_id).unwrap();
+        assert_eq!(thread.moved_from.as_ref().unwrap().from_change_id, change_id);
+        assert!(thread.moved_from.as_ref().unwrap().operation.is_some());
+
+        // Nothing left to move the second time around
+        assert!(follow_rewrites(&jj, &store, &changes).unwrap().moved.is_empty());
+    }
+
+    #[test]
+    fn test_split_moves_only_threads_on_the_split_off_part() {
+        let files = [("a.rs", "fn a() {\n    one();\n}\n"), ("b.rs", "fn b() {\n    two();\n}\n")];
+        let (dir, jj, store, change_id, moving) = setup(&files, "b.rs");
+        let path = dir.path();
+        let commit = log(path, "@", "commit_id");
+        let content = files[0].1;
+        let (_, staying) = store
+            .add_comment(&change_id, "a.rs", 2, 2, Side::New, Author::human("user"), "And this?", &commit, snapshot_lines(content, 2, 2), CommentExtras::default())
+            .unwrap();
+        // a.rs stays in the change, b.rs goes to a new one
+        jj_cmd(path, &["split", "-m", "first", "-r", &change_id, "a.rs"]);
+        let split_off = log(path, &format!("children({change_id}) & mutable()"), "change_id");
+
+        let changes = jj.log_revset("mutable()").unwrap();
+        let moved = follow_rewrites(&jj, &store, &changes).unwrap().moved;
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].thread_id, moving);
+        assert_eq!(moved[0].to_change_id, split_off);
+
+        let review = store.get(&change_id).unwrap().unwrap();
+        assert!(review.find_thread(&staying).is_ok());
+        assert!(store.get(&split_off).unwrap().unwrap().find_thread(&moving).is_ok());
+    }
+
+    #[test]
+    fn test_threads_without_lines_stay_when_successors_are_ambiguous() {
+        let files = [("a.rs", "fn a() {\n    one();\n}\n"), ("b.rs", "fn b() {\n    two();\n}\n")];
+        let (dir, jj, store, change_id, line_thread) = setup(&files, "a.rs");
+        let path = dir.path();
+        let commit = log(path, "@", "commit_id");
+        let (_, change_thread) = store
+            .start_thread(&change_id, ThreadAnchor::Change, None, Author::human("user"), "Overall?", &commit, CommentExtras::default())
+            .unwrap();
+        // a.rs goes into one change and b.rs into another, and the
+        // original change is gone
+        jj_cmd(path, &["new", "-m", "first"]);
+        let first = log(path, "@", "change_id");
+        jj_cmd(path, &["new", "-m", "second"]);
+        let second = log(path, "@", "change_id");
+        jj_cmd(path, &["squash", "--from", &change_id, "--into", &first, "a.rs"]);
+        jj_cmd(path, &["squash", "--from", &change_id, "--into", &second]);
+
+        let changes = jj.log_revset("mutable()").unwrap();
+        let report = follow_rewrites(&jj, &store, &changes).unwrap();
+        assert_eq!(report.moved.len(), 1);
+        assert_eq!(report.moved[0].thread_id, line_thread);
+        assert_eq!(report.moved[0].to_change_id, first);
+        assert_eq!(report.ambiguous.len(), 1);
+        assert_eq!(report.ambiguous[0].thread_id, change_thread);
+        let mut candidates = report.ambiguous[0].candidates.clone();
+        candidates.sort();
+        let mut expected = vec![first, second];
+        expected.sort();
+        assert_eq!(candidates, expected);
+        assert!(store.get(&change_id).unwrap().unwrap().find_thread(&change_thread).is_ok());
+    }
+}
@@ -4,9 +4,12 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use crate::jj::Jj;
-use crate::review::{Author, ReviewStore};
+use crate::vcs::{self, Vcs};
+use crate::storage::{self, Collection, Storage, Versioned};
+use crate::identity::Identity;
+use crate::review::{ReviewStore, ThreadStatus};
 
 // --- Data types ---
 
@@ -23,6 +26,17 @@ pub struct Session {
     pub pushes: Vec<PushEvent>,
     #[serde(default)]
     pub changes: Vec<String>,
+    #[serde(default)]
+    pub version: u32,
+}
+
+impl Versioned for Session {
+    fn version(&self) -> u32 {
+        self.version
+    }
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
 }
 
 fn default_base_bookmark() -> String {
@@ -63,59 +77,32 @@ pub struct CloneMarker {
 // --- SessionStore ---
 
 pub struct SessionStore {
-    base_path: PathBuf,
+    storage: Arc<dyn Storage>,
 }
 
 impl SessionStore {
     pub fn new(repo_path: &Path) -> Self {
-        Self {
-            base_path: repo_path.to_path_buf(),
-        }
+        Self::with_storage(storage::open(repo_path))
     }
 
-    fn sessions_dir(&self) -> PathBuf {
-        self.base_path.join(".aipair").join("sessions")
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
     }
 
-    fn session_file(&self, name: &str) -> PathBuf {
-        self.sessions_dir().join(format!("{name}.json"))
-    }
-
-    pub fn save(&self, session: &Session) -> Result<()> {
-        let dir = self.sessions_dir();
-        fs::create_dir_all(&dir)?;
-        let path = self.session_file(&session.name);
-        let json = serde_json::to_string_pretty(session)?;
-        fs::write(path, json)?;
-        Ok(())
+    /// Save a session loaded earlier (or a new one). Fails with a
+    /// `ConflictError` if it was modified since it was loaded.
+    pub fn save(&self, session: &mut Session) -> Result<()> {
+        let name = session.name.clone();
+        storage::save_doc(self.storage.as_ref(), Collection::Sessions, &name, session)
     }
 
     pub fn get(&self, name: &str) -> Result<Option<Session>> {
-        let path = self.session_file(name);
-        if !path.exists() {
-            return Ok(None);
-        }
-        let json = fs::read_to_string(path)?;
-        let session: Session = serde_json::from_str(&json)?;
-        Ok(Some(session))
+        storage::get_doc(self.storage.as_ref(), Collection::Sessions, name)
     }
 
     pub fn list(&self) -> Result<Vec<Session>> {
-        let dir = self.sessions_dir();
-        if !dir.exists() {
-            return Ok(Vec::new());
-        }
-        let mut sessions = Vec::new();
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.extension().map_or(false, |e| e == "json") {
-                let json = fs::read_to_string(&path)?;
-                let session: Session = serde_json::from_str(&json)?;
-                sessions.push(session);
-            }
-        }
-        sessions.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        let mut sessions: Vec<Session> = storage::list_docs(self.storage.as_ref(), Collection::Sessions)?;
+        sessions.sort_by_key(|s| s.created_at);
         Ok(sessions)
     }
 }
@@ -123,8 +110,8 @@ impl SessionStore {
 // --- Context detection ---
 
 pub enum SessionContext {
-    MainRepo { jj: Jj, repo_path: PathBuf },
-    SessionClone { jj: Jj, marker: CloneMarker },
+    MainRepo { vcs: Box<dyn Vcs>, repo_path: PathBuf },
+    SessionClone { vcs: Box<dyn Vcs>, marker: CloneMarker },
 }
 
 pub fn detect_context() -> Result<SessionContext> {
@@ -136,8 +123,8 @@ pub fn detect_context() -> Result<SessionContext> {
         if marker_path.exists() {
             let json = fs::read_to_string(&marker_path)?;
             let marker: CloneMarker = serde_json::from_str(&json)?;
-            let jj = Jj::new(dir);
-            return Ok(SessionContext::SessionClone { jj, marker });
+            let vcs = vcs::open(dir);
+            return Ok(SessionContext::SessionClone { vcs, marker });
         }
         match dir.parent() {
             Some(parent) => dir = parent,
@@ -146,15 +133,15 @@ pub fn detect_context() -> Result<SessionContext> {
     }
 
     // No marker found — treat as main repo
-    let jj = Jj::discover()?;
-    let repo_path = jj.repo_path().to_path_buf();
-    Ok(SessionContext::MainRepo { jj, repo_path })
+    let vcs = vcs::discover()?;
+    let repo_path = vcs.repo_path().to_path_buf();
+    Ok(SessionContext::MainRepo { vcs, repo_path })
 }
 
 // --- Operations ---
 
 /// Core session creation logic, usable from both CLI and API.
-pub fn session_new_inner(jj: &Jj, store: &SessionStore, name: &str, base_bookmark: &str) -> Result<Session> {
+pub fn session_new_inner(vcs: &dyn Vcs, store: &SessionStore, name: &str, base_bookmark: &str) -> Result<Session> {
     // Validate name
     if !name
         .chars()
@@ -163,7 +150,7 @@ pub fn session_new_inner(jj: &Jj, store: &SessionStore, name: &str, base_bookmar
         anyhow::bail!("Session name must be alphanumeric with hyphens/underscores only");
     }
 
-    let repo_path = jj.repo_path().to_path_buf();
+    let repo_path = vcs.repo_path().to_path_buf();
 
     // Check for duplicate
     if store.get(name)?.is_some() {
@@ -171,7 +158,7 @@ pub fn session_new_inner(jj: &Jj, store: &SessionStore, name: &str, base_bookmar
     }
 
     // Get current base change_id
-    let base_change_id = jj
+    let base_change_id = vcs
         .get_bookmark(base_bookmark)
         .with_context(|| format!("Failed to find '{base_bookmark}' bookmark"))?
         .with_context(|| format!("No '{base_bookmark}' bookmark found"))?;
@@ -184,28 +171,7 @@ pub fn session_new_inner(jj: &Jj, store: &SessionStore, name: &str, base_bookmar
     }
 
     let bookmark = format!("session/{name}");
-    let clone_jj = Jj::git_clone_branches(&repo_path, &clone_path, &[base_bookmark])?;
-
-    // The clone was created with `-b <base>` so git only fetches that branch.
-    // Add a refspec for the session bookmark so jj's auto-import doesn't
-    // discard it after push (jj removes bookmarks not in the fetch refspec).
-    clone_jj.git_config_add(
-        "remote.origin.fetch",
-        &format!("+refs/heads/{bookmark}:refs/remotes/origin/{bookmark}"),
-    )?;
-
-    // Override immutable_heads to only include trunk. jj's default includes
-    // untracked_remote_bookmarks(), which makes other sessions' commits immutable.
-    clone_jj.set_repo_config(
-        "revset-aliases.\"immutable_heads()\"",
-        "present(trunk()) | tags()",
-    )?;
-
-    // The clone's WC lands on root, not main. Create a new change on top of base@origin.
-    clone_jj.new_change_on(&format!("{base_bookmark}@origin"))?;
-
-    // Create bookmark in clone
-    clone_jj.bookmark_create(&bookmark, "@")?;
+    vcs.clone_for_session(&clone_path, base_bookmark, &bookmark)?;
 
     // Write clone marker
     let marker = CloneMarker {
@@ -219,7 +185,7 @@ pub fn session_new_inner(jj: &Jj, store: &SessionStore, name: &str, base_bookmar
     fs::write(&marker_path, serde_json::to_string_pretty(&marker)?)?;
 
     // Save session metadata
-    let session = Session {
+    let mut session = Session {
         name: name.to_string(),
         clone_path: clone_rel,
         bookmark,
@@ -229,19 +195,20 @@ pub fn session_new_inner(jj: &Jj, store: &SessionStore, name: &str, base_bookmar
         created_at: Utc::now(),
         pushes: Vec::new(),
         changes: Vec::new(),
+        version: 0,
     };
-    store.save(&session)?;
+    store.save(&mut session)?;
 
     Ok(session)
 }
 
 pub fn session_new(name: &str, base_bookmark: &str) -> Result<()> {
-    let jj = Jj::discover()?;
-    let repo_path = jj.repo_path().to_path_buf();
+    let vcs = vcs::discover()?;
+    let repo_path = vcs.repo_path().to_path_buf();
     let store = SessionStore::new(&repo_path);
 
     println!("Cloning into .aipair/sessions/{name}/repo...");
-    let session = session_new_inner(&jj, &store, name, base_bookmark)?;
+    let session = session_new_inner(vcs.as_ref(), &store, name, base_bookmark)?;
 
     let clone_path = repo_path.join(&session.clone_path);
 
@@ -273,8 +240,8 @@ pub fn session_new(name: &str, base_bookmark: &str) -> Result<()> {
 
 pub fn push(message: &str, rev: Option<&str>) -> Result<()> {
     let ctx = detect_context()?;
-    let (jj, marker) = match ctx {
-        SessionContext::SessionClone { jj, marker } => (jj, marker),
+    let (vcs, marker) = match ctx {
+        SessionContext::SessionClone { vcs, marker } => (vcs, marker),
         SessionContext::MainRepo { .. } => {
             anyhow::bail!("'push' must be run from a session clone, not the main repo");
         }
@@ -292,7 +259,7 @@ pub fn push(message: &str, rev: Option<&str>) -> Result<()> {
     // Check if there's actually something new to push by comparing against
     // the last push event's commit_id. Change_id alone isn't enough because
     // rebase preserves change_id but produces a new commit_id.
-    let target = jj.get_change(&bookmark_target)?;
+    let target = vcs.get_change(&bookmark_target)?;
     let main_repo_path = PathBuf::from(&marker.main_repo);
     let store = SessionStore::new(&main_repo_path);
     let session_check = store.get(&marker.session_name)?;
@@ -313,18 +280,18 @@ pub fn push(message: &str, rev: Option<&str>) -> Result<()> {
         .context("Session metadata not found in main repo")?;
     let allow_new = session.pushes.is_empty();
 
-    jj.move_bookmark(&marker.bookmark, &bookmark_target)?;
+    vcs.move_bookmark(&marker.bookmark, &bookmark_target)?;
 
     println!("Pushing {}...", marker.bookmark);
-    let push_output = jj.git_push_bookmark(&marker.bookmark, allow_new)?;
+    let push_output = vcs.git_push_bookmark(&marker.bookmark, allow_new)?;
     if !push_output.is_empty() {
         print!("{push_output}");
     }
 
     // Record push event with full snapshot
-    let change = jj.get_change(&bookmark_target)?;
+    let change = vcs.get_change(&bookmark_target)?;
     let base_ref = format!("{}@origin..{}", session.base_bookmark, &bookmark_target);
-    let snapshot_changes = jj.log_revset(&base_ref)?;
+    let snapshot_changes = vcs.log_revset(&base_ref)?;
     let snapshot: Vec<PushChangeSnapshot> = snapshot_changes
         .iter()
         .map(|c| PushChangeSnapshot {
@@ -341,11 +308,21 @@ pub fn push(message: &str, rev: Option<&str>) -> Result<()> {
         changes: snapshot,
     });
 
+    // Threads on changes that were squashed, split or absorbed follow
+    // their lines (only jj records where code went)
+    if let Some(jj) = vcs.as_jj() {
+        let reviews = ReviewStore::new(&main_repo_path);
+        match crate::rewrites::follow_rewrites(jj, &reviews, &snapshot_changes) {
+            Ok(report) => print!("{}", crate::rewrites::format_report(&report)),
+            Err(e) => eprintln!("Warning: failed to move threads of rewritten changes: {e}"),
+        }
+    }
+
     // Record all session change_ids (from clone's perspective)
     let change_ids_ref = format!("{}@origin..{}", session.base_bookmark, &bookmark_target);
-    session.changes = jj.query_change_ids(&change_ids_ref)?;
+    session.changes = vcs.query_change_ids(&change_ids_ref)?;
 
-    store.save(&session)?;
+    store.save(&mut session)?;
 
     println!("Pushed! Summary: {message}");
     Ok(())
@@ -353,8 +330,8 @@ pub fn push(message: &str, rev: Option<&str>) -> Result<()> {
 
 pub fn pull() -> Result<()> {
     let ctx = detect_context()?;
-    let (jj, marker) = match ctx {
-        SessionContext::SessionClone { jj, marker } => (jj, marker),
+    let (vcs, marker) = match ctx {
+        SessionContext::SessionClone { vcs, marker } => (vcs, marker),
         SessionContext::MainRepo { .. } => {
             anyhow::bail!("'pull' must be run from a session clone, not the main repo");
         }
@@ -369,37 +346,50 @@ pub fn pull() -> Result<()> {
     let base_ref = format!("{}@origin", session.base_bookmark);
 
     println!("Fetching from origin...");
-    let fetch_output = jj.git_fetch_branches(&[&session.base_bookmark])?;
+    let fetch_output = vcs.git_fetch_branches(&[&session.base_bookmark])?;
     if !fetch_output.is_empty() {
         print!("{fetch_output}");
     }
 
     // Rebase onto the base ref (could be main@origin or another session's bookmark)
     println!("Rebasing onto {base_ref}...");
-    let rebase_output = jj.rebase("@", &base_ref)?;
+    let rebase_output = vcs.rebase("@", &base_ref)?;
     if !rebase_output.is_empty() {
         print!("{rebase_output}");
     }
 
-    // Check for conflicts
-    let change = jj.get_change("@")?;
-    if change.conflict {
-        println!();
-        println!("WARNING: Rebase produced conflicts! Resolve them before pushing.");
-    } else {
+    // Check for conflicts (git stops the rebase on them instead)
+    let conflicted = match vcs.as_jj() {
+        Some(jj) => jj
+            .log_revset(&format!("({base_ref}..@) & conflicts()"))?
+            .into_iter()
+            .map(|change| Ok((crate::conflict::conflicted_files(jj, &change.commit_id)?, change)))
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    if conflicted.is_empty() {
         println!("Up to date, no conflicts.");
+    } else {
+        println!();
+        println!("WARNING: Rebase produced conflicts! Resolve them before pushing:");
+        for (files, change) in &conflicted {
+            let summary: Vec<String> =
+                files.iter().map(|f| format!("{} ({} conflict(s))", f.path, f.conflict_count())).collect();
+            println!("  {}: {}", &change.change_id[..8.min(change.change_id.len())], summary.join(", "));
+        }
+        println!("Resolve them with `jj resolve`, or from the session in the review UI.");
     }
 
     // Also update the bookmark in the clone after rebase
-    jj.move_bookmark(&marker.bookmark, "@")?;
+    vcs.move_bookmark(&marker.bookmark, "@")?;
 
     Ok(())
 }
 
-pub fn session_merge(name: &str) -> Result<()> {
+pub fn session_merge(name: &str, force: bool) -> Result<()> {
     let ctx = detect_context()?;
-    let (jj, repo_path) = match ctx {
-        SessionContext::MainRepo { jj, repo_path } => (jj, repo_path),
+    let (vcs, repo_path) = match ctx {
+        SessionContext::MainRepo { vcs, repo_path } => (vcs, repo_path),
         SessionContext::SessionClone { .. } => {
             anyhow::bail!("'session merge' must be run from the main repo, not a session clone");
         }
@@ -416,34 +406,46 @@ pub fn session_merge(name: &str) -> Result<()> {
 
     // Fetch to make sure we have latest from the clone's pushes
     println!("Fetching latest...");
-    let _ = jj.git_fetch();
+    let _ = vcs.git_fetch();
 
     // Move main bookmark to the session bookmark tip
     let bookmark = &session.bookmark;
-    let session_tip = jj
+    let session_tip = vcs
         .get_bookmark(bookmark)?
         .context(format!("Bookmark '{bookmark}' not found — was it pushed?"))?;
 
+    // Every change being merged needs an approval on its current commit
+    if !force {
+        let changes = vcs.log_revset(&format!("{}..{}", session.base_bookmark, bookmark))?;
+        let blocked = ReviewStore::new(&repo_path).unapproved(&changes)?;
+        if !blocked.is_empty() {
+            anyhow::bail!(
+                "Cannot merge session '{name}':\n  {}\nUse --force to override.",
+                blocked.join("\n  ")
+            );
+        }
+    }
+
     println!(
         "Moving {} to {bookmark} (change {})...",
         session.base_bookmark,
         &session_tip[..12]
     );
-    jj.move_bookmark(&session.base_bookmark, &session_tip)?;
+    vcs.move_bookmark(&session.base_bookmark, &session_tip)?;
 
     // Delete session bookmark
-    jj.bookmark_delete(bookmark)?;
+    vcs.bookmark_delete(bookmark)?;
 
     // Update status
     session.status = SessionStatus::Merged;
-    store.save(&session)?;
+    store.save(&mut session)?;
 
     // Re-parent child sessions that were stacked on this session's bookmark
     let all_sessions = store.list()?;
     for mut child in all_sessions {
         if child.status == SessionStatus::Active && child.base_bookmark == session.bookmark {
             child.base_bookmark = session.base_bookmark.clone();
-            store.save(&child)?;
+            store.save(&mut child)?;
             println!(
                 "  Re-parented session '{}' onto {}",
                 child.name, child.base_bookmark
@@ -535,7 +537,7 @@ pub fn status() -> Result<()> {
                 }
             }
         }
-        SessionContext::SessionClone { jj, marker } => {
+        SessionContext::SessionClone { vcs, marker } => {
             println!("Session: {}", marker.session_name);
             println!("Bookmark: {}", marker.bookmark);
             println!("Main repo: {}", marker.main_repo);
@@ -550,7 +552,7 @@ pub fn status() -> Result<()> {
             }
 
             // Show current change
-            let change = jj.get_change("@")?;
+            let change = vcs.get_change("@")?;
             println!();
             println!("Current change: {}", &change.change_id[..12]);
             if !change.description.is_empty() {
@@ -568,14 +570,14 @@ pub fn status() -> Result<()> {
 pub fn feedback() -> Result<()> {
     let ctx = detect_context()?;
     let (_jj, marker) = match ctx {
-        SessionContext::SessionClone { jj, marker } => (jj, marker),
+        SessionContext::SessionClone { vcs, marker } => (vcs, marker),
         SessionContext::MainRepo { .. } => {
             anyhow::bail!("'feedback' must be run from a session clone, not the main repo");
         }
     };
 
     let main_repo_path = PathBuf::from(&marker.main_repo);
-    let main_jj = Jj::new(&main_repo_path);
+    let main_vcs = vcs::open(&main_repo_path);
     let store = ReviewStore::new(&main_repo_path);
     let session_store = SessionStore::new(&main_repo_path);
 
@@ -589,37 +591,59 @@ pub fn feedback() -> Result<()> {
         return Ok(());
     }
 
+    let all_reviews: Vec<_> = store.list()?.into_iter().filter(|r| change_ids.contains(&r.change_id)).collect();
+    let verdicts = crate::review::format_verdicts(main_vcs.as_ref(), &all_reviews);
     let reviews = store.list_with_open_threads(Some(&change_ids))?;
-    if reviews.is_empty() {
+    if verdicts.is_empty() && reviews.is_empty() {
         println!("No pending feedback.");
         return Ok(());
     }
 
-    let output = crate::review::format_pending_feedback(&main_jj, reviews);
-    print!("{output}");
+    let output = crate::review::format_pending_feedback(main_vcs.as_ref(), reviews);
+    print!("{verdicts}{output}");
     Ok(())
 }
 
-pub fn respond(change_id: &str, thread_id: &str, message: &str, resolve: bool) -> Result<()> {
+pub fn respond(
+    change_id: &str,
+    thread_id: &str,
+    message: &str,
+    status: Option<ThreadStatus>,
+    addressed_in: Option<&str>,
+) -> Result<()> {
+    if status == Some(ThreadStatus::Resolved) {
+        anyhow::bail!("Only the reviewer can resolve a thread; use --resolve to mark it addressed");
+    }
+    if addressed_in.is_some() && status.is_some_and(|s| s != ThreadStatus::Addressed) {
+        anyhow::bail!("--addressed-in marks the thread addressed; it can't go with --status {}", status.unwrap());
+    }
+
     let ctx = detect_context()?;
-    let marker = match ctx {
-        SessionContext::SessionClone { marker, .. } => marker,
+    let (vcs, marker) = match ctx {
+        SessionContext::SessionClone { vcs, marker } => (vcs, marker),
         SessionContext::MainRepo { .. } => {
             anyhow::bail!("'respond' must be run from a session clone, not the main repo");
         }
     };
 
+    // Review revision numbers are resolved by the store; anything else is a
+    // revision in this clone
+    let addressed_in = match addressed_in {
+        Some(rev) if rev.parse::<u32>().is_ok() => Some(rev.to_string()),
+        Some(rev) => Some(vcs.get_change(rev)?.commit_id),
+        None => None,
+    };
+
     let main_repo_path = PathBuf::from(&marker.main_repo);
     let store = ReviewStore::new(&main_repo_path);
 
-    store.reply_to_thread(change_id, thread_id, Author::Claude, message)?;
+    let author = Identity::load()?.agent(Some(&marker.session_name));
+    let status = if addressed_in.is_some() { Some(ThreadStatus::Addressed) } else { status };
+    store.respond(change_id, thread_id, author, message, status, addressed_in.as_deref())?;
 
-    if resolve {
-        store.resolve_thread(change_id, thread_id)?;
-    }
-
-    let status = if resolve { " and resolved" } else { "" };
-    println!("Responded to thread {}{status}.", &thread_id[..8.min(thread_id.len())]);
+    let status = status.map(|s| format!(" and marked it {s}")).unwrap_or_default();
+    let commit = addressed_in.map(|c| format!(" in {}", &c[..12.min(c.len())])).unwrap_or_default();
+    println!("Responded to thread {}{status}{commit}.", &thread_id[..8.min(thread_id.len())]);
     Ok(())
 }
 
@@ -639,6 +663,7 @@ mod tests {
             created_at: Utc::now(),
             pushes: Vec::new(),
             changes: Vec::new(),
+            version: 0,
         }
     }
 
@@ -673,8 +698,8 @@ mod tests {
         let dir = TempDir::new().unwrap();
         let store = SessionStore::new(dir.path());
 
-        let session = make_session("test-session", "main", SessionStatus::Active);
-        store.save(&session).unwrap();
+        let mut session = make_session("test-session", "main", SessionStatus::Active);
+        store.save(&mut session).unwrap();
 
         let loaded = store.get("test-session").unwrap().unwrap();
         assert_eq!(loaded.name, "test-session");
@@ -682,6 +707,29 @@ mod tests {
         assert_eq!(loaded.status, SessionStatus::Active);
     }
 
+    #[test]
+    fn test_session_store_stale_save_conflicts() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new(dir.path());
+        store
+            .save(&mut make_session("racy", "main", SessionStatus::Active))
+            .unwrap();
+
+        let mut from_push = store.get("racy").unwrap().unwrap();
+        let mut from_merge = store.get("racy").unwrap().unwrap();
+
+        from_merge.status = SessionStatus::Merged;
+        store.save(&mut from_merge).unwrap();
+
+        from_push.changes.push("abc".to_string());
+        let err = store.save(&mut from_push).unwrap_err();
+        assert!(crate::storage::is_conflict(&err));
+
+        let loaded = store.get("racy").unwrap().unwrap();
+        assert_eq!(loaded.status, SessionStatus::Merged);
+        assert!(loaded.changes.is_empty());
+    }
+
     #[test]
     fn test_session_store_get_nonexistent() {
         let dir = TempDir::new().unwrap();
@@ -698,11 +746,11 @@ mod tests {
         // Create sessions with different timestamps (save order shouldn't matter)
         let mut s2 = make_session("beta", "main", SessionStatus::Active);
         s2.created_at = Utc::now();
-        store.save(&s2).unwrap();
+        store.save(&mut s2).unwrap();
 
         let mut s1 = make_session("alpha", "main", SessionStatus::Active);
         s1.created_at = s2.created_at - chrono::Duration::seconds(10);
-        store.save(&s1).unwrap();
+        store.save(&mut s1).unwrap();
 
         let sessions = store.list().unwrap();
         assert_eq!(sessions.len(), 2);
@@ -715,11 +763,11 @@ mod tests {
         let dir = TempDir::new().unwrap();
         let store = SessionStore::new(dir.path());
 
-        let parent = make_session("parent", "main", SessionStatus::Active);
-        store.save(&parent).unwrap();
+        let mut parent = make_session("parent", "main", SessionStatus::Active);
+        store.save(&mut parent).unwrap();
 
-        let child = make_session("child", "session/parent", SessionStatus::Active);
-        store.save(&child).unwrap();
+        let mut child = make_session("child", "session/parent", SessionStatus::Active);
+        store.save(&mut child).unwrap();
 
         let loaded = store.get("child").unwrap().unwrap();
         assert_eq!(loaded.base_bookmark, "session/parent");
@@ -731,25 +779,25 @@ mod tests {
         let store = SessionStore::new(dir.path());
 
         // Set up: parent based on main, child based on parent, grandchild based on child
-        let parent = make_session("parent", "main", SessionStatus::Active);
-        store.save(&parent).unwrap();
+        let mut parent = make_session("parent", "main", SessionStatus::Active);
+        store.save(&mut parent).unwrap();
 
-        let child = make_session("child", "session/parent", SessionStatus::Active);
-        store.save(&child).unwrap();
+        let mut child = make_session("child", "session/parent", SessionStatus::Active);
+        store.save(&mut child).unwrap();
 
-        let grandchild = make_session("grandchild", "session/child", SessionStatus::Active);
-        store.save(&grandchild).unwrap();
+        let mut grandchild = make_session("grandchild", "session/child", SessionStatus::Active);
+        store.save(&mut grandchild).unwrap();
 
         // Simulate merging parent: mark as merged and re-parent children
         let mut parent = store.get("parent").unwrap().unwrap();
         parent.status = SessionStatus::Merged;
-        store.save(&parent).unwrap();
+        store.save(&mut parent).unwrap();
 
         let all_sessions = store.list().unwrap();
         for mut s in all_sessions {
             if s.status == SessionStatus::Active && s.base_bookmark == parent.bookmark {
                 s.base_bookmark = parent.base_bookmark.clone();
-                store.save(&s).unwrap();
+                store.save(&mut s).unwrap();
             }
         }
 
@@ -767,24 +815,24 @@ mod tests {
         let dir = TempDir::new().unwrap();
         let store = SessionStore::new(dir.path());
 
-        let parent = make_session("parent", "main", SessionStatus::Active);
-        store.save(&parent).unwrap();
+        let mut parent = make_session("parent", "main", SessionStatus::Active);
+        store.save(&mut parent).unwrap();
 
         // A merged session that happened to be based on parent
         let mut old_child = make_session("old-child", "session/parent", SessionStatus::Merged);
         old_child.status = SessionStatus::Merged;
-        store.save(&old_child).unwrap();
+        store.save(&mut old_child).unwrap();
 
         // Simulate merging parent
         let mut parent = store.get("parent").unwrap().unwrap();
         parent.status = SessionStatus::Merged;
-        store.save(&parent).unwrap();
+        store.save(&mut parent).unwrap();
 
         let all_sessions = store.list().unwrap();
         for mut s in all_sessions {
             if s.status == SessionStatus::Active && s.base_bookmark == parent.bookmark {
                 s.base_bookmark = parent.base_bookmark.clone();
-                store.save(&s).unwrap();
+                store.save(&mut s).unwrap();
             }
         }
 
@@ -0,0 +1,582 @@
+//! Pluggable persistence for everything under `.aipair/`.
+//!
+//! Stores (`ReviewStore`, `SessionStore`, `TodoStore`, `TimelineStore`) keep
+//! their domain logic and talk to a [`Storage`] backend in terms of JSON
+//! documents grouped into [`Collection`]s plus append-only [`Log`]s. Two
+//! backends exist: the original one-file-per-document JSON layout, and a
+//! single SQLite database at `.aipair/aipair.db`. The database is used
+//! whenever it exists; `aipair migrate-store` moves data between the two.
+//!
+//! Documents carry a `version` counter ([`Versioned`]). [`update_doc`] is an
+//! atomic read-modify-write; [`save_doc`] writes a previously loaded document
+//! and fails with [`ConflictError`] if someone else saved it in between.
+
+mod json;
+mod sqlite;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub use json::JsonStorage;
+pub use sqlite::SqliteStorage;
+
+const DB_FILE: &str = ".aipair/aipair.db";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    Json,
+    Sqlite,
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Json => write!(f, "json"),
+            Backend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+/// A group of keyed documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    /// One review per change id.
+    Reviews,
+    /// Reviews of changes that are gone, moved aside by `aipair review gc`.
+    ArchivedReviews,
+    /// One session per session name.
+    Sessions,
+    /// Singleton documents (the todo tree, timeline import state), one per
+    /// key in [`META_KEYS`].
+    Meta,
+}
+
+/// The keys of [`Collection::Meta`]. In the JSON layout those documents sit
+/// directly in `.aipair/` next to whatever else is kept there, so no other
+/// file there is aipair's.
+pub const META_KEYS: [&str; 2] = [crate::todo::TODOS_KEY, crate::timeline::IMPORT_STATE_KEY];
+
+impl Collection {
+    pub const ALL: [Collection; 4] =
+        [Collection::Reviews, Collection::ArchivedReviews, Collection::Sessions, Collection::Meta];
+
+    /// Whether `key` is one of aipair's documents, which it may delete
+    fn owns(self, key: &str) -> bool {
+        self != Collection::Meta || META_KEYS.contains(&key)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Collection::Reviews => "reviews",
+            Collection::ArchivedReviews => "archived_reviews",
+            Collection::Sessions => "sessions",
+            Collection::Meta => "meta",
+        }
+    }
+}
+
+/// An append-only sequence of entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Log {
+    Timeline,
+}
+
+impl Log {
+    pub const ALL: [Log; 1] = [Log::Timeline];
+
+    fn name(self) -> &'static str {
+        match self {
+            Log::Timeline => "timeline",
+        }
+    }
+}
+
+/// A storage backend. Documents and log entries are opaque JSON strings;
+/// typed access goes through [`get_doc`], [`update_doc`] and [`save_doc`].
+pub trait Storage: Send + Sync {
+    fn backend(&self) -> Backend;
+
+    fn get(&self, collection: Collection, key: &str) -> Result<Option<String>>;
+
+    /// All keys in a collection, in no particular order.
+    fn keys(&self, collection: Collection) -> Result<Vec<String>>;
+
+    /// All documents in a collection as (key, body), in no particular order.
+    fn list(&self, collection: Collection) -> Result<Vec<(String, String)>>;
+
+    /// Atomically read-modify-write one document. `f` receives the current
+    /// body (if any) and returns the body to store, or `None` to leave it
+    /// untouched. No other writer can modify the document while `f` runs.
+    fn update(
+        &self,
+        collection: Collection,
+        key: &str,
+        f: &mut dyn FnMut(Option<&str>) -> Result<Option<String>>,
+    ) -> Result<()>;
+
+    /// Delete a document. Returns false if it didn't exist.
+    fn delete(&self, collection: Collection, key: &str) -> Result<bool>;
+
+    /// Delete a document if `f`, given its current body, agrees. Returns
+    /// whether it was deleted. No other writer can modify the document while
+    /// `f` runs.
+    fn delete_if(&self, collection: Collection, key: &str, f: &mut dyn FnMut(&str) -> Result<bool>) -> Result<bool>;
+
+    fn append(&self, log: Log, entry: &str) -> Result<()>;
+
+    /// All entries of a log, oldest first.
+    fn read_log(&self, log: Log) -> Result<Vec<String>>;
+
+    /// Replace the whole log (used by migration).
+    fn replace_log(&self, log: Log, entries: &[String]) -> Result<()>;
+}
+
+/// Open the storage for a repository: SQLite if `.aipair/aipair.db` exists,
+/// the JSON files otherwise.
+pub fn open(repo_path: &Path) -> Arc<dyn Storage> {
+    let db_path = repo_path.join(DB_FILE);
+    if db_path.exists() {
+        Arc::new(SqliteStorage::new(db_path))
+    } else {
+        Arc::new(JsonStorage::new(repo_path))
+    }
+}
+
+/// A document stored with an optimistic-concurrency version counter.
+pub trait Versioned {
+    fn version(&self) -> u32;
+    fn set_version(&mut self, version: u32);
+}
+
+/// Returned (wrapped in `anyhow::Error`) when a save would overwrite changes
+/// made by another writer since the document was loaded.
+#[derive(Debug)]
+pub struct ConflictError {
+    pub document: String,
+    pub expected: u32,
+    pub found: u32,
+}
+
+impl std::fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Conflict: {} was modified concurrently (loaded version {}, now {}); reload and retry",
+            self.document, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+/// Returns true if `err` is (or wraps) a [`ConflictError`].
+pub fn is_conflict(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ConflictError>().is_some()
+}
+
+fn parse<T: DeserializeOwned>(collection: Collection, key: &str, body: &str) -> Result<T> {
+    serde_json::from_str(body)
+        .with_context(|| format!("Failed to parse {}/{}", collection.name(), key))
+}
+
+pub fn get_doc<T: DeserializeOwned>(
+    storage: &dyn Storage,
+    collection: Collection,
+    key: &str,
+) -> Result<Option<T>> {
+    storage
+        .get(collection, key)?
+        .map(|body| parse(collection, key, &body))
+        .transpose()
+}
+
+/// Just the version of a stored document
+#[derive(serde::Deserialize)]
+struct Stored {
+    #[serde(default)]
+    version: u32,
+}
+
+/// Delete a document if it's still at version `expected`. Returns false,
+/// leaving it alone, if it's gone or was saved since.
+pub fn delete_doc(storage: &dyn Storage, collection: Collection, key: &str, expected: u32) -> Result<bool> {
+    storage.delete_if(collection, key, &mut |body| Ok(parse::<Stored>(collection, key, body)?.version == expected))
+}
+
+/// All documents in a collection. One that doesn't parse is an error naming
+/// its key rather than missing from the result.
+pub fn list_docs<T: DeserializeOwned>(storage: &dyn Storage, collection: Collection) -> Result<Vec<T>> {
+    storage
+        .list(collection)?
+        .iter()
+        .map(|(key, body)| parse(collection, key, body))
+        .collect()
+}
+
+/// Atomically read-modify-write a document. `f` receives the stored document
+/// (or `None`); whatever is left in the slot afterwards is written back with
+/// its version bumped. Returns the written document and `f`'s result.
+pub fn update_doc<T, R>(
+    storage: &dyn Storage,
+    collection: Collection,
+    key: &str,
+    f: impl FnOnce(&mut Option<T>) -> Result<R>,
+) -> Result<(Option<T>, R)>
+where
+    T: Serialize + DeserializeOwned + Versioned,
+{
+    let mut f = Some(f);
+    let mut out = None;
+    storage.update(collection, key, &mut |current| {
+        let mut doc: Option<T> = current.map(|body| parse(collection, key, body)).transpose()?;
+        let stored_version = doc.as_ref().map(|d| d.version()).unwrap_or(0);
+        let f = f.take().expect("update callback invoked twice");
+        let result = f(&mut doc)?;
+        let body = match doc.as_mut() {
+            Some(d) => {
+                d.set_version(stored_version + 1);
+                Some(serde_json::to_string_pretty(d)?)
+            }
+            None => None,
+        };
+        out = Some((doc, result));
+        Ok(body)
+    })?;
+    Ok(out.expect("update callback not invoked"))
+}
+
+/// Version-checked save of a document loaded earlier (or a new one, with
+/// version 0). Fails with [`ConflictError`] if the stored version no longer
+/// matches; on success the document's version is bumped.
+pub fn save_doc<T: Serialize + Versioned>(
+    storage: &dyn Storage,
+    collection: Collection,
+    key: &str,
+    value: &mut T,
+) -> Result<()> {
+    let expected = value.version();
+    value.set_version(expected + 1);
+    let result = serde_json::to_string_pretty(value)
+        .map_err(anyhow::Error::from)
+        .and_then(|body| {
+            storage.update(collection, key, &mut |current| {
+                let found = match current {
+                    Some(c) => parse::<Stored>(collection, key, c)?.version,
+                    None => 0,
+                };
+                if found != expected {
+                    return Err(ConflictError {
+                        document: format!("{}/{}", collection.name(), key),
+                        expected,
+                        found,
+                    }
+                    .into());
+                }
+                Ok(Some(body.clone()))
+            })
+        });
+    if result.is_err() {
+        value.set_version(expected);
+    }
+    result
+}
+
+#[derive(Debug, Default)]
+pub struct MigrateStats {
+    pub documents: usize,
+    pub log_entries: usize,
+}
+
+/// Copy everything from the active backend into `to` and make it the active
+/// one. The JSON files are left behind as a backup when moving to SQLite;
+/// the database is renamed to `aipair.db.bak` when moving back to JSON.
+pub fn migrate(repo_path: &Path, to: Backend) -> Result<MigrateStats> {
+    let from = open(repo_path);
+    if from.backend() == to {
+        anyhow::bail!("Already using the {} backend", to);
+    }
+
+    let db_path = repo_path.join(DB_FILE);
+    match to {
+        Backend::Sqlite => {
+            let tmp_path = with_suffix(&db_path, ".tmp");
+            let _ = std::fs::remove_file(&tmp_path);
+            let stats = copy_all(from.as_ref(), &SqliteStorage::new(tmp_path.clone()))?;
+            std::fs::rename(&tmp_path, &db_path)
+                .with_context(|| format!("Failed to activate {}", db_path.display()))?;
+            Ok(stats)
+        }
+        Backend::Json => {
+            let stats = copy_all(from.as_ref(), &JsonStorage::new(repo_path))?;
+            drop(from);
+            let backup = with_suffix(&db_path, ".bak");
+            std::fs::rename(&db_path, &backup)
+                .with_context(|| format!("Failed to deactivate {}", db_path.display()))?;
+            Ok(stats)
+        }
+    }
+}
+
+/// Make `to` an exact copy of `from`, including removing aipair's documents
+/// that only exist in `to` (e.g. stale JSON files left from an earlier
+/// migration).
+fn copy_all(from: &dyn Storage, to: &dyn Storage) -> Result<MigrateStats> {
+    let mut stats = MigrateStats::default();
+    for collection in Collection::ALL {
+        let keys = from.keys(collection)?;
+        for key in &keys {
+            let Some(body) = from.get(collection, key)? else {
+                continue;
+            };
+            to.update(collection, key, &mut |_| Ok(Some(body.clone())))?;
+            stats.documents += 1;
+        }
+        for stale in to.keys(collection)? {
+            if !keys.contains(&stale) && collection.owns(&stale) {
+                to.delete(collection, &stale)?;
+            }
+        }
+    }
+    for log in Log::ALL {
+        let entries = from.read_log(log)?;
+        stats.log_entries += entries.len();
+        to.replace_log(log, &entries)?;
+    }
+    Ok(stats)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use tempfile::TempDir;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Doc {
+        #[serde(default)]
+        version: u32,
+        value: String,
+    }
+
+    impl Versioned for Doc {
+        fn version(&self) -> u32 {
+            self.version
+        }
+        fn set_version(&mut self, version: u32) {
+            self.version = version;
+        }
+    }
+
+    fn backends(dir: &TempDir) -> Vec<Arc<dyn Storage>> {
+        vec![
+            Arc::new(JsonStorage::new(dir.path())),
+            Arc::new(SqliteStorage::new(dir.path().join("test.db"))),
+        ]
+    }
+
+    fn doc(value: &str) -> Doc {
+        Doc { version: 0, value: value.to_string() }
+    }
+
+    #[test]
+    fn test_save_bumps_version_and_roundtrips() {
+        let dir = TempDir::new().unwrap();
+        for storage in backends(&dir) {
+            let mut d = doc("a");
+            save_doc(storage.as_ref(), Collection::Reviews, "k", &mut d).unwrap();
+            assert_eq!(d.version, 1);
+
+            let loaded: Doc = get_doc(storage.as_ref(), Collection::Reviews, "k").unwrap().unwrap();
+            assert_eq!(loaded.version, 1);
+            assert_eq!(loaded.value, "a");
+            assert_eq!(storage.keys(Collection::Reviews).unwrap(), vec!["k".to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_stale_save_is_a_conflict() {
+        let dir = TempDir::new().unwrap();
+        for storage in backends(&dir) {
+            let s = storage.as_ref();
+            save_doc(s, Collection::Sessions, "k", &mut doc("a")).unwrap();
+
+            let mut first: Doc = get_doc(s, Collection::Sessions, "k").unwrap().unwrap();
+            let mut second: Doc = get_doc(s, Collection::Sessions, "k").unwrap().unwrap();
+
+            first.value = "first".to_string();
+            save_doc(s, Collection::Sessions, "k", &mut first).unwrap();
+
+            second.value = "second".to_string();
+            let err = save_doc(s, Collection::Sessions, "k", &mut second).unwrap_err();
+            assert!(is_conflict(&err), "expected conflict, got: {err}");
+            assert_eq!(second.version, 1);
+
+            let stored: Doc = get_doc(s, Collection::Sessions, "k").unwrap().unwrap();
+            assert_eq!(stored.value, "first");
+        }
+    }
+
+    #[test]
+    fn test_delete_doc_only_deletes_the_expected_version() {
+        let dir = TempDir::new().unwrap();
+        for storage in backends(&dir) {
+            let s = storage.as_ref();
+            save_doc(s, Collection::Reviews, "k", &mut doc("a")).unwrap();
+            let mut loaded: Doc = get_doc(s, Collection::Reviews, "k").unwrap().unwrap();
+            save_doc(s, Collection::Reviews, "k", &mut loaded).unwrap();
+
+            assert!(!delete_doc(s, Collection::Reviews, "k", 1).unwrap());
+            assert!(s.get(Collection::Reviews, "k").unwrap().is_some());
+            assert!(delete_doc(s, Collection::Reviews, "k", 2).unwrap());
+            assert!(s.get(Collection::Reviews, "k").unwrap().is_none());
+            assert!(!delete_doc(s, Collection::Reviews, "k", 2).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_update_creates_and_modifies() {
+        let dir = TempDir::new().unwrap();
+        for storage in backends(&dir) {
+            let s = storage.as_ref();
+            let (created, ()) = update_doc(s, Collection::Meta, "todos", |slot: &mut Option<Doc>| {
+                assert!(slot.is_none());
+                *slot = Some(doc("new"));
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(created.unwrap().version, 1);
+
+            let (updated, len) = update_doc(s, Collection::Meta, "todos", |slot: &mut Option<Doc>| {
+                let d = slot.as_mut().unwrap();
+                d.value.push('!');
+                Ok(d.value.len())
+            })
+            .unwrap();
+            assert_eq!(len, 4);
+            assert_eq!(updated.unwrap().version, 2);
+        }
+    }
+
+    #[test]
+    fn test_failed_update_writes_nothing() {
+        let dir = TempDir::new().unwrap();
+        for storage in backends(&dir) {
+            let s = storage.as_ref();
+            let result = update_doc::<_, ()>(s, Collection::Reviews, "k", |slot: &mut Option<Doc>| {
+                *slot = Some(doc("partial"));
+                anyhow::bail!("nope")
+            });
+            assert!(result.is_err());
+            assert!(s.get(Collection::Reviews, "k").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let dir = TempDir::new().unwrap();
+        for storage in backends(&dir) {
+            save_doc(storage.as_ref(), Collection::Reviews, "k", &mut doc("")).unwrap();
+
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let storage = storage.clone();
+                    std::thread::spawn(move || {
+                        update_doc(storage.as_ref(), Collection::Reviews, "k", |slot: &mut Option<Doc>| {
+                            slot.as_mut().unwrap().value.push_str(&i.to_string());
+                            Ok(())
+                        })
+                        .unwrap();
+                    })
+                })
+                .collect();
+            for h in handles {
+                h.join().unwrap();
+            }
+
+            let d: Doc = get_doc(storage.as_ref(), Collection::Reviews, "k").unwrap().unwrap();
+            assert_eq!(d.value.len(), 8);
+            assert_eq!(d.version, 9);
+        }
+    }
+
+    #[test]
+    fn test_logs_append_in_order() {
+        let dir = TempDir::new().unwrap();
+        for storage in backends(&dir) {
+            storage.append(Log::Timeline, r#"{"n":1}"#).unwrap();
+            storage.append(Log::Timeline, r#"{"n":2}"#).unwrap();
+            assert_eq!(
+                storage.read_log(Log::Timeline).unwrap(),
+                vec![r#"{"n":1}"#.to_string(), r#"{"n":2}"#.to_string()]
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_picks_backend_by_database_presence() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(open(dir.path()).backend(), Backend::Json);
+        std::fs::create_dir_all(dir.path().join(".aipair")).unwrap();
+        std::fs::write(dir.path().join(DB_FILE), b"").unwrap();
+        assert_eq!(open(dir.path()).backend(), Backend::Sqlite);
+    }
+
+    #[test]
+    fn test_migrate_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let json = open(dir.path());
+        save_doc(json.as_ref(), Collection::Reviews, "abc", &mut doc("review")).unwrap();
+        save_doc(json.as_ref(), Collection::Sessions, "s1", &mut doc("session")).unwrap();
+        save_doc(json.as_ref(), Collection::Meta, "todos", &mut doc("todos")).unwrap();
+        json.append(Log::Timeline, r#"{"n":1}"#).unwrap();
+        drop(json);
+
+        let stats = migrate(dir.path(), Backend::Sqlite).unwrap();
+        assert_eq!(stats.documents, 3);
+        assert_eq!(stats.log_entries, 1);
+
+        let sqlite = open(dir.path());
+        assert_eq!(sqlite.backend(), Backend::Sqlite);
+        let review: Doc = get_doc(sqlite.as_ref(), Collection::Reviews, "abc").unwrap().unwrap();
+        assert_eq!(review.value, "review");
+        assert_eq!(review.version, 1);
+        assert_eq!(sqlite.read_log(Log::Timeline).unwrap().len(), 1);
+
+        // Changes made while on SQLite win over the stale JSON backup.
+        sqlite.delete(Collection::Sessions, "s1").unwrap();
+        drop(sqlite);
+        assert!(migrate(dir.path(), Backend::Sqlite).is_err());
+
+        migrate(dir.path(), Backend::Json).unwrap();
+        let json = open(dir.path());
+        assert_eq!(json.backend(), Backend::Json);
+        assert!(json.get(Collection::Sessions, "s1").unwrap().is_none());
+        let todos: Doc = get_doc(json.as_ref(), Collection::Meta, "todos").unwrap().unwrap();
+        assert_eq!(todos.value, "todos");
+        assert!(dir.path().join(".aipair/aipair.db.bak").exists());
+    }
+
+    #[test]
+    fn test_migrate_leaves_other_files_in_aipair_dir() {
+        let dir = TempDir::new().unwrap();
+        let json = open(dir.path());
+        save_doc(json.as_ref(), Collection::Meta, "todos", &mut doc("todos")).unwrap();
+        drop(json);
+        let other = dir.path().join(".aipair/settings.json");
+        std::fs::write(&other, "{}").unwrap();
+        assert_eq!(JsonStorage::new(dir.path()).keys(Collection::Meta).unwrap(), vec!["todos"]);
+
+        migrate(dir.path(), Backend::Sqlite).unwrap();
+        migrate(dir.path(), Backend::Json).unwrap();
+        assert_eq!(std::fs::read_to_string(&other).unwrap(), "{}");
+    }
+}
@@ -0,0 +1,278 @@
+//! The original on-disk layout: one pretty-printed JSON file per document
+//! (`.aipair/reviews/<change>.json`, `.aipair/sessions/<name>.json`,
+//! `.aipair/archived-reviews/<change>.json`, `.aipair/<key>.json` for
+//! singletons) and JSON Lines for logs.
+//!
+//! The `serve` process and `aipair respond` in a session clone both
+//! read-modify-write the same files, so every write goes through an advisory
+//! lock on a sidecar `<file>.lock` and lands via temp-file-plus-rename.
+
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use super::{Backend, Collection, Log, META_KEYS, Storage};
+
+pub struct JsonStorage {
+    aipair_dir: PathBuf,
+}
+
+impl JsonStorage {
+    pub fn new(repo_path: impl AsRef<Path>) -> Self {
+        Self {
+            aipair_dir: repo_path.as_ref().join(".aipair"),
+        }
+    }
+
+    fn collection_dir(&self, collection: Collection) -> PathBuf {
+        match collection {
+            Collection::Reviews => self.aipair_dir.join("reviews"),
+            Collection::ArchivedReviews => self.aipair_dir.join("archived-reviews"),
+            Collection::Sessions => self.aipair_dir.join("sessions"),
+            Collection::Meta => self.aipair_dir.clone(),
+        }
+    }
+
+    fn doc_path(&self, collection: Collection, key: &str) -> PathBuf {
+        self.collection_dir(collection).join(format!("{key}.json"))
+    }
+
+    fn log_path(&self, log: Log) -> PathBuf {
+        match log {
+            Log::Timeline => self.aipair_dir.join("timeline.jsonl"),
+        }
+    }
+}
+
+impl Storage for JsonStorage {
+    fn backend(&self) -> Backend {
+        Backend::Json
+    }
+
+    fn get(&self, collection: Collection, key: &str) -> Result<Option<String>> {
+        read_optional(&self.doc_path(collection, key))
+    }
+
+    fn keys(&self, collection: Collection) -> Result<Vec<String>> {
+        if collection == Collection::Meta {
+            // `.aipair/` itself; only the known singletons in it are ours
+            return Ok(META_KEYS
+                .iter()
+                .filter(|key| self.doc_path(collection, key).is_file())
+                .map(|key| key.to_string())
+                .collect());
+        }
+        let dir = self.collection_dir(collection);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut keys = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_file()
+                && path.extension().is_some_and(|e| e == "json")
+                && let Some(stem) = path.file_stem()
+            {
+                keys.push(stem.to_string_lossy().to_string());
+            }
+        }
+        Ok(keys)
+    }
+
+    fn list(&self, collection: Collection) -> Result<Vec<(String, String)>> {
+        let mut bodies = Vec::new();
+        for key in self.keys(collection)? {
+            if let Some(body) = self.get(collection, &key)? {
+                bodies.push((key, body));
+            }
+        }
+        Ok(bodies)
+    }
+
+    fn update(
+        &self,
+        collection: Collection,
+        key: &str,
+        f: &mut dyn FnMut(Option<&str>) -> Result<Option<String>>,
+    ) -> Result<()> {
+        let path = self.doc_path(collection, key);
+        let _lock = FileLock::acquire(&path)?;
+        let current = read_optional(&path)?;
+        if let Some(body) = f(current.as_deref())? {
+            atomic_write(&path, body.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn delete(&self, collection: Collection, key: &str) -> Result<bool> {
+        let path = self.doc_path(collection, key);
+        let _lock = FileLock::acquire(&path)?;
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("Failed to delete {}", path.display())),
+        }
+    }
+
+    fn delete_if(&self, collection: Collection, key: &str, f: &mut dyn FnMut(&str) -> Result<bool>) -> Result<bool> {
+        let path = self.doc_path(collection, key);
+        let _lock = FileLock::acquire(&path)?;
+        match read_optional(&path)? {
+            Some(body) if f(&body)? => {
+                std::fs::remove_file(&path).with_context(|| format!("Failed to delete {}", path.display()))?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn append(&self, log: Log, entry: &str) -> Result<()> {
+        let path = self.log_path(log);
+        let _lock = FileLock::acquire(&path)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        writeln!(file, "{}", entry)?;
+        Ok(())
+    }
+
+    fn read_log(&self, log: Log) -> Result<Vec<String>> {
+        let content = read_optional(&self.log_path(log))?.unwrap_or_default();
+        Ok(content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    fn replace_log(&self, log: Log, entries: &[String]) -> Result<()> {
+        let path = self.log_path(log);
+        let _lock = FileLock::acquire(&path)?;
+        let mut content = String::new();
+        for entry in entries {
+            content.push_str(entry);
+            content.push('\n');
+        }
+        atomic_write(&path, content.as_bytes())
+    }
+}
+
+/// Read a file, returning `None` if it doesn't exist.
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(c) => Ok(Some(c)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Exclusive advisory lock on `<path>.lock`, released on drop.
+///
+/// The lock lives on a sidecar file because the data file itself is replaced
+/// by rename on every write, which would orphan a lock held on it.
+struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    fn acquire(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let lock_path = lock_path(path);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file: {}", lock_path.display()))?;
+        file.lock()
+            .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+/// Write `contents` to `path` atomically: write a temp file in the same
+/// directory, fsync it, then rename it over the target.
+fn atomic_write(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = dir.join(format!(".{file_name}.{}.tmp", Uuid::new_v4().simple()));
+
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&tmp_path)
+            .with_context(|| format!("Failed to create temp file: {}", tmp_path.display()))?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layout_matches_legacy_files() {
+        let dir = TempDir::new().unwrap();
+        let storage = JsonStorage::new(dir.path());
+        storage.update(Collection::Reviews, "abc", &mut |_| Ok(Some("{}".into()))).unwrap();
+        storage.update(Collection::Meta, "todos", &mut |_| Ok(Some("{}".into()))).unwrap();
+        storage.append(Log::Timeline, "{}").unwrap();
+
+        assert!(dir.path().join(".aipair/reviews/abc.json").exists());
+        assert!(dir.path().join(".aipair/todos.json").exists());
+        assert!(dir.path().join(".aipair/timeline.jsonl").exists());
+    }
+
+    #[test]
+    fn test_keys_skip_lock_and_temp_files_and_clone_dirs() {
+        let dir = TempDir::new().unwrap();
+        let storage = JsonStorage::new(dir.path());
+        storage.update(Collection::Sessions, "s1", &mut |_| Ok(Some("{}".into()))).unwrap();
+        std::fs::create_dir_all(dir.path().join(".aipair/sessions/s1/repo")).unwrap();
+
+        assert_eq!(storage.keys(Collection::Sessions).unwrap(), vec!["s1".to_string()]);
+    }
+
+    #[test]
+    fn test_atomic_write_leaves_no_temp_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("doc.json");
+        atomic_write(&path, b"one").unwrap();
+        atomic_write(&path, b"two").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "two");
+        let names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["doc.json".to_string()]);
+    }
+}
@@ -0,0 +1,199 @@
+//! SQLite backend: every document and log entry in one `.aipair/aipair.db`.
+//!
+//! Updates run in `BEGIN IMMEDIATE` transactions, so read-modify-write is
+//! atomic across processes as well as threads. The connection is opened
+//! lazily and shared behind a mutex.
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior, params};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{Backend, Collection, Log, Storage};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS documents (
+    collection TEXT NOT NULL,
+    key TEXT NOT NULL,
+    body TEXT NOT NULL,
+    PRIMARY KEY (collection, key)
+);
+CREATE TABLE IF NOT EXISTS log_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    log TEXT NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS log_entries_by_log ON log_entries (log, id);
+";
+
+pub struct SqliteStorage {
+    path: PathBuf,
+    conn: Mutex<Option<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            conn: Mutex::new(None),
+        }
+    }
+
+    fn with_conn<T>(&self, f: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        let mut guard = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            *guard = Some(self.connect()?);
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(&self.path)
+            .with_context(|| format!("Failed to open database: {}", self.path.display()))?;
+        conn.busy_timeout(Duration::from_secs(10))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to initialize database: {}", self.path.display()))?;
+        Ok(conn)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn backend(&self) -> Backend {
+        Backend::Sqlite
+    }
+
+    fn get(&self, collection: Collection, key: &str) -> Result<Option<String>> {
+        self.with_conn(|conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT body FROM documents WHERE collection = ?1 AND key = ?2",
+                    params![collection.name(), key],
+                    |row| row.get(0),
+                )
+                .optional()?)
+        })
+    }
+
+    fn keys(&self, collection: Collection) -> Result<Vec<String>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare_cached("SELECT key FROM documents WHERE collection = ?1")?;
+            let keys = stmt
+                .query_map(params![collection.name()], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(keys)
+        })
+    }
+
+    fn list(&self, collection: Collection) -> Result<Vec<(String, String)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare_cached("SELECT key, body FROM documents WHERE collection = ?1")?;
+            let bodies = stmt
+                .query_map(params![collection.name()], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(bodies)
+        })
+    }
+
+    fn update(
+        &self,
+        collection: Collection,
+        key: &str,
+        f: &mut dyn FnMut(Option<&str>) -> Result<Option<String>>,
+    ) -> Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let current: Option<String> = tx
+                .query_row(
+                    "SELECT body FROM documents WHERE collection = ?1 AND key = ?2",
+                    params![collection.name(), key],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(body) = f(current.as_deref())? {
+                tx.execute(
+                    "INSERT INTO documents (collection, key, body) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (collection, key) DO UPDATE SET body = excluded.body",
+                    params![collection.name(), key, body],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, collection: Collection, key: &str) -> Result<bool> {
+        self.with_conn(|conn| {
+            let n = conn.execute(
+                "DELETE FROM documents WHERE collection = ?1 AND key = ?2",
+                params![collection.name(), key],
+            )?;
+            Ok(n > 0)
+        })
+    }
+
+    fn delete_if(&self, collection: Collection, key: &str, f: &mut dyn FnMut(&str) -> Result<bool>) -> Result<bool> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let current: Option<String> = tx
+                .query_row(
+                    "SELECT body FROM documents WHERE collection = ?1 AND key = ?2",
+                    params![collection.name(), key],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let deleted = match current {
+                Some(body) if f(&body)? => {
+                    tx.execute(
+                        "DELETE FROM documents WHERE collection = ?1 AND key = ?2",
+                        params![collection.name(), key],
+                    )?;
+                    true
+                }
+                _ => false,
+            };
+            tx.commit()?;
+            Ok(deleted)
+        })
+    }
+
+    fn append(&self, log: Log, entry: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO log_entries (log, body) VALUES (?1, ?2)",
+                params![log.name(), entry],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn read_log(&self, log: Log) -> Result<Vec<String>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare_cached("SELECT body FROM log_entries WHERE log = ?1 ORDER BY id")?;
+            let entries = stmt
+                .query_map(params![log.name()], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(entries)
+        })
+    }
+
+    fn replace_log(&self, log: Log, entries: &[String]) -> Result<()> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            tx.execute("DELETE FROM log_entries WHERE log = ?1", params![log.name()])?;
+            {
+                let mut stmt = tx.prepare("INSERT INTO log_entries (log, body) VALUES (?1, ?2)")?;
+                for entry in entries {
+                    stmt.execute(params![log.name(), entry])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,247 @@
+//! Suggested edits: replacement lines a reviewer attaches to a comment on a
+//! line thread, applied to the change as a jj change of their own.
+//!
+//! The thread's lines are located at the change's current commit with the
+//! line mapper, the same way the UI places threads. If they were deleted, or
+//! no longer read the way they did when the thread was started, the
+//! suggestion was written against code that's gone and applying it is
+//! refused with a [`SuggestionConflict`].
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::jj::Jj;
+use crate::line_mapper;
+use crate::review::{Review, Side, ThreadAnchor};
+use crate::vcs::Vcs;
+
+/// What applying a suggestion produced
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedSuggestion {
+    /// The new change, or the target change when the edit was squashed into it
+    pub change_id: String,
+    pub file: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub squashed: bool,
+}
+
+/// A suggestion whose lines can't be found as they were
+#[derive(Debug)]
+pub struct SuggestionConflict {
+    pub thread_id: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for SuggestionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Suggestion on thread {} no longer applies: {}", self.thread_id, self.reason)
+    }
+}
+
+impl std::error::Error for SuggestionConflict {}
+
+/// Returns true if `err` is (or wraps) a [`SuggestionConflict`].
+pub fn is_conflict(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<SuggestionConflict>().is_some()
+}
+
+/// Apply the latest suggestion on a thread of `review`, either as a new change
+/// on top of the reviewed change or squashed into it.
+///
+/// The edit is made in a scratch workspace, so the working copy is left
+/// alone.
+pub fn apply_suggestion(jj: &Jj, review: &Review, thread_id: &str, squash: bool) -> Result<AppliedSuggestion> {
+    let thread = review.find_thread(thread_id)?;
+    let replacement = thread
+        .suggestion()
+        .with_context(|| format!("Thread {} has no suggestion", thread.id))?;
+    if thread.anchor != ThreadAnchor::Line || thread.side != Side::New {
+        anyhow::bail!("Thread {} isn't on lines of the change", thread.id);
+    }
+    let conflict = |reason: String| SuggestionConflict { thread_id: thread.id.clone(), reason };
+
+    let commit = jj.get_change(&review.change_id)?.commit_id;
+    let located = line_mapper::map_all_threads(jj, std::slice::from_ref(thread), &commit)
+        .remove(&thread.id)
+        .context("Thread could not be mapped")?;
+    if located.is_deleted {
+        return Err(conflict("its lines were removed".to_string()).into());
+    }
+    let file = located.file.unwrap_or_else(|| thread.file.clone());
+    let content = jj
+        .show_file(&commit, &file)
+        .map_err(|_| conflict(format!("{file} no longer exists")))?;
+
+    let lines: Vec<&str> = content.lines().collect();
+    let current = lines
+        .get(located.line_start.saturating_sub(1)..located.line_end)
+        .ok_or_else(|| conflict(format!("lines {}-{} are past the end of {file}", located.line_start, located.line_end)))?;
+    if let Some(snapshot) = &thread.snapshot
+        && current != snapshot.lines.as_slice()
+    {
+        return Err(conflict(format!(
+            "{file}:{}-{} changed since the suggestion was made",
+            located.line_start, located.line_end
+        ))
+        .into());
+    }
+    let updated = replace_lines(&content, located.line_start, located.line_end, replacement)?;
+
+    let change_id = jj.in_scratch_workspace(&review.change_id, |scratch| {
+        std::fs::write(scratch.repo_path().join(&file), &updated)
+            .with_context(|| format!("Failed to write {file}"))?;
+        if squash {
+            scratch.squash_into("@", &review.change_id)?;
+            Ok(review.change_id.clone())
+        } else {
+            scratch.describe(&format!("Apply suggestion from review thread {}", thread.id))?;
+            scratch.working_copy_change_id()
+        }
+    })?;
+
+    Ok(AppliedSuggestion {
+        change_id,
+        file,
+        line_start: located.line_start,
+        line_end: located.line_end,
+        squashed: squash,
+    })
+}
+
+/// `content` with lines `start..=end` (1-based) replaced by `replacement`.
+/// Fails if the range isn't one of `content`'s.
+fn replace_lines(content: &str, start: usize, end: usize, replacement: &[String]) -> Result<String> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    if start == 0 || start > end || end > lines.len() {
+        anyhow::bail!("Lines {start}-{end} aren't a range of a {}-line file", lines.len());
+    }
+    let mut out = lines[..start - 1].concat();
+    for line in replacement {
+        out.push_str(line);
+        out.push('\n');
+    }
+    // Keep a missing newline at the end of the file missing
+    if end == lines.len() && !content.ends_with('\n') && !replacement.is_empty() {
+        out.pop();
+    }
+    out.push_str(&lines[end..].concat());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_mapper::snapshot_lines;
+    use crate::review::{Author, CommentExtras, ReviewStore};
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_replace_lines() {
+        let content = "a\nb\nc\n";
+        assert_eq!(replace_lines(content, 2, 2, &["B".into(), "B2".into()]).unwrap(), "a\nB\nB2\nc\n");
+        assert_eq!(replace_lines(content, 1, 3, &[]).unwrap(), "");
+        assert_eq!(replace_lines("a\nb", 2, 2, &["x".into()]).unwrap(), "a\nx");
+    }
+
+    #[test]
+    fn test_replace_lines_rejects_bad_ranges() {
+        let content = "a\nb\nc\n";
+        assert!(replace_lines(content, 0, 1, &[]).is_err());
+        assert!(replace_lines(content, 3, 2, &[]).is_err());
+        assert!(replace_lines(content, 2, 4, &[]).is_err());
+        assert!(replace_lines(content, 4, 4, &[]).is_err());
+    }
+
+    fn jj_cmd(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("jj").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("jj {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn log(dir: &std::path::Path, rev: &str, template: &str) -> String {
+        jj_cmd(dir, &["log", "--no-graph", "-r", rev, "-T", template]).trim().to_string()
+    }
+
+    /// A repo with a reviewed change whose thread on line 2 suggests a fix
+    fn setup() -> (TempDir, Jj, ReviewStore, String, String) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path();
+        jj_cmd(path, &["git", "init"]);
+        std::fs::write(path.join(".gitignore"), ".aipair/\n").unwrap();
+        jj_cmd(path, &["new", "-m", "change"]);
+        let content = "fn main() {\n    println!(\"helo\");\n}\n";
+        std::fs::write(path.join("main.rs"), content).unwrap();
+        let jj = Jj::new(path);
+        let change_id = log(path, "@", "change_id");
+        let commit = log(path, "@", "commit_id");
+
+        let store = ReviewStore::new(path);
+        store.get_or_create(&change_id, "@-", &commit).unwrap();
+        let suggestion = CommentExtras { suggestion: Some(vec!["    println!(\"hello\");".into()]), ..CommentExtras::default() };
+        let (_, thread_id) = store
+            .add_comment(&change_id, "main.rs", 2, 2, Side::New, Author::human("user"), "Typo", &commit, snapshot_lines(content, 2, 2), suggestion)
+            .unwrap();
+        (dir, jj, store, change_id, thread_id)
+    }
+
+    #[test]
+    fn test_apply_suggestion_as_new_change() {
+        let (dir, jj, store, change_id, thread_id) = setup();
+        let path = dir.path();
+        // The change moves down a line before the suggestion is applied
+        std::fs::write(path.join("main.rs"), "// hi\nfn main() {\n    println!(\"helo\");\n}\n").unwrap();
+
+        let review = store.get(&change_id).unwrap().unwrap();
+        let feedback = crate::review::format_pending_feedback(&jj, vec![review.clone()]);
+        assert!(feedback.contains("    println!(\"hello\");"), "Output:\n{feedback}");
+        assert!(feedback.contains(&format!("apply-suggestion {} {thread_id}", &change_id[..8])), "Output:\n{feedback}");
+
+        let applied = apply_suggestion(&jj, &review, &thread_id, false).unwrap();
+        assert_eq!((applied.line_start, applied.line_end), (3, 3));
+        assert_ne!(applied.change_id, change_id);
+
+        assert_eq!(log(path, "@", "change_id"), change_id, "working copy should be restored");
+        let parent = log(path, &format!("{}-", applied.change_id), "change_id");
+        assert_eq!(parent, change_id);
+        let applied_content = jj_cmd(path, &["file", "show", "-r", &applied.change_id, "main.rs"]);
+        assert!(applied_content.contains("\"hello\""));
+        assert!(std::fs::read_to_string(path.join("main.rs")).unwrap().contains("\"helo\""));
+    }
+
+    #[test]
+    fn test_apply_suggestion_squashed() {
+        let (dir, jj, store, change_id, thread_id) = setup();
+        let path = dir.path();
+        // Apply from elsewhere so the working copy isn't the target change
+        jj_cmd(path, &["new", "root()"]);
+        let elsewhere = log(path, "@", "change_id");
+        // with edits of its own that haven't been snapshotted yet
+        std::fs::write(path.join("notes.txt"), "todo\n").unwrap();
+
+        let review = store.get(&change_id).unwrap().unwrap();
+        let applied = apply_suggestion(&jj, &review, &thread_id, true).unwrap();
+        assert_eq!(applied.change_id, change_id);
+        assert_eq!(log(path, "@", "change_id"), elsewhere);
+        assert_eq!(jj_cmd(path, &["file", "show", "-r", "@", "notes.txt"]), "todo\n");
+        assert_eq!(log(path, &change_id, r#"diff().files().map(|f| f.path()).join(",")"#), "main.rs");
+        let content = jj_cmd(path, &["file", "show", "-r", &change_id, "main.rs"]);
+        assert_eq!(content, "fn main() {\n    println!(\"hello\");\n}\n");
+    }
+
+    #[test]
+    fn test_apply_suggestion_conflicts_when_lines_changed() {
+        let (dir, jj, store, change_id, thread_id) = setup();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {\n    println!(\"hi\");\n}\n").unwrap();
+
+        let review = store.get(&change_id).unwrap().unwrap();
+        let err = apply_suggestion(&jj, &review, &thread_id, false).unwrap_err();
+        assert!(is_conflict(&err), "{err}");
+        assert_eq!(log(dir.path(), "@", "change_id"), change_id);
+        // Nothing was created on top
+        assert!(log(dir.path(), &format!("children({change_id})"), "change_id").is_empty());
+    }
+}
@@ -40,13 +40,16 @@ pub fn ensure_tmux_session(name: &str, working_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Reader, writer and master handle for a spawned PTY.
+pub type TerminalHandles = (Box<dyn Read + Send>, Box<dyn Write + Send>, Box<dyn MasterPty + Send>);
+
 /// Spawn a PTY running `tmux attach-session -t aipair-{name}`.
 /// Returns (reader, writer, master) where master can be used for resize.
 pub fn spawn_terminal(
     name: &str,
     cols: u16,
     rows: u16,
-) -> Result<(Box<dyn Read + Send>, Box<dyn Write + Send>, Box<dyn MasterPty + Send>)> {
+) -> Result<TerminalHandles> {
     let tmux_name = format!("aipair-{name}");
 
     let pty_system = native_pty_system();
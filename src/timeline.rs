@@ -2,11 +2,14 @@ use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-const TIMELINE_FILE: &str = ".aipair/timeline.jsonl";
-const IMPORT_STATE_FILE: &str = ".aipair/timeline-import-state.json";
+use crate::identity::{Author, DEFAULT_AGENT};
+use crate::storage::{Collection, Log, Storage};
+
+pub(crate) const IMPORT_STATE_KEY: &str = "timeline-import-state";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimelineEntry {
@@ -29,12 +32,12 @@ pub enum TimelineEventData {
     ReviewReply {
         change_id: String,
         thread_id: String,
-        author: String,
+        author: Author,
         text: String,
     },
     ChatMessage {
         session_id: String,
-        author: ChatAuthor,
+        author: Author,
         text: String,
     },
     CodeSnapshot {
@@ -44,15 +47,8 @@ pub enum TimelineEventData {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum ChatAuthor {
-    User,
-    Claude,
-}
-
 pub struct TimelineStore {
-    base_path: PathBuf,
+    storage: Arc<dyn Storage>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -77,59 +73,24 @@ struct ImportState {
 }
 
 impl TimelineStore {
-    pub fn new(repo_path: impl AsRef<Path>) -> Self {
-        Self {
-            base_path: repo_path.as_ref().to_path_buf(),
-        }
-    }
-
-    fn timeline_path(&self) -> PathBuf {
-        self.base_path.join(TIMELINE_FILE)
-    }
-
-    fn import_state_path(&self) -> PathBuf {
-        self.base_path.join(IMPORT_STATE_FILE)
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
     }
 
     pub fn append(&self, entry: &TimelineEntry) -> Result<()> {
-        let path = self.timeline_path();
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&path)
-            .with_context(|| format!("Failed to open timeline: {}", path.display()))?;
-
         let line = serde_json::to_string(entry)?;
-        writeln!(file, "{}", line)?;
-        Ok(())
+        self.storage.append(Log::Timeline, &line)
     }
 
     pub fn read(&self, filter: Option<&TimelineFilter>) -> Result<Vec<TimelineEntry>> {
-        let path = self.timeline_path();
-        if !path.exists() {
-            return Ok(Vec::new());
-        }
-
-        let file = std::fs::File::open(&path)
-            .with_context(|| format!("Failed to open timeline: {}", path.display()))?;
-        let reader = BufReader::new(file);
-
         let mut entries = Vec::new();
-        for line in reader.lines() {
-            let line = line?;
-            if line.trim().is_empty() {
-                continue;
-            }
+        for line in self.storage.read_log(Log::Timeline)? {
             match serde_json::from_str::<TimelineEntry>(&line) {
                 Ok(entry) => {
-                    if let Some(f) = filter {
-                        if !matches_filter(&entry, f) {
-                            continue;
-                        }
+                    if let Some(f) = filter
+                        && !matches_filter(&entry, f)
+                    {
+                        continue;
                     }
                     entries.push(entry);
                 }
@@ -143,7 +104,9 @@ impl TimelineStore {
         Ok(entries)
     }
 
-    pub fn import_claude_sessions(&self, project_path: &Path) -> Result<ImportStats> {
+    /// Import new messages from Claude Code sessions; `user` is whoever was
+    /// talking to Claude.
+    pub fn import_claude_sessions(&self, project_path: &Path, user: &Author) -> Result<ImportStats> {
         let claude_dir = claude_project_dir(project_path)?;
         if !claude_dir.exists() {
             return Ok(ImportStats::default());
@@ -167,7 +130,7 @@ impl TimelineStore {
                 stats.sessions_scanned += 1;
                 let last_offset = state.sessions.get(&file_name).copied().unwrap_or(0);
 
-                match self.import_session_file(&path, &session_id, last_offset) {
+                match self.import_session_file(&path, &session_id, last_offset, user) {
                     Ok((count, new_offset)) => {
                         stats.messages_imported += count;
                         state.sessions.insert(file_name, new_offset);
@@ -188,6 +151,7 @@ impl TimelineStore {
         path: &Path,
         session_id: &str,
         last_offset: u64,
+        user: &Author,
     ) -> Result<(usize, u64)> {
         let mut file = std::fs::File::open(path)?;
         let file_len = file.metadata()?.len();
@@ -209,7 +173,7 @@ impl TimelineStore {
                 continue;
             }
 
-            if let Some(entry) = parse_claude_session_line(&line, session_id) {
+            if let Some(entry) = parse_claude_session_line(&line, session_id, user) {
                 self.append(&entry)?;
                 count += 1;
             }
@@ -219,22 +183,16 @@ impl TimelineStore {
     }
 
     fn load_import_state(&self) -> Result<ImportState> {
-        let path = self.import_state_path();
-        if !path.exists() {
-            return Ok(ImportState::default());
+        match self.storage.get(Collection::Meta, IMPORT_STATE_KEY)? {
+            Some(content) => Ok(serde_json::from_str(&content)?),
+            None => Ok(ImportState::default()),
         }
-        let content = std::fs::read_to_string(&path)?;
-        Ok(serde_json::from_str(&content)?)
     }
 
     fn save_import_state(&self, state: &ImportState) -> Result<()> {
-        let path = self.import_state_path();
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
         let content = serde_json::to_string_pretty(state)?;
-        std::fs::write(&path, content)?;
-        Ok(())
+        self.storage
+            .update(Collection::Meta, IMPORT_STATE_KEY, &mut |_| Ok(Some(content.clone())))
     }
 }
 
@@ -260,7 +218,7 @@ fn claude_project_dir(project_path: &Path) -> Result<PathBuf> {
 
 /// Parse a single line from a Claude Code session JSONL file.
 /// Returns None for lines we skip (tool_use, tool_result, system, thinking, sidechain, etc.)
-fn parse_claude_session_line(line: &str, session_id: &str) -> Option<TimelineEntry> {
+fn parse_claude_session_line(line: &str, session_id: &str, user: &Author) -> Option<TimelineEntry> {
     let val: serde_json::Value = serde_json::from_str(line).ok()?;
 
     // Skip sidechain messages
@@ -307,7 +265,7 @@ fn parse_claude_session_line(line: &str, session_id: &str) -> Option<TimelineEnt
                 timestamp,
                 data: TimelineEventData::ChatMessage {
                     session_id: session_id.to_string(),
-                    author: ChatAuthor::User,
+                    author: user.clone(),
                     text,
                 },
             })
@@ -337,7 +295,7 @@ fn parse_claude_session_line(line: &str, session_id: &str) -> Option<TimelineEnt
                 timestamp,
                 data: TimelineEventData::ChatMessage {
                     session_id: session_id.to_string(),
-                    author: ChatAuthor::Claude,
+                    author: Author::agent(DEFAULT_AGENT, None),
                     text: text_parts.join("\n"),
                 },
             })
@@ -348,15 +306,15 @@ fn parse_claude_session_line(line: &str, session_id: &str) -> Option<TimelineEnt
 }
 
 fn matches_filter(entry: &TimelineEntry, filter: &TimelineFilter) -> bool {
-    if let Some(since) = filter.since {
-        if entry.timestamp < since {
-            return false;
-        }
+    if let Some(since) = filter.since
+        && entry.timestamp < since
+    {
+        return false;
     }
-    if let Some(until) = filter.until {
-        if entry.timestamp > until {
-            return false;
-        }
+    if let Some(until) = filter.until
+        && entry.timestamp > until
+    {
+        return false;
     }
     if let Some(ref change_id) = filter.change_id {
         let entry_change_id = match &entry.data {
@@ -390,7 +348,7 @@ mod tests {
 
     fn setup() -> (TempDir, TimelineStore) {
         let dir = TempDir::new().unwrap();
-        let store = TimelineStore::new(dir.path());
+        let store = TimelineStore::with_storage(crate::storage::open(dir.path()));
         (dir, store)
     }
 
@@ -440,7 +398,7 @@ mod tests {
                 timestamp: Utc::now(),
                 data: TimelineEventData::ChatMessage {
                     session_id: "s1".to_string(),
-                    author: ChatAuthor::User,
+                    author: Author::human("user"),
                     text: "hello".to_string(),
                 },
             })
@@ -457,10 +415,10 @@ mod tests {
     #[test]
     fn test_parse_claude_user_message() {
         let line = r#"{"type":"user","timestamp":"2025-01-15T10:00:00Z","message":{"content":"Hello world"}}"#;
-        let entry = parse_claude_session_line(line, "test-session").unwrap();
+        let entry = parse_claude_session_line(line, "test-session", &Author::human("jelle")).unwrap();
         match entry.data {
             TimelineEventData::ChatMessage { author, text, .. } => {
-                assert_eq!(author, ChatAuthor::User);
+                assert_eq!(author, Author::human("jelle"));
                 assert_eq!(text, "Hello world");
             }
             _ => panic!("Expected ChatMessage"),
@@ -470,10 +428,10 @@ mod tests {
     #[test]
     fn test_parse_claude_assistant_message() {
         let line = r#"{"type":"assistant","timestamp":"2025-01-15T10:01:00Z","message":{"content":[{"type":"text","text":"Sure, I can help."},{"type":"tool_use","name":"Read","input":{}}]}}"#;
-        let entry = parse_claude_session_line(line, "test-session").unwrap();
+        let entry = parse_claude_session_line(line, "test-session", &Author::human("jelle")).unwrap();
         match entry.data {
             TimelineEventData::ChatMessage { author, text, .. } => {
-                assert_eq!(author, ChatAuthor::Claude);
+                assert_eq!(author, Author::agent("claude", None));
                 assert_eq!(text, "Sure, I can help.");
             }
             _ => panic!("Expected ChatMessage"),
@@ -483,13 +441,13 @@ mod tests {
     #[test]
     fn test_skip_sidechain() {
         let line = r#"{"type":"user","timestamp":"2025-01-15T10:00:00Z","isSidechain":true,"message":{"content":"test"}}"#;
-        assert!(parse_claude_session_line(line, "s").is_none());
+        assert!(parse_claude_session_line(line, "s", &Author::human("jelle")).is_none());
     }
 
     #[test]
     fn test_skip_system_type() {
         let line = r#"{"type":"system","timestamp":"2025-01-15T10:00:00Z","message":"init"}"#;
-        assert!(parse_claude_session_line(line, "s").is_none());
+        assert!(parse_claude_session_line(line, "s", &Author::human("jelle")).is_none());
     }
 
     #[test]
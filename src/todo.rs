@@ -2,10 +2,12 @@ use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use ts_rs::TS;
 
-const TODOS_FILE: &str = ".aipair/todos.json";
+use crate::storage::{self, Collection, Storage, Versioned};
+
+pub(crate) const TODOS_KEY: &str = "todos";
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
@@ -17,49 +19,41 @@ pub struct TodoItem {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../web/src/types/")]
 pub struct TodoTree {
     pub root_ids: Vec<String>,
     pub items: HashMap<String, TodoItem>,
+    #[serde(default)]
+    pub version: u32,
 }
 
-impl Default for TodoTree {
-    fn default() -> Self {
-        Self {
-            root_ids: Vec::new(),
-            items: HashMap::new(),
-        }
+impl Versioned for TodoTree {
+    fn version(&self) -> u32 {
+        self.version
+    }
+    fn set_version(&mut self, version: u32) {
+        self.version = version;
     }
 }
 
 pub struct TodoStore {
-    file_path: PathBuf,
+    storage: Arc<dyn Storage>,
 }
 
 impl TodoStore {
-    pub fn new(repo_path: impl AsRef<Path>) -> Self {
-        Self {
-            file_path: repo_path.as_ref().join(TODOS_FILE),
-        }
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
+        Self { storage }
     }
 
     pub fn load(&self) -> Result<TodoTree> {
-        if !self.file_path.exists() {
-            return Ok(TodoTree::default());
-        }
-        let content = std::fs::read_to_string(&self.file_path)?;
-        let tree: TodoTree = serde_json::from_str(&content)?;
-        Ok(tree)
+        Ok(storage::get_doc(self.storage.as_ref(), Collection::Meta, TODOS_KEY)?.unwrap_or_default())
     }
 
-    pub fn save(&self, tree: &TodoTree) -> Result<()> {
-        if let Some(parent) = self.file_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let content = serde_json::to_string_pretty(tree)?;
-        std::fs::write(&self.file_path, content)?;
-        Ok(())
+    /// Save a tree loaded earlier. Fails with a `ConflictError` if the tree
+    /// was modified since it was loaded.
+    pub fn save(&self, tree: &mut TodoTree) -> Result<()> {
+        storage::save_doc(self.storage.as_ref(), Collection::Meta, TODOS_KEY, tree)
     }
 
     pub fn add_item(
@@ -134,19 +128,6 @@ impl TodoStore {
         Ok(())
     }
 
-    pub fn toggle_item(&self, tree: &mut TodoTree, id: &str) -> Result<bool> {
-        let item = tree
-            .items
-            .get_mut(id)
-            .ok_or_else(|| anyhow::anyhow!("Item not found: {}", id))?;
-
-        item.checked = !item.checked;
-        let new_state = item.checked;
-
-        self.save(tree)?;
-        Ok(new_state)
-    }
-
     pub fn delete_item(&self, tree: &mut TodoTree, id: &str) -> Result<()> {
         // Collect all descendant ids to remove
         let mut to_remove = vec![id.to_string()];
@@ -190,10 +171,10 @@ impl TodoStore {
         // Need to collect keys first to avoid borrow issues
         let keys: Vec<String> = tree.items.keys().cloned().collect();
         for key in &keys {
-            if key != id {
-                if let Some(item) = tree.items.get_mut(key) {
-                    item.children.retain(|c| c != id);
-                }
+            if key != id
+                && let Some(item) = tree.items.get_mut(key)
+            {
+                item.children.retain(|c| c != id);
             }
         }
 
@@ -237,7 +218,7 @@ mod tests {
         let dir = TempDir::new().unwrap();
         // Create the .aipair directory
         std::fs::create_dir_all(dir.path().join(".aipair")).unwrap();
-        let store = TodoStore::new(dir.path());
+        let store = TodoStore::with_storage(storage::open(dir.path()));
         (dir, store)
     }
 
@@ -298,22 +279,6 @@ mod tests {
         assert_eq!(tree.items[&id].text, "Updated");
     }
 
-    #[test]
-    fn test_toggle_item() {
-        let (_dir, store) = setup();
-        let mut tree = store.load().unwrap();
-
-        let id = store.add_item(&mut tree, "Task".to_string(), None, None).unwrap();
-        assert!(!tree.items[&id].checked);
-
-        let checked = store.toggle_item(&mut tree, &id).unwrap();
-        assert!(checked);
-        assert!(tree.items[&id].checked);
-
-        let checked = store.toggle_item(&mut tree, &id).unwrap();
-        assert!(!checked);
-    }
-
     #[test]
     fn test_delete_item_with_children() {
         let (_dir, store) = setup();
@@ -357,4 +322,20 @@ mod tests {
         assert_eq!(tree2.items[&id].text, "Persisted");
     }
 
+    #[test]
+    fn test_stale_tree_conflicts_instead_of_losing_update() {
+        let (_dir, store) = setup();
+        let mut first = store.load().unwrap();
+        let mut second = store.load().unwrap();
+
+        store.add_item(&mut first, "From UI".to_string(), None, None).unwrap();
+        let err = store
+            .add_item(&mut second, "From agent".to_string(), None, None)
+            .unwrap_err();
+        assert!(crate::storage::is_conflict(&err));
+
+        let tree = store.load().unwrap();
+        assert_eq!(tree.root_ids.len(), 1);
+        assert_eq!(tree.items[&tree.root_ids[0]].text, "From UI");
+    }
 }
@@ -0,0 +1,179 @@
+//! Listing and undoing what aipair did to a repo.
+//!
+//! Merging moves bookmarks backwards and deletes them, suggestions and
+//! conflict resolutions squash into changes, and none of it asks first.
+//! Every jj command aipair runs to change the repo is tagged (see
+//! `Jj::tagged`), so its operations can be picked out of `jj op log`.
+//! Undoing one restores the repo to the operation before it, which also
+//! undoes everything after it; operations that aren't aipair's, such as the
+//! user's own commands or snapshots of their edits, are only rolled back
+//! when forced. Reviews aren't part of the repo and stay as they are.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::jj::{Jj, Operation};
+
+/// How far back in the operation log to look
+const OPERATION_LIMIT: usize = 500;
+
+/// The result of undoing an operation
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoReport {
+    /// The operation the repo was restored to, as a jj operation expression
+    pub restored_to: String,
+    /// The operations rolled back, newest first
+    pub undone: Vec<Operation>,
+}
+
+/// aipair's operations among the last `limit`, newest first
+pub fn operations(jj: &Jj, limit: usize) -> Result<Vec<Operation>> {
+    Ok(jj.op_log(limit)?.into_iter().filter(|op| op.aipair.is_some()).collect())
+}
+
+/// The operations undoing `operation` rolls back, newest first, ending
+/// with `operation` itself
+fn plan<'a>(log: &'a [Operation], operation: &str) -> Result<&'a [Operation]> {
+    let matches: Vec<usize> = log
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| op.id.starts_with(operation))
+        .map(|(i, _)| i)
+        .collect();
+    let index = match matches.as_slice() {
+        [index] => *index,
+        [] => anyhow::bail!("No operation {operation} among the last {} operations", log.len()),
+        _ => anyhow::bail!("Operation id {operation} is ambiguous"),
+    };
+    if log[index].aipair.is_none() {
+        anyhow::bail!(
+            "Operation {operation} wasn't made by aipair; use `jj op restore` to roll back your own operations"
+        );
+    }
+    Ok(&log[..=index])
+}
+
+/// Restore the repo to just before `operation`. Fails if that would also
+/// roll back operations aipair didn't make, unless `force` is set.
+pub fn undo(jj: &Jj, operation: &str, force: bool) -> Result<UndoReport> {
+    let log = jj.op_log(OPERATION_LIMIT)?;
+    let undone = plan(&log, operation)?;
+    let foreign: Vec<&Operation> = undone.iter().filter(|op| op.aipair.is_none()).collect();
+    if !foreign.is_empty() && !force {
+        let listed: String = foreign.iter().map(|op| format!("\n  {}", format_operation(op))).collect();
+        anyhow::bail!(
+            "Undoing {operation} would also roll back {} operation(s) aipair didn't make:{listed}\nPass --force to roll them back too.",
+            foreign.len()
+        );
+    }
+
+    let restored_to = format!("{}-", undone.last().expect("plan includes the operation").id);
+    jj.op_restore(&restored_to)?;
+    Ok(UndoReport { restored_to, undone: undone.to_vec() })
+}
+
+fn short(id: &str) -> &str {
+    &id[..12.min(id.len())]
+}
+
+fn format_operation(op: &Operation) -> String {
+    let by = op.aipair.as_deref().map(|name| format!(" [aipair {name}]")).unwrap_or_default();
+    format!("{}  {}  {}{by}", short(&op.id), op.time, op.description)
+}
+
+/// List operations for the terminal
+pub fn format_operations(operations: &[Operation]) -> String {
+    if operations.is_empty() {
+        return "No aipair operations in the recent operation log.\n".to_string();
+    }
+    let mut output: String = operations.iter().map(|op| format_operation(op) + "\n").collect();
+    output.push_str("`aipair undo <operation-id>` restores the repo to just before one of these.\n");
+    output
+}
+
+/// Describe an undo for the terminal
+pub fn format_report(report: &UndoReport) -> String {
+    let Some(undone) = report.undone.last() else {
+        return "Nothing was rolled back.\n".to_string();
+    };
+    let mut output = format!("Restored the repo to just before operation {}, rolling back:\n", short(&undone.id));
+    for op in &report.undone {
+        output.push_str(&format!("  {}\n", format_operation(op)));
+    }
+    output.push_str("`jj op log` still has them; `jj op restore` brings them back.\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcs::Vcs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn jj_cmd(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("jj").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("jj {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn op(id: &str, aipair: Option<&str>) -> Operation {
+        Operation {
+            id: id.to_string(),
+            time: "2026-01-01T10:00:00+00:00".to_string(),
+            description: "something".to_string(),
+            aipair: aipair.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_plan_rolls_back_everything_after_the_operation() {
+        let log = vec![
+            op("aaa111", Some("bookmark_delete")),
+            op("bbb222", None),
+            op("ccc333", Some("move_bookmark")),
+            op("ccc444", Some("rebase")),
+            op("ddd555", None),
+        ];
+
+        let undone = plan(&log, "aaa").unwrap();
+        assert_eq!(undone.len(), 1);
+
+        let undone = plan(&log, "ccc3").unwrap();
+        let ids: Vec<_> = undone.iter().map(|op| op.id.as_str()).collect();
+        assert_eq!(ids, vec!["aaa111", "bbb222", "ccc333"]);
+
+        assert!(plan(&log, "ccc").unwrap_err().to_string().contains("ambiguous"));
+        assert!(plan(&log, "ddd").unwrap_err().to_string().contains("wasn't made by aipair"));
+        assert!(plan(&log, "eee").is_err());
+    }
+
+    #[test]
+    fn test_undo_restores_before_aipair_operation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path();
+        jj_cmd(path, &["git", "init"]);
+        let jj = Jj::new(path);
+        jj.bookmark_create("main", "@").unwrap();
+        jj_cmd(path, &["new"]);
+        jj.move_bookmark("main", "@").unwrap();
+
+        let ops = operations(&jj, 50).unwrap();
+        let names: Vec<_> = ops.iter().map(|op| op.aipair.as_deref().unwrap()).collect();
+        assert_eq!(names, vec!["move_bookmark", "bookmark_create"]);
+
+        // The user's `jj new` sits between them
+        let err = undo(&jj, &ops[1].id, false).unwrap_err();
+        assert!(err.to_string().contains("1 operation(s) aipair didn't make"));
+
+        let report = undo(&jj, &ops[0].id, false).unwrap();
+        assert_eq!(report.undone.len(), 1);
+        let parent = jj_cmd(path, &["log", "--no-graph", "-r", "@-", "-T", "change_id"]);
+        assert_eq!(jj.get_bookmark("main").unwrap(), Some(parent.trim().to_string()));
+
+        undo(&jj, &ops[1].id, true).unwrap();
+        assert_eq!(jj.get_bookmark("main").unwrap(), None);
+    }
+}
@@ -0,0 +1,189 @@
+//! The version control operations aipair needs, over jj or plain git.
+//!
+//! Reviews, sessions and line mapping talk to a [`Vcs`]: history queries,
+//! diffs and file contents, bookmarks, pushing and fetching between the main
+//! repo and session clones, and rebasing a session onto its base. Backends:
+//! [`Jj`], which shells out to `jj`; `JjLib` (with the `jj-lib` feature),
+//! which keeps the repo loaded in-process and falls back to [`Jj`]; and
+//! [`Git`] for repos that only have git. [`open`] and [`discover`] pick jj
+//! whenever the repo has a `.jj` directory, in-process if it loads, and put
+//! [`Cached`] in front of the backend.
+//!
+//! The git backend maps jj's concepts onto git's. Branches stand in for
+//! bookmarks, and a commit's change id comes from its `Change-Id:` trailer,
+//! which the commit-msg hook `aipair init` installs keeps across amends and
+//! rebases (commits without one get an id derived from their hash). Revsets
+//! are limited to the forms aipair itself uses; see `Git::rev_args`.
+//! Features that need jj's model, like conflicted commits, the operation
+//! log and the evolution log, get to jj through [`Vcs::as_jj`].
+
+mod cached;
+mod git;
+#[cfg(feature = "jj-lib")]
+mod jj_lib;
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::diff;
+use crate::jj::{Change, Diff, FileDiff, Jj};
+
+pub use cached::Cached;
+pub(crate) use cached::is_commit_id;
+pub use git::Git;
+#[cfg(feature = "jj-lib")]
+pub use jj_lib::JjLib;
+
+pub trait Vcs: Send + Sync {
+    fn repo_path(&self) -> &Path;
+
+    /// The repo's current operation, which changes whenever anything in it
+    /// does; None if the backend doesn't track operations
+    fn operation_id(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// The last operation recorded, without looking for new edits to the
+    /// working copy; None if the backend can't tell
+    fn latest_operation_id(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Changes matching a revset, newest first
+    fn log_revset(&self, revset: &str) -> Result<Vec<Change>>;
+
+    /// Recent changes across all heads
+    fn log(&self, limit: usize) -> Result<Vec<Change>> {
+        self.log_revset(&format!("ancestors(visible_heads(), {limit})"))
+    }
+
+    fn get_change(&self, revision: &str) -> Result<Change>;
+
+    /// Change ids matching a revset, newest first
+    fn query_change_ids(&self, revset: &str) -> Result<Vec<String>>;
+
+    /// (change id, commit id) of each commit matching a revset
+    fn query_commit_ids(&self, revset: &str) -> Result<Vec<(String, String)>>;
+
+    /// Files changed between two revisions, without hunks
+    fn changed_files_between(&self, from: &str, to: &str) -> Result<Vec<FileDiff>>;
+
+    /// Git-format diff between two revisions, limited to `paths` if any
+    fn diff_raw_between_paths(&self, from: &str, to: &str, paths: &[&str], context: Option<usize>) -> Result<String>;
+
+    fn diff_raw_between_ctx(&self, from: &str, to: &str, file: &str, context: Option<usize>) -> Result<String> {
+        self.diff_raw_between_paths(from, to, &[file], context)
+    }
+
+    /// A change's diff against `base`, its parent by default
+    fn diff(&self, change_id: &str, base: Option<&str>, context: usize) -> Result<Diff> {
+        let default_base = format!("{change_id}-");
+        let base = base.unwrap_or(&default_base);
+        let raw = self.diff_raw_between_paths(base, change_id, &[], Some(context))?;
+        let mut patches = diff::parse(&raw);
+        let mut files = self.changed_files_between(base, change_id)?;
+        for file in &mut files {
+            if let Some(i) = patches.iter().position(|p| p.path == file.path) {
+                let patch = patches.swap_remove(i);
+                file.hunks = patch.hunks;
+                file.binary = patch.binary;
+            }
+        }
+
+        Ok(Diff {
+            change_id: change_id.to_string(),
+            base: base.to_string(),
+            files,
+            context,
+        })
+    }
+
+    /// The file's content at `revision`. Fails with [`NoSuchPath`] if
+    /// there's no file there.
+    fn show_file(&self, revision: &str, path: &str) -> Result<String>;
+
+    /// The change id a bookmark points to, if it exists
+    fn get_bookmark(&self, name: &str) -> Result<Option<String>>;
+
+    fn bookmark_create(&self, name: &str, revision: &str) -> Result<()>;
+
+    /// Point a bookmark at `revision`, backwards or sideways if need be
+    fn move_bookmark(&self, name: &str, revision: &str) -> Result<()>;
+
+    fn bookmark_delete(&self, name: &str) -> Result<()>;
+
+    fn git_push_bookmark(&self, bookmark: &str, allow_new: bool) -> Result<String>;
+
+    fn git_fetch(&self) -> Result<String>;
+
+    /// Fetch only specific branches from origin
+    fn git_fetch_branches(&self, branches: &[&str]) -> Result<String>;
+
+    /// Move `revision` onto `destination`
+    fn rebase(&self, revision: &str, destination: &str) -> Result<String>;
+
+    /// Clone this repo into `dest` for a session that works on `bookmark`,
+    /// starting from `base`
+    fn clone_for_session(&self, dest: &Path, base: &str, bookmark: &str) -> Result<Box<dyn Vcs>>;
+
+    /// The jj repo behind this, for features only jj has
+    fn as_jj(&self) -> Option<&Jj> {
+        None
+    }
+}
+
+/// Returned (wrapped in `anyhow::Error`) by [`Vcs::show_file`] when the
+/// revision has no file at the path.
+#[derive(Debug)]
+pub struct NoSuchPath {
+    pub path: String,
+}
+
+impl std::fmt::Display for NoSuchPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No such path: {}", self.path)
+    }
+}
+
+impl std::error::Error for NoSuchPath {}
+
+/// Returns true if `err` is (or wraps) a [`NoSuchPath`].
+pub fn is_no_such_path(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<NoSuchPath>().is_some()
+}
+
+/// The jj repo behind `vcs`, or an error naming what needed it
+pub fn require_jj<'a>(vcs: &'a dyn Vcs, feature: &str) -> Result<&'a Jj> {
+    vcs.as_jj().with_context(|| format!("{feature} needs a jj repo; this one only has git"))
+}
+
+/// The repo at `path`, through jj if it has a `.jj` directory
+pub fn open(path: impl AsRef<Path>) -> Box<dyn Vcs> {
+    Box::new(Cached::new(open_backend(path.as_ref()).into()))
+}
+
+/// The backend for the repo at `path`, without a cache in front, for
+/// holding on to across several [`Cached`]
+pub fn open_backend(path: &Path) -> Box<dyn Vcs> {
+    if !path.join(".jj").exists() {
+        return Box::new(Git::new(path));
+    }
+    #[cfg(feature = "jj-lib")]
+    match JjLib::open(path) {
+        Ok(lib) => return Box::new(lib),
+        Err(err) => tracing::warn!("Running jj for each query instead: {err:#}"),
+    }
+    Box::new(Jj::new(path))
+}
+
+/// The repo containing the current directory
+pub fn discover() -> Result<Box<dyn Vcs>> {
+    match Jj::discover() {
+        Ok(jj) => Ok(open(jj.repo_path())),
+        Err(jj_error) => match Git::discover() {
+            Ok(git) => Ok(Box::new(Cached::new(Arc::new(git)))),
+            Err(_) => Err(jj_error),
+        },
+    }
+}
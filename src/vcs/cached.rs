@@ -0,0 +1,251 @@
+//! Caching in front of any [`Vcs`].
+//!
+//! Queries that resolve revsets (logs, changes, bookmarks) are cached per
+//! repo and keyed by the operation they were answered at; any jj command,
+//! including the snapshot of an edited working copy, moves the repo on to a
+//! new operation, and entries of old ones age out as the caches fill up.
+//! Diffs and file contents are cached by the commit ids their
+//! revisions resolve to, across operations and repos, since commits never
+//! change. Backends without operation ids only get the latter, for
+//! revisions given as full commit ids.
+//!
+//! A `Cached` asks for the operation id once, on its first query, and
+//! answers as of that operation from then on, so open one per request or
+//! command. Changes made through it move it to the next operation; changes
+//! made anywhere else, including through [`Vcs::as_jj`], only show up in
+//! the next `Cached`.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use super::Vcs;
+use crate::cache::Cache;
+use crate::jj::{Change, FileDiff, Jj};
+
+/// An operation id and a query answered at it
+type QueryKey = (String, String);
+
+/// Revset results per repo, by operation
+struct Queries {
+    logs: Cache<QueryKey, Vec<Change>>,
+    changes: Cache<QueryKey, Change>,
+    commit_ids: Cache<QueryKey, Vec<(String, String)>>,
+    bookmarks: Cache<QueryKey, Option<String>>,
+}
+
+impl Queries {
+    fn new() -> Self {
+        Self {
+            logs: Cache::new("logs", 256),
+            changes: Cache::new("changes", 1024),
+            commit_ids: Cache::new("commit_ids", 1024),
+            bookmarks: Cache::new("bookmarks", 64),
+        }
+    }
+}
+
+/// Query caches by repo path
+static QUERIES: LazyLock<Mutex<HashMap<PathBuf, Arc<Queries>>>> = LazyLock::new(Default::default);
+
+/// Changed files between two commits
+static CHANGED_FILES: LazyLock<Cache<(String, String), Vec<FileDiff>>> =
+    LazyLock::new(|| Cache::new("changed_files", 1024));
+
+/// Commits, paths and context of a raw diff
+type DiffKey = (String, String, Vec<String>, Option<usize>);
+
+/// Raw diffs
+static DIFFS: LazyLock<Cache<DiffKey, String>> = LazyLock::new(|| Cache::new("diffs", 1024));
+
+/// File contents by commit and path
+static FILES: LazyLock<Cache<(String, String), String>> = LazyLock::new(|| Cache::new("files", 512));
+
+pub struct Cached {
+    inner: Arc<dyn Vcs>,
+    queries: Arc<Queries>,
+    /// The operation queries are answered as of, once looked up
+    operation: Mutex<Option<Option<String>>>,
+}
+
+impl Cached {
+    pub fn new(inner: Arc<dyn Vcs>) -> Self {
+        let queries = QUERIES
+            .lock()
+            .unwrap()
+            .entry(inner.repo_path().to_path_buf())
+            .or_insert_with(|| Arc::new(Queries::new()))
+            .clone();
+        Self { inner, queries, operation: Mutex::new(None) }
+    }
+
+    /// The operation this instance answers as of, looked up on first use
+    fn operation(&self) -> Result<Option<String>> {
+        let mut operation = self.operation.lock().unwrap();
+        if operation.is_none() {
+            *operation = Some(self.inner.operation_id()?);
+        }
+        Ok(operation.clone().flatten())
+    }
+
+    /// Pass on the result of a change to the repo, and look the operation
+    /// up again on the next query
+    fn changed<T>(&self, result: Result<T>) -> Result<T> {
+        *self.operation.lock().unwrap() = None;
+        result
+    }
+
+    /// `query` answered as of this instance's operation, from `cache` if
+    /// it's there. Backends that can't tell when answers go stale always
+    /// compute them. An answer is only cached if the repo is still at that
+    /// operation once it's computed, since it may describe a later one.
+    fn query<V: Clone>(
+        &self,
+        cache: impl FnOnce(&Queries) -> &Cache<QueryKey, V>,
+        query: &str,
+        compute: impl FnOnce() -> Result<V>,
+    ) -> Result<V> {
+        let Some(operation) = self.operation()? else {
+            return compute();
+        };
+        let cache = cache(&self.queries);
+        let key = (operation, query.to_string());
+        if let Some(value) = cache.get(&key) {
+            return Ok(value);
+        }
+        let value = compute()?;
+        if self.inner.latest_operation_id()?.is_none_or(|latest| latest == key.0) {
+            cache.insert(key, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// The commit id a revision names, if it names exactly one. Only full
+    /// commit ids can be told apart without the query caches.
+    fn commit_id(&self, revision: &str) -> Result<Option<String>> {
+        if is_commit_id(revision) {
+            return Ok(Some(revision.to_string()));
+        }
+        if self.operation()?.is_none() {
+            return Ok(None);
+        }
+        let commits = self.query(|q| &q.commit_ids, revision, || self.inner.query_commit_ids(revision))?;
+        Ok(match commits.as_slice() {
+            [(_, commit_id)] => Some(commit_id.clone()),
+            _ => None,
+        })
+    }
+
+    /// Both revisions as commit ids, when the caches can be used for them
+    fn commit_pair(&self, from: &str, to: &str) -> Result<Option<(String, String)>> {
+        let (Some(from), Some(to)) = (self.commit_id(from)?, self.commit_id(to)?) else {
+            return Ok(None);
+        };
+        Ok(Some((from, to)))
+    }
+}
+
+/// Whether `revision` is a full commit id, which names the same commit in
+/// every operation
+pub(crate) fn is_commit_id(revision: &str) -> bool {
+    revision.len() == 40 && revision.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+impl Vcs for Cached {
+    fn repo_path(&self) -> &Path {
+        self.inner.repo_path()
+    }
+
+    fn operation_id(&self) -> Result<Option<String>> {
+        self.operation()
+    }
+
+    fn latest_operation_id(&self) -> Result<Option<String>> {
+        self.inner.latest_operation_id()
+    }
+
+    fn log_revset(&self, revset: &str) -> Result<Vec<Change>> {
+        self.query(|q| &q.logs, revset, || self.inner.log_revset(revset))
+    }
+
+    fn get_change(&self, revision: &str) -> Result<Change> {
+        self.query(|q| &q.changes, revision, || self.inner.get_change(revision))
+    }
+
+    fn query_change_ids(&self, revset: &str) -> Result<Vec<String>> {
+        Ok(self
+            .query_commit_ids(revset)?
+            .into_iter()
+            .map(|(change_id, _)| change_id)
+            .collect())
+    }
+
+    fn query_commit_ids(&self, revset: &str) -> Result<Vec<(String, String)>> {
+        self.query(|q| &q.commit_ids, revset, || self.inner.query_commit_ids(revset))
+    }
+
+    fn changed_files_between(&self, from: &str, to: &str) -> Result<Vec<FileDiff>> {
+        match self.commit_pair(from, to)? {
+            Some(key) => CHANGED_FILES.get_or_try_insert(key, || self.inner.changed_files_between(from, to)),
+            None => self.inner.changed_files_between(from, to),
+        }
+    }
+
+    fn diff_raw_between_paths(&self, from: &str, to: &str, paths: &[&str], context: Option<usize>) -> Result<String> {
+        match self.commit_pair(from, to)? {
+            Some((from_commit, to_commit)) => {
+                let key = (from_commit, to_commit, paths.iter().map(|p| p.to_string()).collect(), context);
+                DIFFS.get_or_try_insert(key, || self.inner.diff_raw_between_paths(from, to, paths, context))
+            }
+            None => self.inner.diff_raw_between_paths(from, to, paths, context),
+        }
+    }
+
+    fn show_file(&self, revision: &str, path: &str) -> Result<String> {
+        match self.commit_id(revision)? {
+            Some(commit) => FILES.get_or_try_insert((commit, path.to_string()), || self.inner.show_file(revision, path)),
+            None => self.inner.show_file(revision, path),
+        }
+    }
+
+    fn get_bookmark(&self, name: &str) -> Result<Option<String>> {
+        self.query(|q| &q.bookmarks, name, || self.inner.get_bookmark(name))
+    }
+
+    fn bookmark_create(&self, name: &str, revision: &str) -> Result<()> {
+        self.changed(self.inner.bookmark_create(name, revision))
+    }
+
+    fn move_bookmark(&self, name: &str, revision: &str) -> Result<()> {
+        self.changed(self.inner.move_bookmark(name, revision))
+    }
+
+    fn bookmark_delete(&self, name: &str) -> Result<()> {
+        self.changed(self.inner.bookmark_delete(name))
+    }
+
+    fn git_push_bookmark(&self, bookmark: &str, allow_new: bool) -> Result<String> {
+        self.changed(self.inner.git_push_bookmark(bookmark, allow_new))
+    }
+
+    fn git_fetch(&self) -> Result<String> {
+        self.changed(self.inner.git_fetch())
+    }
+
+    fn git_fetch_branches(&self, branches: &[&str]) -> Result<String> {
+        self.changed(self.inner.git_fetch_branches(branches))
+    }
+
+    fn rebase(&self, revision: &str, destination: &str) -> Result<String> {
+        self.changed(self.inner.rebase(revision, destination))
+    }
+
+    fn clone_for_session(&self, dest: &Path, base: &str, bookmark: &str) -> Result<Box<dyn Vcs>> {
+        Ok(Box::new(Cached::new(self.inner.clone_for_session(dest, base, bookmark)?.into())))
+    }
+
+    fn as_jj(&self) -> Option<&Jj> {
+        self.inner.as_jj()
+    }
+}
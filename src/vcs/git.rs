@@ -0,0 +1,690 @@
+//! The git backend, for repos without jj.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use super::{NoSuchPath, Vcs};
+use crate::jj::{Change, FileDiff, FileStatus};
+
+/// The trailer that carries a commit's change id
+const CHANGE_ID_TRAILER: &str = "Change-Id";
+
+/// First line of the commit-msg hook aipair installs, to recognize it
+const HOOK_MARKER: &str = "# aipair: give commits a Change-Id trailer";
+
+/// Gives each new commit a jj-style change id (32 letters k-z), unless its
+/// message already has one
+const CHANGE_ID_HOOK: &str = r#"#!/bin/sh
+# aipair: give commits a Change-Id trailer
+grep -q '^Change-Id: ' "$1" && exit 0
+id=$(head -c 16 /dev/urandom | od -An -tx1 | tr -d ' \n' | tr '0123456789abcdef' 'zyxwvutsrqponmlk')
+git interpret-trailers --in-place --trailer "Change-Id: $id" "$1"
+"#;
+
+/// What git diffs a root commit against
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Fields of one commit in `git log` output, separated by 0x1f
+const LOG_FORMAT: &str = "--format=%H%x1f%T%x1f%P%x1f%ae%x1f%cI%x1f%B";
+
+/// (change id, commit id) of every commit reachable from a ref
+type ChangeMap = Arc<Vec<(String, String)>>;
+
+pub struct Git {
+    repo_path: PathBuf,
+    /// The change map, with the `git show-ref --head` output it was built for
+    changes: Mutex<Option<(String, ChangeMap)>>,
+}
+
+impl Git {
+    pub fn new(repo_path: impl AsRef<Path>) -> Self {
+        Self {
+            repo_path: repo_path.as_ref().to_path_buf(),
+            changes: Mutex::new(None),
+        }
+    }
+
+    pub fn discover() -> Result<Self> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--show-toplevel"])
+            .output()
+            .context("Failed to run git rev-parse")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Not in a git repository: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let root = String::from_utf8(output.stdout)?.trim().to_string();
+        Ok(Self::new(root))
+    }
+
+    /// Run git in the repo and return its stdout
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .current_dir(&self.repo_path)
+            .args(["-c", "core.quotePath=false"])
+            .args(args)
+            .output()
+            .with_context(|| format!("Failed to run git {}", args[0]))?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Install the hook that gives new commits a change id. Returns false
+    /// if the repo already has a commit-msg hook of its own.
+    pub fn install_change_id_hook(&self) -> Result<bool> {
+        let hooks = PathBuf::from(self.git(&["rev-parse", "--git-path", "hooks"])?.trim());
+        let hooks = self.repo_path.join(hooks);
+        let hook = hooks.join("commit-msg");
+        if hook.exists() {
+            return Ok(std::fs::read_to_string(&hook)?.contains(HOOK_MARKER));
+        }
+        std::fs::create_dir_all(&hooks)?;
+        std::fs::write(&hook, CHANGE_ID_HOOK)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(true)
+    }
+
+    /// The branch HEAD is on, if any
+    fn current_branch(&self) -> Option<String> {
+        let branch = self.git(&["symbolic-ref", "--quiet", "--short", "HEAD"]).ok()?;
+        Some(branch.trim().to_string())
+    }
+
+    /// The commit a jj-style revision names: `@` is HEAD, a trailing `-`
+    /// is the parent and `name@origin` a remote branch. Anything else goes
+    /// to git as is, and if git doesn't know it, letters k-z are taken as a
+    /// change id (prefix ok).
+    fn resolve(&self, revision: &str) -> Result<String> {
+        if revision == EMPTY_TREE {
+            return Ok(revision.to_string());
+        }
+        if let Some(child) = revision.strip_suffix('-') {
+            let commit = self.resolve(child)?;
+            let parents = self.git(&["rev-list", "--parents", "-n", "1", &commit])?;
+            return Ok(parents.split_whitespace().nth(1).unwrap_or(EMPTY_TREE).to_string());
+        }
+        let rev = match revision {
+            "@" => "HEAD".to_string(),
+            _ => match revision.strip_suffix("@origin") {
+                Some(branch) => format!("refs/remotes/origin/{branch}"),
+                None => revision.to_string(),
+            },
+        };
+        if let Ok(commit) = self.git(&["rev-parse", "--verify", "--quiet", &format!("{rev}^{{commit}}")]) {
+            return Ok(commit.trim().to_string());
+        }
+        if is_change_id(&rev)
+            && let Some(commit) = self.commit_for_change(&rev)?
+        {
+            return Ok(commit);
+        }
+        anyhow::bail!("Revision `{revision}` doesn't exist")
+    }
+
+    /// The newest commit reachable from any ref with the given change id
+    fn commit_for_change(&self, prefix: &str) -> Result<Option<String>> {
+        let mut found: Option<(String, String)> = None;
+        for (change_id, commit_id) in self.change_map()?.iter().cloned() {
+            if !change_id.starts_with(prefix) {
+                continue;
+            }
+            match &found {
+                None => found = Some((change_id, commit_id)),
+                Some((other, _)) if *other != change_id => {
+                    anyhow::bail!("Change id prefix {prefix} is ambiguous")
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(found.map(|(_, commit_id)| commit_id))
+    }
+
+    /// The change ids of every commit reachable from a ref, rebuilt only
+    /// when HEAD or a ref has moved
+    fn change_map(&self) -> Result<ChangeMap> {
+        // Fails in a repo without commits, which has no changes either
+        let refs = self.git(&["show-ref", "--head"]).unwrap_or_default();
+        if let Some((built_for, map)) = &*self.changes.lock().unwrap()
+            && *built_for == refs
+        {
+            return Ok(map.clone());
+        }
+        let map = Arc::new(self.change_ids(&["--all".to_string()])?);
+        *self.changes.lock().unwrap() = Some((refs, map.clone()));
+        Ok(map)
+    }
+
+    /// (change id, commit id) of the commits `git log <args>` lists
+    fn change_ids(&self, args: &[String]) -> Result<Vec<(String, String)>> {
+        let mut log_args = vec!["log", "-z", "--format=%H%x1f%(trailers:key=Change-Id,valueonly)"];
+        log_args.extend(args.iter().map(String::as_str));
+        let output = self.git(&log_args)?;
+        Ok(output
+            .split('\0')
+            .filter_map(|record| {
+                let (commit_id, trailer) = record.trim_start_matches('\n').split_once('\x1f')?;
+                let change_id = trailer
+                    .lines()
+                    .map(str::trim)
+                    .find(|id| is_change_id(id))
+                    .map(str::to_string)
+                    .unwrap_or_else(|| change_id_from_commit(commit_id));
+                Some((change_id, commit_id.to_string()))
+            })
+            .collect())
+    }
+
+    /// `git log` arguments selecting a revset. Only the forms aipair uses
+    /// are supported: a single revision, `a | b | ...`, `all()`, `::x`,
+    /// `x..y` (where `y` may be `visible_heads()`) and `ancestors(x, n)`.
+    fn rev_args(&self, revset: &str) -> Result<Vec<String>> {
+        let revset = revset.trim();
+        if revset == "all()" {
+            return Ok(vec!["--all".to_string()]);
+        }
+        if let Some(inner) = revset.strip_prefix("ancestors(").and_then(|r| r.strip_suffix(')')) {
+            let (head, depth) = inner.rsplit_once(',').context("ancestors() needs a depth")?;
+            let depth: usize = depth.trim().parse().context("ancestors() needs a numeric depth")?;
+            let head = match head.trim() {
+                "visible_heads()" => "--all".to_string(),
+                head => self.resolve(head)?,
+            };
+            return Ok(vec![format!("--max-count={depth}"), head]);
+        }
+        if let Some(head) = revset.strip_prefix("::") {
+            return Ok(vec![self.resolve(head)?]);
+        }
+        if let Some((from, to)) = revset.split_once("..") {
+            let to = match to.trim() {
+                "visible_heads()" => "--all".to_string(),
+                to => self.resolve(to)?,
+            };
+            return Ok(vec![to, format!("^{}", self.resolve(from.trim())?)]);
+        }
+        if revset.contains('|') {
+            let mut args = vec!["--no-walk".to_string()];
+            for revision in revset.split('|') {
+                args.push(self.resolve(revision.trim())?);
+            }
+            return Ok(args);
+        }
+        if revset.contains(|c: char| c.is_whitespace() || "()&|~:".contains(c)) {
+            anyhow::bail!("The git backend doesn't support the revset `{revset}`");
+        }
+        Ok(vec!["--no-walk".to_string(), self.resolve(revset)?])
+    }
+
+    /// The changes `git log <args>` lists
+    fn log_changes(&self, args: &[String]) -> Result<Vec<Change>> {
+        let mut log_args = vec!["log", "-z", LOG_FORMAT];
+        log_args.extend(args.iter().map(String::as_str));
+        let output = self.git(&log_args)?;
+        let commits: Vec<LogCommit> = output.split('\0').filter_map(parse_log_record).collect();
+
+        // Parents' trees and change ids, for emptiness and parent_change_ids
+        let mut parents: Vec<String> = commits.iter().flat_map(|c| c.parents.clone()).collect();
+        parents.sort();
+        parents.dedup();
+        let mut parent_info: HashMap<String, (String, String)> = HashMap::new();
+        if !parents.is_empty() {
+            let mut args = vec!["log", "-z", "--no-walk=unsorted", "--format=%H%x1f%T%x1f%(trailers:key=Change-Id,valueonly)"];
+            args.extend(parents.iter().map(String::as_str));
+            for record in self.git(&args)?.split('\0') {
+                let mut fields = record.trim_start_matches('\n').splitn(3, '\x1f');
+                let (Some(commit), Some(tree), Some(trailer)) = (fields.next(), fields.next(), fields.next()) else {
+                    continue;
+                };
+                let change_id = trailer
+                    .lines()
+                    .map(str::trim)
+                    .find(|id| is_change_id(id))
+                    .map(str::to_string)
+                    .unwrap_or_else(|| change_id_from_commit(commit));
+                parent_info.insert(commit.to_string(), (tree.to_string(), change_id));
+            }
+        }
+        let head = self.git(&["rev-parse", "--verify", "--quiet", "HEAD"]).unwrap_or_default();
+        let head = head.trim();
+
+        Ok(commits
+            .into_iter()
+            .map(|c| {
+                let parent_tree = match c.parents.first() {
+                    Some(parent) => parent_info.get(parent).map(|(tree, _)| tree.as_str()),
+                    None => Some(EMPTY_TREE),
+                };
+                Change {
+                    change_id: c.change_id,
+                    empty: parent_tree == Some(c.tree.as_str()),
+                    is_working_copy: c.commit_id == head,
+                    parent_change_ids: c
+                        .parents
+                        .iter()
+                        .map(|p| parent_info.get(p).map(|(_, id)| id.clone()).unwrap_or_else(|| change_id_from_commit(p)))
+                        .collect(),
+                    commit_id: c.commit_id,
+                    description: c.description,
+                    author: c.author,
+                    timestamp: c.timestamp,
+                    conflict: false,
+                }
+            })
+            .collect())
+    }
+}
+
+impl Vcs for Git {
+    fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    fn log_revset(&self, revset: &str) -> Result<Vec<Change>> {
+        self.log_changes(&self.rev_args(revset)?)
+    }
+
+    fn get_change(&self, revision: &str) -> Result<Change> {
+        let commit = self.resolve(revision)?;
+        self.log_changes(&["--no-walk".to_string(), commit])?
+            .pop()
+            .with_context(|| format!("No commit for {revision}"))
+    }
+
+    fn query_change_ids(&self, revset: &str) -> Result<Vec<String>> {
+        Ok(self.query_commit_ids(revset)?.into_iter().map(|(change_id, _)| change_id).collect())
+    }
+
+    fn query_commit_ids(&self, revset: &str) -> Result<Vec<(String, String)>> {
+        self.change_ids(&self.rev_args(revset)?)
+    }
+
+    fn changed_files_between(&self, from: &str, to: &str) -> Result<Vec<FileDiff>> {
+        let (from, to) = (self.resolve(from)?, self.resolve(to)?);
+        let output = self.git(&["diff", "--name-status", "-z", "-M", "-C", &from, &to])?;
+        Ok(parse_name_status(&output))
+    }
+
+    fn diff_raw_between_paths(&self, from: &str, to: &str, paths: &[&str], context: Option<usize>) -> Result<String> {
+        let (from, to) = (self.resolve(from)?, self.resolve(to)?);
+        let context = context.map(|c| format!("-U{c}"));
+        let mut args = vec!["diff", "--no-color", "--no-ext-diff", "-M", "-C", "--src-prefix=a/", "--dst-prefix=b/"];
+        args.extend(context.as_deref());
+        args.extend([from.as_str(), to.as_str(), "--"]);
+        args.extend_from_slice(paths);
+        self.git(&args)
+    }
+
+    fn show_file(&self, revision: &str, path: &str) -> Result<String> {
+        let commit = self.resolve(revision)?;
+        self.git(&["show", &format!("{commit}:{path}")]).map_err(|err| {
+            // "path '<path>' does not exist in '<commit>'", or "exists on
+            // disk, but not in '<commit>'"
+            let message = err.to_string();
+            if message.contains("does not exist in") || message.contains("but not in") {
+                NoSuchPath { path: path.to_string() }.into()
+            } else {
+                err
+            }
+        })
+    }
+
+    fn get_bookmark(&self, name: &str) -> Result<Option<String>> {
+        let branch = format!("refs/heads/{name}");
+        if self.git(&["rev-parse", "--verify", "--quiet", &branch]).is_err() {
+            return Ok(None);
+        }
+        let ids = self.change_ids(&["--no-walk".to_string(), branch])?;
+        Ok(ids.into_iter().next().map(|(change_id, _)| change_id))
+    }
+
+    fn bookmark_create(&self, name: &str, revision: &str) -> Result<()> {
+        self.git(&["branch", name, &self.resolve(revision)?])?;
+        Ok(())
+    }
+
+    fn move_bookmark(&self, name: &str, revision: &str) -> Result<()> {
+        let commit = self.resolve(revision)?;
+        if self.current_branch().as_deref() == Some(name) {
+            // Moving the checked-out branch moves the working tree along,
+            // keeping uncommitted edits
+            self.git(&["reset", "--keep", &commit])?;
+        } else {
+            self.git(&["branch", "--force", name, &commit])?;
+        }
+        Ok(())
+    }
+
+    fn bookmark_delete(&self, name: &str) -> Result<()> {
+        self.git(&["branch", "-D", name])?;
+        Ok(())
+    }
+
+    fn git_push_bookmark(&self, bookmark: &str, _allow_new: bool) -> Result<String> {
+        let output = Command::new("git")
+            .current_dir(&self.repo_path)
+            .args(["push", "--force-with-lease", "origin", &format!("refs/heads/{bookmark}:refs/heads/{bookmark}")])
+            .output()
+            .context("Failed to run git push")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git push failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        // git reports what it pushed on stderr
+        Ok(String::from_utf8(output.stderr)?)
+    }
+
+    fn git_fetch(&self) -> Result<String> {
+        self.git(&["fetch"])
+    }
+
+    fn git_fetch_branches(&self, branches: &[&str]) -> Result<String> {
+        let refspecs: Vec<String> = branches.iter().map(|b| format!("+refs/heads/{b}:refs/remotes/origin/{b}")).collect();
+        let mut args = vec!["fetch", "origin"];
+        args.extend(refspecs.iter().map(String::as_str));
+        self.git(&args)
+    }
+
+    fn rebase(&self, revision: &str, destination: &str) -> Result<String> {
+        if revision != "@" {
+            anyhow::bail!("The git backend can only rebase the checked-out branch, not {revision}");
+        }
+        let destination = self.resolve(destination)?;
+        self.git(&["rebase", &destination]).context(
+            "The rebase stopped on conflicts; resolve them and run `git rebase --continue`, or `git rebase --abort`",
+        )
+    }
+
+    fn clone_for_session(&self, dest: &Path, base: &str, bookmark: &str) -> Result<Box<dyn Vcs>> {
+        let output = Command::new("git")
+            .args(["clone", "--single-branch", "--branch", base])
+            .arg(&self.repo_path)
+            .arg(dest)
+            .output()
+            .context("Failed to run git clone")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git clone failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let clone = Git::new(dest);
+        // Keep a remote-tracking branch for the session's pushes, as jj does
+        clone.git(&[
+            "config",
+            "--add",
+            "remote.origin.fetch",
+            &format!("+refs/heads/{bookmark}:refs/remotes/origin/{bookmark}"),
+        ])?;
+        clone.git(&["checkout", "-b", bookmark])?;
+        clone.install_change_id_hook()?;
+        Ok(Box::new(clone))
+    }
+}
+
+/// Whether `id` looks like a change id: jj's reverse-hex letters k-z
+fn is_change_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| ('k'..='z').contains(&c))
+}
+
+/// The change id of a commit without a Change-Id trailer: its hash spelled
+/// the way jj spells change ids
+fn change_id_from_commit(commit_id: &str) -> String {
+    commit_id
+        .chars()
+        .take(32)
+        .map(|c| match c.to_digit(16) {
+            Some(digit) => (b'z' - digit as u8) as char,
+            None => c,
+        })
+        .collect()
+}
+
+struct LogCommit {
+    commit_id: String,
+    tree: String,
+    parents: Vec<String>,
+    author: String,
+    timestamp: String,
+    change_id: String,
+    description: String,
+}
+
+/// Parse one `LOG_FORMAT` record, splitting the change id trailer off the
+/// description
+fn parse_log_record(record: &str) -> Option<LogCommit> {
+    let mut fields = record.trim_start_matches('\n').splitn(6, '\x1f');
+    let commit_id = fields.next()?.to_string();
+    let tree = fields.next()?.to_string();
+    let parents = fields.next()?.split_whitespace().map(str::to_string).collect();
+    let author = fields.next()?.to_string();
+    let timestamp = fields.next()?.to_string();
+    let message = fields.next()?;
+
+    let prefix = format!("{CHANGE_ID_TRAILER}: ");
+    let mut change_id = None;
+    let lines: Vec<&str> = message
+        .lines()
+        .filter(|line| match line.strip_prefix(&prefix).map(str::trim) {
+            Some(id) if is_change_id(id) && change_id.is_none() => {
+                change_id = Some(id.to_string());
+                false
+            }
+            _ => true,
+        })
+        .collect();
+    Some(LogCommit {
+        change_id: change_id.unwrap_or_else(|| change_id_from_commit(&commit_id)),
+        commit_id,
+        tree,
+        parents,
+        author,
+        timestamp,
+        description: lines.join("\n").trim_end().to_string(),
+    })
+}
+
+/// Parse `git diff --name-status -z`: a status, then one path, or two for
+/// renames and copies
+fn parse_name_status(output: &str) -> Vec<FileDiff> {
+    let mut fields = output.split('\0').filter(|f| !f.is_empty());
+    let mut files = Vec::new();
+    while let Some(status) = fields.next() {
+        let file = match status.chars().next() {
+            Some('R' | 'C') => {
+                let (Some(from), Some(path)) = (fields.next(), fields.next()) else {
+                    break;
+                };
+                let from = from.to_string();
+                let status = if status.starts_with('R') {
+                    FileStatus::Renamed { from }
+                } else {
+                    FileStatus::Copied { from }
+                };
+                FileDiff::new(path.to_string(), status)
+            }
+            Some(kind) => {
+                let Some(path) = fields.next() else { break };
+                let status = match kind {
+                    'A' => FileStatus::Added,
+                    'D' => FileStatus::Deleted,
+                    _ => FileStatus::Modified,
+                };
+                FileDiff::new(path.to_string(), status)
+            }
+            None => break,
+        };
+        files.push(file);
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git_cmd(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        if !output.status.success() {
+            panic!("git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn commit(dir: &Path, file: &str, content: &str, message: &str) {
+        std::fs::write(dir.join(file), content).unwrap();
+        git_cmd(dir, &["add", "-A"]);
+        git_cmd(dir, &["commit", "-q", "-m", message]);
+    }
+
+    /// A repo on `main` with the change id hook and one commit
+    fn make_git_repo() -> (TempDir, Git) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path();
+        git_cmd(path, &["init", "-q", "-b", "main"]);
+        git_cmd(path, &["config", "user.email", "test@example.com"]);
+        git_cmd(path, &["config", "user.name", "Test"]);
+        let git = Git::new(path);
+        assert!(git.install_change_id_hook().unwrap());
+        commit(path, "a.txt", "one\n", "First");
+        (dir, git)
+    }
+
+    #[test]
+    fn test_change_ids_survive_amend() {
+        let (dir, git) = make_git_repo();
+        let path = dir.path();
+        commit(path, "a.txt", "one\ntwo\n", "Second\n\nWith a body");
+
+        let change = git.get_change("@").unwrap();
+        assert!(is_change_id(&change.change_id));
+        assert_eq!(change.change_id.len(), 32);
+        assert_eq!(change.description, "Second\n\nWith a body");
+        assert!(change.is_working_copy);
+        let first = git.get_change("@-").unwrap();
+        assert_eq!(change.parent_change_ids, vec![first.change_id.clone()]);
+
+        std::fs::write(path.join("a.txt"), "one\n2\n").unwrap();
+        git_cmd(path, &["commit", "-q", "-a", "--amend", "--no-edit"]);
+        let amended = git.get_change(&change.change_id[..8]).unwrap();
+        assert_eq!(amended.change_id, change.change_id);
+        assert_ne!(amended.commit_id, change.commit_id);
+
+        let changes = git.log_revset("ancestors(main, 10)").unwrap();
+        let ids: Vec<_> = changes.iter().map(|c| c.change_id.as_str()).collect();
+        assert_eq!(ids, vec![change.change_id.as_str(), first.change_id.as_str()]);
+        assert_eq!(git.query_change_ids(&format!("{}..@", first.change_id)).unwrap(), vec![change.change_id.clone()]);
+        let both = format!("{} | {}", first.commit_id, amended.commit_id);
+        assert_eq!(git.log_revset(&both).unwrap().len(), 2);
+        assert!(git.log_revset("mutable()").is_err());
+    }
+
+    #[test]
+    fn test_diff_and_show_file() {
+        let (dir, git) = make_git_repo();
+        let path = dir.path();
+        git_cmd(path, &["mv", "a.txt", "b.txt"]);
+        commit(path, "c.txt", "new\n", "Move and add");
+
+        let mut files = git.changed_files_between("@-", "@").unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(files[0].status, FileStatus::Renamed { from: "a.txt".into() });
+        assert_eq!(files[1].status, FileStatus::Added);
+
+        let diff = git.diff("@", None, 3).unwrap();
+        let added = diff.files.iter().find(|f| f.path == "c.txt").unwrap();
+        assert_eq!(added.hunks[0].lines[0].content, "new");
+        assert_eq!(git.show_file("@", "b.txt").unwrap(), "one\n");
+        assert!(crate::vcs::is_no_such_path(&git.show_file("@", "a.txt").unwrap_err()));
+
+        // The first commit diffs against nothing
+        let first = git.get_change("@-").unwrap();
+        assert_eq!(git.diff(&first.change_id, None, 3).unwrap().files[0].status, FileStatus::Added);
+    }
+
+    #[test]
+    fn test_bookmarks_and_session_clone() {
+        let (dir, git) = make_git_repo();
+        let path = dir.path();
+        let main = git.get_bookmark("main").unwrap().unwrap();
+        assert_eq!(git.get_bookmark("nope").unwrap(), None);
+
+        let clone_path = path.join(".aipair/sessions/s/repo");
+        let clone = git.clone_for_session(&clone_path, "main", "session/s").unwrap();
+        git_cmd(&clone_path, &["config", "user.email", "agent@example.com"]);
+        git_cmd(&clone_path, &["config", "user.name", "Agent"]);
+        commit(&clone_path, "a.txt", "one\ntwo\n", "Work");
+        clone.move_bookmark("session/s", "@").unwrap();
+        clone.git_push_bookmark("session/s", true).unwrap();
+
+        let tip = git.get_bookmark("session/s").unwrap().unwrap();
+        assert_eq!(tip, clone.get_change("@").unwrap().change_id);
+        assert_eq!(git.log_revset("main..session/s").unwrap().len(), 1);
+
+        // Merging moves the checked-out main along with its files
+        git.move_bookmark("main", &tip).unwrap();
+        git.bookmark_delete("session/s").unwrap();
+        assert_ne!(git.get_bookmark("main").unwrap().unwrap(), main);
+        assert_eq!(std::fs::read_to_string(path.join("a.txt")).unwrap(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_parse_name_status() {
+        let files = parse_name_status("M\0src/a.rs\0R087\0old.rs\0new.rs\0A\0b c.rs\0D\0gone.rs\0");
+        assert_eq!(
+            files,
+            vec![
+                FileDiff::new("src/a.rs".into(), FileStatus::Modified),
+                FileDiff::new("new.rs".into(), FileStatus::Renamed { from: "old.rs".into() }),
+                FileDiff::new("b c.rs".into(), FileStatus::Added),
+                FileDiff::new("gone.rs".into(), FileStatus::Deleted),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_change_id_from_commit() {
+        assert_eq!(change_id_from_commit("0123456789abcdef"), "zyxwvutsrqponmlk");
+        assert!(is_change_id(&change_id_from_commit("4b825dc642cb6eb9a060e54bf8d69288fbee4904")));
+    }
+
+    #[test]
+    fn test_branches_win_over_change_id_prefixes() {
+        let (dir, git) = make_git_repo();
+        let path = dir.path();
+        let first = git.get_change("@").unwrap();
+        commit(path, "a.txt", "one\ntwo\n", "Second\n\nChange-Id: workworkworkworkworkworkworkwork");
+        let second = git.get_change("@").unwrap();
+        assert_eq!(git.get_change("work").unwrap().commit_id, second.commit_id);
+
+        // A branch named like a change id prefix is the branch
+        git_cmd(path, &["branch", "work", &first.commit_id]);
+        assert_eq!(git.get_change("work").unwrap().commit_id, first.commit_id);
+        assert_eq!(git.get_change("workwork").unwrap().commit_id, second.commit_id);
+        assert!(git.get_change("zzzzzzzzzzzzzzzz").is_err());
+    }
+}
@@ -0,0 +1,774 @@
+//! The jj backend that keeps the repo loaded in-process.
+//!
+//! [`Jj`] spawns a `jj` process per query, and each one loads the repo, its
+//! operation log and its index from scratch. `JjLib` loads them once with
+//! jj-lib and answers the read-only queries itself: logs and revsets, diffs,
+//! file contents and bookmarks. Loaded repos are shared by everything in the
+//! process that opens the same path.
+//!
+//! Before each query it picks up operations other processes have added.
+//! Edits to the working copy are only looked for when the operation id is
+//! asked for, by snapshotting it in memory: [`super::Cached`] asks once per
+//! instance, so that's once per request or command rather than before every
+//! query. Edits are recorded by having `jj` snapshot them, so commits are
+//! only ever written the way jj writes them. Everything else that changes the repo, and
+//! whatever jj-lib alone can't answer the way `jj` would (revsets that use
+//! aliases from jj's config, diffs of conflicts and symlinks), goes to the
+//! subprocess [`Jj`]. Git refs changed with plain git in a colocated repo
+//! show up after the next jj command imports them.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use futures_util::StreamExt;
+use jj_lib::backend::{CommitId, Timestamp, TreeValue};
+use jj_lib::commit::Commit;
+use jj_lib::config::StackedConfig;
+use jj_lib::conflicts::ConflictMarkerStyle;
+use jj_lib::copies::{CopyOperation, CopyRecords};
+use jj_lib::fsmonitor::FsmonitorSettings;
+use jj_lib::git_backend::GitBackend;
+use jj_lib::gitignore::GitIgnoreFile;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::merge::MergedTreeValue;
+use jj_lib::object_id::ObjectId;
+use jj_lib::ref_name::WorkspaceNameBuf;
+use jj_lib::repo::{ReadonlyRepo, Repo, StoreFactories};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathUiConverter};
+use jj_lib::revset::{
+    self, DefaultSymbolResolver, RevsetAliasesMap, RevsetDiagnostics, RevsetExtensions, RevsetParseContext,
+    RevsetResolutionError, RevsetWorkspaceContext,
+};
+use jj_lib::settings::UserSettings;
+use jj_lib::working_copy::SnapshotOptions;
+use jj_lib::workspace::{Workspace, default_working_copy_factories};
+use pollster::FutureExt;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use tracing::debug;
+
+use super::{NoSuchPath, Vcs};
+use crate::diff::{self, LineKind};
+use crate::jj::{Change, FileDiff, FileStatus, Jj};
+
+/// Largest untracked file a snapshot picks up, jj's default
+const MAX_NEW_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Repos loaded so far, by the path they were opened with
+static LOADED: LazyLock<Mutex<HashMap<PathBuf, JjLib>>> = LazyLock::new(Default::default);
+
+#[derive(Clone)]
+pub struct JjLib {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// For changes to the repo and queries jj-lib can't answer
+    jj: Jj,
+    root: PathBuf,
+    workspace_name: WorkspaceNameBuf,
+    loaded: Mutex<Loaded>,
+}
+
+struct Loaded {
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    /// Ignore patterns from outside the working copy, like jj's
+    base_ignores: Arc<GitIgnoreFile>,
+}
+
+impl JjLib {
+    /// The repo at `path`, loaded on first use, or again if it has been
+    /// replaced since (as session clones are)
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut loaded = LOADED.lock().unwrap();
+        if let Some(lib) = loaded.get(path)
+            && lib.inner.loaded.lock().unwrap().reload_if_moved().is_ok()
+        {
+            return Ok(lib.clone());
+        }
+        let lib = Self::load(path)?;
+        loaded.insert(path.to_path_buf(), lib.clone());
+        Ok(lib)
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let loaded = Loaded::load(path)?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                jj: Jj::new(path),
+                root: loaded.workspace.workspace_root().to_path_buf(),
+                workspace_name: loaded.workspace.workspace_name().to_owned(),
+                loaded: Mutex::new(loaded),
+            }),
+        })
+    }
+
+    /// The repo as of its latest operation
+    fn repo(&self) -> Result<Arc<ReadonlyRepo>> {
+        let mut loaded = self.inner.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        Ok(loaded.repo.clone())
+    }
+
+    /// The repo as of its latest operation, with edits to the working copy
+    /// snapshotted
+    fn repo_with_edits(&self) -> Result<Arc<ReadonlyRepo>> {
+        let mut loaded = self.inner.loaded.lock().unwrap();
+        self.reload(&mut loaded)?;
+        if loaded.working_copy_edited(&self.inner.workspace_name)? {
+            // Any jj command snapshots the working copy before it runs
+            self.inner.jj.working_copy_change_id()?;
+            loaded.reload_if_moved()?;
+        }
+        Ok(loaded.repo.clone())
+    }
+
+    fn reload(&self, loaded: &mut Loaded) -> Result<()> {
+        if loaded.reload_if_moved().is_err() {
+            // Replaced since it was loaded, as session clones are
+            *loaded = Loaded::load(&self.inner.root)?;
+        }
+        Ok(())
+    }
+
+    /// Commits matching a revset, newest first, or None when it needs
+    /// something only `jj` knows, such as an alias from its config
+    fn evaluate(&self, repo: &ReadonlyRepo, revset: &str) -> Result<Option<Vec<CommitId>>> {
+        let aliases = RevsetAliasesMap::new();
+        let extensions = RevsetExtensions::default();
+        let path_converter = RepoPathUiConverter::Fs {
+            cwd: self.inner.root.clone(),
+            base: self.inner.root.clone(),
+        };
+        let context = RevsetParseContext {
+            aliases_map: &aliases,
+            local_variables: HashMap::new(),
+            user_email: "",
+            date_pattern_context: chrono::Local::now().into(),
+            extensions: &extensions,
+            workspace: Some(RevsetWorkspaceContext {
+                path_converter: &path_converter,
+                workspace_name: &self.inner.workspace_name,
+            }),
+        };
+
+        let expression = match revset::parse(&mut RevsetDiagnostics::new(), revset, &context) {
+            Ok(expression) => expression,
+            Err(err) => {
+                debug!("Leaving revset {revset} to jj: {err}");
+                return Ok(None);
+            }
+        };
+        let symbol_resolver = DefaultSymbolResolver::new(repo, extensions.symbol_resolvers());
+        let ids = expression
+            .resolve_user_expression(repo, &symbol_resolver)?
+            .evaluate(repo)?
+            .iter()
+            .collect::<Result<_, _>>()?;
+        Ok(Some(ids))
+    }
+
+    /// The single commit a revision names, or None if `jj` has to resolve it
+    fn commit(&self, repo: &ReadonlyRepo, revision: &str) -> Result<Option<Commit>> {
+        let Some(ids) = self.evaluate(repo, revision)? else {
+            return Ok(None);
+        };
+        match ids.as_slice() {
+            [id] => Ok(Some(repo.store().get_commit(id)?)),
+            [] => anyhow::bail!("Revset `{revision}` didn't resolve to any revisions"),
+            _ => anyhow::bail!("Revset `{revision}` resolved to more than one revision"),
+        }
+    }
+
+    fn change(&self, repo: &ReadonlyRepo, commit: &Commit) -> Result<Change> {
+        let parents: Vec<Commit> = commit.parents().collect::<Result<_, _>>()?;
+        Ok(Change {
+            change_id: commit.change_id().reverse_hex(),
+            commit_id: commit.id().hex(),
+            description: commit.description().trim_end().to_string(),
+            author: commit.author().email.clone(),
+            timestamp: format_timestamp(&commit.committer().timestamp),
+            empty: commit.is_empty(repo)?,
+            conflict: commit.has_conflict()?,
+            is_working_copy: repo.view().get_wc_commit_id(&self.inner.workspace_name) == Some(commit.id()),
+            parent_change_ids: parents.iter().map(|p| p.change_id().reverse_hex()).collect(),
+        })
+    }
+
+    /// Files that differ between two commits, with renames and copies
+    /// detected the way `jj diff` does
+    fn file_changes(&self, repo: &ReadonlyRepo, from: &Commit, to: &Commit) -> Result<Vec<FileChange>> {
+        let mut copy_records = CopyRecords::default();
+        let records = repo.store().get_copy_records(None, from.id(), to.id())?;
+        copy_records.add_records(records.collect::<Vec<_>>().block_on())?;
+
+        let (from_tree, to_tree) = (from.tree()?, to.tree()?);
+        from_tree
+            .diff_stream_with_copies(&to_tree, &EverythingMatcher, &copy_records)
+            .collect::<Vec<_>>()
+            .block_on()
+            .into_iter()
+            .map(|entry| {
+                let (before, after) = entry.values?;
+                Ok(FileChange { source: entry.path.source, path: entry.path.target, before, after })
+            })
+            .collect()
+    }
+}
+
+impl Loaded {
+    fn load(path: &Path) -> Result<Self> {
+        let settings = UserSettings::from_config(StackedConfig::with_defaults())?;
+        let workspace = Workspace::load(
+            &settings,
+            path,
+            &StoreFactories::default(),
+            &default_working_copy_factories(),
+        )
+        .with_context(|| format!("Failed to load jj repo at {}", path.display()))?;
+        let repo = workspace.repo_loader().load_at_head()?;
+        let base_ignores = base_ignores(&repo);
+        Ok(Self { workspace, repo, base_ignores })
+    }
+
+    /// Load the repo again if another process added operations since
+    fn reload_if_moved(&mut self) -> Result<()> {
+        let heads = self.repo.op_heads_store().get_op_heads()?;
+        if heads.as_slice() != [self.repo.op_id().clone()] {
+            self.repo = self.workspace.repo_loader().load_at_head()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the files on disk differ from the working-copy commit. The
+    /// snapshot is only compared, never recorded.
+    fn working_copy_edited(&mut self, workspace_name: &WorkspaceNameBuf) -> Result<bool> {
+        let Some(wc_commit_id) = self.repo.view().get_wc_commit_id(workspace_name) else {
+            return Ok(false);
+        };
+        let wc_commit = self.repo.store().get_commit(wc_commit_id)?;
+        let options = SnapshotOptions {
+            base_ignores: self.base_ignores.clone(),
+            fsmonitor_settings: FsmonitorSettings::None,
+            progress: None,
+            start_tracking_matcher: &EverythingMatcher,
+            max_new_file_size: MAX_NEW_FILE_SIZE,
+            conflict_marker_style: ConflictMarkerStyle::default(),
+        };
+        // Dropping the lock without finishing leaves the working copy's
+        // state as jj last wrote it
+        let mut locked = self.workspace.start_working_copy_mutation()?;
+        let (tree_id, _) = locked.locked_wc().snapshot(&options)?;
+        Ok(&tree_id != wc_commit.tree_id())
+    }
+}
+
+/// One entry of a tree diff
+struct FileChange {
+    source: Option<(RepoPathBuf, CopyOperation)>,
+    path: RepoPathBuf,
+    before: MergedTreeValue,
+    after: MergedTreeValue,
+}
+
+impl FileChange {
+    /// Where the file was before, which differs from `path` for renames
+    /// and copies
+    fn source_path(&self) -> &RepoPath {
+        self.source.as_ref().map_or(&self.path, |(from, _)| from)
+    }
+
+    fn status(&self) -> FileStatus {
+        match &self.source {
+            Some((from, CopyOperation::Rename)) => FileStatus::Renamed { from: from.as_internal_file_string().to_string() },
+            Some((from, CopyOperation::Copy)) => FileStatus::Copied { from: from.as_internal_file_string().to_string() },
+            None if self.before.is_absent() => FileStatus::Added,
+            None if self.after.is_absent() => FileStatus::Deleted,
+            None => FileStatus::Modified,
+        }
+    }
+
+    fn touches(&self, paths: &[&str]) -> bool {
+        let source = self.source.as_ref().map(|(from, _)| from.as_internal_file_string());
+        paths.is_empty()
+            || paths
+                .iter()
+                .any(|p| *p == self.path.as_internal_file_string() || Some(*p) == source)
+    }
+}
+
+impl Vcs for JjLib {
+    fn repo_path(&self) -> &Path {
+        self.inner.jj.repo_path()
+    }
+
+    /// The only query that looks for edits to the working copy
+    fn operation_id(&self) -> Result<Option<String>> {
+        Ok(Some(self.repo_with_edits()?.op_id().hex()))
+    }
+
+    fn latest_operation_id(&self) -> Result<Option<String>> {
+        Ok(Some(self.repo()?.op_id().hex()))
+    }
+
+    fn log_revset(&self, revset: &str) -> Result<Vec<Change>> {
+        let repo = self.repo()?;
+        let Some(ids) = self.evaluate(&repo, revset)? else {
+            return self.inner.jj.log_revset(revset);
+        };
+        ids.iter()
+            // The root commit has no parent and can't be diffed
+            .filter(|id| *id != repo.store().root_commit_id())
+            .map(|id| self.change(&repo, &repo.store().get_commit(id)?))
+            .collect()
+    }
+
+    fn get_change(&self, revision: &str) -> Result<Change> {
+        let repo = self.repo()?;
+        let Some(ids) = self.evaluate(&repo, revision)? else {
+            return self.inner.jj.get_change(revision);
+        };
+        let id = ids.first().with_context(|| format!("Revset `{revision}` didn't resolve to any revisions"))?;
+        self.change(&repo, &repo.store().get_commit(id)?)
+    }
+
+    fn query_change_ids(&self, revset: &str) -> Result<Vec<String>> {
+        Ok(self
+            .query_commit_ids(revset)?
+            .into_iter()
+            .map(|(change_id, _)| change_id)
+            .collect())
+    }
+
+    fn query_commit_ids(&self, revset: &str) -> Result<Vec<(String, String)>> {
+        let repo = self.repo()?;
+        let Some(ids) = self.evaluate(&repo, revset)? else {
+            return self.inner.jj.query_commit_ids(revset);
+        };
+        ids.iter()
+            .map(|id| {
+                let commit = repo.store().get_commit(id)?;
+                Ok((commit.change_id().reverse_hex(), id.hex()))
+            })
+            .collect()
+    }
+
+    fn changed_files_between(&self, from: &str, to: &str) -> Result<Vec<FileDiff>> {
+        let repo = self.repo()?;
+        let (Some(from_commit), Some(to_commit)) = (self.commit(&repo, from)?, self.commit(&repo, to)?) else {
+            return self.inner.jj.changed_files_between(from, to);
+        };
+        Ok(self
+            .file_changes(&repo, &from_commit, &to_commit)?
+            .iter()
+            .map(|change| FileDiff::new(change.path.as_internal_file_string().to_string(), change.status()))
+            .collect())
+    }
+
+    fn diff_raw_between_paths(&self, from: &str, to: &str, paths: &[&str], context: Option<usize>) -> Result<String> {
+        let repo = self.repo()?;
+        let (Some(from_commit), Some(to_commit)) = (self.commit(&repo, from)?, self.commit(&repo, to)?) else {
+            return self.inner.jj.diff_raw_between_paths(from, to, paths, context);
+        };
+        let changes = self.file_changes(&repo, &from_commit, &to_commit)?;
+        let changes: Vec<&FileChange> = changes.iter().filter(|c| c.touches(paths)).collect();
+
+        let mut output = String::new();
+        for change in changes {
+            let before = file_content(&repo, change.source_path(), &change.before)?;
+            let after = file_content(&repo, &change.path, &change.after)?;
+            let (Some(before), Some(after)) = (before, after) else {
+                // Conflicts, symlinks and submodules are shown the way jj shows them
+                return self.inner.jj.diff_raw_between_paths(from, to, paths, context);
+            };
+            write_git_patch(&mut output, change, before, after, context.unwrap_or(diff::DEFAULT_CONTEXT));
+        }
+        Ok(output)
+    }
+
+    fn show_file(&self, revision: &str, path: &str) -> Result<String> {
+        let repo = self.repo()?;
+        let Some(commit) = self.commit(&repo, revision)? else {
+            return self.inner.jj.show_file(revision, path);
+        };
+        let repo_path = RepoPath::from_internal_string(path)?;
+        let value = commit.tree()?.path_value(repo_path)?;
+        match file_content(&repo, repo_path, &value)? {
+            Some(Some(file)) => Ok(String::from_utf8(file.content)?),
+            Some(None) => Err(NoSuchPath { path: path.to_string() }.into()),
+            None => self.inner.jj.show_file(revision, path),
+        }
+    }
+
+    fn get_bookmark(&self, name: &str) -> Result<Option<String>> {
+        let repo = self.repo()?;
+        match self.evaluate(&repo, name) {
+            Ok(Some(ids)) => match ids.first() {
+                Some(id) => Ok(Some(repo.store().get_commit(id)?.change_id().reverse_hex())),
+                None => Ok(Some(String::new())),
+            },
+            Ok(None) => self.inner.jj.get_bookmark(name),
+            Err(err) => match err.downcast_ref::<RevsetResolutionError>() {
+                Some(RevsetResolutionError::NoSuchRevision { .. }) => Ok(None),
+                _ => Err(err),
+            },
+        }
+    }
+
+    fn bookmark_create(&self, name: &str, revision: &str) -> Result<()> {
+        self.inner.jj.bookmark_create(name, revision)
+    }
+
+    fn move_bookmark(&self, name: &str, revision: &str) -> Result<()> {
+        self.inner.jj.move_bookmark(name, revision)
+    }
+
+    fn bookmark_delete(&self, name: &str) -> Result<()> {
+        self.inner.jj.bookmark_delete(name)
+    }
+
+    fn git_push_bookmark(&self, bookmark: &str, allow_new: bool) -> Result<String> {
+        self.inner.jj.git_push_bookmark(bookmark, allow_new)
+    }
+
+    fn git_fetch(&self) -> Result<String> {
+        self.inner.jj.git_fetch()
+    }
+
+    fn git_fetch_branches(&self, branches: &[&str]) -> Result<String> {
+        self.inner.jj.git_fetch_branches(branches)
+    }
+
+    fn rebase(&self, revision: &str, destination: &str) -> Result<String> {
+        self.inner.jj.rebase(revision, destination)
+    }
+
+    fn clone_for_session(&self, dest: &Path, base: &str, bookmark: &str) -> Result<Box<dyn Vcs>> {
+        self.inner.jj.clone_for_session(dest, base, bookmark)?;
+        Ok(super::open_backend(dest))
+    }
+
+    fn as_jj(&self) -> Option<&Jj> {
+        Some(&self.inner.jj)
+    }
+}
+
+/// A file's content and mode at one side of a diff
+struct FileContent {
+    content: Vec<u8>,
+    executable: bool,
+}
+
+/// The file a tree value holds: Some(None) when there's no file there, and
+/// None when it's something other than a plain file
+fn file_content(repo: &ReadonlyRepo, path: &RepoPath, value: &MergedTreeValue) -> Result<Option<Option<FileContent>>> {
+    match value.as_resolved() {
+        Some(None) => Ok(Some(None)),
+        Some(Some(TreeValue::File { id, executable })) => {
+            let mut content = Vec::new();
+            repo.store().read_file(path, id)?.read_to_end(&mut content)?;
+            Ok(Some(Some(FileContent { content, executable: *executable })))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Append one file's section of a git-format diff, as `jj diff --git`
+/// prints it
+fn write_git_patch(
+    output: &mut String,
+    change: &FileChange,
+    before: Option<FileContent>,
+    after: Option<FileContent>,
+    context: usize,
+) {
+    let from = change.source_path().as_internal_file_string();
+    let to = change.path.as_internal_file_string();
+    let mode = |file: &FileContent| if file.executable { "100755" } else { "100644" };
+
+    output.push_str(&format!("diff --git a/{from} b/{to}\n"));
+    match (&before, &after, &change.source) {
+        (None, Some(new), _) => output.push_str(&format!("new file mode {}\n", mode(new))),
+        (Some(old), None, _) => output.push_str(&format!("deleted file mode {}\n", mode(old))),
+        (Some(old), Some(new), source) => {
+            if mode(old) != mode(new) {
+                output.push_str(&format!("old mode {}\nnew mode {}\n", mode(old), mode(new)));
+            }
+            if let Some((_, operation)) = source {
+                let verb = match operation {
+                    CopyOperation::Rename => "rename",
+                    CopyOperation::Copy => "copy",
+                };
+                output.push_str(&format!("{verb} from {from}\n{verb} to {to}\n"));
+            }
+        }
+        (None, None, _) => return,
+    }
+
+    let old_name = if before.is_some() { format!("a/{from}") } else { "/dev/null".to_string() };
+    let new_name = if after.is_some() { format!("b/{to}") } else { "/dev/null".to_string() };
+    let old = before.map(|f| f.content).unwrap_or_default();
+    let new = after.map(|f| f.content).unwrap_or_default();
+    if old == new {
+        return;
+    }
+    let texts = (std::str::from_utf8(&old), std::str::from_utf8(&new));
+    let (Ok(old_text), Ok(new_text)) = texts else {
+        output.push_str(&format!("Binary files a/{from} and b/{to} differ\n"));
+        return;
+    };
+    if old.contains(&0) || new.contains(&0) {
+        output.push_str(&format!("Binary files a/{from} and b/{to} differ\n"));
+        return;
+    }
+
+    output.push_str(&format!("--- {old_name}\n+++ {new_name}\n"));
+    for hunk in diff::diff_texts(old_text, new_text, context) {
+        output.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count
+        ));
+        for line in hunk.lines {
+            let prefix = match line.kind {
+                LineKind::Context => ' ',
+                LineKind::Add => '+',
+                LineKind::Delete => '-',
+            };
+            output.push(prefix);
+            output.push_str(&line.content);
+            output.push('\n');
+        }
+    }
+}
+
+/// A commit timestamp the way `jj log` prints it in JSON
+fn format_timestamp(timestamp: &Timestamp) -> String {
+    let offset = FixedOffset::east_opt(timestamp.tz_offset * 60).unwrap_or(FixedOffset::east_opt(0).unwrap());
+    DateTime::from_timestamp_millis(timestamp.timestamp.0)
+        .unwrap_or_default()
+        .with_timezone(&offset)
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// The ignore files jj reads besides the working copy's own `.gitignore`s:
+/// git's global excludes file at its default location, and the backing git
+/// repo's `info/exclude`
+fn base_ignores(repo: &ReadonlyRepo) -> Arc<GitIgnoreFile> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    let global = config_home.map(|dir| dir.join("git").join("ignore"));
+    let exclude = repo
+        .store()
+        .backend_impl()
+        .downcast_ref::<GitBackend>()
+        .map(|git| git.git_repo_path().join("info").join("exclude"));
+
+    let mut ignores = GitIgnoreFile::empty();
+    for file in global.into_iter().chain(exclude) {
+        match ignores.chain_with_file("", file) {
+            Ok(chained) => ignores = chained,
+            Err(err) => debug!("Skipping ignore file: {err}"),
+        }
+    }
+    ignores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::backend::MergedTreeId;
+    use jj_lib::merge::Merge;
+    use jj_lib::merged_tree::MergedTreeBuilder;
+    use jj_lib::op_store::RefTarget;
+    use jj_lib::ref_name::RefName;
+    use tempfile::TempDir;
+
+    /// A jj repo, built with jj-lib since tests can't count on `jj`
+    fn init(dir: &Path) -> Arc<ReadonlyRepo> {
+        let settings = UserSettings::from_config(StackedConfig::with_defaults()).unwrap();
+        Workspace::init_internal_git(&settings, dir).unwrap().1
+    }
+
+    /// Commit `files` (None deletes) on top of `parent`, optionally moving a
+    /// bookmark to it
+    fn commit(
+        repo: &Arc<ReadonlyRepo>,
+        parent: &Commit,
+        files: &[(&str, Option<&str>)],
+        bookmark: Option<&str>,
+    ) -> (Arc<ReadonlyRepo>, Commit) {
+        let store = repo.store();
+        let mut builder = MergedTreeBuilder::new(parent.tree_id().clone());
+        for (path, content) in files {
+            let path = RepoPathBuf::from_internal_string(*path).unwrap();
+            let value = content.map(|content| {
+                let id = store.write_file(&path, &mut content.as_bytes()).block_on().unwrap();
+                TreeValue::File { id, executable: false }
+            });
+            builder.set_or_remove(path, Merge::resolved(value));
+        }
+        let tree_id: MergedTreeId = builder.write_tree(store).unwrap();
+
+        let mut tx = repo.start_transaction();
+        let commit = tx
+            .repo_mut()
+            .new_commit(vec![parent.id().clone()], tree_id)
+            .set_description(format!("Change {}", files[0].0))
+            .write()
+            .unwrap();
+        if let Some(name) = bookmark {
+            tx.repo_mut().set_local_bookmark_target(RefName::new(name), RefTarget::normal(commit.id().clone()));
+        }
+        (tx.commit("test").unwrap(), commit)
+    }
+
+    #[test]
+    fn test_log_and_bookmarks() {
+        let dir = TempDir::new().unwrap();
+        let repo = init(dir.path());
+        let root = repo.store().root_commit();
+        let (repo, first) = commit(&repo, &root, &[("a.txt", Some("one\n"))], None);
+        let (_, second) = commit(&repo, &first, &[("b.txt", Some("two\n"))], Some("main"));
+
+        let lib = JjLib::open(dir.path()).unwrap();
+        let changes = lib.log_revset("::main").unwrap();
+        let ids: Vec<_> = changes.iter().map(|c| c.commit_id.clone()).collect();
+        assert_eq!(ids, vec![second.id().hex(), first.id().hex()]);
+        assert_eq!(changes[0].parent_change_ids, vec![first.change_id().reverse_hex()]);
+        assert_eq!(changes[0].description, "Change b.txt");
+        assert!(!changes[0].empty && !changes[0].is_working_copy);
+
+        let working_copy = lib.get_change("@").unwrap();
+        assert!(working_copy.is_working_copy && working_copy.empty);
+
+        assert_eq!(lib.get_bookmark("main").unwrap(), Some(second.change_id().reverse_hex()));
+        assert_eq!(lib.get_bookmark("nope").unwrap(), None);
+        assert_eq!(lib.query_change_ids("main-").unwrap(), vec![first.change_id().reverse_hex()]);
+        assert!(lib.get_change("nope").unwrap_err().to_string().contains("doesn't exist"));
+    }
+
+    #[test]
+    fn test_diff_and_show_file() {
+        let dir = TempDir::new().unwrap();
+        let repo = init(dir.path());
+        let root = repo.store().root_commit();
+        let moved = "a\nb\nc\nd\n";
+        let (repo, base) = commit(
+            &repo,
+            &root,
+            &[("kept.txt", Some("1\n2\n3\n")), ("gone.txt", Some("bye\n")), ("old.txt", Some(moved))],
+            None,
+        );
+        let (_, change) = commit(
+            &repo,
+            &base,
+            &[
+                ("kept.txt", Some("1\ntwo\n3\n")),
+                ("gone.txt", None),
+                ("new.txt", Some("hi\n")),
+                ("old.txt", None),
+                ("renamed.txt", Some(moved)),
+            ],
+            None,
+        );
+        let change_id = change.change_id().reverse_hex();
+
+        let lib = JjLib::open(dir.path()).unwrap();
+        let files = lib.changed_files_between(&format!("{change_id}-"), &change_id).unwrap();
+        let statuses: Vec<_> = files.iter().map(|f| (f.path.as_str(), f.status.clone())).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("gone.txt", FileStatus::Deleted),
+                ("kept.txt", FileStatus::Modified),
+                ("new.txt", FileStatus::Added),
+                ("renamed.txt", FileStatus::Renamed { from: "old.txt".to_string() }),
+            ]
+        );
+
+        let diff = lib.diff(&change_id, None, 0).unwrap();
+        let kept = diff.files.iter().find(|f| f.path == "kept.txt").unwrap();
+        assert_eq!(kept.hunks.len(), 1);
+        assert_eq!((kept.hunks[0].old_start, kept.hunks[0].new_count), (2, 1));
+        let lines: Vec<_> = kept.hunks[0].lines.iter().map(|l| (l.kind, l.content.as_str())).collect();
+        assert_eq!(lines, vec![(LineKind::Delete, "2"), (LineKind::Add, "two")]);
+        let added = diff.files.iter().find(|f| f.path == "new.txt").unwrap();
+        assert_eq!(added.hunks[0].lines.len(), 1);
+        assert!(diff.files.iter().find(|f| f.path == "renamed.txt").unwrap().hunks.is_empty());
+
+        let raw = lib.diff_raw_between_ctx(&format!("{change_id}-"), &change_id, "gone.txt", None).unwrap();
+        assert!(raw.starts_with("diff --git a/gone.txt b/gone.txt\ndeleted file mode 100644\n"), "{raw}");
+
+        assert_eq!(lib.show_file(&change_id, "kept.txt").unwrap(), "1\ntwo\n3\n");
+        assert!(lib.show_file(&change_id, "gone.txt").is_err());
+    }
+
+    #[test]
+    fn test_notices_edits_to_the_working_copy() {
+        let dir = TempDir::new().unwrap();
+        init(dir.path());
+        let lib = JjLib::open(dir.path()).unwrap();
+        let mut loaded = lib.inner.loaded.lock().unwrap();
+        assert!(!loaded.working_copy_edited(&lib.inner.workspace_name).unwrap());
+
+        std::fs::write(dir.path().join("edited.txt"), "new\n").unwrap();
+        assert!(loaded.working_copy_edited(&lib.inner.workspace_name).unwrap());
+    }
+
+    #[test]
+    fn test_only_the_operation_id_snapshots_edits() {
+        let dir = TempDir::new().unwrap();
+        init(dir.path());
+        let lib = JjLib::open(dir.path()).unwrap();
+        let operation = lib.operation_id().unwrap();
+
+        std::fs::write(dir.path().join("edited.txt"), "new\n").unwrap();
+        assert!(lib.show_file("@", "edited.txt").is_err());
+        assert_ne!(lib.operation_id().unwrap(), operation);
+        assert_eq!(lib.show_file("@", "edited.txt").unwrap(), "new\n");
+    }
+
+    #[test]
+    fn test_cached_queries_follow_operations() {
+        let dir = TempDir::new().unwrap();
+        let repo = init(dir.path());
+        let root = repo.store().root_commit();
+        let (repo, first) = commit(&repo, &root, &[("a.txt", Some("one\n"))], Some("main"));
+
+        let vcs = crate::vcs::open(dir.path());
+        assert_eq!(vcs.get_bookmark("main").unwrap(), Some(first.change_id().reverse_hex()));
+        assert_eq!(vcs.show_file("main", "a.txt").unwrap(), "one\n");
+        assert!(crate::vcs::is_no_such_path(&vcs.show_file("main", "b.txt").unwrap_err()));
+
+        // Moving the bookmark is a new operation: a `Cached` keeps answering
+        // as of the one it started with, and the next one moves on
+        let (_, second) = commit(&repo, &first, &[("a.txt", Some("two\n"))], Some("main"));
+        assert_eq!(vcs.get_bookmark("main").unwrap(), Some(first.change_id().reverse_hex()));
+        let vcs = crate::vcs::open(dir.path());
+        assert_eq!(vcs.get_bookmark("main").unwrap(), Some(second.change_id().reverse_hex()));
+        assert_eq!(vcs.show_file("main", "a.txt").unwrap(), "two\n");
+        assert_eq!(vcs.show_file(&first.id().hex(), "a.txt").unwrap(), "one\n");
+    }
+
+    #[test]
+    fn test_cached_at_different_operations_interleave() {
+        let dir = TempDir::new().unwrap();
+        let repo = init(dir.path());
+        let root = repo.store().root_commit();
+        let (repo, first) = commit(&repo, &root, &[("a.txt", Some("one\n"))], Some("main"));
+
+        let old = crate::vcs::open(dir.path());
+        assert_eq!(old.get_bookmark("main").unwrap(), Some(first.change_id().reverse_hex()));
+
+        // Each keeps its own operation's answer however their queries interleave
+        let (_, second) = commit(&repo, &first, &[("a.txt", Some("two\n"))], Some("main"));
+        let new = crate::vcs::open(dir.path());
+        for _ in 0..2 {
+            assert_eq!(new.get_bookmark("main").unwrap(), Some(second.change_id().reverse_hex()));
+            assert_eq!(old.get_bookmark("main").unwrap(), Some(first.change_id().reverse_hex()));
+        }
+    }
+}
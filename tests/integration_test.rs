@@ -91,7 +91,7 @@ impl TestHarness {
         // Wait for server to be ready
         let client = Client::new();
         for _ in 0..50 {
-            if client.get(&format!("{}/api/health", base_url)).send().await.is_ok() {
+            if client.get(format!("{}/api/health", base_url)).send().await.is_ok() {
                 break;
             }
             tokio::time::sleep(Duration::from_millis(100)).await;
@@ -105,9 +105,13 @@ impl TestHarness {
         }
     }
 
+    fn repo_path(&self) -> &Path {
+        self._temp_dir.path()
+    }
+
     async fn get(&self, path: &str) -> reqwest::Response {
         self.client
-            .get(&format!("{}{}", self.base_url, path))
+            .get(format!("{}{}", self.base_url, path))
             .send()
             .await
             .expect("Request failed")
@@ -115,7 +119,7 @@ impl TestHarness {
 
     async fn post(&self, path: &str, body: serde_json::Value) -> reqwest::Response {
         self.client
-            .post(&format!("{}{}", self.base_url, path))
+            .post(format!("{}{}", self.base_url, path))
             .json(&body)
             .send()
             .await
@@ -163,7 +167,266 @@ async fn test_get_diff() {
     assert_eq!(response.status(), 200);
 
     let body: serde_json::Value = response.json().await.unwrap();
-    assert!(body["diff"]["raw"].as_str().is_some());
+    assert!(body["diff"]["files"].as_array().is_some());
+}
+
+#[tokio::test]
+async fn test_diff_hunks_and_context_expansion() {
+    let harness = TestHarness::new().await;
+    std::fs::write(harness.repo_path().join(".gitignore"), ".aipair/\n").unwrap();
+
+    // A long file with one line changed in the middle
+    let before: String = (1..=40).map(|i| format!("line {i}\n")).collect();
+    let after = before.replace("line 20\n", "line twenty\n");
+    std::fs::write(harness.repo_path().join("long.txt"), &before).unwrap();
+    jj_cmd(harness.repo_path(), &["new", "-m", "Edit long file"]);
+    std::fs::write(harness.repo_path().join("long.txt"), &after).unwrap();
+
+    let change_id = jj_cmd(harness.repo_path(), &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+    let response = harness.get(&format!("/api/changes/{}/diff", change_id)).await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["diff"].get("raw").is_none());
+    assert_eq!(body["diff"]["context"], 3);
+
+    // Only the changed line plus three lines of context either side
+    let file = &body["diff"]["files"][0];
+    assert_eq!(file["path"], "long.txt");
+    assert_eq!(file["status"], "modified");
+    let hunk = &file["hunks"][0];
+    assert_eq!(hunk["new_start"], 17);
+    let lines = hunk["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), 8);
+    assert_eq!(lines[3], serde_json::json!({ "kind": "delete", "content": "line 20", "old_line": 20, "new_line": null }));
+    assert_eq!(lines[4], serde_json::json!({ "kind": "add", "content": "line twenty", "old_line": null, "new_line": 20 }));
+
+    // More context on request
+    let response = harness.get(&format!("/api/changes/{}/diff?context=5", change_id)).await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["diff"]["files"][0]["hunks"][0]["new_start"], 15);
+
+    // Expanding the hidden lines above the hunk
+    let response = harness
+        .get(&format!("/api/changes/{}/lines?path=long.txt&start=1&end=16", change_id))
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["start"], 1);
+    assert_eq!(body["total_lines"], 40);
+    assert_eq!(body["lines"].as_array().unwrap().len(), 16);
+    assert_eq!(body["lines"][15], "line 16");
+
+    // The old side reads from the parent
+    let response = harness
+        .get(&format!("/api/changes/{}/lines?path=long.txt&start=20&end=20&side=old", change_id))
+        .await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["lines"], serde_json::json!(["line 20"]));
+}
+
+#[tokio::test]
+async fn test_diff_reports_renames() {
+    let harness = TestHarness::new().await;
+    std::fs::write(harness.repo_path().join(".gitignore"), ".aipair/\n").unwrap();
+    // Move the file back to its committed content under a new name
+    std::fs::remove_file(harness.repo_path().join("test.txt")).unwrap();
+    std::fs::write(harness.repo_path().join("renamed.txt"), "hello world\n").unwrap();
+
+    let change_id = jj_cmd(harness.repo_path(), &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+    let response = harness.get(&format!("/api/changes/{}/diff", change_id)).await;
+    assert_eq!(response.status(), 200);
+
+    // One renamed entry instead of a delete plus an add
+    let body: serde_json::Value = response.json().await.unwrap();
+    let files = body["diff"]["files"].as_array().unwrap();
+    let renamed = files.iter().find(|f| f["path"] == "renamed.txt").expect("renamed file listed");
+    assert_eq!(renamed["status"], serde_json::json!({ "renamed": { "from": "test.txt" } }));
+    assert!(!files.iter().any(|f| f["path"] == "test.txt"), "unexpected files: {files:?}");
+}
+
+#[tokio::test]
+async fn test_interdiff_excludes_upstream_changes() {
+    let harness = TestHarness::new().await;
+    let repo = harness.repo_path();
+    let change_id = jj_cmd(repo, &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+
+    // Ignore .aipair in the parent so review writes don't land in any commit
+    jj_cmd(repo, &["edit", "@-"]);
+    std::fs::write(repo.join(".gitignore"), ".aipair/\n").unwrap();
+    jj_cmd(repo, &["edit", &change_id]);
+
+    // Commenting records revision 1
+    harness
+        .post(&format!("/api/changes/{}/review", change_id), serde_json::json!({ "base": "@-" }))
+        .await;
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/comments", change_id),
+            serde_json::json!({ "file": "test.txt", "line_start": 2, "line_end": 2, "text": "Reword" }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+
+    // Upstream adds a file (rebasing the change), then the author edits again
+    jj_cmd(repo, &["edit", "@-"]);
+    std::fs::write(repo.join("upstream.txt"), "from upstream\n").unwrap();
+    jj_cmd(repo, &["edit", &change_id]);
+    std::fs::write(repo.join("test.txt"), "hello world\nmore content\neven more\n").unwrap();
+
+    let response = harness.get(&format!("/api/changes/{}/interdiff?from=1", change_id)).await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["parent_moved"], true);
+
+    // Only the author's new line, not upstream's file
+    let files = body["diff"]["files"].as_array().unwrap();
+    assert_eq!(files.len(), 1, "unexpected files: {files:?}");
+    assert_eq!(files[0]["path"], "test.txt");
+    let added: Vec<_> = files[0]["hunks"][0]["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|l| l["kind"] != "context")
+        .map(|l| l["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(added, vec!["even more"]);
+
+    // Threads come mapped onto the newer revision
+    let threads = body["threads"].as_array().unwrap();
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0]["display_line_start"], 2);
+}
+
+#[tokio::test]
+async fn test_thread_fix_shows_the_commented_region() {
+    let harness = TestHarness::new().await;
+    let repo = harness.repo_path();
+    let change_id = jj_cmd(repo, &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+    jj_cmd(repo, &["edit", "@-"]);
+    std::fs::write(repo.join(".gitignore"), ".aipair/\n").unwrap();
+    jj_cmd(repo, &["edit", &change_id]);
+
+    let content: String = (1..=30).map(|i| format!("line {i}\n")).collect();
+    std::fs::write(repo.join("test.txt"), &content).unwrap();
+    harness
+        .post(&format!("/api/changes/{}/review", change_id), serde_json::json!({ "base": "@-" }))
+        .await;
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/comments", change_id),
+            serde_json::json!({ "file": "test.txt", "line_start": 2, "line_end": 2, "text": "Reword" }),
+        )
+        .await;
+    let thread_id = response.json::<serde_json::Value>().await.unwrap()["thread_id"].as_str().unwrap().to_string();
+
+    // The fix touches the commented line; an unrelated edit far away doesn't
+    // belong in it
+    let fixed = content.replace("line 2\n", "line two\n").replace("line 25\n", "line twenty-five\n");
+    std::fs::write(repo.join("test.txt"), fixed).unwrap();
+    let commit = jj_cmd(repo, &["log", "--no-graph", "-r", "@", "-T", "commit_id"]).trim().to_string();
+
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/threads/{}/status", change_id, thread_id),
+            serde_json::json!({ "status": "addressed", "addressed_in": commit }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["review"]["threads"][0]["addressed_in"], commit.as_str());
+
+    let response = harness.get(&format!("/api/changes/{}/threads/{}/fix", change_id, thread_id)).await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["to"], commit.as_str());
+    let hunks = body["files"][0]["hunks"].as_array().unwrap();
+    assert_eq!(hunks.len(), 1, "unexpected hunks: {hunks:?}");
+    let changed: Vec<_> = hunks[0]["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|l| l["kind"] != "context")
+        .map(|l| l["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(changed, vec!["line 2", "line two"]);
+}
+
+#[tokio::test]
+async fn test_unseen_since_last_review() {
+    let harness = TestHarness::new().await;
+    let repo = harness.repo_path();
+    let change_id = jj_cmd(repo, &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+    jj_cmd(repo, &["edit", "@-"]);
+    std::fs::write(repo.join(".gitignore"), ".aipair/\n").unwrap();
+    jj_cmd(repo, &["edit", &change_id]);
+
+    harness
+        .post(&format!("/api/changes/{}/review", change_id), serde_json::json!({ "base": "@-" }))
+        .await;
+
+    // Never reviewed: the whole change is new
+    let body: serde_json::Value = harness
+        .get(&format!("/api/changes/{}/unseen", change_id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(body["since_revision"].is_null());
+    assert_eq!(body["diff"]["files"][0]["path"], "test.txt");
+
+    // The change list only covers main's ancestors
+    jj_cmd(repo, &["bookmark", "set", "main", "-r", &change_id]);
+    let unseen = |body: &serde_json::Value| {
+        body["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["change_id"] == change_id.as_str())
+            .unwrap()["has_unseen_revision"]
+            .clone()
+    };
+
+    let response = harness
+        .post(&format!("/api/changes/{}/seen", change_id), serde_json::json!({}))
+        .await;
+    assert_eq!(response.status(), 200);
+    let changes: serde_json::Value = harness.get("/api/changes").await.json().await.unwrap();
+    assert_eq!(unseen(&changes), false);
+    // Another reviewer still hasn't seen it
+    let changes: serde_json::Value = harness.get("/api/changes?reviewer=alice").await.json().await.unwrap();
+    assert_eq!(unseen(&changes), true);
+
+    // The author pushes more; only that is new
+    std::fs::write(repo.join("test.txt"), "hello world\nmore content\neven more\n").unwrap();
+    let body: serde_json::Value = harness
+        .get(&format!("/api/changes/{}/unseen", change_id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["since_revision"], 1);
+    let added: Vec<_> = body["diff"]["files"][0]["hunks"][0]["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|l| l["kind"] == "add")
+        .map(|l| l["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(added, vec!["even more"]);
+    assert_eq!(body["unread"].as_array().unwrap().len(), 0);
+
+    let changes: serde_json::Value = harness.get("/api/changes").await.json().await.unwrap();
+    assert_eq!(unseen(&changes), true);
 }
 
 #[tokio::test]
@@ -211,6 +474,251 @@ async fn test_review_workflow() {
     assert_eq!(threads[0]["comments"][0]["text"], "This looks good!");
 }
 
+#[tokio::test]
+async fn test_gc_archives_reviews_of_abandoned_changes() {
+    let harness = TestHarness::new().await;
+    let dir = harness.repo_path();
+    let merged = jj_cmd(dir, &["log", "--no-graph", "-r", "main", "-T", "change_id"]).trim().to_string();
+    let abandoned = jj_cmd(dir, &["log", "--no-graph", "-r", "@", "-T", "change_id"]).trim().to_string();
+    for change_id in [&merged, &abandoned] {
+        let response = harness
+            .post(&format!("/api/changes/{change_id}/review"), serde_json::json!({ "base": "@-" }))
+            .await;
+        assert_eq!(response.status(), 200);
+    }
+    jj_cmd(dir, &["abandon", "@"]);
+
+    // A dry run reports without archiving
+    let response = harness.post("/api/reviews/gc", serde_json::json!({ "dry_run": true })).await;
+    let report: serde_json::Value = response.json().await.unwrap();
+    let entries = report["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["change_id"], abandoned.as_str());
+    assert_eq!(entries[0]["state"], "abandoned");
+    assert_eq!(entries[0]["archived"], true);
+    assert_eq!(entries[1]["state"], "merged");
+    assert_eq!(entries[1]["archived"], false);
+    assert!(dir.join(format!(".aipair/reviews/{abandoned}.json")).exists());
+
+    let output = aipair_ok(dir, &["review", "gc"]);
+    assert!(output.contains("Archived 1 review(s)"), "{output}");
+    assert!(!dir.join(format!(".aipair/reviews/{abandoned}.json")).exists());
+    assert!(dir.join(format!(".aipair/archived-reviews/{abandoned}.json")).exists());
+    assert!(dir.join(format!(".aipair/reviews/{merged}.json")).exists());
+
+    aipair_ok(dir, &["review", "restore", &abandoned[..8]]);
+    assert!(dir.join(format!(".aipair/reviews/{abandoned}.json")).exists());
+}
+
+#[tokio::test]
+async fn test_change_level_thread_blocks_merge() {
+    let harness = TestHarness::new().await;
+
+    // Keep review writes from amending the working change
+    std::fs::write(harness.repo_path().join(".gitignore"), ".aipair/\n").unwrap();
+
+    // The working change (main is its parent)
+    let change_id = jj_cmd(harness.repo_path(), &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+
+    harness
+        .post(
+            &format!("/api/changes/{}/review", change_id),
+            serde_json::json!({ "base": "@-" }),
+        )
+        .await;
+
+    // A comment on the change as a whole needs no file or lines
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/comments", change_id),
+            serde_json::json!({ "anchor": "change", "text": "Split this in two" }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let thread = &body["review"]["threads"][0];
+    assert_eq!(thread["anchor"], "change");
+
+    // Line comments still need their range
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/comments", change_id),
+            serde_json::json!({ "file": "test.txt", "text": "Where?" }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    // The open change-level thread gates the merge like a line thread would
+    let response = harness
+        .post(&format!("/api/changes/{}/merge", change_id), serde_json::json!({}))
+        .await;
+    assert_eq!(response.status(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(
+        body["message"].as_str().unwrap().contains("1 open blocking thread"),
+        "unexpected merge response: {body}"
+    );
+
+    // Downgraded to a nit it no longer blocks; the missing approval does
+    let thread_id = thread["id"].as_str().unwrap();
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/threads/{}/classify", change_id, thread_id),
+            serde_json::json!({ "severity": "nit", "labels": ["design", " design", ""] }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["review"]["threads"][0]["severity"], "nit");
+    assert_eq!(body["review"]["threads"][0]["labels"], serde_json::json!(["design"]));
+
+    let response = harness
+        .post(&format!("/api/changes/{}/merge", change_id), serde_json::json!({}))
+        .await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(
+        body["message"].as_str().unwrap().contains("has not been approved"),
+        "unexpected merge response: {body}"
+    );
+}
+
+#[tokio::test]
+async fn test_merge_requires_approval_on_current_commit() {
+    let harness = TestHarness::new().await;
+    let repo = harness.repo_path();
+    std::fs::write(repo.join(".gitignore"), ".aipair/\n").unwrap();
+    let change_id = jj_cmd(repo, &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+    harness
+        .post(&format!("/api/changes/{}/review", change_id), serde_json::json!({ "base": "@-" }))
+        .await;
+
+    let merge_path = format!("/api/changes/{}/merge", change_id);
+    let verdict_path = format!("/api/changes/{}/verdict", change_id);
+    let merge = || harness.post(&merge_path, serde_json::json!({}));
+    let verdict = |verdict: &str| {
+        harness.post(&verdict_path, serde_json::json!({ "verdict": verdict, "comment": "Looked at it" }))
+    };
+
+    let response = verdict("changes_requested").await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["review"]["verdicts"][0]["kind"], "changes_requested");
+    let body: serde_json::Value = merge().await.json().await.unwrap();
+    assert!(body["message"].as_str().unwrap().contains("changes were requested"), "{body}");
+
+    // An approval on an older commit doesn't count
+    verdict("approved").await;
+    std::fs::write(repo.join("test.txt"), "hello world\nmore content\nand more\n").unwrap();
+    let response = harness
+        .post(&format!("/api/changes/{}/seen", change_id), serde_json::json!({}))
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = merge().await.json().await.unwrap();
+    assert!(body["message"].as_str().unwrap().contains("has not been approved"), "{body}");
+
+    verdict("approved").await;
+    let response = merge().await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["success"], true, "{body}");
+}
+
+#[tokio::test]
+async fn test_draft_review_submitted_at_once() {
+    let harness = TestHarness::new().await;
+    std::fs::write(harness.repo_path().join(".gitignore"), ".aipair/\n").unwrap();
+    let change_id = jj_cmd(harness.repo_path(), &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+    harness
+        .post(&format!("/api/changes/{}/review", change_id), serde_json::json!({ "base": "@-" }))
+        .await;
+
+    for (line, text) in [(1, "First"), (2, "Second")] {
+        let response = harness
+            .post(
+                &format!("/api/changes/{}/comments", change_id),
+                serde_json::json!({ "file": "test.txt", "line_start": line, "line_end": line, "text": text, "draft": true }),
+            )
+            .await;
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        let threads = body["review"]["threads"].as_array().unwrap();
+        assert!(threads.iter().all(|t| t["comments"][0]["draft"] == true));
+    }
+
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/review/submit", change_id),
+            serde_json::json!({ "verdict": "changes_requested", "comment": "See comments" }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["published"], 2);
+    assert_eq!(body["review"]["verdicts"][0]["kind"], "changes_requested");
+    let threads = body["review"]["threads"].as_array().unwrap();
+    assert!(threads.iter().all(|t| t["comments"][0]["draft"] == false));
+
+    // Nothing left to submit
+    let response = harness
+        .post(&format!("/api/changes/{}/review/submit", change_id), serde_json::json!({}))
+        .await;
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_apply_suggestion_squashes_into_change() {
+    let harness = TestHarness::new().await;
+    std::fs::write(harness.repo_path().join(".gitignore"), ".aipair/\n").unwrap();
+    let change_id = jj_cmd(harness.repo_path(), &["log", "--no-graph", "-r", "@", "-T", "change_id"])
+        .trim()
+        .to_string();
+    harness
+        .post(&format!("/api/changes/{}/review", change_id), serde_json::json!({ "base": "@-" }))
+        .await;
+
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/comments", change_id),
+            serde_json::json!({
+                "file": "test.txt", "line_start": 2, "line_end": 2, "text": "Plural",
+                "suggestion": ["more contents"],
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["review"]["threads"][0]["comments"][0]["suggestion"][0], "more contents");
+    let thread_id = body["thread_id"].as_str().unwrap().to_string();
+
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/threads/{}/apply-suggestion", change_id, thread_id),
+            serde_json::json!({ "squash": true }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["change_id"], change_id.as_str());
+    assert_eq!(
+        jj_cmd(harness.repo_path(), &["file", "show", "-r", &change_id, "test.txt"]),
+        "hello world\nmore contents\n"
+    );
+
+    // The lines no longer read as they did, so applying again conflicts
+    let response = harness
+        .post(
+            &format!("/api/changes/{}/threads/{}/apply-suggestion", change_id, thread_id),
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(response.status(), 409);
+}
+
 #[tokio::test]
 async fn test_thread_relocation_after_edit() {
     // Custom setup: file with multiple lines so we can track line movement
@@ -271,7 +779,7 @@ async fn test_thread_relocation_after_edit() {
     let client = Client::new();
     for _ in 0..50 {
         if client
-            .get(&format!("{}/api/health", base_url))
+            .get(format!("{}/api/health", base_url))
             .send()
             .await
             .is_ok()
@@ -283,7 +791,7 @@ async fn test_thread_relocation_after_edit() {
 
     // Get change_id for the working change
     let response = client
-        .get(&format!("{}/api/changes", base_url))
+        .get(format!("{}/api/changes", base_url))
         .send()
         .await
         .unwrap();
@@ -293,7 +801,7 @@ async fn test_thread_relocation_after_edit() {
 
     // Create a review
     client
-        .post(&format!("{}/api/changes/{}/review", base_url, change_id))
+        .post(format!("{}/api/changes/{}/review", base_url, change_id))
         .json(&serde_json::json!({ "base": "@-" }))
         .send()
         .await
@@ -301,7 +809,7 @@ async fn test_thread_relocation_after_edit() {
 
     // Add a comment on line 5
     let response = client
-        .post(&format!("{}/api/changes/{}/comments", base_url, change_id))
+        .post(format!("{}/api/changes/{}/comments", base_url, change_id))
         .json(&serde_json::json!({
             "file": "code.rs",
             "line_start": 5,
@@ -315,7 +823,7 @@ async fn test_thread_relocation_after_edit() {
 
     // Verify initial display positions match stored positions
     let response = client
-        .get(&format!("{}/api/changes/{}/review", base_url, change_id))
+        .get(format!("{}/api/changes/{}/review", base_url, change_id))
         .send()
         .await
         .unwrap();
@@ -335,7 +843,7 @@ async fn test_thread_relocation_after_edit() {
 
     // Fetch review again — thread should now have updated display positions
     let response = client
-        .get(&format!("{}/api/changes/{}/review", base_url, change_id))
+        .get(format!("{}/api/changes/{}/review", base_url, change_id))
         .send()
         .await
         .unwrap();
@@ -362,7 +870,7 @@ async fn test_thread_relocation_after_edit() {
     std::fs::write(repo_path.join("code.rs"), &deleted_content).unwrap();
 
     let response = client
-        .get(&format!("{}/api/changes/{}/review", base_url, change_id))
+        .get(format!("{}/api/changes/{}/review", base_url, change_id))
         .send()
         .await
         .unwrap();
@@ -371,6 +879,7 @@ async fn test_thread_relocation_after_edit() {
     assert_eq!(thread["is_deleted"], true, "thread should be marked deleted after its line was removed");
 
     let _ = server.kill();
+    let _ = server.wait();
 }
 
 // --- Session lifecycle helpers (no server needed) ---
@@ -431,7 +940,7 @@ async fn start_server(dir: &Path) -> (Child, String) {
     // Wait for server to be ready
     let client = Client::new();
     for _ in 0..50 {
-        if client.get(&format!("{}/api/health", base_url)).send().await.is_ok() {
+        if client.get(format!("{}/api/health", base_url)).send().await.is_ok() {
             break;
         }
         tokio::time::sleep(Duration::from_millis(100)).await;
@@ -517,8 +1026,11 @@ fn test_session_lifecycle() {
     let out = aipair_ok(&clone_dir, &["push", "-m", "After rebase", "--rev", "@-"]);
     assert!(out.contains("Pushed!"), "push after rebase: {}", out);
 
-    // 11. session merge from main
-    let out = aipair_ok(&main_dir, &["session", "merge", "test-session"]);
+    // 11. session merge from main — nothing was approved, so it needs --force
+    let out = aipair(&main_dir, &["session", "merge", "test-session"]);
+    assert!(!out.status.success(), "merge without approval should fail");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("not approved"));
+    let out = aipair_ok(&main_dir, &["session", "merge", "test-session", "--force"]);
     assert!(out.contains("merged"), "merge: {}", out);
 
     // 12. session list — should show merged status
@@ -566,7 +1078,7 @@ async fn test_session_push_api() {
 
     // Live view should show the pushed change
     let resp = client
-        .get(&format!("{}/api/sessions/test-session/changes", base_url))
+        .get(format!("{}/api/sessions/test-session/changes", base_url))
         .send()
         .await
         .unwrap();
@@ -581,7 +1093,7 @@ async fn test_session_push_api() {
 
     // Historical view (version=0) should also work
     let resp = client
-        .get(&format!("{}/api/sessions/test-session/changes?version=0", base_url))
+        .get(format!("{}/api/sessions/test-session/changes?version=0", base_url))
         .send()
         .await
         .unwrap();
@@ -621,7 +1133,7 @@ async fn test_session_feedback_respond() {
 
     // Get session changes to find the session change_id
     let resp = client
-        .get(&format!("{}/api/sessions/test-session/changes", base_url))
+        .get(format!("{}/api/sessions/test-session/changes", base_url))
         .send()
         .await
         .unwrap();
@@ -632,14 +1144,14 @@ async fn test_session_feedback_respond() {
 
     // Create a review and add a comment via API
     client
-        .post(&format!("{}/api/changes/{}/review", base_url, change_id))
+        .post(format!("{}/api/changes/{}/review", base_url, change_id))
         .json(&serde_json::json!({}))
         .send()
         .await
         .unwrap();
 
     let resp = client
-        .post(&format!("{}/api/changes/{}/comments", base_url, change_id))
+        .post(format!("{}/api/changes/{}/comments", base_url, change_id))
         .json(&serde_json::json!({
             "file": "feature.txt",
             "line_start": 1,
@@ -677,11 +1189,21 @@ async fn test_session_feedback_respond() {
         ],
     );
     assert!(
-        out.contains("Responded") && out.contains("resolved"),
+        out.contains("Responded") && out.contains("addressed"),
         "respond output: {}",
         out
     );
 
+    // Addressed is a claim for the reviewer to verify: the thread stays
+    // unresolved, with the agent's transition on record
+    let review: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(main_dir.join(format!(".aipair/reviews/{change_id}.json"))).unwrap(),
+    )
+    .unwrap();
+    let thread = &review["threads"][0];
+    assert_eq!(thread["status"], "addressed");
+    assert_eq!(thread["history"][0]["author"]["kind"], "agent");
+
     // `aipair feedback` again → should show no pending feedback
     let out = aipair_ok(&clone_dir, &["feedback"]);
     assert!(
@@ -718,7 +1240,7 @@ async fn test_session_api() {
 
     // GET /api/changes → verify sessions metadata
     let resp = client
-        .get(&format!("{}/api/changes", base_url))
+        .get(format!("{}/api/changes", base_url))
         .send()
         .await
         .unwrap();
@@ -739,7 +1261,7 @@ async fn test_session_api() {
 
     // GET /api/sessions/api-session/changes → verify session-scoped changes
     let resp = client
-        .get(&format!("{}/api/sessions/api-session/changes", base_url))
+        .get(format!("{}/api/sessions/api-session/changes", base_url))
         .send()
         .await
         .unwrap();
@@ -759,7 +1281,7 @@ async fn test_session_api() {
 
     // GET /api/sessions/api-session/changes?version=live → verify live endpoint
     let resp = client
-        .get(&format!("{}/api/sessions/api-session/changes?version=live", base_url))
+        .get(format!("{}/api/sessions/api-session/changes?version=live", base_url))
         .send()
         .await
         .unwrap();
@@ -767,9 +1289,20 @@ async fn test_session_api() {
     let body: serde_json::Value = resp.json().await.unwrap();
     assert!(body["changes"].as_array().is_some(), "Live should have changes");
 
+    // Approve every change in the session so it can merge
+    for change in session_changes {
+        let resp = client
+            .post(format!("{}/api/changes/{}/verdict", base_url, change["change_id"].as_str().unwrap()))
+            .json(&serde_json::json!({ "verdict": "approved" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
     // POST /api/sessions/api-session/merge
     let resp = client
-        .post(&format!("{}/api/sessions/api-session/merge", base_url))
+        .post(format!("{}/api/sessions/api-session/merge", base_url))
         .json(&serde_json::json!({}))
         .send()
         .await
@@ -780,7 +1313,7 @@ async fn test_session_api() {
 
     // GET /api/changes → session should now be merged
     let resp = client
-        .get(&format!("{}/api/changes", base_url))
+        .get(format!("{}/api/changes", base_url))
         .send()
         .await
         .unwrap();
@@ -793,4 +1326,72 @@ async fn test_session_api() {
     assert_eq!(session["status"], "merged");
 
     let _ = server.kill();
+    let _ = server.wait();
+}
+
+fn git_cmd(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run git");
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(
+        output.status.success(),
+        "git {:?} failed:\nstdout: {}\nstderr: {}",
+        args, stdout, stderr
+    );
+    format!("{}{}", stdout, stderr)
+}
+
+#[test]
+fn test_git_session_lifecycle() {
+    let temp_dir = TempDir::new().unwrap();
+    let main_dir = temp_dir.path().join("main");
+    std::fs::create_dir(&main_dir).unwrap();
+
+    // A plain git repo, no jj anywhere
+    git_cmd(&main_dir, &["init", "--initial-branch", "main"]);
+    git_cmd(&main_dir, &["config", "user.name", "Test"]);
+    git_cmd(&main_dir, &["config", "user.email", "test@example.com"]);
+    std::fs::write(main_dir.join(".gitignore"), ".aipair/\n").unwrap();
+    std::fs::write(main_dir.join("test.txt"), "hello\n").unwrap();
+    git_cmd(&main_dir, &["add", "-A"]);
+    git_cmd(&main_dir, &["commit", "-m", "Initial commit"]);
+
+    let out = aipair_ok(&main_dir, &["session", "new", "git-session"]);
+    assert!(out.contains("Session 'git-session' created!"), "new: {}", out);
+    let clone_dir = main_dir.join(".aipair/sessions/git-session/repo");
+
+    // Work in the clone is committed with git; the hook gives it a change id
+    git_cmd(&clone_dir, &["config", "user.name", "Test"]);
+    git_cmd(&clone_dir, &["config", "user.email", "test@example.com"]);
+    std::fs::write(clone_dir.join("session-file.txt"), "from session\n").unwrap();
+    git_cmd(&clone_dir, &["add", "-A"]);
+    git_cmd(&clone_dir, &["commit", "-m", "Session work"]);
+    let message = git_cmd(&clone_dir, &["log", "-1", "--format=%B"]);
+    assert!(message.contains("Change-Id: "), "commit message: {}", message);
+
+    let out = aipair_ok(&clone_dir, &["push", "-m", "First push", "--rev", "@"]);
+    assert!(out.contains("Pushed!"), "push: {}", out);
+    let out = aipair_ok(&main_dir, &["session", "list"]);
+    assert!(out.contains("First push"), "list after push: {}", out);
+
+    // Main moves on; pulling rebases the session onto it
+    std::fs::write(main_dir.join("other.txt"), "other content\n").unwrap();
+    git_cmd(&main_dir, &["add", "-A"]);
+    git_cmd(&main_dir, &["commit", "-m", "Other work"]);
+    let out = aipair_ok(&clone_dir, &["pull"]);
+    assert!(out.contains("no conflicts"), "pull: {}", out);
+    assert!(clone_dir.join("other.txt").exists());
+
+    let out = aipair_ok(&clone_dir, &["push", "-m", "After rebase", "--rev", "@"]);
+    assert!(out.contains("Pushed!"), "push after rebase: {}", out);
+
+    let out = aipair_ok(&main_dir, &["session", "merge", "git-session", "--force"]);
+    assert!(out.contains("merged"), "merge: {}", out);
+    assert!(main_dir.join("session-file.txt").exists());
+    let log = git_cmd(&main_dir, &["log", "--format=%s", "main"]);
+    assert!(log.starts_with("Session work\n"), "main after merge: {}", log);
 }
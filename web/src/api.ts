@@ -1,8 +1,8 @@
 // Import types from generated types
-import type { Change, Diff, Review, GraphRow, TodoTree, SessionSummary } from './types';
+import type { Author, Change, Comment, ConflictedFile, Diff, FileDiff, FileLines, Review, GraphRow, VerdictKind, TodoTree, SessionSummary, Severity, Side, Thread, ThreadAnchor, Identity } from './types';
 
 // Re-export types for consumers
-export type { Change, Diff, FileDiff, Review, Thread, Comment, Author, ThreadStatus, GraphRow, NodeLine, PadLine, TodoItem, TodoTree, SessionSummary } from './types';
+export type { Change, ConflictedFile, ConflictRegion, Diff, DiffLine, FileDiff, FileLines, Hunk, Review, ReviewerProgress, Thread, Verdict, VerdictKind, Comment, Author, AuthorKind, Identity, Severity, Side, StatusChange, ThreadAnchor, ThreadStatus, GraphRow, NodeLine, PadLine, TodoItem, TodoTree, SessionSummary } from './types';
 
 const API_BASE = '/api';
 
@@ -28,6 +28,10 @@ export interface DiffResponse {
   diff: Diff;
   target_message?: string;
   message_diff?: DiffChunk[];
+  // Set for interdiffs: whether the change was rebased between the two
+  // revisions, and files where upstream edits couldn't be separated out
+  parent_moved?: boolean;
+  conflicted_files?: string[];
 }
 
 export async function fetchDiff(changeId: string, commitId?: string, baseCommitId?: string, session?: string): Promise<DiffResponse> {
@@ -45,6 +49,99 @@ export async function fetchDiff(changeId: string, commitId?: string, baseCommitI
   return { diff: data.diff, target_message: data.target_message, message_diff: data.message_diff };
 }
 
+// What the author changed between two revisions of a change, without
+// upstream changes picked up by rebasing. `from` and `to` are review revision
+// numbers or commit ids.
+export async function fetchInterdiff(changeId: string, from: string, to?: string, session?: string): Promise<DiffResponse> {
+  const params = new URLSearchParams({ from });
+  if (to) params.set('to', to);
+  if (session) params.set('session', session);
+  const res = await fetch(`${API_BASE}/changes/${changeId}/interdiff?${params}`);
+  if (!res.ok) throw new Error(`Failed to fetch interdiff: ${res.statusText}`);
+  const data = await res.json();
+  return {
+    diff: data.diff,
+    target_message: data.target_message,
+    message_diff: data.message_diff,
+    parent_moved: data.parent_moved,
+    conflicted_files: data.conflicted_files,
+  };
+}
+
+// The thread's region, from the commit it was made on to the commit said to
+// address it
+export interface ThreadFix {
+  from: string;
+  to: string;
+  files: FileDiff[];
+  parent_moved: boolean;
+  conflicted_files: string[];
+}
+
+export async function fetchThreadFix(changeId: string, threadId: string, session?: string): Promise<ThreadFix> {
+  const params = new URLSearchParams();
+  if (session) params.set('session', session);
+  const res = await fetch(`${API_BASE}/changes/${changeId}/threads/${threadId}/fix?${params}`);
+  if (!res.ok) throw new Error(await res.text() || `Failed to fetch fix: ${res.statusText}`);
+  return res.json();
+}
+
+// The conflicted files of a change, each split into agreed text and conflicts
+export async function fetchConflicts(changeId: string, session?: string): Promise<ConflictedFile[]> {
+  const params = new URLSearchParams();
+  if (session) params.set('session', session);
+  const res = await fetch(`${API_BASE}/changes/${changeId}/conflicts?${params}`);
+  if (!res.ok) throw new Error(await res.text() || `Failed to fetch conflicts: ${res.statusText}`);
+  const data = await res.json();
+  return data.files;
+}
+
+// Pick a side per conflict, or give the file's resolved text
+export type Resolution = { sides: number[] } | { text: string };
+
+export interface ResolvedConflict {
+  change_id: string;
+  path: string;
+  // Files still conflicted in the change
+  remaining: string[];
+}
+
+// Resolve one file, in the change itself or (separate) as a change on top
+export async function resolveConflict(
+  changeId: string,
+  path: string,
+  resolution: Resolution,
+  opts: { separate?: boolean; session?: string } = {}
+): Promise<ResolvedConflict> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/conflicts/resolve`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ path, ...resolution, ...opts }),
+  });
+  if (!res.ok) throw new Error(await res.text() || `Failed to resolve conflict: ${res.statusText}`);
+  return res.json();
+}
+
+// Lines of a file on one side of a diff, to fill in context the diff left out.
+// Without `end`, everything from `start` to the end of the file.
+export async function fetchFileLines(
+  changeId: string,
+  path: string,
+  start: number,
+  end: number | undefined,
+  opts: { side?: Side; commit?: string; base?: string; session?: string } = {}
+): Promise<FileLines> {
+  const params = new URLSearchParams({ path, start: String(start) });
+  if (end !== undefined) params.set('end', String(end));
+  if (opts.side) params.set('side', opts.side);
+  if (opts.commit) params.set('commit', opts.commit);
+  if (opts.base) params.set('base', opts.base);
+  if (opts.session) params.set('session', opts.session);
+  const res = await fetch(`${API_BASE}/changes/${changeId}/lines?${params}`);
+  if (!res.ok) throw new Error(`Failed to fetch lines: ${res.statusText}`);
+  return res.json();
+}
+
 export async function fetchReview(changeId: string): Promise<Review | null> {
   const res = await fetch(`${API_BASE}/changes/${changeId}/review`);
   if (!res.ok) throw new Error(`Failed to fetch review: ${res.statusText}`);
@@ -68,7 +165,10 @@ export async function addComment(
   file: string,
   lineStart: number,
   lineEnd: number,
-  text: string
+  text: string,
+  side: Side = 'new',
+  draft = false,
+  suggestion?: string[]
 ): Promise<{ review: Review; thread_id: string }> {
   const res = await fetch(`${API_BASE}/changes/${changeId}/comments`, {
     method: 'POST',
@@ -77,28 +177,125 @@ export async function addComment(
       file,
       line_start: lineStart,
       line_end: lineEnd,
+      side,
       text,
+      draft,
+      suggestion,
     }),
   });
   if (!res.ok) throw new Error(`Failed to add comment: ${res.statusText}`);
   return res.json();
 }
 
+// Start a thread on a whole file, the commit description, or the whole change
+export async function startThread(
+  changeId: string,
+  anchor: Exclude<ThreadAnchor, 'line'>,
+  text: string,
+  file?: string,
+  draft = false
+): Promise<{ review: Review; thread_id: string }> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/comments`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ anchor, file, text, draft }),
+  });
+  if (!res.ok) throw new Error(`Failed to add comment: ${res.statusText}`);
+  return res.json();
+}
+
+// Short human-readable description of what a thread is attached to
+export function threadLocation(thread: Thread): string {
+  switch (thread.anchor) {
+    case 'file':
+      return `${thread.display_file ?? thread.file} (file)`;
+    case 'description':
+      return 'commit description';
+    case 'change':
+      return 'whole change';
+    default: {
+      const start = thread.display_line_start ?? thread.line_start;
+      const end = thread.display_line_end ?? thread.line_end;
+      return `${thread.display_file ?? thread.file}:${start}-${end}`;
+    }
+  }
+}
+
 export async function replyToThread(
   changeId: string,
   threadId: string,
-  text: string
+  text: string,
+  draft = false,
+  suggestion?: string[]
 ): Promise<Review> {
   const res = await fetch(`${API_BASE}/changes/${changeId}/threads/${threadId}/reply`, {
     method: 'POST',
     headers: { 'Content-Type': 'application/json' },
-    body: JSON.stringify({ text }),
+    body: JSON.stringify({ text, draft, suggestion }),
   });
   if (!res.ok) throw new Error(`Failed to reply: ${res.statusText}`);
   const data = await res.json();
   return data.review;
 }
 
+// Change the text of one of the reviewer's comments; the old text is kept
+export async function editComment(changeId: string, threadId: string, commentId: string, text: string): Promise<Review> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/threads/${threadId}/comments/${commentId}`, {
+    method: 'PATCH',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ text }),
+  });
+  if (!res.ok) throw new Error(`Failed to edit comment: ${res.statusText}`);
+  const data = await res.json();
+  return data.review;
+}
+
+// Delete one of the reviewer's comments (and its thread, if it was the last)
+export async function deleteComment(changeId: string, threadId: string, commentId: string): Promise<Review> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/threads/${threadId}/comments/${commentId}`, {
+    method: 'DELETE',
+  });
+  if (!res.ok) throw new Error(`Failed to delete comment: ${res.statusText}`);
+  const data = await res.json();
+  return data.review;
+}
+
+// Pull a ```suggestion fenced block out of comment text, GitHub style. The
+// block's lines replace the commented lines when the suggestion is applied.
+export function splitSuggestion(text: string): { text: string; suggestion?: string[] } {
+  const match = text.match(/```suggestion\n([\s\S]*?)\n?```/);
+  if (!match) return { text };
+  const rest = (text.slice(0, match.index) + text.slice(match.index! + match[0].length)).trim();
+  return {
+    text: rest || 'Suggested change',
+    suggestion: match[1] === '' ? [] : match[1].split('\n'),
+  };
+}
+
+export interface AppliedSuggestion {
+  change_id: string;
+  file: string;
+  line_start: number;
+  line_end: number;
+  squashed: boolean;
+}
+
+// Apply a thread's suggestion as a new change on top, or squashed into the change
+export async function applySuggestion(
+  changeId: string,
+  threadId: string,
+  squash: boolean,
+  session?: string
+): Promise<AppliedSuggestion> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/threads/${threadId}/apply-suggestion`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ squash, session }),
+  });
+  if (!res.ok) throw new Error(await res.text() || `Failed to apply suggestion: ${res.statusText}`);
+  return res.json();
+}
+
 export async function resolveThread(
   changeId: string,
   threadId: string
@@ -123,6 +320,95 @@ export async function reopenThread(
   return data.review;
 }
 
+// Set a thread's severity and/or replace its labels
+export async function classifyThread(
+  changeId: string,
+  threadId: string,
+  severity?: Severity,
+  labels?: string[]
+): Promise<Review> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/threads/${threadId}/classify`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ severity, labels }),
+  });
+  if (!res.ok) throw new Error(`Failed to classify: ${res.statusText}`);
+  const data = await res.json();
+  return data.review;
+}
+
+// Who is reviewing from this machine; the server attributes comments,
+// verdicts and read markers to them
+export async function fetchIdentity(): Promise<Identity> {
+  const res = await fetch(`${API_BASE}/identity`);
+  if (!res.ok) throw new Error(`Failed to fetch identity: ${res.statusText}`);
+  return res.json();
+}
+
+// How an author is shown: their name, and the session for agents
+export function authorLabel(author: Author): string {
+  return author.session ? `${author.name} (${author.session})` : author.name;
+}
+
+// Record what the reviewer has seen: with `threadId`, that thread's comments;
+// otherwise the change's current revision and every comment on it.
+export async function markSeen(changeId: string, threadId?: string): Promise<Review> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/seen`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ thread_id: threadId }),
+  });
+  if (!res.ok) throw new Error(`Failed to mark seen: ${res.statusText}`);
+  const data = await res.json();
+  return data.review;
+}
+
+// Whether a comment by someone else is one the reviewer hasn't read.
+// Mirrors Review::unread_replies on the server.
+// When a comment last changed: posted, or edited since
+export function commentUpdatedAt(comment: Comment): string {
+  return comment.edits.length > 0 ? comment.edits[comment.edits.length - 1].edited_at : comment.timestamp;
+}
+
+export function isUnread(review: Review, thread: Thread, comment: Comment, reviewer: string): boolean {
+  if (comment.author.kind === 'human' && comment.author.name === reviewer) return false;
+  const progress = review.seen[reviewer];
+  const marks = [progress?.last_read_comment, progress?.threads[thread.id]].filter((t): t is string => !!t);
+  return marks.every(t => Date.parse(commentUpdatedAt(comment)) > Date.parse(t));
+}
+
+// Approve the change's current commit or request changes on it
+export async function setVerdict(changeId: string, verdict: VerdictKind, comment?: string): Promise<Review> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/verdict`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ verdict, comment }),
+  });
+  if (!res.ok) throw new Error(`Failed to set verdict: ${res.statusText}`);
+  const data = await res.json();
+  return data.review;
+}
+
+// Publish every draft comment on the change at once, optionally with a verdict
+export async function submitReview(
+  changeId: string,
+  verdict?: VerdictKind,
+  comment?: string
+): Promise<{ review: Review; published: number }> {
+  const res = await fetch(`${API_BASE}/changes/${changeId}/review/submit`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ verdict, comment }),
+  });
+  if (!res.ok) throw new Error(`Failed to submit review: ${res.statusText}`);
+  return res.json();
+}
+
+// Number of draft comments waiting to be submitted
+export function draftCount(review: Review): number {
+  return review.threads.reduce((n, t) => n + t.comments.filter(c => c.draft).length, 0);
+}
+
 export interface MergeResult {
   success: boolean;
   message: string;
@@ -254,8 +540,8 @@ export interface TimelineEntry {
   line_start?: number;
   line_end?: number;
   text?: string;
-  // ReviewReply fields
-  author?: string;
+  // ReviewReply and ChatMessage fields
+  author?: Author;
   // ChatMessage fields
   session_id?: string;
   // CodeSnapshot fields
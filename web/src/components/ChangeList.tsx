@@ -65,8 +65,27 @@ const ChangeItem = forwardRef<HTMLButtonElement, ChangeItemProps>(function Chang
           </span>
         )}
         <span className="flex-1" />
+        {!change.merged && change.has_unseen_revision && (
+          <span className="w-1.5 h-1.5 rounded-full bg-blue-500" title="Changed since you last reviewed it" />
+        )}
+        {change.unread_reply_count > 0 && (
+          <span className="text-xs bg-purple-100 text-purple-700 px-1 py-0.5 rounded leading-none" title="Unread replies">
+            {change.unread_reply_count} new
+          </span>
+        )}
+        {change.verdict === 'approved' && (
+          <span className="font-mono text-xs font-bold text-green-600" title="Approved at the current commit">✓</span>
+        )}
+        {change.verdict === 'changes_requested' && (
+          <span className="font-mono text-xs font-bold text-red-600" title="Changes requested">✗</span>
+        )}
         {change.open_thread_count > 0 && (
-          <span className="text-xs bg-amber-100 text-amber-700 px-1 py-0.5 rounded leading-none" title="Open threads">
+          <span
+            className={`text-xs px-1 py-0.5 rounded leading-none ${
+              change.blocking_thread_count > 0 ? 'bg-red-100 text-red-700' : 'bg-amber-100 text-amber-700'
+            }`}
+            title={`Open threads (${change.blocking_thread_count} blocking)`}
+          >
             {change.open_thread_count}
           </span>
         )}
@@ -1,7 +1,8 @@
-import { useRef, useEffect, forwardRef } from 'react';
-import { Thread } from '../types';
+import { useRef, useEffect, useState, forwardRef } from 'react';
+import { Comment, Severity, Thread, ThreadStatus, VerdictKind } from '../types';
 import { useAppContext } from '../context';
-import { replyToThread, resolveThread, reopenThread, type Change, type Review } from '../hooks';
+import { authorLabel, draftCount, fetchThreadFix, isUnread, threadLocation, type ThreadFix } from '../api';
+import { applySuggestion, classifyThread, deleteComment, editComment, markSeen, useIdentity, setVerdict, submitReview, replyToThread, resolveThread, reopenThread, type Change, type Review } from '../hooks';
 
 interface CommentPanelProps {
   review: Review;
@@ -17,6 +18,7 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
     navigateThreads,
   } = useAppContext();
   const threadsFocused = focusedPanel === 'threads';
+  const identity = useIdentity();
 
   const replyInputRef = useRef<HTMLTextAreaElement>(null);
   const threadRefs = useRef<Map<string, HTMLDivElement>>(new Map());
@@ -35,8 +37,8 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
       if (e.target instanceof HTMLTextAreaElement || e.target instanceof HTMLInputElement) return;
       if (!threadsFocused) return;
 
-      // Compute thread IDs for navigation (open first, then resolved sorted by revision)
-      const openIds = review.threads.filter((t) => t.status === 'open').map((t) => t.id);
+      // Compute thread IDs for navigation (unresolved first, then resolved sorted by revision)
+      const openIds = review.threads.filter((t) => t.status !== 'resolved').map((t) => t.id);
       const resolvedIds = review.threads
         .filter((t) => t.status === 'resolved')
         .slice()
@@ -71,14 +73,14 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
       if (e.key === 'r') {
         e.preventDefault();
         const thread = review.threads.find((t) => t.id === selectedThreadId);
-        if (thread?.status === 'open') {
+        if (thread && thread.status !== 'resolved') {
           startReply(selectedThreadId);
         }
       } else if (e.key === 'x') {
         e.preventDefault();
         const thread = review.threads.find((t) => t.id === selectedThreadId);
         if (thread) {
-          if (thread.status === 'open') {
+          if (thread.status !== 'resolved') {
             resolveThread(selectedChange.change_id, selectedThreadId);
           } else {
             reopenThread(selectedChange.change_id, selectedThreadId);
@@ -98,7 +100,7 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
     }
   }, [replyingToThread, threadsFocused]);
 
-  const openThreads = review.threads.filter((t) => t.status === 'open');
+  const openThreads = review.threads.filter((t) => t.status !== 'resolved');
   // Sort resolved threads by revision (newest first), then by id for stable ordering
   const resolvedThreads = review.threads
     .filter((t) => t.status === 'resolved')
@@ -110,12 +112,73 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
       return b.id.localeCompare(a.id); // stable secondary sort
     });
   const totalThreads = review.threads.length;
+  const unreadIn = (thread: Thread) => thread.comments.filter(c => isUnread(review, thread, c, identity.name));
+  const unreadCount = review.threads.reduce((n, t) => n + unreadIn(t).length, 0);
+  const drafts = draftCount(review);
+  // With drafts pending, a verdict is submitted together with them
+  const giveVerdict = (verdict: VerdictKind, comment?: string) =>
+    drafts > 0 ? submitReview(review.change_id, verdict, comment) : setVerdict(review.change_id, verdict, comment);
 
   return (
     <div className="divide-y divide-gray-200">
       {/* Comments header */}
       <div className={`p-4 ${threadsFocused ? 'bg-blue-50/50' : ''}`}>
-        <h3 className="font-semibold text-sm text-gray-700">Comments ({totalThreads})</h3>
+        <div className="flex items-center justify-between gap-2">
+          <h3 className="font-semibold text-sm text-gray-700">
+            Comments ({totalThreads})
+            {unreadCount > 0 && <span className="ml-2 text-xs font-normal text-purple-600">{unreadCount} new</span>}
+          </h3>
+          <span className="flex-1" />
+          {selectedChange.verdict === 'approved' && (
+            <span className="text-xs bg-green-100 text-green-700 px-1.5 py-0.5 rounded">Approved</span>
+          )}
+          {selectedChange.verdict === 'changes_requested' && (
+            <span className="text-xs bg-red-100 text-red-700 px-1.5 py-0.5 rounded">Changes requested</span>
+          )}
+          {selectedChange.verdict !== 'approved' && (
+            <button
+              onClick={() => giveVerdict('approved').catch(e => console.error('Failed to approve:', e))}
+              className="px-2 py-1 text-xs text-green-600 hover:text-green-700 hover:bg-green-50 rounded"
+              title="Approve the current commit"
+            >
+              Approve
+            </button>
+          )}
+          {selectedChange.verdict !== 'changes_requested' && (
+            <button
+              onClick={() => {
+                const comment = prompt('What needs to change? (optional)');
+                if (comment === null) return;
+                giveVerdict('changes_requested', comment || undefined)
+                  .catch(e => console.error('Failed to request changes:', e));
+              }}
+              className="px-2 py-1 text-xs text-red-600 hover:text-red-700 hover:bg-red-50 rounded"
+              title="Request changes on the current commit"
+            >
+              Request changes
+            </button>
+          )}
+          {drafts > 0 && (
+            <button
+              onClick={() => submitReview(review.change_id).catch(e => console.error('Failed to submit review:', e))}
+              className="px-2 py-1 text-xs text-blue-600 hover:text-blue-700 hover:bg-blue-50 rounded"
+              title="Publish draft comments without a verdict"
+            >
+              Submit review ({drafts})
+            </button>
+          )}
+          {selectedChange.has_unseen_revision || unreadCount > 0 ? (
+            <button
+              onClick={() => markSeen(review.change_id).catch(e => console.error('Failed to mark reviewed:', e))}
+              className="px-2 py-1 text-xs text-blue-600 hover:text-blue-700 hover:bg-blue-50 rounded"
+              title="Remember this revision and its comments as seen"
+            >
+              Mark reviewed
+            </button>
+          ) : (
+            <span className="text-xs text-gray-400">Reviewed</span>
+          )}
+        </div>
       </div>
 
       {openThreads.length > 0 && (
@@ -133,6 +196,7 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
                 }}
                 thread={thread}
                 changeId={review.change_id}
+                unread={unreadIn(thread)}
                 replyInputRef={selectedThreadId === thread.id ? replyInputRef : undefined}
               />
             ))}
@@ -155,6 +219,7 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
                 }}
                 thread={thread}
                 changeId={review.change_id}
+                unread={unreadIn(thread)}
                 replyInputRef={undefined}
               />
             ))}
@@ -165,14 +230,32 @@ export function CommentPanel({ review, selectedChange }: CommentPanelProps) {
   );
 }
 
+// Labels for the states between open and resolved
+const STATUS_LABELS: Partial<Record<ThreadStatus, string>> = {
+  acknowledged: 'acknowledged',
+  in_progress: 'in progress',
+  addressed: 'addressed — verify',
+};
+
+const SEVERITIES: Severity[] = ['blocking', 'suggestion', 'question', 'nit'];
+
+const SEVERITY_STYLES: Record<Severity, string> = {
+  blocking: 'bg-red-100 text-red-700',
+  suggestion: 'bg-blue-100 text-blue-700',
+  question: 'bg-purple-100 text-purple-700',
+  nit: 'bg-gray-100 text-gray-600',
+};
+
 interface ThreadCardProps {
   thread: Thread;
   changeId: string;
+  // Comments by others the reviewer hasn't read yet
+  unread: Comment[];
   replyInputRef?: React.RefObject<HTMLTextAreaElement>;
 }
 
 const ThreadCard = forwardRef<HTMLDivElement, ThreadCardProps>(function ThreadCard(
-  { thread, changeId, replyInputRef },
+  { thread, changeId, unread, replyInputRef },
   ref
 ) {
   // Get state from context
@@ -187,10 +270,16 @@ const ThreadCard = forwardRef<HTMLDivElement, ThreadCardProps>(function ThreadCa
     cancelReply,
     setReplyText,
     setSubmittingReply,
+    selectedSessionName,
   } = useAppContext();
   const threadsFocused = focusedPanel === 'threads';
 
+  const identity = useIdentity();
   const selected = selectedThreadId === thread.id;
+  const [fix, setFix] = useState<ThreadFix | null>(null);
+  // The comment being edited, and its new text
+  const [editing, setEditing] = useState<{ id: string; text: string } | null>(null);
+  const hasSuggestion = thread.comments.some((c) => c.suggestion && !c.draft);
   const replying = replyingToThread && selected && threadsFocused;
 
   const handleSubmitReply = async () => {
@@ -207,9 +296,50 @@ const ThreadCard = forwardRef<HTMLDivElement, ThreadCardProps>(function ThreadCa
     }
   };
 
+  const isOwn = (comment: Comment) => comment.author.kind === 'human' && comment.author.name === identity.name;
+
+  const handleSaveEdit = async () => {
+    if (!editing || !editing.text.trim()) return;
+    try {
+      await editComment(changeId, thread.id, editing.id, editing.text.trim());
+      setEditing(null);
+    } catch (e) {
+      alert(e instanceof Error ? e.message : String(e));
+    }
+  };
+
+  const handleDelete = async (comment: Comment) => {
+    if (!confirm('Delete this comment?')) return;
+    try {
+      await deleteComment(changeId, thread.id, comment.id);
+    } catch (e) {
+      alert(e instanceof Error ? e.message : String(e));
+    }
+  };
+
+  const handleApplySuggestion = async (squash: boolean) => {
+    try {
+      await applySuggestion(changeId, thread.id, squash, selectedSessionName ?? undefined);
+    } catch (e) {
+      alert(e instanceof Error ? e.message : String(e));
+    }
+  };
+
+  const handleToggleFix = async () => {
+    if (fix) {
+      setFix(null);
+      return;
+    }
+    try {
+      setFix(await fetchThreadFix(changeId, thread.id, selectedSessionName ?? undefined));
+    } catch (e) {
+      alert(e instanceof Error ? e.message : String(e));
+    }
+  };
+
   const handleToggleStatus = async () => {
     try {
-      if (thread.status === 'open') {
+      if (thread.status !== 'resolved') {
         await resolveThread(changeId, thread.id);
       } else {
         await reopenThread(changeId, thread.id);
@@ -222,7 +352,12 @@ const ThreadCard = forwardRef<HTMLDivElement, ThreadCardProps>(function ThreadCa
   return (
     <div
       ref={ref}
-      onClick={() => setSelectedThreadId(thread.id)}
+      onClick={() => {
+        setSelectedThreadId(thread.id);
+        if (unread.length > 0) {
+          markSeen(changeId, thread.id).catch(e => console.error('Failed to mark read:', e));
+        }
+      }}
       className={`bg-white border rounded-lg p-3 shadow-sm cursor-pointer transition-colors ${
         selected && threadsFocused
           ? 'border-blue-500 ring-2 ring-blue-200'
@@ -232,32 +367,155 @@ const ThreadCard = forwardRef<HTMLDivElement, ThreadCardProps>(function ThreadCa
       }`}
     >
       <div className="text-xs text-gray-400 mb-2 font-mono">
-        {thread.file}:{thread.line_start}
+        {thread.anchor === 'line' ? `${thread.file}:${thread.line_start}` : threadLocation(thread)}
+        {thread.anchor === 'line' && thread.side === 'old' && <span className="ml-1 text-red-400">(old)</span>}
         {thread.created_at_revision != null && (
           <span className="ml-2 text-gray-500">v{thread.created_at_revision}</span>
         )}
+        {thread.moved_from && (
+          <span className="ml-2 text-gray-500" title={thread.moved_from.operation ?? undefined}>
+            moved from {thread.moved_from.from_change_id.slice(0, 8)}
+          </span>
+        )}
+        <span className={`ml-2 px-1 rounded leading-none ${SEVERITY_STYLES[thread.severity]}`}>{thread.severity}</span>
+        {STATUS_LABELS[thread.status] && (
+          <span className="ml-1 px-1 rounded leading-none bg-amber-100 text-amber-700">{STATUS_LABELS[thread.status]}</span>
+        )}
+        {thread.labels.map((label) => (
+          <span key={label} className="ml-1 px-1 rounded leading-none bg-gray-100 text-gray-500">{label}</span>
+        ))}
       </div>
 
       <div className="space-y-2">
-        {thread.comments.map((comment, idx) => (
+        {thread.comments.map((comment) => (
           <div
-            key={idx}
+            key={comment.id}
             className={`text-sm ${
-              comment.author === 'claude' ? 'bg-purple-50 border-l-2 border-purple-400 pl-2' : ''
+              comment.author.kind !== 'human' ? 'bg-purple-50 border-l-2 border-purple-400 pl-2' : ''
             }`}
           >
             <span
               className={`text-xs font-semibold ${
-                comment.author === 'claude' ? 'text-purple-600' : 'text-blue-600'
+                comment.author.kind !== 'human' ? 'text-purple-600' : 'text-blue-600'
               }`}
+              title={comment.author.kind}
             >
-              {comment.author}:
+              {authorLabel(comment.author)}:
             </span>{' '}
-            <span className="text-gray-700">{comment.text}</span>
+            {unread.includes(comment) && (
+              <span className="text-xs bg-purple-100 text-purple-700 px-1 rounded leading-none">new</span>
+            )}
+            {comment.draft && (
+              <span className="text-xs bg-yellow-100 text-yellow-700 px-1 rounded leading-none">draft</span>
+            )}{' '}
+            {editing?.id === comment.id ? (
+              <div className="mt-1" onClick={(e) => e.stopPropagation()}>
+                <textarea
+                  value={editing.text}
+                  onChange={(e) => setEditing({ id: comment.id, text: e.target.value })}
+                  onKeyDown={(e) => {
+                    e.stopPropagation();
+                    if (e.key === 'Enter' && (e.metaKey || e.ctrlKey)) handleSaveEdit();
+                    if (e.key === 'Escape') setEditing(null);
+                  }}
+                  className="w-full p-2 border border-gray-300 rounded text-sm resize-none focus:outline-none focus:ring-1 focus:ring-blue-500"
+                  rows={2}
+                  autoFocus
+                />
+                <div className="flex gap-2 text-xs">
+                  <button onClick={handleSaveEdit} className="text-blue-600 hover:text-blue-700">Save</button>
+                  <button onClick={() => setEditing(null)} className="text-gray-500 hover:text-gray-700">Cancel</button>
+                </div>
+              </div>
+            ) : (
+              <span className="text-gray-700">{comment.text}</span>
+            )}
+            {comment.edits.length > 0 && (
+              <span
+                className="ml-1 text-xs text-gray-400"
+                title={comment.edits.map((e) => `${new Date(e.edited_at).toLocaleString()}: ${e.previous_text}`).join('\n')}
+              >
+                (edited)
+              </span>
+            )}
+            {selected && isOwn(comment) && editing?.id !== comment.id && (
+              <span className="ml-2 text-xs space-x-2">
+                <button
+                  onClick={(e) => {
+                    e.stopPropagation();
+                    setEditing({ id: comment.id, text: comment.text });
+                  }}
+                  className="text-gray-400 hover:text-gray-600"
+                >
+                  Edit
+                </button>
+                <button
+                  onClick={(e) => {
+                    e.stopPropagation();
+                    handleDelete(comment);
+                  }}
+                  className="text-gray-400 hover:text-red-600"
+                >
+                  Delete
+                </button>
+              </span>
+            )}
+            {comment.suggestion && (
+              <pre className="mt-1 text-xs bg-green-50 border border-green-200 rounded p-2 overflow-x-auto">
+                {comment.suggestion.join('\n')}
+              </pre>
+            )}
           </div>
         ))}
       </div>
 
+      {selected && thread.addressed_in && (
+        <div className="mt-2 text-xs">
+          <button
+            onClick={(e) => {
+              e.stopPropagation();
+              handleToggleFix();
+            }}
+            className="text-blue-600 hover:text-blue-700"
+            title="What changed here between the commented commit and the fix"
+          >
+            {fix ? 'Hide fix' : `Show fix (${thread.addressed_in.slice(0, 8)})`}
+          </button>
+          {fix && (
+            <pre className="mt-1 bg-gray-50 border border-gray-200 rounded p-2 overflow-x-auto">
+              {fix.files.length === 0 && <span className="text-gray-400">No changes in this region</span>}
+              {fix.files.flatMap((file) =>
+                file.hunks.flatMap((hunk, hidx) => [
+                  <div key={`${file.path}:${hidx}`} className="text-gray-400">
+                    {file.path} @@ -{hunk.old_start} +{hunk.new_start} @@
+                  </div>,
+                  ...hunk.lines.map((line, lidx) => (
+                    <div
+                      key={`${file.path}:${hidx}:${lidx}`}
+                      className={line.kind === 'add' ? 'text-green-700' : line.kind === 'delete' ? 'text-red-700' : 'text-gray-600'}
+                    >
+                      {line.kind === 'add' ? '+' : line.kind === 'delete' ? '-' : ' '}
+                      {line.content}
+                    </div>
+                  )),
+                ])
+              )}
+            </pre>
+          )}
+        </div>
+      )}
+
+      {selected && thread.history.length > 0 && (
+        <ul className="mt-2 space-y-0.5 text-xs text-gray-400">
+          {thread.history.map((change, idx) => (
+            <li key={idx} title={new Date(change.timestamp).toLocaleString()}>
+              {authorLabel(change.author)}: {change.from.replace('_', ' ')} → {change.to.replace('_', ' ')}
+              {change.revision != null && ` (v${change.revision})`}
+            </li>
+          ))}
+        </ul>
+      )}
+
       {/* Actions for selected thread */}
       {selected && (
         <div className="mt-3 pt-3 border-t border-gray-100">
@@ -319,9 +577,58 @@ const ThreadCard = forwardRef<HTMLDivElement, ThreadCardProps>(function ThreadCa
                   handleToggleStatus();
                 }}
                 className="px-2 py-1 text-xs text-green-600 hover:text-green-700 hover:bg-green-50 rounded"
+                title={thread.status === 'addressed' ? 'Confirm the agent addressed this' : undefined}
               >
-                Resolve
+                {thread.status === 'addressed' ? 'Verify' : 'Resolve'}
               </button>
+              {thread.status === 'addressed' && (
+                <button
+                  onClick={(e) => {
+                    e.stopPropagation();
+                    reopenThread(changeId, thread.id).catch(err => console.error('Failed to reopen:', err));
+                  }}
+                  className="px-2 py-1 text-xs text-amber-600 hover:text-amber-700 hover:bg-amber-50 rounded"
+                  title="Not addressed yet; send it back to the agent"
+                >
+                  Reopen
+                </button>
+              )}
+              <select
+                value={thread.severity}
+                onClick={(e) => e.stopPropagation()}
+                onChange={(e) => {
+                  classifyThread(changeId, thread.id, e.target.value as Severity)
+                    .catch(err => console.error('Failed to set severity:', err));
+                }}
+                className="text-xs text-gray-600 bg-transparent border border-gray-200 rounded px-1 py-0.5"
+                title="Only blocking threads hold up a merge"
+              >
+                {SEVERITIES.map((s) => <option key={s} value={s}>{s}</option>)}
+              </select>
+              {hasSuggestion && (
+                <span className="flex gap-1">
+                  <button
+                    onClick={(e) => {
+                      e.stopPropagation();
+                      handleApplySuggestion(false);
+                    }}
+                    className="px-2 py-1 text-xs text-blue-600 hover:text-blue-700 hover:bg-blue-50 rounded"
+                    title="Apply the suggestion as a new change on top of this one"
+                  >
+                    Apply
+                  </button>
+                  <button
+                    onClick={(e) => {
+                      e.stopPropagation();
+                      handleApplySuggestion(true);
+                    }}
+                    className="px-2 py-1 text-xs text-blue-600 hover:text-blue-700 hover:bg-blue-50 rounded"
+                    title="Squash the suggestion into this change"
+                  >
+                    Squash in
+                  </button>
+                </span>
+              )}
               <button
                 onClick={(e) => {
                   e.stopPropagation();
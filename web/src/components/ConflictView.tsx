@@ -0,0 +1,140 @@
+import { useState } from 'react';
+import type { ConflictedFile, ConflictRegion } from '../types';
+import { resolveConflict, useConflicts, type Change } from '../hooks';
+
+interface ConflictViewProps {
+  change: Change;
+  session?: string;
+}
+
+// The conflicts of a conflicted change, resolvable by picking a side per
+// conflict or editing the file's text
+export function ConflictView({ change, session }: ConflictViewProps) {
+  const files = useConflicts(change.change_id, change.commit_id, session);
+  if (files.length === 0) return null;
+
+  return (
+    <div className="m-4 border border-red-200 rounded-lg bg-red-50">
+      <div className="px-3 py-2 text-sm font-semibold text-red-700 border-b border-red-200">
+        Conflicts in {files.length} file{files.length === 1 ? '' : 's'}
+      </div>
+      {files.map((file) => (
+        <ConflictedFileView key={file.path} file={file} changeId={change.change_id} session={session} />
+      ))}
+    </div>
+  );
+}
+
+function conflicts(regions: ConflictRegion[]) {
+  return regions.filter((r): r is Extract<ConflictRegion, { kind: 'conflict' }> => r.kind === 'conflict');
+}
+
+// The file with the chosen side of each conflict, for editing by hand
+function render(file: ConflictedFile, choices: number[]): string {
+  let i = 0;
+  const lines = file.regions.flatMap((r) => (r.kind === 'resolved' ? r.lines : r.sides[choices[i++]] ?? []));
+  return lines.join('\n') + (file.final_newline && lines.length > 0 ? '\n' : '');
+}
+
+function ConflictedFileView({ file, changeId, session }: { file: ConflictedFile; changeId: string; session?: string }) {
+  const count = conflicts(file.regions).length;
+  const [choices, setChoices] = useState<(number | undefined)[]>(() => Array(count).fill(undefined));
+  const [text, setText] = useState<string | null>(null);
+  const [separate, setSeparate] = useState(false);
+  const [submitting, setSubmitting] = useState(false);
+  const allChosen = choices.every((c) => c !== undefined);
+
+  const handleResolve = async () => {
+    setSubmitting(true);
+    try {
+      const resolution = text !== null ? { text } : { sides: choices as number[] };
+      await resolveConflict(changeId, file.path, resolution, { separate, session });
+    } catch (e) {
+      alert(e instanceof Error ? e.message : String(e));
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  let conflictIndex = 0;
+  return (
+    <div className="p-3 border-b border-red-100 last:border-b-0">
+      <div className="flex items-center gap-2 mb-2 text-sm">
+        <span className="font-mono font-semibold">{file.path}</span>
+        <span className="text-xs text-gray-500">
+          {count} conflict{count === 1 ? '' : 's'}
+        </span>
+        <button
+          onClick={() => setText(text === null ? render(file, choices.map((c) => c ?? 0)) : null)}
+          className="ml-auto text-xs text-blue-600 hover:text-blue-700"
+        >
+          {text === null ? 'Edit text' : 'Pick sides'}
+        </button>
+      </div>
+
+      {text !== null ? (
+        <textarea
+          value={text}
+          onChange={(e) => setText(e.target.value)}
+          onKeyDown={(e) => e.stopPropagation()}
+          className="w-full h-64 p-2 font-mono text-xs border border-gray-300 rounded bg-white"
+        />
+      ) : (
+        <div className="font-mono text-xs bg-white border border-gray-200 rounded overflow-x-auto">
+          {file.regions.map((region, idx) => {
+            if (region.kind === 'resolved') {
+              return (
+                <pre key={idx} className="px-2 text-gray-500">
+                  {region.lines.join('\n')}
+                </pre>
+              );
+            }
+            const i = conflictIndex++;
+            return (
+              <div key={idx} className="border-y border-red-200">
+                {region.sides.map((lines, side) => (
+                  <label
+                    key={side}
+                    className={`block px-2 py-1 cursor-pointer ${
+                      choices[i] === side ? 'bg-green-50' : 'hover:bg-gray-50'
+                    }`}
+                  >
+                    <input
+                      type="radio"
+                      name={`${file.path}-${i}`}
+                      checked={choices[i] === side}
+                      onChange={() => setChoices(choices.map((c, j) => (j === i ? side : c)))}
+                      className="mr-2"
+                    />
+                    <span className="text-gray-400">side #{side + 1}</span>
+                    <pre>{lines === null ? '(deletes the file)' : lines.length > 0 ? lines.join('\n') : '(nothing)'}</pre>
+                  </label>
+                ))}
+                {region.bases.map((lines, base) => (
+                  <details key={`base-${base}`} className="px-2 py-1 text-gray-400">
+                    <summary>base{region.bases.length > 1 ? ` #${base + 1}` : ''}</summary>
+                    <pre>{lines.join('\n')}</pre>
+                  </details>
+                ))}
+              </div>
+            );
+          })}
+        </div>
+      )}
+
+      <div className="flex items-center gap-3 mt-2 text-xs">
+        <button
+          onClick={handleResolve}
+          disabled={submitting || (text === null && !allChosen)}
+          className="px-2 py-1 bg-green-600 text-white rounded hover:bg-green-700 disabled:opacity-50"
+        >
+          {submitting ? 'Resolving...' : 'Resolve'}
+        </button>
+        <label className="flex items-center gap-1 text-gray-600">
+          <input type="checkbox" checked={separate} onChange={(e) => setSeparate(e.target.checked)} />
+          as a new change on top
+        </label>
+      </div>
+    </div>
+  );
+}
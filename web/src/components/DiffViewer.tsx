@@ -1,6 +1,6 @@
 import { useState, useEffect, useCallback, useMemo, useRef, forwardRef, useImperativeHandle } from 'react';
 import { VList, VListHandle } from 'virtua';
-import { Thread, Diff, Review, addComment, DiffChunk } from '../api';
+import { Thread, ThreadAnchor, Diff, DiffLine, FileDiff, Review, Side, addComment, startThread, splitSuggestion, threadLocation, authorLabel, fetchFileLines, DiffChunk } from '../api';
 import { useAppContext } from '../context';
 import { replyToThread, resolveThread, reopenThread } from '../hooks';
 import { mutate } from 'swr';
@@ -23,15 +23,35 @@ const COMMIT_MESSAGE_FILE = '__commit__';
 
 // Flattened row types for virtualization
 type Row =
-  | { type: 'file-header'; path: string }
+  | { type: 'change-header' }
+  | FileHeaderRow
   | { type: 'hunk-header'; header: string }
   | { type: 'line'; file: string; line: ParsedLine }
   | { type: 'thread'; thread: Thread }
-  | { type: 'comment-editor'; file: string; lineStart: number; lineEnd: number }
-  | { type: 'collapsed'; file: string; lines: ParsedLine[]; id: string }
+  | { type: 'comment-editor'; anchor: ThreadAnchor; file: string; lineStart: number; lineEnd: number }
+  | CollapsedRow
+  | { type: 'note'; text: string }
   | { type: 'commit-header'; changeId: string; commitId: string }
   | { type: 'commit-line'; lineNum: number; content: string; diffTag?: 'equal' | 'delete' | 'insert' };
 
+// Renamed and copied files name their source in the header
+type FileHeaderRow = {
+  type: 'file-header';
+  path: string;
+  origin?: { kind: 'renamed' | 'copied'; from: string };
+};
+
+// Unchanged lines the diff left out, fetched when expanded. `newEnd` is null
+// for the lines after the last hunk, whose count isn't known up front.
+interface Gap {
+  newStart: number;
+  newEnd: number | null;
+  // Old-side line number minus new-side line number within the gap
+  oldOffset: number;
+}
+
+type CollapsedRow = { type: 'collapsed'; file: string; id: string; gap: Gap };
+
 interface ParsedLine {
   type: 'context' | 'add' | 'delete';
   content: string;
@@ -39,170 +59,156 @@ interface ParsedLine {
   newLineNum?: number;
 }
 
-const CONTEXT_LINES_TO_SHOW = 3;
+function toParsedLine(line: DiffLine): ParsedLine {
+  return {
+    type: line.kind,
+    content: line.content,
+    oldLineNum: line.old_line ?? undefined,
+    newLineNum: line.new_line ?? undefined,
+  };
+}
 
-function parseDiffToRows(raw: string, expandedSections: Set<string>): Row[] {
-  const rows: Row[] = [];
-  const lines = raw.split('\n');
-
-  let currentFile = '';
-  let oldLine = 0;
-  let newLine = 0;
-  let inHunk = false;
-
-  // First pass: parse all lines
-  const allRows: Row[] = [];
-
-  for (const line of lines) {
-    if (line.startsWith('diff --git')) {
-      inHunk = false;
-    } else if (line.startsWith('+++ b/')) {
-      currentFile = line.slice(6);
-      allRows.push({ type: 'file-header', path: currentFile });
-    } else if (line.startsWith('@@')) {
-      const match = line.match(/@@ -(\d+)(?:,\d+)? \+(\d+)(?:,\d+)? @@/);
-      if (match) {
-        oldLine = parseInt(match[1], 10);
-        newLine = parseInt(match[2], 10);
-      }
-      allRows.push({ type: 'hunk-header', header: line });
-      inHunk = true;
-    } else if (inHunk) {
-      if (line.startsWith('+')) {
-        allRows.push({
-          type: 'line',
-          file: currentFile,
-          line: { type: 'add', content: line.slice(1), newLineNum: newLine++ },
-        });
-      } else if (line.startsWith('-')) {
-        allRows.push({
-          type: 'line',
-          file: currentFile,
-          line: { type: 'delete', content: line.slice(1), oldLineNum: oldLine++ },
-        });
-      } else if (line.startsWith(' ') || line === '') {
-        allRows.push({
-          type: 'line',
-          file: currentFile,
-          line: { type: 'context', content: line.slice(1), oldLineNum: oldLine++, newLineNum: newLine++ },
-        });
-      }
-    }
-  }
+function fileOrigin(file: FileDiff): FileHeaderRow['origin'] {
+  if (typeof file.status !== 'object') return undefined;
+  return 'renamed' in file.status
+    ? { kind: 'renamed', from: file.status.renamed.from }
+    : { kind: 'copied', from: file.status.copied.from };
+}
+
+// The lines of a gap once they have been fetched, a placeholder otherwise
+function gapRows(file: string, gap: Gap, expanded: Map<string, string[]>): Row[] {
+  const id = `${file}:${gap.newStart}`;
+  const lines = expanded.get(id);
+  if (!lines) return [{ type: 'collapsed', file, id, gap }];
+  return lines.map((content, i): Row => ({
+    type: 'line',
+    file,
+    line: {
+      type: 'context',
+      content,
+      newLineNum: gap.newStart + i,
+      oldLineNum: gap.newStart + i + gap.oldOffset,
+    },
+  }));
+}
 
-  // Second pass: collapse context lines
-  let i = 0;
-  while (i < allRows.length) {
-    const row = allRows[i];
+function diffToRows(diff: Diff, expanded: Map<string, string[]>): Row[] {
+  const rows: Row[] = [];
 
-    if (row.type !== 'line' || row.line.type !== 'context') {
-      rows.push(row);
-      i++;
+  for (const file of diff.files) {
+    rows.push({ type: 'file-header', path: file.path, origin: fileOrigin(file) });
+    if (file.binary) {
+      rows.push({ type: 'note', text: 'Binary file not shown' });
       continue;
     }
 
-    // Found a context line - collect the run of context lines
-    const contextLines: ParsedLine[] = [];
-    let contextFile = row.file;
-
-    while (i < allRows.length) {
-      const r = allRows[i];
-      if (r.type === 'line' && r.line.type === 'context' && r.file === contextFile) {
-        contextLines.push(r.line);
-        i++;
-      } else {
-        break;
+    // Added and deleted files are in the diff whole; anything else can have
+    // unchanged lines before, between and after its hunks
+    const hasGaps = file.status !== 'added' && file.status !== 'deleted';
+    let nextNew = 1;
+    let oldOffset = 0;
+    for (const hunk of file.hunks) {
+      if (hasGaps && hunk.new_start > nextNew) {
+        const gap = { newStart: nextNew, newEnd: hunk.new_start - 1, oldOffset: hunk.old_start - hunk.new_start };
+        rows.push(...gapRows(file.path, gap, expanded));
       }
-    }
-
-    // If context run is short enough, just show all lines
-    if (contextLines.length <= CONTEXT_LINES_TO_SHOW * 2 + 1) {
-      for (const line of contextLines) {
-        rows.push({ type: 'line', file: contextFile, line });
+      const header = `@@ -${hunk.old_start},${hunk.old_count} +${hunk.new_start},${hunk.new_count} @@ ${hunk.section}`;
+      rows.push({ type: 'hunk-header', header: header.trimEnd() });
+      for (const line of hunk.lines) {
+        rows.push({ type: 'line', file: file.path, line: toParsedLine(line) });
       }
-      continue;
+      nextNew = hunk.new_start + hunk.new_count;
+      oldOffset = hunk.old_start + hunk.old_count - nextNew;
     }
 
-    // Check if this section is expanded
-    const collapsedId = `${contextFile}:${contextLines[0].newLineNum}`;
-    if (expandedSections.has(collapsedId)) {
-      for (const line of contextLines) {
-        rows.push({ type: 'line', file: contextFile, line });
+    // A last hunk with less trailing context than asked for reaches the end of the file
+    const last = file.hunks[file.hunks.length - 1];
+    if (hasGaps && last) {
+      let trailing = 0;
+      while (trailing < last.lines.length && last.lines[last.lines.length - 1 - trailing].kind === 'context') {
+        trailing++;
+      }
+      if (trailing >= diff.context) {
+        rows.push(...gapRows(file.path, { newStart: nextNew, newEnd: null, oldOffset }, expanded));
       }
-      continue;
-    }
-
-    // Show first few context lines
-    for (let j = 0; j < CONTEXT_LINES_TO_SHOW; j++) {
-      rows.push({ type: 'line', file: contextFile, line: contextLines[j] });
-    }
-
-    // Add collapsed section
-    const hiddenLines = contextLines.slice(CONTEXT_LINES_TO_SHOW, -CONTEXT_LINES_TO_SHOW);
-    rows.push({
-      type: 'collapsed',
-      file: contextFile,
-      lines: hiddenLines,
-      id: collapsedId,
-    });
-
-    // Show last few context lines
-    for (let j = contextLines.length - CONTEXT_LINES_TO_SHOW; j < contextLines.length; j++) {
-      rows.push({ type: 'line', file: contextFile, line: contextLines[j] });
     }
   }
 
   return rows;
 }
 
-// Insert thread and editor rows into the base diff rows
-function insertInlineRows(
-  baseRows: Row[],
-  threads: Thread[],
-  selectedLines: { file: string; start: number; end: number } | null
-): Row[] {
+// What a new comment attaches to. Only line comments use start/end/side.
+interface SelectedLines {
+  anchor: ThreadAnchor;
+  file: string;
+  start: number;
+  end: number;
+  side: Side;
+}
+
+// Key for a line on one side of the diff. Added lines only exist on the new
+// side, deleted lines only on the old side, context lines on both.
+function lineKeys(file: string, line: ParsedLine): string[] {
+  const keys: string[] = [];
+  if (line.newLineNum !== undefined) keys.push(`new:${file}:${line.newLineNum}`);
+  if (line.oldLineNum !== undefined) keys.push(`old:${file}:${line.oldLineNum}`);
+  return keys;
+}
+
+// Insert line and file thread rows into the base diff rows
+function insertInlineRows(baseRows: Row[], threads: Thread[]): Row[] {
   const result: Row[] = [];
 
-  // Build a map of file:lineEnd -> threads that end on that line
+  // Build a map of side:file:lineEnd -> threads that end on that line
   // Use display_line_end (mapped position) when available, falling back to stored line_end
   const threadsByEndLine = new Map<string, Thread[]>();
+  const threadsByFile = new Map<string, Thread[]>();
   for (const thread of threads) {
+    // Threads follow their code into renamed files and other modules
+    const file = thread.display_file ?? thread.file;
+    if (thread.anchor === 'file') {
+      const existing = threadsByFile.get(file) || [];
+      existing.push(thread);
+      threadsByFile.set(file, existing);
+      continue;
+    }
     const displayEnd = thread.display_line_end ?? thread.line_end;
-    const key = `${thread.file}:${displayEnd}`;
+    const key = `${thread.side}:${file}:${displayEnd}`;
     const existing = threadsByEndLine.get(key) || [];
     existing.push(thread);
     threadsByEndLine.set(key, existing);
   }
 
-  // Track where to insert the comment editor
-  const editorKey = selectedLines ? `${selectedLines.file}:${selectedLines.end}` : null;
-
   for (const row of baseRows) {
     result.push(row);
 
-    if (row.type === 'line') {
-      const lineNum = row.line.newLineNum ?? row.line.oldLineNum ?? 0;
-      const key = `${row.file}:${lineNum}`;
+    // File threads sit right under the file header
+    if (row.type === 'file-header') {
+      for (const thread of threadsByFile.get(row.path) || []) {
+        result.push({ type: 'thread', thread });
+      }
+    }
 
-      // Insert threads that end on this line
-      // Only insert after lines with newLineNum to avoid duplicates on changed lines
-      // TODO: support threads on deleted lines (see TODO.md)
-      if (row.line.newLineNum !== undefined) {
-        const lineThreads = threadsByEndLine.get(key) || [];
-        for (const thread of lineThreads) {
+    // Threads on lines the diff left out sit under the placeholder
+    if (row.type === 'collapsed') {
+      const { newStart, newEnd, oldOffset } = row.gap;
+      for (const thread of threads) {
+        if (thread.anchor !== 'line' || (thread.display_file ?? thread.file) !== row.file) continue;
+        const end = thread.display_line_end ?? thread.line_end;
+        const newLine = thread.side === 'old' ? end - oldOffset : end;
+        if (newLine >= newStart && (newEnd === null || newLine <= newEnd)) {
           result.push({ type: 'thread', thread });
         }
       }
+    }
 
-      // Insert comment editor after the selected line range ends
-      // Only insert after lines with newLineNum to avoid duplicates on changed lines
-      if (editorKey === key && row.line.newLineNum !== undefined) {
-        result.push({
-          type: 'comment-editor',
-          file: selectedLines!.file,
-          lineStart: selectedLines!.start,
-          lineEnd: selectedLines!.end,
-        });
+    if (row.type === 'line') {
+      // Insert threads that end on this line (on either side)
+      for (const key of lineKeys(row.file, row.line)) {
+        const lineThreads = threadsByEndLine.get(key) || [];
+        for (const thread of lineThreads) {
+          result.push({ type: 'thread', thread });
+        }
       }
     }
   }
@@ -210,6 +216,71 @@ function insertInlineRows(
   return result;
 }
 
+// Whether a new comment on `target` belongs under this row
+function isEditorAnchorRow(row: Row, target: SelectedLines): boolean {
+  switch (target.anchor) {
+    case 'change':
+      return row.type === 'change-header';
+    case 'description':
+      return row.type === 'commit-header';
+    case 'file':
+      return row.type === 'file-header' && row.path === target.file;
+    default:
+      if (target.file === COMMIT_MESSAGE_FILE) {
+        return row.type === 'commit-line' && row.lineNum === target.end;
+      }
+      return (
+        row.type === 'line' &&
+        lineKeys(row.file, row.line).includes(`${target.side}:${target.file}:${target.end}`)
+      );
+  }
+}
+
+// Insert the comment editor after the target row and any threads below it
+function insertEditorRow(rows: Row[], target: SelectedLines): Row[] {
+  let idx = rows.findIndex((row) => isEditorAnchorRow(row, target));
+  if (idx === -1) return rows;
+  idx++;
+  while (idx < rows.length && rows[idx].type === 'thread') idx++;
+  return [
+    ...rows.slice(0, idx),
+    {
+      type: 'comment-editor',
+      anchor: target.anchor,
+      file: target.file,
+      lineStart: target.start,
+      lineEnd: target.end,
+    },
+    ...rows.slice(idx),
+  ];
+}
+
+// The line a new comment on this row would attach to: the new side when the
+// line exists there, otherwise the old side (a deleted line).
+function commentTarget(file: string, line: ParsedLine): SelectedLines | null {
+  if (line.newLineNum !== undefined && line.newLineNum > 0) {
+    return { anchor: 'line', file, start: line.newLineNum, end: line.newLineNum, side: 'new' };
+  }
+  if (line.type === 'delete' && line.oldLineNum !== undefined && line.oldLineNum > 0) {
+    return { anchor: 'line', file, start: line.oldLineNum, end: line.oldLineNum, side: 'old' };
+  }
+  return null;
+}
+
+// The thread a new comment on a header row would start, if any
+function headerTarget(row: Row): SelectedLines | null {
+  switch (row.type) {
+    case 'change-header':
+      return { anchor: 'change', file: '', start: 0, end: 0, side: 'new' };
+    case 'commit-header':
+      return { anchor: 'description', file: '', start: 0, end: 0, side: 'new' };
+    case 'file-header':
+      return { anchor: 'file', file: row.path, start: 0, end: 0, side: 'new' };
+    default:
+      return null;
+  }
+}
+
 export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function DiffViewer(
   { diff, targetMessage, messageDiff, review, changeId, description },
   ref
@@ -217,6 +288,7 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
   // Get UI state from context
   const {
     focusedPanel,
+    selectedSessionName,
     selectedThreadId,
     replyingToThread,
     replyText,
@@ -235,7 +307,8 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
   const [focusedIndex, setFocusedIndex] = useState(0);
   const [editorOpen, setEditorOpen] = useState(false);
   const [submitting, setSubmitting] = useState(false);
-  const [expandedSections, setExpandedSections] = useState<Set<string>>(new Set());
+  // Lines fetched for expanded gaps, by gap id
+  const [expandedSections, setExpandedSections] = useState<Map<string, string[]>>(new Map());
 
   const listRef = useRef<VListHandle>(null);
   const containerRef = useRef<HTMLDivElement>(null);
@@ -244,8 +317,13 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
 
   // Parse diff into base rows
   const baseRows = useMemo(() => {
-    return parseDiffToRows(diff.raw, expandedSections);
-  }, [diff.raw, expandedSections]);
+    return diffToRows(diff, expandedSections);
+  }, [diff, expandedSections]);
+
+  // Fetched lines belong to the diff they were fetched for
+  useEffect(() => {
+    setExpandedSections(new Map());
+  }, [diff]);
 
   // Build commit message rows
   const commitRows = useMemo((): Row[] => {
@@ -297,15 +375,29 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
   const { rows, selectedLines } = useMemo(() => {
     const threads = review.threads;
 
-    // Separate commit message threads from file threads
-    const commitThreads = threads.filter((t) => t.file === COMMIT_MESSAGE_FILE);
-    const fileThreads = threads.filter((t) => t.file !== COMMIT_MESSAGE_FILE);
+    // Change and description threads go under their headers; commit message
+    // line threads under their commit line; the rest into the diff
+    const changeThreads = threads.filter((t) => t.anchor === 'change');
+    const descriptionThreads = threads.filter((t) => t.anchor === 'description');
+    const commitThreads = threads.filter((t) => t.anchor === 'line' && t.file === COMMIT_MESSAGE_FILE);
+    const fileThreads = threads.filter(
+      (t) => (t.anchor === 'line' || t.anchor === 'file') && t.file !== COMMIT_MESSAGE_FILE
+    );
+
+    const changeRows: Row[] = [{ type: 'change-header' }];
+    for (const thread of changeThreads) {
+      changeRows.push({ type: 'thread', thread });
+    }
 
-    // Insert threads and editor into commit rows
+    // Insert threads into commit rows
     let commitRowsWithThreads: Row[] = [];
     for (const row of commitRows) {
       commitRowsWithThreads.push(row);
-      if (row.type === 'commit-line') {
+      if (row.type === 'commit-header') {
+        for (const thread of descriptionThreads) {
+          commitRowsWithThreads.push({ type: 'thread', thread });
+        }
+      } else if (row.type === 'commit-line') {
         // Insert threads that end on this line
         const lineThreads = commitThreads.filter((t) => t.line_end === row.lineNum);
         for (const thread of lineThreads) {
@@ -314,53 +406,34 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
       }
     }
 
-    // Combine commit rows + diff rows
-    const allBaseRows = [...commitRowsWithThreads, ...baseRows];
-    const rowsWithThreads = insertInlineRows(allBaseRows, fileThreads, null);
+    // Combine change header + commit rows + diff rows
+    const rowsWithThreads = [
+      ...changeRows,
+      ...commitRowsWithThreads,
+      ...insertInlineRows(baseRows, fileThreads),
+    ];
 
     // Derive selectedLines from focused row when editor is open
-    let selectedLines: { file: string; start: number; end: number } | null = null;
+    let selectedLines: SelectedLines | null = null;
     if (editorOpen) {
       const focusedRow = rowsWithThreads[focusedIndex];
-      if (focusedRow?.type === 'line' && focusedRow.line.newLineNum !== undefined) {
-        selectedLines = {
-          file: focusedRow.file,
-          start: focusedRow.line.newLineNum,
-          end: focusedRow.line.newLineNum,
-        };
+      if (focusedRow?.type === 'line') {
+        selectedLines = commentTarget(focusedRow.file, focusedRow.line);
       } else if (focusedRow?.type === 'commit-line') {
         selectedLines = {
+          anchor: 'line',
           file: COMMIT_MESSAGE_FILE,
           start: focusedRow.lineNum,
           end: focusedRow.lineNum,
+          side: 'new',
         };
+      } else if (focusedRow) {
+        selectedLines = headerTarget(focusedRow);
       }
     }
 
     // Insert editor row if editing
-    let rows: Row[];
-    if (selectedLines) {
-      if (selectedLines.file === COMMIT_MESSAGE_FILE) {
-        // Insert editor after the commit line
-        const withEditor: Row[] = [];
-        for (const row of commitRowsWithThreads) {
-          withEditor.push(row);
-          if (row.type === 'commit-line' && row.lineNum === selectedLines.end) {
-            withEditor.push({
-              type: 'comment-editor',
-              file: COMMIT_MESSAGE_FILE,
-              lineStart: selectedLines.start,
-              lineEnd: selectedLines.end,
-            });
-          }
-        }
-        rows = [...withEditor, ...insertInlineRows(baseRows, fileThreads, null)];
-      } else {
-        rows = [...commitRowsWithThreads, ...insertInlineRows(baseRows, fileThreads, selectedLines)];
-      }
-    } else {
-      rows = rowsWithThreads;
-    }
+    const rows = selectedLines ? insertEditorRow(rowsWithThreads, selectedLines) : rowsWithThreads;
 
     return { rows, selectedLines };
   }, [baseRows, commitRows, review.threads, editorOpen, focusedIndex]);
@@ -390,11 +463,15 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
     [threadRowIndices]
   );
 
-  // Find indices of navigable rows (lines, threads, collapsed sections, and commit lines) for keyboard nav
+  // Find indices of navigable rows (lines, threads, collapsed sections, commit lines and headers) for keyboard nav
   const navigableIndices = useMemo(() => {
     return rows
       .map((row, idx) =>
-        row.type === 'line' || row.type === 'thread' || row.type === 'collapsed' || row.type === 'commit-line'
+        row.type === 'line' ||
+        row.type === 'thread' ||
+        row.type === 'collapsed' ||
+        row.type === 'commit-line' ||
+        headerTarget(row) !== null
           ? idx
           : -1
       )
@@ -445,26 +522,52 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
     []
   );
 
-  const handleExpandSection = useCallback((id: string) => {
-    setExpandedSections((prev) => {
-      const next = new Set(prev);
-      next.add(id);
-      return next;
-    });
-  }, []);
+  const handleExpandSection = useCallback(
+    async (row: CollapsedRow) => {
+      const { newStart, newEnd } = row.gap;
+      try {
+        const res = await fetchFileLines(changeId, row.file, newStart, newEnd ?? undefined, {
+          commit: diff.change_id,
+          base: diff.base,
+          session: selectedSessionName ?? undefined,
+        });
+        setExpandedSections((prev) => new Map(prev).set(row.id, res.lines));
+      } catch (err) {
+        console.error('Failed to expand context:', err);
+      }
+    },
+    [changeId, diff.change_id, diff.base, selectedSessionName]
+  );
 
-  const handleSubmitComment = useCallback(async () => {
+  // With `draft`, the comment waits in the pending review until it is submitted
+  const handleSubmitComment = useCallback(async (draft = false) => {
     if (!selectedLines || !commentText.trim() || !changeId) return;
 
     setSubmitting(true);
     try {
-      const result = await addComment(
-        changeId,
-        selectedLines.file,
-        selectedLines.start,
-        selectedLines.end,
-        commentText.trim()
-      );
+      // Suggestions replace lines of the change, so only the new side has them
+      const { text, suggestion } = selectedLines.side === 'new'
+        ? splitSuggestion(commentText.trim())
+        : { text: commentText.trim(), suggestion: undefined };
+      const result =
+        selectedLines.anchor === 'line'
+          ? await addComment(
+              changeId,
+              selectedLines.file,
+              selectedLines.start,
+              selectedLines.end,
+              text,
+              selectedLines.side,
+              draft,
+              suggestion
+            )
+          : await startThread(
+              changeId,
+              selectedLines.anchor,
+              commentText.trim(),
+              selectedLines.anchor === 'file' ? selectedLines.file : undefined,
+              draft
+            );
       // Update SWR cache with new review
       mutate(['review', changeId], result.review, false);
       setEditorOpen(false);
@@ -495,7 +598,7 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
     if (!thread) return;
 
     try {
-      if (thread.status === 'open') {
+      if (thread.status !== 'resolved') {
         await resolveThread(changeId, threadId);
       } else {
         await reopenThread(changeId, threadId);
@@ -545,14 +648,14 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
           // Expand collapsed section
           if (row.type === 'collapsed') {
             e.preventDefault();
-            handleExpandSection(row.id);
+            handleExpandSection(row);
             break;
           }
-          // Create new comment - works on code lines and commit lines
-          if (row.type === 'line' && row.line.newLineNum !== undefined) {
+          // Create new comment - works on code lines, commit lines and headers
+          if (row.type === 'line' && commentTarget(row.file, row.line)) {
             e.preventDefault();
             setEditorOpen(true);
-          } else if (row.type === 'commit-line') {
+          } else if (row.type === 'commit-line' || headerTarget(row)) {
             e.preventDefault();
             setEditorOpen(true);
           }
@@ -609,7 +712,7 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
         case 'r': {
           // Reply to selected thread (must be focused on thread row)
           const row = rows[focusedIndex];
-          if (row.type === 'thread' && row.thread.status === 'open') {
+          if (row.type === 'thread' && row.thread.status !== 'resolved') {
             e.preventDefault();
             startReply(row.thread.id);
           }
@@ -634,9 +737,31 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
   // Render function - only called for visible rows
   const renderRow = useCallback(
     (row: Row, idx: number) => {
+      if (row.type === 'change-header') {
+        const isFocusedRow = idx === focusedIndex;
+        return (
+          <div
+            onClick={() => handleLineClick(idx)}
+            className={`border-b px-4 py-1 text-xs cursor-pointer ${
+              isFocusedRow && focused
+                ? 'bg-blue-100 border-blue-300 text-blue-700'
+                : 'bg-white border-gray-200 text-gray-400 hover:text-gray-600'
+            }`}
+          >
+            Comment on the whole change
+          </div>
+        );
+      }
+
       if (row.type === 'commit-header') {
+        const isFocusedRow = idx === focusedIndex;
         return (
-          <div className="bg-amber-50 border-b border-amber-200 px-4 py-2 sticky top-0 z-10">
+          <div
+            onClick={() => handleLineClick(idx)}
+            className={`border-b border-amber-200 px-4 py-2 sticky top-0 z-10 cursor-pointer ${
+              isFocusedRow && focused ? 'bg-amber-100' : 'bg-amber-50'
+            }`}
+          >
             <span className="text-amber-700 font-semibold">Commit Message</span>
           </div>
         );
@@ -703,9 +828,20 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
       }
 
       if (row.type === 'file-header') {
+        const isFocusedRow = idx === focusedIndex;
         return (
-          <div className="bg-gray-100 border-b border-gray-200 px-4 py-2 sticky top-0 z-10">
+          <div
+            onClick={() => handleLineClick(idx)}
+            className={`border-b border-gray-200 px-4 py-2 sticky top-0 z-10 cursor-pointer ${
+              isFocusedRow && focused ? 'bg-blue-100' : 'bg-gray-100'
+            }`}
+          >
             <span className="text-blue-600 font-semibold">{row.path}</span>
+            {row.origin && (
+              <span className="text-gray-500 text-sm ml-2">
+                {row.origin.kind} from {row.origin.from}
+              </span>
+            )}
           </div>
         );
       }
@@ -716,6 +852,10 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
         );
       }
 
+      if (row.type === 'note') {
+        return <div className="text-gray-400 italic px-4 py-1 text-xs">{row.text}</div>;
+      }
+
       if (row.type === 'thread') {
         const { thread } = row;
         const isFocusedRow = idx === focusedIndex;
@@ -731,12 +871,25 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
             }`}
           >
             <div className="text-xs text-gray-400 mb-2 font-mono">
-              {thread.file}:{thread.display_line_start ?? thread.line_start}-{thread.display_line_end ?? thread.line_end}
+              {threadLocation(thread)}
+              {thread.anchor === 'line' && thread.side === 'old' && (
+                <span className="ml-1 text-red-400" title="Lines in the parent (old side of the diff)">
+                  (old)
+                </span>
+              )}
               {thread.is_displaced && !thread.is_deleted && (
                 <span className="ml-1 text-amber-500" title={`Originally :${thread.line_start}-${thread.line_end}`}>
                   (moved)
                 </span>
               )}
+              {thread.match_confidence != null && thread.match_confidence < 1 && !thread.is_deleted && (
+                <span
+                  className="ml-1 text-amber-500"
+                  title="The diff lost track of these lines; found again by matching their text"
+                >
+                  ({Math.round(thread.match_confidence * 100)}% match)
+                </span>
+              )}
               {thread.is_deleted && (
                 <span className="ml-1 text-red-500">
                   (lines deleted)
@@ -749,17 +902,18 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
                 <div
                   key={cidx}
                   className={`text-sm ${
-                    comment.author === 'claude'
+                    comment.author.kind !== 'human'
                       ? 'bg-purple-50 border-l-2 border-purple-400 pl-2 py-1'
                       : ''
                   }`}
                 >
                   <span
                     className={`text-xs font-semibold ${
-                      comment.author === 'claude' ? 'text-purple-600' : 'text-blue-600'
+                      comment.author.kind !== 'human' ? 'text-purple-600' : 'text-blue-600'
                     }`}
+                    title={comment.author.kind}
                   >
-                    {comment.author}:
+                    {authorLabel(comment.author)}:
                   </span>{' '}
                   <span className="text-gray-700">{comment.text}</span>
                 </div>
@@ -854,7 +1008,13 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
         return (
           <div className="ml-24 mr-4 my-2 bg-white border border-blue-400 rounded-lg p-3 font-sans shadow-sm ring-2 ring-blue-200">
             <div className="text-xs text-gray-500 mb-2">
-              New comment on lines {row.lineStart}-{row.lineEnd}
+              {row.anchor === 'line'
+                ? `New comment on lines ${row.lineStart}-${row.lineEnd}`
+                : row.anchor === 'file'
+                  ? `New comment on ${row.file}`
+                  : row.anchor === 'description'
+                    ? 'New comment on the commit description'
+                    : 'New comment on the whole change'}
             </div>
             <textarea
               ref={textareaRef}
@@ -863,13 +1023,13 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
               onKeyDown={(e) => {
                 if (e.key === 'Enter' && !e.shiftKey) {
                   e.preventDefault();
-                  if (commentText.trim()) handleSubmitComment();
+                  if (commentText.trim()) handleSubmitComment(e.metaKey || e.ctrlKey);
                 } else if (e.key === 'Escape') {
                   setEditorOpen(false);
                   clearNewComment();
                 }
               }}
-              placeholder="Add your comment... (Enter to submit, Esc to cancel)"
+              placeholder="Add your comment... (Enter to submit, Ctrl+Enter to add to review, Esc to cancel)"
               className="w-full bg-gray-50 border border-gray-200 rounded p-2 text-sm resize-none"
               rows={3}
             />
@@ -884,7 +1044,14 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
                 Cancel
               </button>
               <button
-                onClick={handleSubmitComment}
+                onClick={() => handleSubmitComment(true)}
+                disabled={!commentText.trim() || submitting}
+                className="px-3 py-1 text-sm border border-blue-600 text-blue-600 hover:bg-blue-50 rounded disabled:opacity-50"
+              >
+                Add to review
+              </button>
+              <button
+                onClick={() => handleSubmitComment()}
                 disabled={!commentText.trim() || submitting}
                 className="px-3 py-1 text-sm bg-blue-600 hover:bg-blue-700 text-white rounded disabled:opacity-50"
               >
@@ -899,7 +1066,7 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
         const isFocusedRow = idx === focusedIndex;
         return (
           <div
-            onClick={() => handleExpandSection(row.id)}
+            onClick={() => handleExpandSection(row)}
             className={`flex border-y text-xs py-1 cursor-pointer ${
               isFocusedRow && focused
                 ? 'bg-blue-100 border-blue-300 text-blue-700'
@@ -910,7 +1077,8 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
           >
             <span className="w-24 text-center shrink-0">⋯</span>
             <span className="pl-4">
-              {row.lines.length} lines hidden — {isFocusedRow && focused ? 'Enter to expand' : 'click to expand'}
+              {row.gap.newEnd === null ? 'Remaining lines' : `${row.gap.newEnd - row.gap.newStart + 1} lines`} hidden —{' '}
+              {isFocusedRow && focused ? 'Enter to expand' : 'click to expand'}
             </span>
           </div>
         );
@@ -920,8 +1088,8 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
       const { line } = row;
       const isFocusedLine = idx === focusedIndex;
       const isEditing = isFocusedLine && editorOpen;
-      // Only allow clicking on lines with newLineNum (not pure deletions)
-      const canClick = line.newLineNum !== undefined && line.newLineNum > 0;
+      // Deleted lines are commented on the old side
+      const canClick = commentTarget(row.file, line) !== null;
 
       return (
         <div
@@ -990,7 +1158,7 @@ export const DiffViewer = forwardRef<DiffViewerHandle, DiffViewerProps>(function
     ]
   );
 
-  if (!diff || rows.length === 0) {
+  if (!diff || (baseRows.length === 0 && commitRows.length === 0)) {
     return <div className="p-8 text-center text-gray-400">No diff content</div>;
   }
 
@@ -1,6 +1,7 @@
 import { useRef, useMemo } from 'react';
 import { DiffViewer, DiffViewerHandle } from './DiffViewer';
 import { CommentPanel } from './CommentPanel';
+import { ConflictView } from './ConflictView';
 import { useAppContext } from '../context';
 import { useDiff, useReview, useChanges, type Change } from '../hooks';
 
@@ -50,6 +51,7 @@ export function SelectedChangeView({ change }: SelectedChangeViewProps) {
         className="flex-1 overflow-auto"
         onClick={() => setFocusedPanel('diff')}
       >
+        {change.conflict && <ConflictView change={change} session={selectedSessionName ?? undefined} />}
         <DiffViewer
           ref={diffViewerRef}
           diff={diffResponse.diff}
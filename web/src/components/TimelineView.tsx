@@ -1,5 +1,5 @@
 import { useEffect, useState } from 'react';
-import { fetchTimeline, type TimelineEntry } from '../api';
+import { authorLabel, fetchTimeline, type TimelineEntry } from '../api';
 
 function formatTime(timestamp: string): string {
   const date = new Date(timestamp);
@@ -21,7 +21,7 @@ function formatFullTime(timestamp: string): string {
 }
 
 function ChatMessageEvent({ entry }: { entry: TimelineEntry }) {
-  const isUser = entry.author === 'user';
+  const isUser = entry.author?.kind === 'human';
   const text = entry.text || '';
   // Truncate long messages
   const truncated = text.length > 500 ? text.slice(0, 500) + '...' : text;
@@ -37,7 +37,7 @@ function ChatMessageEvent({ entry }: { entry: TimelineEntry }) {
       >
         <div className="flex items-center gap-2 mb-1">
           <span className="font-medium text-xs text-gray-500">
-            {isUser ? 'You' : 'Claude'}
+            {entry.author ? authorLabel(entry.author) : 'unknown'}
           </span>
           <span className="text-xs text-gray-400" title={formatFullTime(entry.timestamp)}>
             {formatTime(entry.timestamp)}
@@ -65,8 +65,9 @@ function ReviewCommentEvent({ entry }: { entry: TimelineEntry }) {
           </span>
         </div>
         <div className="text-xs text-gray-500 mb-1">
-          {entry.file}:{entry.line_start}
-          {entry.line_end !== entry.line_start ? `-${entry.line_end}` : ''}
+          {/* File, description and change comments are logged without lines */}
+          {entry.line_start ? `${entry.file}:${entry.line_start}` : entry.file}
+          {entry.line_start && entry.line_end !== entry.line_start ? `-${entry.line_end}` : ''}
           <span className="ml-2 text-gray-400">{entry.change_id?.slice(0, 8)}</span>
         </div>
         <div className="text-gray-800">{entry.text}</div>
@@ -76,13 +77,12 @@ function ReviewCommentEvent({ entry }: { entry: TimelineEntry }) {
 }
 
 function ReviewReplyEvent({ entry }: { entry: TimelineEntry }) {
-  const isUser = entry.author === 'user';
   return (
     <div className="flex justify-center">
       <div className="bg-orange-50 border border-orange-200 rounded-lg px-3 py-2 text-sm max-w-[85%]">
         <div className="flex items-center gap-2 mb-1">
           <span className="text-orange-700 font-medium text-xs">
-            {isUser ? 'You' : 'Claude'} replied
+            {entry.author ? authorLabel(entry.author) : 'unknown'} replied
           </span>
           <span className="text-xs text-gray-400" title={formatFullTime(entry.timestamp)}>
             {formatTime(entry.timestamp)}
@@ -3,8 +3,12 @@ import {
   fetchChanges,
   fetchSessionChanges,
   fetchDiff,
+  fetchInterdiff,
   fetchReview,
   fetchTodos,
+  fetchIdentity,
+  fetchConflicts,
+  resolveConflict as apiResolveConflict,
   createReview,
   createTodo as apiCreateTodo,
   updateTodo as apiUpdateTodo,
@@ -12,7 +16,15 @@ import {
   replyToThread as apiReplyToThread,
   resolveThread as apiResolveThread,
   reopenThread as apiReopenThread,
+  classifyThread as apiClassifyThread,
+  editComment as apiEditComment,
+  deleteComment as apiDeleteComment,
   mergeChange as apiMergeChange,
+  markSeen as apiMarkSeen,
+  setVerdict as apiSetVerdict,
+  submitReview as apiSubmitReview,
+  applySuggestion as apiApplySuggestion,
+  splitSuggestion,
   createSession as apiCreateSession,
   mergeSession as apiMergeSession,
   type Change,
@@ -22,6 +34,10 @@ import {
   type Review,
   type TodoTree,
   type SessionSummary,
+  type Severity,
+  type VerdictKind,
+  type ConflictedFile,
+  type Resolution,
 } from './api';
 
 // Hook for fetching changes list with DAG graph (suspense mode - always returns data)
@@ -60,7 +76,10 @@ export function useDiff(changeId: string, commitId?: string, baseCommitId?: stri
 
   const { data } = useSWR<DiffResponse>(
     key,
-    () => fetchDiff(changeId, commitId, baseCommitId, session),
+    // With a base commit, show only what the author changed since it
+    () => baseCommitId
+      ? fetchInterdiff(changeId, baseCommitId, commitId, session)
+      : fetchDiff(changeId, commitId, baseCommitId, session),
     {
       suspense: true,
       revalidateOnFocus: false,
@@ -72,6 +91,27 @@ export function useDiff(changeId: string, commitId?: string, baseCommitId?: stri
 }
 
 // Hook for fetching review (suspense mode - requires changeId)
+export function useConflicts(changeId: string, commitId: string, session?: string): ConflictedFile[] {
+  const { data } = useSWR(['conflicts', changeId, commitId, session ?? ''], () => fetchConflicts(changeId, session), {
+    suspense: true,
+    revalidateOnFocus: false,
+  });
+  return data!;
+}
+
+export async function resolveConflict(
+  changeId: string,
+  path: string,
+  resolution: Resolution,
+  opts: { separate?: boolean; session?: string } = {}
+) {
+  const resolved = await apiResolveConflict(changeId, path, resolution, opts);
+  // The change's commit, conflict flag and diff all change
+  mutate('changes');
+  mutate((key) => Array.isArray(key) && ['conflicts', 'diff', 'session-changes'].includes(key[0]));
+  return resolved;
+}
+
 export function useReview(changeId: string): Review {
   const key = ['review', changeId];
 
@@ -96,8 +136,9 @@ export function useReview(changeId: string): Review {
 }
 
 // Mutation helpers that update the cache
-export async function replyToThread(changeId: string, threadId: string, text: string) {
-  const review = await apiReplyToThread(changeId, threadId, text);
+export async function replyToThread(changeId: string, threadId: string, text: string, draft = false) {
+  const { text: body, suggestion } = splitSuggestion(text);
+  const review = await apiReplyToThread(changeId, threadId, body, draft, suggestion);
   // Update the cache with the new review
   mutate(['review', changeId], review, false);
   return review;
@@ -115,6 +156,58 @@ export async function reopenThread(changeId: string, threadId: string) {
   return review;
 }
 
+export async function editComment(changeId: string, threadId: string, commentId: string, text: string) {
+  const review = await apiEditComment(changeId, threadId, commentId, text);
+  mutate(['review', changeId], review, false);
+  return review;
+}
+
+export async function deleteComment(changeId: string, threadId: string, commentId: string) {
+  const review = await apiDeleteComment(changeId, threadId, commentId);
+  mutate(['review', changeId], review, false);
+  // Thread counts in the change list
+  mutate('changes');
+  return review;
+}
+
+export async function classifyThread(changeId: string, threadId: string, severity?: Severity, labels?: string[]) {
+  const review = await apiClassifyThread(changeId, threadId, severity, labels);
+  mutate(['review', changeId], review, false);
+  // Blocking counts in the change list
+  mutate('changes');
+  return review;
+}
+
+export async function markSeen(changeId: string, threadId?: string) {
+  const review = await apiMarkSeen(changeId, threadId);
+  mutate(['review', changeId], review, false);
+  // Unseen/unread markers in the change list
+  mutate('changes');
+  return review;
+}
+
+export async function setVerdict(changeId: string, verdict: VerdictKind, comment?: string) {
+  const review = await apiSetVerdict(changeId, verdict, comment);
+  mutate(['review', changeId], review, false);
+  mutate('changes');
+  return review;
+}
+
+export async function submitReview(changeId: string, verdict?: VerdictKind, comment?: string) {
+  const { review } = await apiSubmitReview(changeId, verdict, comment);
+  mutate(['review', changeId], review, false);
+  mutate('changes');
+  return review;
+}
+
+export async function applySuggestion(changeId: string, threadId: string, squash: boolean, session?: string) {
+  const applied = await apiApplySuggestion(changeId, threadId, squash, session);
+  // A new change, or new content in this one
+  mutate('changes');
+  mutate((key) => Array.isArray(key) && key[0] === 'diff' && key[1] === changeId);
+  return applied;
+}
+
 export async function mergeChange(changeId: string, force = false) {
   const result = await apiMergeChange(changeId, force);
   if (result.success) {
@@ -124,6 +217,15 @@ export async function mergeChange(changeId: string, force = false) {
   return result;
 }
 
+// Who is reviewing from this machine; doesn't change while the server runs
+export function useIdentity() {
+  const { data } = useSWR('identity', () => fetchIdentity(), {
+    suspense: true,
+    revalidateOnFocus: false,
+  });
+  return data!;
+}
+
 // Hook for fetching todos (suspense mode, polled every 3s)
 export function useTodos(): TodoTree {
   const { data } = useSWR('todos', () => fetchTodos(), {
@@ -1,3 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AuthorKind } from "./AuthorKind";
 
-export type Author = "user" | "claude";
+export type Author = { kind: AuthorKind, name: string, 
+/**
+ * The aipair session the author was working in, for agents
+ */
+session: string | null, };
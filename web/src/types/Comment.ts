@@ -1,4 +1,23 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Author } from "./Author";
+import type { CommentEdit } from "./CommentEdit";
 
-export type Comment = { author: Author, text: string, timestamp: string, };
+export type Comment = { 
+/**
+ * Unique within the thread. Comments from before comments had ids get
+ * one derived from their timestamp when loaded.
+ */
+id: string, author: Author, text: string, timestamp: string, 
+/**
+ * Part of a review that hasn't been submitted yet; the agent doesn't
+ * see it until it is
+ */
+draft: boolean, 
+/**
+ * Replacement text for the thread's anchored lines, one entry per line
+ */
+suggestion: Array<string> | null, 
+/**
+ * Earlier versions of the text, oldest first
+ */
+edits: Array<CommentEdit>, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A comment's text before an edit
+ */
+export type CommentEdit = { previous_text: string, edited_at: string, };
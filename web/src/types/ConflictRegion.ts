@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A stretch of a conflicted file: either text all sides agree on, or a
+ * conflict between the sides
+ */
+export type ConflictRegion = { "kind": "resolved", lines: Array<string>, } | { "kind": "conflict", 
+/**
+ * What each side has here, in jj's side order; None for a side
+ * that deletes the file
+ */
+sides: Array<Array<string> | null>, 
+/**
+ * The common ancestors the sides diverged from, one fewer than sides
+ */
+bases: Array<Array<string>>, };
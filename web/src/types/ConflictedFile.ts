@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConflictRegion } from "./ConflictRegion";
+
+export type ConflictedFile = { path: string, regions: Array<ConflictRegion>, 
+/**
+ * Whether the last line ends in a newline
+ */
+final_newline: boolean, };
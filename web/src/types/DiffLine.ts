@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LineKind } from "./LineKind";
+
+export type DiffLine = { kind: LineKind, content: string, 
+/**
+ * Line number in the old file (None for added lines)
+ */
+old_line: number | null, 
+/**
+ * Line number in the new file (None for deleted lines)
+ */
+new_line: number | null, };
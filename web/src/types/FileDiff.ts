@@ -1,4 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { FileStatus } from "./FileStatus";
+import type { Hunk } from "./Hunk";
 
-export type FileDiff = { path: string, status: FileStatus, };
+export type FileDiff = { path: string, status: FileStatus, 
+/**
+ * Empty for summaries, and for renames and copies without edits
+ */
+hunks: Array<Hunk>, binary: boolean, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A slice of a file at some revision, for expanding context in the UI
+ */
+export type FileLines = { 
+/**
+ * Line number of the first entry in `lines`
+ */
+start: number, lines: Array<string>, total_lines: number, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DiffLine } from "./DiffLine";
+
+export type Hunk = { old_start: number, old_count: number, new_start: number, new_count: number, 
+/**
+ * Text after the closing `@@`, usually the enclosing function
+ */
+section: string, lines: Array<DiffLine>, };
@@ -1,5 +1,19 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ReviewerProgress } from "./ReviewerProgress";
 import type { Revision } from "./Revision";
 import type { Thread } from "./Thread";
+import type { Verdict } from "./Verdict";
 
-export type Review = { change_id: string, base: string, created_at: string, threads: Array<Thread>, revisions: Array<Revision>, working_commit_id: string | null, };
+export type Review = { change_id: string, base: string, created_at: string, threads: Array<Thread>, revisions: Array<Revision>, working_commit_id: string | null, 
+/**
+ * What each reviewer has already looked at, keyed by reviewer name
+ */
+seen: { [key in string]?: ReviewerProgress }, 
+/**
+ * Approvals and change requests, oldest first
+ */
+verdicts: Array<Verdict>, 
+/**
+ * Bumped on every save; used to detect concurrent modifications.
+ */
+version: number, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How far one reviewer has got through a change.
+ */
+export type ReviewerProgress = { 
+/**
+ * The last revision they looked at the diff of
+ */
+last_reviewed_revision: number | null, 
+/**
+ * Timestamp of the last comment they read, across all threads
+ */
+last_read_comment: string | null, 
+/**
+ * The same per thread, for threads read on their own since
+ */
+threads: { [key in string]?: string }, };
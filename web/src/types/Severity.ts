@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How much a thread matters. Only blocking threads hold up a merge; the
+ * variants are ordered most important first.
+ */
+export type Severity = "blocking" | "suggestion" | "question" | "nit";
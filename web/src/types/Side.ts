@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Side of a diff. `Old` lines are numbered in the change's parent, which
+ * lets a thread point at lines the change deleted.
+ */
+export type Side = "old" | "new";
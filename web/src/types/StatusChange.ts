@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Author } from "./Author";
+import type { ThreadStatus } from "./ThreadStatus";
+
+/**
+ * One entry in a thread's status history.
+ */
+export type StatusChange = { from: ThreadStatus, to: ThreadStatus, author: Author, timestamp: string, 
+/**
+ * The change's latest revision at the time
+ */
+revision: number | null, };
@@ -1,9 +1,50 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Comment } from "./Comment";
+import type { Severity } from "./Severity";
+import type { Side } from "./Side";
+import type { StatusChange } from "./StatusChange";
+import type { ThreadAnchor } from "./ThreadAnchor";
+import type { ThreadMove } from "./ThreadMove";
 import type { ThreadStatus } from "./ThreadStatus";
 
-export type Thread = { id: string, file: string, line_start: number, line_end: number, status: ThreadStatus, comments: Array<Comment>, created_at_commit: string | null, created_at_revision: number | null, 
+export type Thread = { id: string, 
+/**
+ * What the thread is attached to; `file` and the line range only
+ * apply to the anchors that use them
+ */
+anchor: ThreadAnchor, file: string, line_start: number, line_end: number, 
+/**
+ * Which side of the diff the lines refer to
+ */
+side: Side, status: ThreadStatus, 
+/**
+ * Every status change, oldest first
+ */
+history: Array<StatusChange>, 
+/**
+ * The commit the agent says addresses the thread
+ */
+addressed_in: string | null, 
+/**
+ * Set when the thread moved here from a change that was squashed,
+ * split or absorbed
+ */
+moved_from: ThreadMove | null, severity: Severity, 
+/**
+ * Free-form categories such as "perf" or "style"
+ */
+labels: Array<string>, comments: Array<Comment>, created_at_commit: string | null, created_at_revision: number | null, 
 /**
  * Display position after mapping through diffs (not persisted)
  */
-display_line_start: number | null, display_line_end: number | null, is_displaced: boolean, is_deleted: boolean, };
+display_line_start: number | null, display_line_end: number | null, is_displaced: boolean, is_deleted: boolean, 
+/**
+ * How sure the display position is: 1.0 when the diff was followed
+ * exactly, lower for a content match (not persisted)
+ */
+match_confidence: number | null, 
+/**
+ * The file the thread's code lives in now, when it was renamed or the
+ * code moved to another file (not persisted)
+ */
+display_file: string | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What a thread is attached to. Line threads carry a file and line range;
+ * file threads only a file; description and change threads neither.
+ */
+export type ThreadAnchor = "line" | "file" | "description" | "change";
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where a thread came from when it followed its lines to another change
+ */
+export type ThreadMove = { from_change_id: string, 
+/**
+ * The jj operation that rewrote the changes, e.g. "squash commits into ..."
+ */
+operation: string | null, timestamp: string, };
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type ThreadStatus = "open" | "resolved";
+/**
+ * Where a thread is in its life. The agent moves threads up to
+ * `Addressed`; only the reviewer verifies them and resolves them.
+ */
+export type ThreadStatus = "open" | "acknowledged" | "in_progress" | "addressed" | "resolved";
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { VerdictKind } from "./VerdictKind";
+
+/**
+ * A reviewer's overall call on one revision of a change.
+ */
+export type Verdict = { reviewer: string, kind: VerdictKind, 
+/**
+ * The revision it was given on; a later revision supersedes it
+ */
+revision: number, comment: string | null, created_at: string, };
@@ -2,29 +2,55 @@
 // Only extend types here when the API adds fields not in the Rust structs
 
 export type { Author } from './Author';
+export type { AuthorKind } from './AuthorKind';
 export type { Comment } from './Comment';
+export type { CommentEdit } from './CommentEdit';
+export type { ConflictedFile } from './ConflictedFile';
+export type { ConflictRegion } from './ConflictRegion';
 export type { Diff } from './Diff';
+export type { DiffLine } from './DiffLine';
 export type { FileDiff } from './FileDiff';
+export type { FileLines } from './FileLines';
 export type { FileStatus } from './FileStatus';
+export type { Hunk } from './Hunk';
+export type { LineKind } from './LineKind';
 export type { Review } from './Review';
+export type { ReviewerProgress } from './ReviewerProgress';
 export type { Revision } from './Revision';
+export type { Severity } from './Severity';
+export type { Side } from './Side';
+export type { StatusChange } from './StatusChange';
 export type { Thread } from './Thread';
+export type { ThreadAnchor } from './ThreadAnchor';
 export type { ThreadStatus } from './ThreadStatus';
 export type { TodoItem } from './TodoItem';
+export type { Verdict } from './Verdict';
+export type { VerdictKind } from './VerdictKind';
 export type { TodoTree } from './TodoTree';
 
 // Import base Change type and extend with API-computed fields
 import type { Change as BaseChange } from './Change';
+import type { VerdictKind } from './VerdictKind';
 
 export interface Change extends BaseChange {
   // These fields are computed by the API, not stored in Rust
   merged: boolean;
   open_thread_count: number;
+  blocking_thread_count: number;
   revision_count: number;
   has_pending_changes: boolean;
+  has_unseen_revision: boolean;
+  unread_reply_count: number;
+  verdict: VerdictKind | null;
   session_name?: string;
 }
 
+// Who is reviewing from this machine (identity::Identity on the server)
+export interface Identity {
+  name: string;
+  agent: string;
+}
+
 export interface PushChangeSnapshot {
   change_id: string;
   commit_id: string;